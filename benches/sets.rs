@@ -11,6 +11,7 @@ enum SetupFor {
     Insert,
     Remove,
     Contains,
+    ContainsSparse,
 }
 
 impl SetupFor {
@@ -19,6 +20,7 @@ impl SetupFor {
             Self::Insert => "insert",
             Self::Remove => "remove",
             Self::Contains => "contains",
+            Self::ContainsSparse => "contains sparse",
         }
     }
 }
@@ -43,6 +45,15 @@ impl Set for HashSet<usize> {
                     set.insert(i);
                 }
 
+                set
+            }
+            SetupFor::ContainsSparse => {
+                let mut set = Self::new();
+
+                for i in 0..1000 {
+                    set.insert(i * 7);
+                }
+
                 set
             }
         }
@@ -72,6 +83,15 @@ impl Set for BTreeSet<usize> {
                     set.insert(i);
                 }
 
+                set
+            }
+            SetupFor::ContainsSparse => {
+                let mut set = Self::new();
+
+                for i in 0..1000 {
+                    set.insert(i * 7);
+                }
+
                 set
             }
         }
@@ -101,6 +121,15 @@ impl Set for BTreeIndexSet<u64> {
                     set.insert(i);
                 }
 
+                set
+            }
+            SetupFor::ContainsSparse => {
+                let mut set = Self::new();
+
+                for i in 0..1000 {
+                    set.insert(i * 7);
+                }
+
                 set
             }
         }
@@ -130,6 +159,15 @@ impl Set for BTreeIndexSet<u128> {
                     set.insert(i);
                 }
 
+                set
+            }
+            SetupFor::ContainsSparse => {
+                let mut set = Self::new();
+
+                for i in 0..1000 {
+                    set.insert(i * 7);
+                }
+
                 set
             }
         }
@@ -159,6 +197,15 @@ impl Set for VecIndexSet<u64> {
                     set.insert(i);
                 }
 
+                set
+            }
+            SetupFor::ContainsSparse => {
+                let mut set = Self::new();
+
+                for i in 0..1000 {
+                    set.insert(i * 7);
+                }
+
                 set
             }
         }
@@ -188,6 +235,15 @@ impl Set for VecIndexSet<u128> {
                     set.insert(i);
                 }
 
+                set
+            }
+            SetupFor::ContainsSparse => {
+                let mut set = Self::new();
+
+                for i in 0..1000 {
+                    set.insert(i * 7);
+                }
+
                 set
             }
         }
@@ -235,6 +291,10 @@ fn bench_set_contains<S: Set>(c: &mut Criterion) {
     bench::<S, _, _>(c, SetupFor::Contains, Set::op_contains);
 }
 
+fn bench_set_contains_sparse<S: Set>(c: &mut Criterion) {
+    bench::<S, _, _>(c, SetupFor::ContainsSparse, Set::op_contains);
+}
+
 criterion_group!(
     benches,
     bench_set_insert::<HashSet<usize>>,
@@ -255,5 +315,11 @@ criterion_group!(
     bench_set_contains::<BTreeIndexSet<u128>>,
     bench_set_contains::<VecIndexSet<u64>>,
     bench_set_contains::<VecIndexSet<u128>>,
+    bench_set_contains_sparse::<HashSet<usize>>,
+    bench_set_contains_sparse::<BTreeSet<usize>>,
+    bench_set_contains_sparse::<BTreeIndexSet<u64>>,
+    bench_set_contains_sparse::<BTreeIndexSet<u128>>,
+    bench_set_contains_sparse::<VecIndexSet<u64>>,
+    bench_set_contains_sparse::<VecIndexSet<u128>>,
 );
 criterion_main!(benches);
@@ -0,0 +1,207 @@
+//! Adapters implementing [`IndexSet`] over other collections.
+
+use alloc::collections::BTreeSet;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::hash::BuildHasher;
+use core::iter::FusedIterator;
+#[cfg(feature = "std")]
+use std::collections::HashSet;
+
+use super::IndexSet;
+
+/// Adapter implementing [`IndexSet`] over a standard
+/// collection of [`usize`] values.
+///
+/// This allows code written against the [`IndexSet`] trait to
+/// operate on a [`BTreeSet`] or, with the `std` feature, on a
+/// `HashSet`, without converting the data to an index set.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct StdSetAdapter<T>(pub T);
+
+impl<T> StdSetAdapter<T> {
+    /// Return the collection wrapped by this [`StdSetAdapter`].
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> From<T> for StdSetAdapter<T> {
+    #[inline]
+    fn from(set: T) -> Self {
+        Self(set)
+    }
+}
+
+impl<T: FromIterator<usize>> FromIterator<usize> for StdSetAdapter<T> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = usize>>(iter: I) -> Self {
+        Self(T::from_iter(iter))
+    }
+}
+
+impl IndexSet for StdSetAdapter<BTreeSet<usize>> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    fn insert(&mut self, index: usize) -> bool {
+        self.0.insert(index)
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> bool {
+        self.0.remove(&index)
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    #[inline]
+    fn contains(&self, index: usize) -> bool {
+        self.0.contains(&index)
+    }
+
+    #[inline]
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        self.0.iter().copied()
+    }
+
+    #[inline]
+    fn union(&mut self, other: &Self) {
+        self.0.extend(other.0.iter().copied());
+    }
+
+    #[inline]
+    fn successor(&self, index: usize) -> Option<usize> {
+        let start = index.checked_add(1)?;
+        self.0.range(start..).next().copied()
+    }
+
+    #[inline]
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        self.0.range(..index).next_back().copied()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<H: BuildHasher> IndexSet for StdSetAdapter<HashSet<usize, H>> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    #[inline]
+    fn insert(&mut self, index: usize) -> bool {
+        self.0.insert(index)
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> bool {
+        self.0.remove(&index)
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    #[inline]
+    fn contains(&self, index: usize) -> bool {
+        self.0.contains(&index)
+    }
+
+    /// Return an iterator over the indices in this set,
+    /// in ascending order.
+    ///
+    /// The indices of the hash set are sorted
+    /// upfront, in a new allocation.
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        let mut indices: Vec<_> = self.0.iter().copied().collect();
+        indices.sort_unstable();
+        indices.into_iter()
+    }
+
+    #[inline]
+    fn union(&mut self, other: &Self) {
+        self.0.extend(other.0.iter().copied());
+    }
+
+    #[inline]
+    fn reserve(&mut self, size: usize) {
+        self.0.reserve(size);
+    }
+
+    #[inline]
+    fn successor(&self, index: usize) -> Option<usize> {
+        self.0.iter().copied().filter(|&i| i > index).min()
+    }
+
+    #[inline]
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        self.0.iter().copied().filter(|&i| i < index).max()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test the adapter over binary tree sets.
+    #[test]
+    fn test_std_set_adapter_btree_set() {
+        let mut set = StdSetAdapter::from(BTreeSet::from([6, 1, 4]));
+
+        assert!(set.iter().eq([1, 4, 6]));
+        assert_eq!(set.successor(4), Some(6));
+        assert_eq!(set.predecessor(4), Some(1));
+        assert_eq!(set.successor(usize::MAX), None);
+
+        set.union(&[2, 8].into_iter().collect());
+        set.remove(4);
+        assert!(set.iter().eq([1, 2, 6, 8]));
+        assert_eq!(set.into_inner(), BTreeSet::from([1, 2, 6, 8]));
+    }
+
+    /// Test the adapter over hash sets.
+    #[test]
+    #[cfg(feature = "std")]
+    fn test_std_set_adapter_hash_set() {
+        let mut set = StdSetAdapter::from(HashSet::<usize>::from([6, 1, 4]));
+
+        assert!(set.iter().eq([1, 4, 6]));
+        assert_eq!(set.successor(4), Some(6));
+        assert_eq!(set.predecessor(4), Some(1));
+
+        set.union(&[2, 8].into_iter().collect());
+        set.remove(4);
+        assert!(set.iter().eq([1, 2, 6, 8]));
+    }
+
+    /// Test the adapters against the reference model.
+    #[test]
+    #[cfg(feature = "test-util")]
+    fn test_std_set_adapter_random_ops() {
+        for seed in 0..8 {
+            crate::test_util::check_random_ops::<StdSetAdapter<BTreeSet<usize>>>(seed, 256, 512);
+            #[cfg(feature = "std")]
+            crate::test_util::check_random_ops::<StdSetAdapter<HashSet<usize>>>(seed, 256, 512);
+        }
+    }
+}
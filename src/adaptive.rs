@@ -0,0 +1,225 @@
+//! Compact binary representation of index sets, adapting to their density.
+//!
+//! An index set is encoded either as a sorted list of its indices,
+//! or as a list of the non-empty 64-bit lanes of its bitmap, whichever
+//! is shorter. The first byte of the encoding tells which one follows:
+//!
+//! ```text
+//! index-set = indices / lanes
+//! indices   = %x00 count *gap
+//! lanes     = %x01 count *(gap lane)
+//! ```
+//!
+//! All integers but `lane` are unsigned LEB128 varints, in their
+//! shortest form. `count` is the number of indices, or lanes, that
+//! follow. The first `gap` is the first index, or lane number, and
+//! each following `gap` is the distance to the previous index, or
+//! lane number, minus one. Each `lane` is a non-zero [`u64`], in
+//! little endian order, whose bit `B` marks the presence of index
+//! `64 * L + B`, where `L` is the lane number.
+//!
+//! Sparse sets thus spend a byte or two per index, rather than a
+//! mostly empty lane, whereas dense sets spend a bit per index.
+
+use alloc::vec::Vec;
+
+use super::error::Error;
+
+/// Number of indices in each lane of an encoded index set.
+const LANE_BITS: usize = u64::BITS as usize;
+
+/// Number of bytes of each lane of an encoded index set.
+const LANE_BYTES: usize = size_of::<u64>();
+
+/// Tag of the sorted index list encoding.
+const TAG_INDICES: u8 = 0;
+
+/// Tag of the bitmap lane list encoding.
+const TAG_LANES: u8 = 1;
+
+/// Return the length of `value`, encoded as a varint.
+#[inline]
+const fn varint_len(value: usize) -> usize {
+    let bits = usize::BITS - (value | 1).leading_zeros();
+    bits.div_ceil(7) as usize
+}
+
+/// Append `value` to `out`, encoded as a varint.
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Encode pairs of start indices and 64-bit lanes, in
+/// ascending order, choosing the shortest encoding.
+pub(crate) fn encode<I>(lanes: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = (usize, u64)>,
+{
+    let lanes: Vec<_> = lanes
+        .into_iter()
+        .map(|(start_index, lane)| (start_index / LANE_BITS, lane))
+        .collect();
+
+    let num_of_indices = lanes
+        .iter()
+        .map(|&(_, lane)| lane.count_ones() as usize)
+        .sum();
+    let mut indices_len = 1 + varint_len(num_of_indices);
+    let mut lanes_len = 1 + varint_len(lanes.len());
+    let mut next_index = 0;
+    let mut next_lane = 0;
+
+    for &(lane_number, lane) in &lanes {
+        lanes_len += varint_len(lane_number - next_lane) + LANE_BYTES;
+        next_lane = lane_number + 1;
+        for index in lane_indices(lane_number, lane) {
+            indices_len += varint_len(index - next_index);
+            next_index = index.wrapping_add(1);
+        }
+    }
+
+    let mut out = Vec::new();
+    if indices_len <= lanes_len {
+        out.reserve_exact(indices_len);
+        out.push(TAG_INDICES);
+        write_varint(&mut out, num_of_indices);
+        let mut next_index = 0;
+        for &(lane_number, lane) in &lanes {
+            for index in lane_indices(lane_number, lane) {
+                write_varint(&mut out, index - next_index);
+                next_index = index.wrapping_add(1);
+            }
+        }
+    } else {
+        out.reserve_exact(lanes_len);
+        out.push(TAG_LANES);
+        write_varint(&mut out, lanes.len());
+        let mut next_lane = 0;
+        for &(lane_number, lane) in &lanes {
+            write_varint(&mut out, lane_number - next_lane);
+            out.extend_from_slice(&lane.to_le_bytes());
+            next_lane = lane_number + 1;
+        }
+    }
+
+    out
+}
+
+/// Iterate over the indices in `lane`, whose
+/// lane number is `lane_number`, in ascending order.
+#[inline]
+fn lane_indices(lane_number: usize, lane: u64) -> impl Iterator<Item = usize> {
+    (0..LANE_BITS)
+        .filter(move |&bit| lane & (1 << bit) != 0)
+        .map(move |bit| lane_number * LANE_BITS + bit)
+}
+
+/// Reads integers from an encoded index set.
+struct Reader<'a> {
+    /// The remaining input.
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    /// Read a byte from the input.
+    #[inline]
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let (&byte, rest) = self.bytes.split_first().ok_or(Error::InvalidEncoding)?;
+        self.bytes = rest;
+        Ok(byte)
+    }
+
+    /// Read a varint from the input, rejecting those
+    /// which are not in their shortest form.
+    fn read_varint(&mut self) -> Result<usize, Error> {
+        let mut value = 0usize;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_byte()?;
+            let bits = usize::from(byte & 0x7f);
+            if shift >= usize::BITS || (bits << shift) >> shift != bits {
+                return Err(Error::IndexOutOfBounds);
+            }
+            value |= bits << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if byte == 0 && shift > 7 {
+                    return Err(Error::InvalidEncoding);
+                }
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Read a lane from the input.
+    #[inline]
+    fn read_lane(&mut self) -> Result<u64, Error> {
+        let (lane, rest) = self
+            .bytes
+            .split_first_chunk()
+            .ok_or(Error::InvalidEncoding)?;
+        self.bytes = rest;
+        Ok(u64::from_le_bytes(*lane))
+    }
+
+    /// Read `count` gaps from the input, and return the values
+    /// they lead to, in ascending order, none of which may exceed
+    /// `max`, pairing each of them with the result of `item`.
+    fn read_gaps<T, F>(&mut self, max: usize, mut item: F) -> Result<Vec<(usize, T)>, Error>
+    where
+        F: FnMut(&mut Self) -> Result<T, Error>,
+    {
+        let count = self.read_varint()?;
+        let mut items = Vec::with_capacity(count.min(self.bytes.len()));
+        let mut next = Some(0usize);
+        for _ in 0..count {
+            let gap = self.read_varint()?;
+            let value = next
+                .and_then(|next| next.checked_add(gap))
+                .filter(|&value| value <= max)
+                .ok_or(Error::IndexOutOfBounds)?;
+            items.push((value, item(self)?));
+            next = value.checked_add(1);
+        }
+        Ok(items)
+    }
+}
+
+/// Decode an index set, and return the 64-bit lanes it
+/// carries, along with their start indices, in ascending order.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Vec<(usize, u64)>, Error> {
+    let mut reader = Reader { bytes };
+    let mut lanes: Vec<(usize, u64)> = Vec::new();
+
+    match reader.read_byte()? {
+        TAG_INDICES => {
+            for (index, ()) in reader.read_gaps(usize::MAX, |_| Ok(()))? {
+                let start_index = index - index % LANE_BITS;
+                let bit = 1 << (index % LANE_BITS);
+                match lanes.last_mut() {
+                    Some((last, lane)) if *last == start_index => *lane |= bit,
+                    _ => lanes.push((start_index, bit)),
+                }
+            }
+        }
+        TAG_LANES => {
+            let max_lane_number = usize::MAX / LANE_BITS;
+            for (lane_number, lane) in reader.read_gaps(max_lane_number, Reader::read_lane)? {
+                if lane == 0 {
+                    return Err(Error::ZeroBlock);
+                }
+                lanes.push((lane_number * LANE_BITS, lane));
+            }
+        }
+        _ => return Err(Error::InvalidEncoding),
+    }
+
+    if !reader.bytes.is_empty() {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(lanes)
+}
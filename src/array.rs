@@ -0,0 +1,369 @@
+//! Fixed size index set backed by an array, usable in `const` contexts.
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::ops::Index;
+
+use super::debug_indices;
+use super::IndexSet;
+
+/// Number of bits in each word of an [`ArrayIndexSet`].
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Fixed size index set backed by an array of `WORDS` words.
+///
+/// Unlike the other index sets in this crate, an [`ArrayIndexSet`]
+/// can only store indices lower than [`ArrayIndexSet::CAPACITY`],
+/// but it can be built and queried at compile time:
+///
+/// ```
+/// use index_set::array::ArrayIndexSet;
+///
+/// const TERMINATORS: ArrayIndexSet<4> = ArrayIndexSet::from_indices(&[0x0c, 0x0d, 0xf3]);
+///
+/// const _: () = assert!(TERMINATORS.contains(0x0d));
+/// const _: () = assert!(!TERMINATORS.contains(0x0e));
+/// ```
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ArrayIndexSet<const WORDS: usize> {
+    /// Bit vectors containing the actual boolean values
+    /// to be asserted.
+    ///
+    /// If the bit `B` is set, at the bit vector with index `W`, then
+    /// the index `64 * W + B` is in the set.
+    bit_sets: [u64; WORDS],
+}
+
+impl<const WORDS: usize> ArrayIndexSet<WORDS> {
+    /// The number of indices that can be stored in this [`ArrayIndexSet`].
+    ///
+    /// All indices in the set must be lower than this value.
+    pub const CAPACITY: usize = WORDS * WORD_BITS;
+
+    /// Create a new [`ArrayIndexSet`].
+    pub const fn new() -> Self {
+        Self {
+            bit_sets: [0; WORDS],
+        }
+    }
+
+    /// Create a new [`ArrayIndexSet`] containing the given indices.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if any of the indices is out of bounds.
+    pub const fn from_indices(indices: &[usize]) -> Self {
+        let mut set = Self::new();
+        let mut i = 0;
+        while i < indices.len() {
+            set.insert(indices[i]);
+            i += 1;
+        }
+        set
+    }
+
+    /// Return the number of [`usize`] values present
+    /// in this [`ArrayIndexSet`].
+    pub const fn len(&self) -> usize {
+        let mut len = 0;
+        let mut i = 0;
+        while i < WORDS {
+            len += self.bit_sets[i].count_ones() as usize;
+            i += 1;
+        }
+        len
+    }
+
+    /// Checks if this [`ArrayIndexSet`] has no inner indexes
+    /// stored within.
+    pub const fn is_empty(&self) -> bool {
+        let mut i = 0;
+        while i < WORDS {
+            if self.bit_sets[i] != 0 {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    /// Add a new index to this [`ArrayIndexSet`].
+    ///
+    /// Returns `true` if the index was not already present.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `index` is not lower than [`ArrayIndexSet::CAPACITY`].
+    pub const fn insert(&mut self, index: usize) -> bool {
+        assert!(index < Self::CAPACITY, "index out of bounds");
+        let mask = 1 << (index % WORD_BITS);
+        let word = &mut self.bit_sets[index / WORD_BITS];
+        let added = *word & mask == 0;
+        *word |= mask;
+        added
+    }
+
+    /// Remove an index from this [`ArrayIndexSet`].
+    ///
+    /// Returns `true` if the index was present.
+    pub const fn remove(&mut self, index: usize) -> bool {
+        if index >= Self::CAPACITY {
+            return false;
+        }
+        let mask = 1 << (index % WORD_BITS);
+        let word = &mut self.bit_sets[index / WORD_BITS];
+        let removed = *word & mask != 0;
+        *word &= !mask;
+        removed
+    }
+
+    /// Remove all indices from this [`ArrayIndexSet`].
+    #[inline]
+    pub const fn clear(&mut self) {
+        self.bit_sets = [0; WORDS];
+    }
+
+    /// Check the presence of an index in this [`ArrayIndexSet`].
+    pub const fn contains(&self, index: usize) -> bool {
+        index < Self::CAPACITY && self.bit_sets[index / WORD_BITS] & (1 << (index % WORD_BITS)) != 0
+    }
+
+    /// Merge two [`ArrayIndexSet`] instances.
+    pub const fn union(&mut self, other: &Self) {
+        let mut i = 0;
+        while i < WORDS {
+            self.bit_sets[i] |= other.bit_sets[i];
+            i += 1;
+        }
+    }
+}
+
+impl<const WORDS: usize> Default for ArrayIndexSet<WORDS> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const WORDS: usize> fmt::Debug for ArrayIndexSet<WORDS> {
+    /// Format this [`ArrayIndexSet`].
+    ///
+    /// The alternate form (`{:#?}`) renders the indices stored
+    /// in the set, eliding them past a fixed cap, whereas the
+    /// default form renders the raw bit sets.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            debug_indices(self, f)
+        } else {
+            f.debug_struct("ArrayIndexSet")
+                .field("bit_sets", &self.bit_sets)
+                .finish()
+        }
+    }
+}
+
+impl<const WORDS: usize> IndexSet for ArrayIndexSet<WORDS> {
+    #[inline]
+    fn len(&self) -> usize {
+        ArrayIndexSet::len(self)
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        ArrayIndexSet::is_empty(self)
+    }
+
+    #[inline]
+    fn insert(&mut self, index: usize) -> bool {
+        ArrayIndexSet::insert(self, index)
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> bool {
+        ArrayIndexSet::remove(self, index)
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        ArrayIndexSet::clear(self)
+    }
+
+    #[inline]
+    fn contains(&self, index: usize) -> bool {
+        ArrayIndexSet::contains(self, index)
+    }
+
+    #[inline]
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        self.bit_sets
+            .iter()
+            .enumerate()
+            .flat_map(|(word_index, &set)| {
+                (0..WORD_BITS).filter_map(move |bit_set_index| {
+                    let is_bit_set = set & (1 << bit_set_index) != 0;
+                    if is_bit_set {
+                        Some(word_index * WORD_BITS + bit_set_index)
+                    } else {
+                        None
+                    }
+                })
+            })
+    }
+
+    #[inline]
+    fn union(&mut self, other: &Self) {
+        ArrayIndexSet::union(self, other)
+    }
+}
+
+impl<const WORDS: usize> Index<usize> for ArrayIndexSet<WORDS> {
+    type Output = bool;
+
+    /// Check the presence of an index in this set,
+    /// such that `set[index]` is equivalent to
+    /// `set.contains(index)`.
+    #[inline]
+    fn index(&self, index: usize) -> &bool {
+        if self.contains(index) {
+            &true
+        } else {
+            &false
+        }
+    }
+}
+
+impl<const WORDS: usize> FromIterator<usize> for ArrayIndexSet<WORDS> {
+    #[inline]
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = usize>,
+    {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<const WORDS: usize> Extend<usize> for ArrayIndexSet<WORDS> {
+    #[inline]
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = usize>,
+    {
+        for index in iter {
+            self.insert(index);
+        }
+    }
+}
+
+/// Encodes this set as its fixed size array of
+/// words, such that its encoded size is bounded.
+#[cfg(feature = "scale")]
+impl<const WORDS: usize> parity_scale_codec::Encode for ArrayIndexSet<WORDS> {
+    #[inline]
+    fn size_hint(&self) -> usize {
+        self.bit_sets.size_hint()
+    }
+
+    #[inline]
+    fn encode_to<O>(&self, dest: &mut O)
+    where
+        O: parity_scale_codec::Output + ?Sized,
+    {
+        self.bit_sets.encode_to(dest)
+    }
+}
+
+#[cfg(feature = "scale")]
+impl<const WORDS: usize> parity_scale_codec::EncodeLike for ArrayIndexSet<WORDS> {}
+
+#[cfg(feature = "scale")]
+impl<const WORDS: usize> parity_scale_codec::Decode for ArrayIndexSet<WORDS> {
+    #[inline]
+    fn decode<I>(input: &mut I) -> Result<Self, parity_scale_codec::Error>
+    where
+        I: parity_scale_codec::Input,
+    {
+        let bit_sets = parity_scale_codec::Decode::decode(input)?;
+        Ok(Self { bit_sets })
+    }
+}
+
+#[cfg(feature = "scale")]
+impl<const WORDS: usize> parity_scale_codec::MaxEncodedLen for ArrayIndexSet<WORDS> {
+    #[inline]
+    fn max_encoded_len() -> usize {
+        <[u64; WORDS]>::max_encoded_len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Set = ArrayIndexSet<4>;
+
+    /// Test const construction and queries.
+    #[test]
+    fn test_array_index_set_const() {
+        const SET: Set = {
+            let mut set = Set::from_indices(&[1, 4, 200]);
+            set.insert(63);
+            set.insert(64);
+            set.remove(4);
+            set
+        };
+        const LEN: usize = SET.len();
+
+        const { assert!(SET.contains(64)) };
+        const { assert!(!SET.contains(Set::CAPACITY)) };
+
+        assert_eq!(LEN, 4);
+        assert!(SET[200] && !SET[4]);
+        assert_eq!(SET.iter().collect::<Vec<_>>(), [1, 63, 64, 200]);
+    }
+
+    /// Test that out of bounds insertions are rejected.
+    #[test]
+    #[should_panic = "index out of bounds"]
+    fn test_array_index_set_insert_out_of_bounds() {
+        let mut set = Set::new();
+        set.insert(Set::CAPACITY);
+    }
+
+    /// Test the union method of array index sets.
+    #[test]
+    fn test_array_index_set_union() {
+        let mut set: Set = [1, 4, 6].into_iter().collect();
+        let other: Set = [4, 100, 255].into_iter().collect();
+
+        IndexSet::union(&mut set, &other);
+
+        assert_eq!(set.iter().collect::<Vec<_>>(), [1, 4, 6, 100, 255]);
+        assert!(!set.is_empty());
+        assert!(Set::new().is_empty());
+    }
+
+    /// Test SCALE encoding of array index sets.
+    #[test]
+    #[cfg(feature = "scale")]
+    fn test_array_index_set_scale() {
+        use parity_scale_codec::{Decode, Encode, MaxEncodedLen};
+
+        let set: Set = [1, 4, 6, 100, 255].into_iter().collect();
+        let bytes = set.encode();
+
+        assert_eq!(bytes.len(), Set::max_encoded_len());
+        assert_eq!(Set::max_encoded_len(), 4 * size_of::<u64>());
+        assert_eq!(Set::decode(&mut bytes.as_slice()).unwrap(), set);
+    }
+
+    /// Test array index sets against a reference implementation.
+    #[test]
+    #[cfg(feature = "test-util")]
+    fn test_array_index_set_against_model() {
+        for seed in 0..8 {
+            crate::test_util::check_random_ops::<Set>(seed, 500, Set::CAPACITY - 1);
+        }
+    }
+}
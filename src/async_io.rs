@@ -0,0 +1,182 @@
+//! Helpers to stream index sets over async I/O.
+
+use core::future::poll_fn;
+use core::pin::Pin;
+
+use futures_io::{AsyncRead, AsyncWrite};
+use std::io;
+
+use super::error::Error;
+
+/// Size, in bytes, of the chunks of encoded bit sets
+/// written at once.
+pub(crate) const CHUNK_SIZE: usize = 4096;
+
+/// Write all of `buf` to `writer`.
+pub(crate) async fn write_all<W>(writer: &mut W, mut buf: &[u8]) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    while !buf.is_empty() {
+        let written = poll_fn(|cx| Pin::new(&mut *writer).poll_write(cx, buf)).await?;
+        if written == 0 {
+            return Err(io::ErrorKind::WriteZero.into());
+        }
+        buf = &buf[written..];
+    }
+    Ok(())
+}
+
+/// Flush the buffered data of `writer`.
+#[inline]
+pub(crate) async fn flush<W>(writer: &mut W) -> io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    poll_fn(|cx| Pin::new(&mut *writer).poll_flush(cx)).await
+}
+
+/// Fill all of `buf` with data read from `reader`.
+pub(crate) async fn read_exact<R>(reader: &mut R, mut buf: &mut [u8]) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+{
+    while !buf.is_empty() {
+        let read = poll_fn(|cx| Pin::new(&mut *reader).poll_read(cx, buf)).await?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        buf = &mut buf[read..];
+    }
+    Ok(())
+}
+
+/// Create an error for invalid encoded index sets.
+#[inline]
+pub(crate) fn invalid_data(error: Error) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::Future;
+    use core::task::{Context, Poll, Waker};
+
+    use super::*;
+    use crate::vec::VecIndexSet;
+    use crate::IndexSet;
+
+    /// Drive `future` to completion on the current thread.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = core::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// Stream which moves a single byte at a time,
+    /// after first reporting that it is not ready.
+    struct Trickle<T> {
+        inner: T,
+        ready: bool,
+    }
+
+    impl<T> Trickle<T> {
+        fn new(inner: T) -> Self {
+            Self {
+                inner,
+                ready: false,
+            }
+        }
+
+        fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+            self.ready = !self.ready;
+            if self.ready {
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        }
+    }
+
+    impl<T: AsyncRead + Unpin> AsyncRead for Trickle<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            core::task::ready!(self.poll_ready(cx));
+            let len = buf.len().min(1);
+            Pin::new(&mut self.inner).poll_read(cx, &mut buf[..len])
+        }
+    }
+
+    impl<T: AsyncWrite + Unpin> AsyncWrite for Trickle<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            core::task::ready!(self.poll_ready(cx));
+            let len = buf.len().min(1);
+            Pin::new(&mut self.inner).poll_write(cx, &buf[..len])
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_close(cx)
+        }
+    }
+
+    /// Test streaming index sets in their canonical encoding.
+    #[test]
+    fn test_vec_index_set_async_round_trip() {
+        let set: VecIndexSet<u16> = (0..5000).step_by(7).chain([1 << 40]).collect();
+
+        let mut bytes = Trickle::new(Vec::new());
+        block_on(set.write_to(&mut bytes)).unwrap();
+        assert_eq!(bytes.inner, set.to_bytes().unwrap());
+
+        let read = block_on(VecIndexSet::<u16>::read_from(Trickle::new(
+            &bytes.inner[..],
+        )));
+        assert_eq!(read.unwrap(), set);
+
+        let empty = block_on(VecIndexSet::<u16>::read_from(&0u32.to_le_bytes()[..]));
+        assert!(empty.unwrap().is_empty());
+    }
+
+    /// Test rejecting invalid streamed encodings.
+    #[test]
+    fn test_vec_index_set_async_invalid() {
+        let read = |bytes: &[u8]| {
+            block_on(VecIndexSet::<u8>::read_from(bytes))
+                .unwrap_err()
+                .kind()
+        };
+
+        let set: VecIndexSet<u8> = [1, 4, 9].into_iter().collect();
+        let bytes = set.to_bytes().unwrap();
+        assert_eq!(
+            read(&bytes[..bytes.len() - 1]),
+            io::ErrorKind::UnexpectedEof
+        );
+
+        let mut unsorted = bytes.clone();
+        unsorted[4..].rotate_left(9);
+        assert_eq!(read(&unsorted), io::ErrorKind::InvalidData);
+
+        let mut overflowing = 1u32.to_le_bytes().to_vec();
+        overflowing.extend_from_slice(&u64::MAX.to_le_bytes());
+        overflowing.extend_from_slice(&1u16.to_le_bytes());
+        let read = block_on(VecIndexSet::<u16>::read_from(&overflowing[..]));
+        assert_eq!(read.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
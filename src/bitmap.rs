@@ -0,0 +1,36 @@
+//! Dense byte bitmaps of indices.
+
+/// Order of the bits within each byte of a dense bitmap.
+///
+/// In both orders, the index `8 * B + b` is held by the
+/// byte `B` of the bitmap; they differ in which bit of the
+/// byte holds `b`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BitOrder {
+    /// The least significant bit of each byte comes first,
+    /// such that index `b` is held by the bit `1 << b`.
+    #[default]
+    Lsb0,
+    /// The most significant bit of each byte comes first,
+    /// such that index `b` is held by the bit `0x80 >> b`.
+    ///
+    /// This matches the bitmaps of e.g. Redis `GETBIT`.
+    Msb0,
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+impl BitOrder {
+    /// Convert `byte` between this bit order and [`BitOrder::Lsb0`].
+    #[inline]
+    pub(crate) const fn convert_byte(self, byte: u8) -> u8 {
+        match self {
+            Self::Lsb0 => byte,
+            Self::Msb0 => byte.reverse_bits(),
+        }
+    }
+}
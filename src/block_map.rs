@@ -0,0 +1,1191 @@
+//! Ordered map of map indices to bit sets, tuned for [`usize`] keys.
+
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::mem;
+use core::ops::RangeInclusive;
+
+use alloc::vec;
+use alloc::vec::Vec;
+
+use super::storage;
+
+/// Maximum number of entries of each node of a [`BlockMap`].
+const NODE_CAP: usize = 32;
+
+/// Minimum number of entries of each node of a [`BlockMap`],
+/// other than its root.
+const NODE_MIN: usize = NODE_CAP / 4;
+
+/// Maximum height of a [`BlockMap`].
+///
+/// Nodes other than the root hold at least [`NODE_MIN`] entries,
+/// so a map of [`usize::MAX`] bit sets stays below this height.
+const MAX_HEIGHT: usize = 24;
+
+/// Ordered map of map indices to bit sets, stored as a B+tree.
+///
+/// Unlike a [`BTreeMap`], whose nodes have room for eleven entries
+/// allocated up front, each node of a [`BlockMap`] holds up to
+/// [`NODE_CAP`] map indices and bit sets in two arrays, which only
+/// grow as entries are added. Nodes are split unevenly when entries
+/// are appended to them, so that sets built in ascending order of
+/// indices are packed tightly.
+///
+/// [`BTreeMap`]: alloc::collections::BTreeMap
+#[derive(Clone)]
+pub(crate) struct BlockMap<S> {
+    /// The root node of this map.
+    root: Node<S>,
+    /// The number of branch levels above the leaves of this map.
+    height: usize,
+    /// The number of bit sets in this map.
+    len: usize,
+}
+
+/// Node of a [`BlockMap`].
+#[derive(Clone)]
+struct Node<S> {
+    /// The map indices of the bit sets of a leaf, or the first
+    /// map index of each child of a branch, in ascending order.
+    keys: Vec<usize>,
+    /// The bit sets or the children matching `keys`.
+    kind: Kind<S>,
+}
+
+/// Entries of a [`Node`].
+#[derive(Clone)]
+enum Kind<S> {
+    /// The bit sets of a leaf.
+    Leaf(Vec<S>),
+    /// The children of a branch, none of which are empty.
+    Branch(Vec<Node<S>>),
+}
+
+/// Make room for one more entry in `entries`, without ever
+/// growing its capacity past [`NODE_CAP`].
+#[inline]
+fn reserve_one<T>(entries: &mut Vec<T>) {
+    if entries.len() == entries.capacity() {
+        let capacity = (entries.capacity() * 2).clamp(4, NODE_CAP);
+        entries.reserve_exact(capacity - entries.len());
+    }
+}
+
+/// Split `entries` in two at position `at`, returning the entries
+/// from `at` onwards in a new [`Vec`] sized to fit them.
+#[inline]
+fn split_entries<T>(entries: &mut Vec<T>, at: usize) -> Vec<T> {
+    if at == 0 {
+        mem::take(entries)
+    } else {
+        entries.split_off(at)
+    }
+}
+
+/// Insert `key` and `value` at position `pos` of the entries of a
+/// node, splitting the node first if it is full, and return the
+/// entries split off the node, if any.
+fn insert_entry<T>(
+    keys: &mut Vec<usize>,
+    values: &mut Vec<T>,
+    pos: usize,
+    key: usize,
+    value: T,
+) -> Option<(Vec<usize>, Vec<T>)> {
+    if keys.len() < NODE_CAP {
+        reserve_one(keys);
+        reserve_one(values);
+        keys.insert(pos, key);
+        values.insert(pos, value);
+        return None;
+    }
+
+    // appending to a full node leaves most of its entries
+    // behind, so that ascending insertions fill nodes up
+    let appending = pos == NODE_CAP;
+    let at = if appending {
+        NODE_CAP - NODE_MIN
+    } else {
+        NODE_CAP / 2
+    };
+    let mut tail_keys = split_entries(keys, at);
+    let mut tail_values = split_entries(values, at);
+    if pos < at {
+        keys.insert(pos, key);
+        values.insert(pos, value);
+    } else {
+        reserve_one(&mut tail_keys);
+        reserve_one(&mut tail_values);
+        tail_keys.insert(pos - at, key);
+        tail_values.insert(pos - at, value);
+    }
+    if appending {
+        keys.shrink_to_fit();
+        values.shrink_to_fit();
+    }
+    Some((tail_keys, tail_values))
+}
+
+/// Return the position of the child of a branch, whose first
+/// map indices are `keys`, which holds or would hold `map_index`.
+#[inline]
+fn child_pos(keys: &[usize], map_index: usize) -> usize {
+    keys.partition_point(|&first| first <= map_index)
+        .saturating_sub(1)
+}
+
+/// Merge the children at positions `pos` and `pos + 1` of a branch,
+/// if their entries fit in a single node, or else even them out.
+fn rebalance<S>(keys: &mut Vec<usize>, children: &mut Vec<Node<S>>, pos: usize) {
+    let total = children[pos].len() + children[pos + 1].len();
+    if total <= NODE_CAP {
+        keys.remove(pos + 1);
+        let right = children.remove(pos + 1);
+        children[pos].append_node(right);
+        return;
+    }
+
+    let half = total / 2;
+    let [left, right] = &mut children[pos..=pos + 1] else {
+        unreachable!()
+    };
+    if left.len() > half {
+        let moved = left.split_tail(half);
+        let rest = mem::replace(right, moved);
+        right.append_node(rest);
+    } else {
+        let rest = right.split_tail(half - left.len());
+        let moved = mem::replace(right, rest);
+        left.append_node(moved);
+    }
+    keys[pos + 1] = right.keys[0];
+}
+
+/// Restore the invariants of the child at position `pos`
+/// of a branch, after bit sets were removed from it.
+fn fix_child<S>(keys: &mut Vec<usize>, children: &mut Vec<Node<S>>, pos: usize) {
+    if children[pos].keys.is_empty() {
+        keys.remove(pos);
+        children.remove(pos);
+        return;
+    }
+    keys[pos] = children[pos].keys[0];
+    if children[pos].len() < NODE_MIN {
+        if pos + 1 < children.len() {
+            rebalance(keys, children, pos);
+        } else if pos > 0 {
+            rebalance(keys, children, pos - 1);
+        }
+    }
+}
+
+impl<S> Node<S> {
+    /// An empty leaf.
+    const EMPTY: Self = Self {
+        keys: Vec::new(),
+        kind: Kind::Leaf(Vec::new()),
+    };
+
+    /// Return the number of entries of this [`Node`].
+    #[inline]
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Return the children of this [`Node`], which must be a branch.
+    #[inline]
+    fn children(&self) -> &[Self] {
+        match &self.kind {
+            Kind::Branch(children) => children,
+            Kind::Leaf(_) => unreachable!("leaves have no children"),
+        }
+    }
+
+    /// Return the bit sets of this [`Node`], which must be a leaf.
+    #[inline]
+    fn words(&self) -> &[S] {
+        match &self.kind {
+            Kind::Leaf(words) => words,
+            Kind::Branch(_) => unreachable!("branches hold no bit sets"),
+        }
+    }
+
+    /// Return the number of bit sets under this [`Node`].
+    fn count(&self) -> usize {
+        match &self.kind {
+            Kind::Leaf(_) => self.len(),
+            Kind::Branch(children) => children.iter().map(Self::count).sum(),
+        }
+    }
+
+    /// Return a mutable reference to the bit set at `map_index`, if any.
+    fn get_mut(&mut self, map_index: usize) -> Option<&mut S> {
+        let mut node = self;
+        loop {
+            match &mut node.kind {
+                Kind::Leaf(words) => {
+                    let pos = node.keys.binary_search(&map_index).ok()?;
+                    return Some(&mut words[pos]);
+                }
+                Kind::Branch(children) => node = &mut children[child_pos(&node.keys, map_index)],
+            }
+        }
+    }
+
+    /// Return a mutable reference to the first (or last, if `last`
+    /// is set) bit set under this [`Node`], along with its map index.
+    fn edge_mut(&mut self, last: bool) -> Option<(usize, &mut S)> {
+        let mut node = self;
+        loop {
+            let pos = if last { node.len().checked_sub(1)? } else { 0 };
+            match &mut node.kind {
+                Kind::Leaf(words) => return Some((*node.keys.get(pos)?, &mut words[pos])),
+                Kind::Branch(children) => node = &mut children[pos],
+            }
+        }
+    }
+
+    /// Insert `set` at `map_index`, returning the previous bit set
+    /// at `map_index`, if any, and the node split off this one to
+    /// make room for `set`, if any.
+    fn insert(&mut self, map_index: usize, set: S) -> (Option<S>, Option<Self>) {
+        match &mut self.kind {
+            Kind::Leaf(words) => match self.keys.binary_search(&map_index) {
+                Ok(pos) => (Some(mem::replace(&mut words[pos], set)), None),
+                Err(pos) => {
+                    let split = insert_entry(&mut self.keys, words, pos, map_index, set);
+                    (None, split.map(|(keys, words)| Self::leaf(keys, words)))
+                }
+            },
+            Kind::Branch(children) => {
+                let pos = child_pos(&self.keys, map_index);
+                let (old, split) = children[pos].insert(map_index, set);
+                self.keys[pos] = children[pos].keys[0];
+                let split = split.and_then(|node| {
+                    insert_entry(&mut self.keys, children, pos + 1, node.keys[0], node)
+                });
+                (
+                    old,
+                    split.map(|(keys, children)| Self::branch(keys, children)),
+                )
+            }
+        }
+    }
+
+    /// Remove the bit set at `map_index`, returning it, if any.
+    fn remove(&mut self, map_index: usize) -> Option<S> {
+        match &mut self.kind {
+            Kind::Leaf(words) => {
+                let pos = self.keys.binary_search(&map_index).ok()?;
+                self.keys.remove(pos);
+                Some(words.remove(pos))
+            }
+            Kind::Branch(children) => {
+                let pos = child_pos(&self.keys, map_index);
+                let set = children[pos].remove(map_index)?;
+                fix_child(&mut self.keys, children, pos);
+                Some(set)
+            }
+        }
+    }
+
+    /// Attach `node`, of height `node_height`, after the last (or before
+    /// the first, unless `last` is set) node of that height under this
+    /// [`Node`], of height `height`, returning the node split off this
+    /// one to make room for `node`, if any.
+    fn attach(
+        &mut self,
+        height: usize,
+        node: Self,
+        node_height: usize,
+        last: bool,
+    ) -> Option<Self> {
+        let Kind::Branch(children) = &mut self.kind else {
+            unreachable!("nodes are attached to branches")
+        };
+        let (pos, node) = if height == node_height + 1 {
+            (if last { children.len() } else { 0 }, node)
+        } else {
+            let pos = if last { children.len() - 1 } else { 0 };
+            let split = children[pos].attach(height - 1, node, node_height, last);
+            self.keys[pos] = children[pos].keys[0];
+            (pos + 1, split?)
+        };
+        insert_entry(&mut self.keys, children, pos, node.keys[0], node)
+            .map(|(keys, children)| Self::branch(keys, children))
+    }
+
+    /// Split this [`Node`] in two at `map_index`, returning a node of
+    /// the same height with the bit sets at `map_index` and above.
+    fn split_off(&mut self, map_index: usize) -> Self {
+        let pos = self.keys.partition_point(|&key| key < map_index);
+        let part = match &mut self.kind {
+            Kind::Leaf(_) => None,
+            Kind::Branch(children) => pos
+                .checked_sub(1)
+                .map(|prev| children[prev].split_off(map_index))
+                .filter(|part| !part.keys.is_empty()),
+        };
+        let mut tail = self.split_tail(pos);
+        if let (Some(part), Kind::Branch(children)) = (part, &mut tail.kind) {
+            reserve_one(&mut tail.keys);
+            reserve_one(children);
+            tail.keys.insert(0, part.keys[0]);
+            children.insert(0, part);
+        }
+        tail
+    }
+
+    /// Split the entries of this [`Node`] in two at position `at`,
+    /// returning a node with the entries from `at` onwards.
+    fn split_tail(&mut self, at: usize) -> Self {
+        let keys = split_entries(&mut self.keys, at);
+        match &mut self.kind {
+            Kind::Leaf(words) => Self::leaf(keys, split_entries(words, at)),
+            Kind::Branch(children) => Self::branch(keys, split_entries(children, at)),
+        }
+    }
+
+    /// Move the entries of `other`, a node of the same height whose
+    /// map indices are all greater, to the end of this [`Node`].
+    fn append_node(&mut self, other: Self) {
+        self.keys.reserve_exact(other.len());
+        self.keys.extend(other.keys);
+        match (&mut self.kind, other.kind) {
+            (Kind::Leaf(words), Kind::Leaf(other)) => {
+                words.reserve_exact(other.len());
+                words.extend(other);
+            }
+            (Kind::Branch(children), Kind::Branch(other)) => {
+                children.reserve_exact(other.len());
+                children.extend(other);
+            }
+            _ => unreachable!("nodes of different heights"),
+        }
+    }
+
+    /// Call `f` on each map index and bit set under this
+    /// [`Node`], in ascending order, consuming it.
+    fn into_each<F: FnMut(usize, S)>(self, f: &mut F) {
+        match self.kind {
+            Kind::Leaf(words) => {
+                for (map_index, set) in self.keys.into_iter().zip(words) {
+                    f(map_index, set);
+                }
+            }
+            Kind::Branch(children) => {
+                for child in children {
+                    child.into_each(f);
+                }
+            }
+        }
+    }
+
+    /// Create a new leaf [`Node`].
+    #[inline]
+    const fn leaf(keys: Vec<usize>, words: Vec<S>) -> Self {
+        Self {
+            keys,
+            kind: Kind::Leaf(words),
+        }
+    }
+
+    /// Create a new branch [`Node`].
+    #[inline]
+    const fn branch(keys: Vec<usize>, children: Vec<Self>) -> Self {
+        Self {
+            keys,
+            kind: Kind::Branch(children),
+        }
+    }
+}
+
+impl<S> Default for BlockMap<S> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> BlockMap<S> {
+    /// Create a new, empty [`BlockMap`].
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        Self {
+            root: Node::EMPTY,
+            height: 0,
+            len: 0,
+        }
+    }
+
+    /// Check if this [`BlockMap`] holds no bit sets.
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Remove all bit sets from this [`BlockMap`].
+    #[inline]
+    pub(crate) fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Return a reference to the bit set at `map_index`, if any.
+    #[inline]
+    pub(crate) fn get(&self, map_index: usize) -> Option<&S> {
+        let mut node = &self.root;
+        for _ in 0..self.height {
+            node = &node.children()[child_pos(&node.keys, map_index)];
+        }
+        let pos = node.keys.binary_search(&map_index).ok()?;
+        Some(&node.words()[pos])
+    }
+
+    /// Insert `set` at `map_index`, returning
+    /// the previous bit set at `map_index`, if any.
+    pub(crate) fn insert(&mut self, map_index: usize, set: S) -> Option<S> {
+        let (old, split) = self.root.insert(map_index, set);
+        if old.is_none() {
+            self.len += 1;
+        }
+        if let Some(node) = split {
+            self.grow(node);
+        }
+        old
+    }
+
+    /// Remove the bit set at `map_index`, returning it, if any.
+    pub(crate) fn remove(&mut self, map_index: usize) -> Option<S> {
+        let set = self.root.remove(map_index)?;
+        self.len -= 1;
+        self.shrink();
+        Some(set)
+    }
+
+    /// Append `set` at `map_index`, which must be greater than
+    /// the map indices of all other bit sets of this [`BlockMap`].
+    #[inline]
+    pub(crate) fn push(&mut self, map_index: usize, set: S) {
+        debug_assert!(self.last_key().is_none_or(|last| last < map_index));
+        self.insert(map_index, set);
+    }
+
+    /// Return the greatest map index of this [`BlockMap`], if any.
+    #[inline]
+    pub(crate) fn last_key(&self) -> Option<usize> {
+        let mut node = &self.root;
+        for _ in 0..self.height {
+            node = node.children().last()?;
+        }
+        node.keys.last().copied()
+    }
+
+    /// Return a mutable reference to the first bit set of this
+    /// [`BlockMap`], along with its map index, if any.
+    #[inline]
+    pub(crate) fn first_mut(&mut self) -> Option<(usize, &mut S)> {
+        self.root.edge_mut(false)
+    }
+
+    /// Return a mutable reference to the last bit set of this
+    /// [`BlockMap`], along with its map index, if any.
+    #[inline]
+    pub(crate) fn last_mut(&mut self) -> Option<(usize, &mut S)> {
+        self.root.edge_mut(true)
+    }
+
+    /// Remove the first bit set of this [`BlockMap`], if any.
+    #[inline]
+    pub(crate) fn pop_first(&mut self) -> Option<(usize, S)> {
+        let (map_index, _) = self.first_mut()?;
+        Some((map_index, self.remove(map_index)?))
+    }
+
+    /// Remove the last bit set of this [`BlockMap`], if any.
+    #[inline]
+    pub(crate) fn pop_last(&mut self) -> Option<(usize, S)> {
+        let map_index = self.last_key()?;
+        Some((map_index, self.remove(map_index)?))
+    }
+
+    /// Split this [`BlockMap`] in two at `map_index`, returning
+    /// the bit sets at `map_index` and above.
+    pub(crate) fn split_off(&mut self, map_index: usize) -> Self {
+        let mut tail = Self {
+            root: self.root.split_off(map_index),
+            height: self.height,
+            len: 0,
+        };
+        tail.len = tail.root.count();
+        self.len -= tail.len;
+        self.fix_border(true);
+        tail.fix_border(false);
+        tail
+    }
+
+    /// Move the bit sets of `other`, whose map indices must all be
+    /// greater than those of this [`BlockMap`], to its end.
+    pub(crate) fn append(&mut self, other: Self) {
+        debug_assert!(self
+            .last_key()
+            .is_none_or(|last| other.iter().next().is_none_or(|(first, _)| last < first)));
+        if other.is_empty() {
+            return;
+        }
+        if self.is_empty() {
+            *self = other;
+            return;
+        }
+
+        self.len += other.len;
+        let split = match self.height.cmp(&other.height) {
+            Ordering::Equal => Some(other.root),
+            Ordering::Greater => self
+                .root
+                .attach(self.height, other.root, other.height, true),
+            Ordering::Less => {
+                let root = mem::replace(&mut self.root, other.root);
+                let split = self.root.attach(other.height, root, self.height, false);
+                self.height = other.height;
+                split
+            }
+        };
+        if let Some(node) = split {
+            self.grow(node);
+        }
+        self.fix_border(true);
+        self.fix_border(false);
+    }
+
+    /// Update each bit set of this [`BlockMap`] with `f`, in
+    /// ascending order of map indices, keeping only those
+    /// for which `f` returns `true`.
+    pub(crate) fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &mut S) -> bool,
+    {
+        let map = mem::take(self);
+        map.root.into_each(&mut |map_index, mut set| {
+            if f(map_index, &mut set) {
+                self.push(map_index, set);
+            }
+        });
+    }
+
+    /// Put the root of this [`BlockMap`] and `sibling`,
+    /// which was split off it, under a new root.
+    fn grow(&mut self, sibling: Node<S>) {
+        let root = mem::replace(&mut self.root, Node::EMPTY);
+        self.root = Node::branch(vec![root.keys[0], sibling.keys[0]], vec![root, sibling]);
+        self.height += 1;
+        debug_assert!(self.height < MAX_HEIGHT);
+    }
+
+    /// Replace the root of this [`BlockMap`] by its child,
+    /// for as long as it is a branch with a single child.
+    fn shrink(&mut self) {
+        while let Kind::Branch(children) = &mut self.root.kind {
+            match children.len() {
+                0 => {
+                    self.root = Node::EMPTY;
+                    self.height = 0;
+                }
+                1 => {
+                    self.root = children.pop().unwrap_or(Node::EMPTY);
+                    self.height -= 1;
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Restore the invariants of the nodes on the last (or first,
+    /// unless `last` is set) border of this [`BlockMap`], after it
+    /// was split or joined with another map.
+    ///
+    /// Each node on the border is left with more than [`NODE_MIN`]
+    /// entries, so that merging its last (or first) child with a
+    /// sibling leaves it with at least [`NODE_MIN`] entries.
+    fn fix_border(&mut self, last: bool) {
+        self.shrink();
+        let mut node = &mut self.root;
+        while let Kind::Branch(children) = &mut node.kind {
+            let pos = if last { children.len() - 1 } else { 0 };
+            if children.len() > 1 && children[pos].len() <= NODE_MIN {
+                rebalance(&mut node.keys, children, pos.saturating_sub(1));
+            }
+            let pos = if last { children.len() - 1 } else { 0 };
+            node = &mut children[pos];
+        }
+        self.shrink();
+    }
+}
+
+impl<S: storage::Storage> BlockMap<S> {
+    /// Update the bit set at `map_index` with `f`, starting from an
+    /// empty bit set if there is none, and remove it if left empty.
+    #[inline]
+    pub(crate) fn update<F>(&mut self, map_index: usize, f: F)
+    where
+        F: FnOnce(&mut S),
+    {
+        match self.root.get_mut(map_index) {
+            Some(set) => {
+                f(set);
+                if *set == S::ZERO {
+                    self.remove(map_index);
+                }
+            }
+            None => {
+                let mut set = S::ZERO;
+                f(&mut set);
+                if set != S::ZERO {
+                    self.insert(map_index, set);
+                }
+            }
+        }
+    }
+}
+
+impl<S> BlockMap<S> {
+    /// Iterate over the map indices and bit sets
+    /// of this [`BlockMap`], in ascending order.
+    #[inline]
+    pub(crate) fn iter(&self) -> Iter<'_, S> {
+        Iter {
+            range: self.range(0..=usize::MAX),
+            remaining: self.len,
+        }
+    }
+
+    /// Iterate over the map indices and bit sets of this [`BlockMap`]
+    /// whose map indices lie within `map_range`, in ascending order.
+    pub(crate) fn range(&self, map_range: RangeInclusive<usize>) -> Range<'_, S> {
+        let (start, end) = (*map_range.start(), *map_range.end());
+        let (front, leaf) = Cursor::seek(&self.root, self.height, start);
+        let front = front.skip(leaf.keys.partition_point(|&key| key < start), leaf);
+        let (back, leaf) = Cursor::seek(&self.root, self.height, end);
+        let back = back.take(leaf.keys.partition_point(|&key| key <= end), leaf);
+        Range {
+            root: &self.root,
+            height: self.height,
+            front,
+            back,
+            start,
+            end,
+            done: self.is_empty() || start > end,
+        }
+    }
+}
+
+impl<S> FromIterator<(usize, S)> for BlockMap<S> {
+    /// Build a [`BlockMap`] out of pairs of map indices and bit sets,
+    /// keeping the last bit set of repeated map indices.
+    fn from_iter<I: IntoIterator<Item = (usize, S)>>(iter: I) -> Self {
+        let mut pairs: Vec<_> = iter.into_iter().collect();
+        pairs.sort_by_key(|&(map_index, _)| map_index);
+
+        let mut map = Self::new();
+        let mut pairs = pairs.into_iter().peekable();
+        while let Some((map_index, mut set)) = pairs.next() {
+            while let Some((_, next)) = pairs.next_if(|&(i, _)| i == map_index) {
+                set = next;
+            }
+            map.push(map_index, set);
+        }
+        map
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<S> zeroize::Zeroize for Node<S> {
+    fn zeroize(&mut self) {
+        self.keys.zeroize();
+        match &mut self.kind {
+            Kind::Leaf(words) => {
+                words.clear();
+                words.spare_capacity_mut().zeroize();
+            }
+            Kind::Branch(children) => {
+                children.iter_mut().for_each(zeroize::Zeroize::zeroize);
+                children.clear();
+            }
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl<S> zeroize::Zeroize for BlockMap<S> {
+    fn zeroize(&mut self) {
+        self.root.zeroize();
+        self.clear();
+    }
+}
+
+impl<S: PartialEq> PartialEq for BlockMap<S> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len && self.iter().eq(other.iter())
+    }
+}
+
+impl<S: Eq> Eq for BlockMap<S> {}
+
+impl<S: PartialOrd> PartialOrd for BlockMap<S> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<S: Ord> Ord for BlockMap<S> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<S: Hash> Hash for BlockMap<S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len.hash(state);
+        for block in self.iter() {
+            block.hash(state);
+        }
+    }
+}
+
+impl<S: fmt::Debug> fmt::Debug for BlockMap<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// Serialize a [`BlockMap`] as a map, like a [`BTreeMap`] would be.
+///
+/// [`BTreeMap`]: alloc::collections::BTreeMap
+#[cfg(feature = "serialize-serde")]
+impl<S: serde::Serialize> serde::Serialize for BlockMap<S> {
+    #[inline]
+    fn serialize<Z: serde::Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+/// Serialize a [`BlockMap`] like a [`BTreeMap`] would be.
+///
+/// [`BTreeMap`]: alloc::collections::BTreeMap
+#[cfg(feature = "serialize-borsh")]
+impl<S: borsh::BorshSerialize> borsh::BorshSerialize for BlockMap<S> {
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        let len = u32::try_from(self.len).map_err(|_| crate::error::Error::LimitExceeded)?;
+        borsh::BorshSerialize::serialize(&len, writer)?;
+        for (map_index, set) in self.iter() {
+            borsh::BorshSerialize::serialize(&map_index, writer)?;
+            borsh::BorshSerialize::serialize(set, writer)?;
+        }
+        Ok(())
+    }
+}
+
+/// Describe a [`BlockMap`] with the schema of a [`BTreeMap`],
+/// which shares its borsh representation.
+///
+/// [`BTreeMap`]: alloc::collections::BTreeMap
+#[cfg(feature = "serialize-borsh")]
+impl<S: borsh::BorshSchema> borsh::BorshSchema for BlockMap<S> {
+    fn add_definitions_recursively(
+        definitions: &mut alloc::collections::BTreeMap<
+            borsh::schema::Declaration,
+            borsh::schema::Definition,
+        >,
+    ) {
+        <alloc::collections::BTreeMap<usize, S>>::add_definitions_recursively(definitions);
+    }
+
+    fn declaration() -> borsh::schema::Declaration {
+        <alloc::collections::BTreeMap<usize, S>>::declaration()
+    }
+}
+
+/// Position of an iterator within a [`BlockMap`].
+struct Cursor<'a, S> {
+    /// The position of the current leaf among the children
+    /// of each branch on the way down from the root.
+    path: [u8; MAX_HEIGHT],
+    /// The map indices left to yield from the current leaf.
+    keys: &'a [usize],
+    /// The bit sets matching `keys`.
+    words: &'a [S],
+}
+
+impl<S> Clone for Cursor<'_, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for Cursor<'_, S> {}
+
+impl<'a, S> Cursor<'a, S> {
+    /// Position a new [`Cursor`] on the leaf under `root`, a node of
+    /// height `height`, which holds or would hold `map_index`, and
+    /// return it along with that leaf.
+    fn seek(root: &'a Node<S>, height: usize, map_index: usize) -> (Self, &'a Node<S>) {
+        let mut path = [0; MAX_HEIGHT];
+        let mut node = root;
+        for step in &mut path[..height] {
+            let pos = child_pos(&node.keys, map_index);
+            *step = pos as u8;
+            node = &node.children()[pos];
+        }
+        let cursor = Self {
+            path,
+            keys: &[],
+            words: &[],
+        };
+        (cursor, node)
+    }
+
+    /// Yield the entries of `leaf` from position `pos` onwards.
+    #[inline]
+    fn skip(self, pos: usize, leaf: &'a Node<S>) -> Self {
+        Self {
+            keys: &leaf.keys[pos..],
+            words: &leaf.words()[pos..],
+            ..self
+        }
+    }
+
+    /// Yield the entries of `leaf` up to position `pos`.
+    #[inline]
+    fn take(self, pos: usize, leaf: &'a Node<S>) -> Self {
+        Self {
+            keys: &leaf.keys[..pos],
+            words: &leaf.words()[..pos],
+            ..self
+        }
+    }
+
+    /// Move this [`Cursor`] to the next leaf under `root`, or to the
+    /// previous one unless `forward` is set, returning `false` if
+    /// there is no such leaf.
+    fn step(&mut self, root: &'a Node<S>, height: usize, forward: bool) -> bool {
+        let mut node = root;
+        let mut level = None;
+        for (depth, &pos) in self.path[..height].iter().enumerate() {
+            let children = node.children();
+            let pos = usize::from(pos);
+            if forward && pos + 1 < children.len() || !forward && pos > 0 {
+                level = Some(depth);
+            }
+            node = &children[pos];
+        }
+        let Some(level) = level else {
+            return false;
+        };
+
+        let mut node = root;
+        for (depth, step) in self.path[..height].iter_mut().enumerate() {
+            let children = node.children();
+            let pos = match depth.cmp(&level) {
+                Ordering::Less => usize::from(*step),
+                Ordering::Equal if forward => usize::from(*step) + 1,
+                Ordering::Equal => usize::from(*step) - 1,
+                Ordering::Greater if forward => 0,
+                Ordering::Greater => children.len() - 1,
+            };
+            *step = pos as u8;
+            node = &children[pos];
+        }
+        self.keys = &node.keys;
+        self.words = node.words();
+        true
+    }
+}
+
+/// Iterator over the map indices and bit sets of a
+/// [`BlockMap`] within a range of map indices.
+pub(crate) struct Range<'a, S> {
+    /// The root node of the map.
+    root: &'a Node<S>,
+    /// The height of the map.
+    height: usize,
+    /// The position of the next bit set.
+    front: Cursor<'a, S>,
+    /// The position past the last bit set.
+    back: Cursor<'a, S>,
+    /// The lowest map index left to yield.
+    start: usize,
+    /// The greatest map index left to yield.
+    end: usize,
+    /// Whether all bit sets were yielded.
+    done: bool,
+}
+
+impl<S> Clone for Range<'_, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self { ..*self }
+    }
+}
+
+impl<'a, S> Iterator for Range<'a, S> {
+    type Item = (usize, &'a S);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, &'a S)> {
+        if self.done {
+            return None;
+        }
+        while self.front.keys.is_empty() {
+            if !self.front.step(self.root, self.height, true) {
+                self.done = true;
+                return None;
+            }
+        }
+        let map_index = self.front.keys[0];
+        let set = &self.front.words[0];
+        if map_index > self.end {
+            self.done = true;
+            return None;
+        }
+        self.front.keys = &self.front.keys[1..];
+        self.front.words = &self.front.words[1..];
+        if map_index == self.end {
+            self.done = true;
+        } else {
+            self.start = map_index + 1;
+        }
+        Some((map_index, set))
+    }
+}
+
+impl<'a, S> DoubleEndedIterator for Range<'a, S> {
+    #[inline]
+    fn next_back(&mut self) -> Option<(usize, &'a S)> {
+        if self.done {
+            return None;
+        }
+        while self.back.keys.is_empty() {
+            if !self.back.step(self.root, self.height, false) {
+                self.done = true;
+                return None;
+            }
+        }
+        let last = self.back.keys.len() - 1;
+        let map_index = self.back.keys[last];
+        let set = &self.back.words[last];
+        if map_index < self.start {
+            self.done = true;
+            return None;
+        }
+        self.back.keys = &self.back.keys[..last];
+        self.back.words = &self.back.words[..last];
+        if map_index == self.start {
+            self.done = true;
+        } else {
+            self.end = map_index - 1;
+        }
+        Some((map_index, set))
+    }
+}
+
+impl<S> FusedIterator for Range<'_, S> {}
+
+/// Iterator over the map indices and bit sets of a [`BlockMap`].
+pub(crate) struct Iter<'a, S> {
+    /// The bit sets left to iterate over.
+    range: Range<'a, S>,
+    /// The number of bit sets left to yield.
+    remaining: usize,
+}
+
+impl<S> Clone for Iter<'_, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            range: self.range.clone(),
+            ..*self
+        }
+    }
+}
+
+impl<'a, S> Iterator for Iter<'a, S> {
+    type Item = (usize, &'a S);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, &'a S)> {
+        let block = self.range.next()?;
+        self.remaining -= 1;
+        Some(block)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, S> DoubleEndedIterator for Iter<'a, S> {
+    #[inline]
+    fn next_back(&mut self) -> Option<(usize, &'a S)> {
+        let block = self.range.next_back()?;
+        self.remaining -= 1;
+        Some(block)
+    }
+}
+
+impl<S> ExactSizeIterator for Iter<'_, S> {}
+
+impl<S> FusedIterator for Iter<'_, S> {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeMap;
+
+    use super::*;
+
+    /// Check the invariants of `node`, of height `height`, returning
+    /// the number of bit sets under it.
+    fn check_node(node: &Node<u32>, height: usize, is_root: bool) -> usize {
+        assert!(node.len() <= NODE_CAP);
+        assert!(node.keys.capacity() <= NODE_CAP);
+        assert!(is_root || node.len() >= NODE_MIN);
+        assert!(node.keys.windows(2).all(|pair| pair[0] < pair[1]));
+        match &node.kind {
+            Kind::Leaf(words) => {
+                assert_eq!(height, 0);
+                assert_eq!(words.len(), node.len());
+                assert!(words.capacity() <= NODE_CAP);
+                node.len()
+            }
+            Kind::Branch(children) => {
+                assert!(height > 0);
+                assert_eq!(children.len(), node.len());
+                let mut count = 0;
+                for (child, &first) in children.iter().zip(&node.keys) {
+                    assert_eq!(child.keys[0], first);
+                    count += check_node(child, height - 1, false);
+                }
+                count
+            }
+        }
+    }
+
+    /// Check that `map` holds the same bit sets as `expected`,
+    /// and that its nodes are consistent.
+    fn assert_same(map: &BlockMap<u32>, expected: &BTreeMap<usize, u32>) {
+        assert_eq!(map.iter().len(), expected.len());
+        assert!(map.iter().eq(expected.iter().map(|(&k, v)| (k, v))));
+        assert!(map
+            .iter()
+            .rev()
+            .eq(expected.iter().rev().map(|(&k, v)| (k, v))));
+        assert_eq!(check_node(&map.root, map.height, true), expected.len());
+        assert!(map.height == 0 || map.root.len() > 1);
+    }
+
+    /// Test the map against a [`BTreeMap`], over a pseudo-random
+    /// sequence of operations.
+    #[test]
+    fn test_block_map_against_btree_map() {
+        let mut map = BlockMap::new();
+        let mut expected = BTreeMap::new();
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+
+        for step in 0..40_000u32 {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            let key = (state % 10_000) as usize;
+
+            if state.is_multiple_of(3) {
+                assert_eq!(map.remove(key), expected.remove(&key));
+            } else {
+                assert_eq!(map.insert(key, step), expected.insert(key, step));
+            }
+            assert_eq!(map.get(key), expected.get(&key));
+
+            if step.is_multiple_of(5_000) {
+                assert_same(&map, &expected);
+                let (lo, hi) = (key / 2, key + 1_000);
+                assert!(map
+                    .range(lo..=hi)
+                    .eq(expected.range(lo..=hi).map(|(&k, v)| (k, v))));
+                assert!(map
+                    .range(lo..=hi)
+                    .rev()
+                    .eq(expected.range(lo..=hi).rev().map(|(&k, v)| (k, v))));
+            }
+        }
+        assert_same(&map, &expected);
+
+        map.retain(|key, set| {
+            *set += 1;
+            key % 3 != 0
+        });
+        expected.retain(|key, _| key % 3 != 0);
+        expected.values_mut().for_each(|set| *set += 1);
+        assert_same(&map, &expected);
+
+        for at in [5_000, 7_000, 2_000, 0, 20_000] {
+            let mut tail = map.split_off(at);
+            let mut expected_tail = expected.split_off(&at);
+            assert_same(&map, &expected);
+            assert_same(&tail, &expected_tail);
+
+            assert_eq!(map.pop_first(), expected.pop_first());
+            assert_eq!(tail.pop_last(), expected_tail.pop_last());
+            map.append(tail);
+            expected.append(&mut expected_tail);
+            assert_same(&map, &expected);
+        }
+
+        let collected: BlockMap<u32> = expected.iter().map(|(&k, &v)| (k, v)).collect();
+        assert_eq!(collected, map);
+    }
+
+    /// Test joining maps of different heights, and iterating
+    /// from both ends of the joined map at once.
+    #[test]
+    fn test_block_map_append_heights() {
+        for (low, high) in [(1, 5_000), (5_000, 1), (3_000, 3_000), (40, 900)] {
+            let mut map: BlockMap<u32> = (0..low).map(|k| (k, k as u32)).collect();
+            let tail: BlockMap<u32> = (low..low + high).map(|k| (k, k as u32)).collect();
+            map.append(tail);
+
+            let expected: BTreeMap<usize, u32> = (0..low + high).map(|k| (k, k as u32)).collect();
+            assert_same(&map, &expected);
+
+            let mut iter = map.iter();
+            let mut seen = 0;
+            while let (Some((front, _)), Some((back, _))) = (iter.next(), iter.next_back()) {
+                assert_eq!(front, seen);
+                assert_eq!(back, low + high - 1 - seen);
+                seen += 1;
+            }
+            assert_eq!(iter.len(), 0);
+            assert_eq!(seen, (low + high) / 2);
+        }
+    }
+
+    /// Test that nodes filled in ascending order of
+    /// map indices are packed beyond the minimum fill.
+    #[test]
+    fn test_block_map_ascending_fill() {
+        let map: BlockMap<u32> = (0..10_000).map(|k| (k, 1)).collect();
+        let mut leaves = 0;
+        let mut node_stack = vec![&map.root];
+        while let Some(node) = node_stack.pop() {
+            match &node.kind {
+                Kind::Leaf(_) => leaves += 1,
+                Kind::Branch(children) => node_stack.extend(children),
+            }
+        }
+        assert!(leaves <= 10_000 / (NODE_CAP - NODE_MIN) + 1);
+    }
+}
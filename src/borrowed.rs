@@ -0,0 +1,285 @@
+//! Zero-copy views over encoded index sets.
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::marker::PhantomData;
+
+#[cfg(feature = "serialize-serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::error::Error;
+use super::iter::Indices;
+use super::storage;
+use super::vec::VecIndexSet;
+
+/// Size, in bytes, of the header holding the number of bit sets.
+const HEADER_SIZE: usize = size_of::<u32>();
+
+/// Size, in bytes, of an encoded map index.
+const KEY_SIZE: usize = size_of::<u64>();
+
+/// Read-only view over an index set encoded with
+/// [`VecIndexSet::to_bytes`], which is never copied.
+///
+/// With the `serialize-serde` feature, a [`VecIndexSetRef`] can be
+/// deserialized from formats that lend out contiguous bytes, such
+/// as data read from memory mapped storage.
+pub struct VecIndexSetRef<'a, S = u64> {
+    /// The encoded index set, header included.
+    bytes: &'a [u8],
+    /// The storage type of the bit sets.
+    _storage: PhantomData<S>,
+}
+
+impl<S> Clone for VecIndexSetRef<'_, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for VecIndexSetRef<'_, S> {}
+
+impl<'a, S: storage::Storage + 'a> VecIndexSetRef<'a, S> {
+    /// Size, in bytes, of an encoded pair of map index and bit set.
+    const PAIR_SIZE: usize = KEY_SIZE + S::WIDTH;
+
+    /// Validate `bytes` as an encoded index set, and
+    /// create a new [`VecIndexSetRef`] over them.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Self, Error> {
+        let (header, encoded_blocks) = bytes
+            .split_first_chunk::<HEADER_SIZE>()
+            .ok_or(Error::InvalidEncoding)?;
+        let num_of_blocks = u32::from_le_bytes(*header) as usize;
+        if num_of_blocks.checked_mul(Self::PAIR_SIZE) != Some(encoded_blocks.len()) {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let set = Self {
+            bytes,
+            _storage: PhantomData,
+        };
+        let mut last_key = None;
+        for i in 0..num_of_blocks {
+            let key = set.raw_key(i);
+            if !usize::try_from(key).is_ok_and(|key| key <= crate::max_map_index::<S>()) {
+                return Err(Error::IndexOutOfBounds);
+            }
+            if last_key.is_some_and(|last_key| last_key >= key) {
+                return Err(Error::UnsortedBlocks);
+            }
+            last_key = Some(key);
+        }
+
+        Ok(set)
+    }
+
+    /// Return the number of indices present
+    /// in this [`VecIndexSetRef`].
+    pub fn len(&self) -> usize {
+        self.blocks().map(|(_, set)| set.num_of_high_bits()).sum()
+    }
+
+    /// Checks if this [`VecIndexSetRef`] has no
+    /// indices stored within.
+    ///
+    /// Encoded bit sets may be empty, hence this
+    /// checks bit sets until a non-empty one is found.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.blocks().all(|(_, set)| set == S::ZERO)
+    }
+
+    /// Check the presence of an index in this [`VecIndexSetRef`].
+    pub fn contains(&self, index: usize) -> bool {
+        let (map_index, bit_set_index) = crate::calculate_map_and_set_indices::<S>(index);
+        let map_index = map_index as u64;
+
+        let (mut low, mut high) = (0, self.num_of_blocks());
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match self.raw_key(mid).cmp(&map_index) {
+                core::cmp::Ordering::Less => low = mid + 1,
+                core::cmp::Ordering::Greater => high = mid,
+                core::cmp::Ordering::Equal => {
+                    return self.word(mid) & S::from_usize(1 << bit_set_index) != S::ZERO;
+                }
+            }
+        }
+
+        false
+    }
+
+    /// Return an iterator over the indices in
+    /// this [`VecIndexSetRef`], in ascending order.
+    pub fn iter(&self) -> impl FusedIterator<Item = usize> + 'a {
+        Indices::new(self.blocks())
+    }
+
+    /// Copy the indices of this [`VecIndexSetRef`] into a new
+    /// [`VecIndexSet`].
+    pub fn to_owned(&self) -> VecIndexSet<S> {
+        let mut set = VecIndexSet::with_capacity(self.num_of_blocks());
+        set.merge_blocks(self.blocks().filter(|&(_, set)| set != S::ZERO));
+        set
+    }
+
+    /// Return the encoded bytes of this [`VecIndexSetRef`].
+    #[inline]
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.bytes
+    }
+
+    /// Return the number of bit sets in this [`VecIndexSetRef`].
+    #[inline]
+    fn num_of_blocks(&self) -> usize {
+        (self.bytes.len() - HEADER_SIZE) / Self::PAIR_SIZE
+    }
+
+    /// Return the map index of the `i`-th bit set.
+    #[inline]
+    fn raw_key(&self, i: usize) -> u64 {
+        let start = HEADER_SIZE + i * Self::PAIR_SIZE;
+        u64::from_le_bytes(self.bytes[start..start + KEY_SIZE].try_into().unwrap())
+    }
+
+    /// Return the `i`-th bit set.
+    #[inline]
+    fn word(&self, i: usize) -> S {
+        let start = HEADER_SIZE + i * Self::PAIR_SIZE + KEY_SIZE;
+        S::from_le_slice(&self.bytes[start..start + S::WIDTH])
+    }
+
+    /// Iterate over the map indices and bit sets of
+    /// this [`VecIndexSetRef`], in ascending order.
+    #[inline]
+    fn blocks(&self) -> impl FusedIterator<Item = (usize, S)> + Clone + 'a {
+        let this = *self;
+        (0..this.num_of_blocks()).map(move |i| (this.raw_key(i) as usize, this.word(i)))
+    }
+}
+
+impl<S: storage::Storage> fmt::Debug for VecIndexSetRef<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<S: storage::Storage> PartialEq<VecIndexSet<S>> for VecIndexSetRef<'_, S> {
+    #[inline]
+    fn eq(&self, other: &VecIndexSet<S>) -> bool {
+        use crate::IndexSet;
+
+        self.iter().eq(other.iter())
+    }
+}
+
+#[cfg(feature = "serialize-serde")]
+impl<S> Serialize for VecIndexSetRef<'_, S>
+where
+    S: storage::Storage,
+{
+    #[inline]
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+#[cfg(feature = "serialize-serde")]
+impl<'de: 'a, 'a, S> Deserialize<'de> for VecIndexSetRef<'a, S>
+where
+    S: storage::Storage + 'a,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let bytes = <&'a [u8]>::deserialize(deserializer)?;
+        Self::from_bytes(bytes).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test reading back encoded index sets.
+    #[test]
+    fn test_vec_index_set_ref_from_bytes() {
+        let set: VecIndexSet<u16> = [1, 4, 6, 100, 1 << 40].into_iter().collect();
+        let bytes = set.to_bytes().unwrap();
+        let view = VecIndexSetRef::<u16>::from_bytes(&bytes).unwrap();
+
+        assert_eq!(view, set);
+        assert_eq!(view.len(), 5);
+        assert!(view.contains(100) && view.contains(1 << 40) && !view.contains(5));
+        assert_eq!(view.to_owned(), set);
+        assert_eq!(view.as_bytes(), bytes.as_slice());
+
+        #[cfg(feature = "serialize-borsh")]
+        assert_eq!(borsh::to_vec(&set).unwrap(), bytes);
+    }
+
+    /// Test rejecting invalid encodings.
+    #[test]
+    fn test_vec_index_set_ref_invalid_bytes() {
+        let set: VecIndexSet<u8> = [1, 4].into_iter().collect();
+        let bytes = set.to_bytes().unwrap();
+        let error = |bytes: &[u8]| VecIndexSetRef::<u8>::from_bytes(bytes).unwrap_err();
+
+        assert_eq!(error(&bytes[..2]), Error::InvalidEncoding);
+        assert_eq!(error(&bytes[..bytes.len() - 1]), Error::InvalidEncoding);
+
+        let mut unsorted = bytes.clone();
+        unsorted[4..].rotate_left(9);
+        assert_eq!(error(&unsorted), Error::UnsortedBlocks);
+
+        let mut overflowing = 1u32.to_le_bytes().to_vec();
+        overflowing.extend_from_slice(&u64::MAX.to_le_bytes());
+        overflowing.extend_from_slice(&1u16.to_le_bytes());
+        assert_eq!(
+            VecIndexSetRef::<u16>::from_bytes(&overflowing).unwrap_err(),
+            Error::IndexOutOfBounds
+        );
+
+        assert!(VecIndexSetRef::<u8>::from_bytes(&0u32.to_le_bytes())
+            .unwrap()
+            .is_empty());
+    }
+
+    /// Test views over encodings holding empty bit sets.
+    #[test]
+    fn test_vec_index_set_ref_zero_words() {
+        let mut bytes = 1u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&3u64.to_le_bytes());
+        bytes.extend_from_slice(&0u16.to_le_bytes());
+        let view = VecIndexSetRef::<u16>::from_bytes(&bytes).unwrap();
+
+        assert!(view.is_empty());
+        assert_eq!(view.len(), 0);
+        assert_eq!(view.iter().next(), None);
+        assert!(crate::IndexSet::is_empty(&view.to_owned()));
+
+        bytes[..4].copy_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&4u64.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        let view = VecIndexSetRef::<u16>::from_bytes(&bytes).unwrap();
+
+        assert!(!view.is_empty());
+        assert_eq!(view.len(), 1);
+        assert_eq!(view, view.to_owned());
+    }
+
+    /// Test deserializing views borrowing their bytes.
+    #[test]
+    #[cfg(feature = "serialize-serde")]
+    fn test_vec_index_set_ref_serde_borrow() {
+        use serde::de::value::{BorrowedBytesDeserializer, Error};
+
+        let set: VecIndexSet = [1, 4, 6, 100].into_iter().collect();
+        let bytes = set.to_bytes().unwrap();
+
+        let deserializer = BorrowedBytesDeserializer::<Error>::new(&bytes);
+        let view = VecIndexSetRef::<u64>::deserialize(deserializer).unwrap();
+
+        assert_eq!(view, set);
+        assert_eq!(view.as_bytes().as_ptr(), bytes.as_ptr());
+    }
+}
@@ -1,7 +1,11 @@
-//! Index set backed by a [`BTreeMap`].
+//! Index set backed by an ordered map of bit sets.
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::ops::RangeInclusive;
 
-use alloc::collections::btree_map::Entry;
 use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
 #[cfg(feature = "serialize-borsh")]
 use alloc::{format, string::ToString};
 #[cfg(feature = "serialize-borsh")]
@@ -9,8 +13,17 @@ use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 #[cfg(feature = "serialize-serde")]
 use serde::{Deserialize, Serialize};
 
+use super::bit_range_mask;
+use super::bit_set_indices;
+use super::block_map::BlockMap;
 use super::calculate_map_and_set_indices;
+use super::debug_indices;
+use super::error::FromBlocksError;
+use super::flip_bits;
+use super::iter::{Drain, Indices};
 use super::macros::*;
+#[cfg(feature = "serialize-serde")]
+use super::serde_compat::Shape;
 use super::storage;
 use super::IndexSet;
 
@@ -21,7 +34,7 @@ mod borsh_deserialize {
     use super::*;
 
     /// Deserialize a [`BTreeIndexSet`] from borsh data.
-    pub fn from<R, S>(reader: &mut R) -> Result<BTreeMap<usize, S>, borsh::io::Error>
+    pub fn from<R, S>(reader: &mut R) -> Result<BlockMap<S>, borsh::io::Error>
     where
         R: borsh::io::Read,
         S: borsh::de::BorshDeserialize,
@@ -32,23 +45,65 @@ mod borsh_deserialize {
                 unreachable!()
             };
             if a > b {
-                return Err(borsh::io::Error::new(
-                    borsh::io::ErrorKind::Other,
-                    "BTreeIndexSet should have been sorted",
-                ));
+                return Err(crate::error::Error::UnsortedBlocks.into());
             }
         }
+        if bit_sets
+            .last()
+            .is_some_and(|&(map_index, _)| map_index > crate::max_map_index::<S>())
+        {
+            return Err(crate::error::Error::IndexOutOfBounds.into());
+        }
         Ok(bit_sets.into_iter().collect())
     }
 }
 
-/// Index set backed by a [`BTreeMap`].
-#[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg(feature = "serialize-serde")]
+mod serde_deserialize {
+    use super::*;
+
+    /// Serialized representation of a [`BTreeIndexSet`](super::BTreeIndexSet).
+    #[derive(Deserialize)]
+    #[serde(rename = "BTreeIndexSet")]
+    pub struct Repr<S> {
+        /// Map of indices to bit vectors.
+        #[serde(deserialize_with = "from", bound(deserialize = "S: Deserialize<'de>"))]
+        pub bit_sets: BTreeMap<usize, S>,
+    }
+
+    /// Deserialize a [`BTreeIndexSet`] from serde data.
+    pub fn from<'de, D, S>(deserializer: D) -> Result<BTreeMap<usize, S>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+        S: Deserialize<'de>,
+    {
+        let bit_sets: BTreeMap<usize, S> = Deserialize::deserialize(deserializer)?;
+        if bit_sets
+            .last_key_value()
+            .is_some_and(|(&map_index, _)| map_index > crate::max_map_index::<S>())
+        {
+            return Err(serde::de::Error::custom(
+                crate::error::Error::IndexOutOfBounds,
+            ));
+        }
+        Ok(bit_sets)
+    }
+}
+
+/// Index set backed by an ordered map of bit sets.
+///
+/// The map is a shallow B-tree tuned for [`usize`] keys: bit
+/// sets are stored in sorted chunks of contiguous keys and
+/// values, indexed by the first key of each chunk.
+///
+/// Lookups, such as [`IndexSet::contains`] and
+/// [`IndexSet::iter`], never panic nor allocate.
+#[derive(Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 #[cfg_attr(
     feature = "serialize-borsh",
     derive(BorshSerialize, BorshDeserialize, BorshSchema)
 )]
-#[cfg_attr(feature = "serialize-serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serialize-serde", derive(Serialize))]
 #[repr(transparent)]
 pub struct BTreeIndexSet<S = u64> {
     /// Map of indices to bit vectors, containing the actual boolean
@@ -60,14 +115,29 @@ pub struct BTreeIndexSet<S = u64> {
         feature = "serialize-borsh",
         borsh(deserialize_with = "borsh_deserialize::from")
     )]
-    bit_sets: BTreeMap<usize, S>,
+    bit_sets: BlockMap<S>,
+}
+
+/// Human readable formats also accept a plain array of indices,
+/// besides the serialized representation of a [`BTreeIndexSet`].
+#[cfg(feature = "serialize-serde")]
+impl<'de, S: Deserialize<'de> + storage::Storage> Deserialize<'de> for BTreeIndexSet<S> {
+    #[inline]
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match super::serde_compat::deserialize::<_, serde_deserialize::Repr<S>>(deserializer)? {
+            Shape::Repr(repr) => Ok(Self {
+                bit_sets: repr.bit_sets.into_iter().collect(),
+            }),
+            Shape::Indices(indices) => Ok(indices.into_iter().collect()),
+        }
+    }
 }
 
 impl<S> BTreeIndexSet<S> {
     /// Create a new [`BTreeIndexSet`].
     pub const fn new() -> Self {
         Self {
-            bit_sets: BTreeMap::new(),
+            bit_sets: BlockMap::new(),
         }
     }
 
@@ -84,12 +154,440 @@ impl<S> BTreeIndexSet<S> {
     }
 }
 
+impl<S: storage::Storage> BTreeIndexSet<S> {
+    /// Build a [`BTreeIndexSet`] from a map of map indices
+    /// to bit sets, which must all be non-empty.
+    ///
+    /// This is a checked alternative to implementing [`TryFrom`],
+    /// which conflicts with the [`From`] implementation for
+    /// iterators of indices.
+    pub fn try_from_blocks(bit_sets: BTreeMap<usize, S>) -> Result<Self, FromBlocksError> {
+        super::check_invariants(bit_sets.iter().map(|(&map_index, &set)| (map_index, set)))?;
+        Ok(Self {
+            bit_sets: bit_sets.into_iter().collect(),
+        })
+    }
+
+    /// Remove all indices greater than or equal to `max`
+    /// from this [`BTreeIndexSet`].
+    pub fn truncate(&mut self, max: usize) {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(max);
+        let mut tail = self.bit_sets.split_off(map_index);
+
+        if bit_set_index > 0 {
+            if let Some(set) = tail.remove(map_index) {
+                let set = set & bit_range_mask(0, bit_set_index - 1);
+                if set != S::ZERO {
+                    self.bit_sets.insert(map_index, set);
+                }
+            }
+        }
+
+        self.debug_assert_invariants();
+    }
+
+    /// Move all indices greater than or equal to `at` from
+    /// this [`BTreeIndexSet`] into a new set, which is returned.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(at);
+        let mut tail = self.bit_sets.split_off(map_index);
+
+        // NB: the bit set at `map_index` may hold indices
+        // on both sides of `at`
+        if bit_set_index > 0 {
+            let mask = bit_range_mask(0, bit_set_index - 1);
+            let mut low = S::ZERO;
+            tail.update(map_index, |set| {
+                low = *set & mask;
+                *set &= !mask;
+            });
+            if low != S::ZERO {
+                self.bit_sets.insert(map_index, low);
+            }
+        }
+
+        let tail = Self { bit_sets: tail };
+        self.debug_assert_invariants();
+        tail.debug_assert_invariants();
+        tail
+    }
+
+    /// Move all indices of `other` into this [`BTreeIndexSet`],
+    /// leaving `other` empty.
+    ///
+    /// The bit sets of `other` above those of this set are moved
+    /// a chunk at a time, rather than one bit set at a time.
+    pub fn append(&mut self, other: &mut Self) {
+        let tail = match self.bit_sets.last_key() {
+            Some(last) => other.bit_sets.split_off(last + 1),
+            None => core::mem::take(&mut other.bit_sets),
+        };
+        self.merge_blocks(other.blocks());
+        self.bit_sets.append(tail);
+        other.bit_sets.clear();
+        self.debug_assert_invariants();
+    }
+
+    /// Remove up to `n` of the lowest indices from this
+    /// [`BTreeIndexSet`], returning them in ascending order.
+    ///
+    /// Bit sets whose indices are all removed are dropped
+    /// at once, rather than one index at a time.
+    pub fn pop_smallest(&mut self, n: usize) -> Vec<usize> {
+        let mut popped = Vec::new();
+
+        while let Some((map_index, set)) = self.bit_sets.first_mut() {
+            let remaining = n - popped.len();
+            if remaining == 0 {
+                break;
+            }
+            if set.num_of_high_bits() <= remaining {
+                popped.extend(bit_set_indices(*set).map(|bit| map_index * S::WIDTH + bit));
+                self.bit_sets.pop_first();
+            } else {
+                for _ in 0..remaining {
+                    popped.push(map_index * S::WIDTH + set.trailing_zeros());
+                    *set = set.without_lowest_bit();
+                }
+                break;
+            }
+        }
+
+        self.debug_assert_invariants();
+        popped
+    }
+
+    /// Remove up to `n` of the greatest indices from this
+    /// [`BTreeIndexSet`], returning them in descending order.
+    ///
+    /// Bit sets whose indices are all removed are dropped
+    /// at once, rather than one index at a time.
+    pub fn pop_largest(&mut self, n: usize) -> Vec<usize> {
+        let mut popped = Vec::new();
+
+        while let Some((map_index, set)) = self.bit_sets.last_mut() {
+            let remaining = n - popped.len();
+            if remaining == 0 {
+                break;
+            }
+            let indices = bit_set_indices(*set).rev();
+            if set.num_of_high_bits() <= remaining {
+                popped.extend(indices.map(|bit| map_index * S::WIDTH + bit));
+                self.bit_sets.pop_last();
+            } else {
+                for bit in indices.take(remaining) {
+                    popped.push(map_index * S::WIDTH + bit);
+                    *set &= !S::from_usize(1 << bit);
+                }
+                break;
+            }
+        }
+
+        self.debug_assert_invariants();
+        popped
+    }
+
+    /// Add the indices of the bits set in `mask` to the bit
+    /// set at `map_index` of this [`BTreeIndexSet`].
+    ///
+    /// The bit set holds the indices from `map_index * W` up to
+    /// `map_index * W + W - 1`, where `W` is the width of the bit set.
+    /// Bits of `mask` at positions `W` and above are ignored.
+    pub fn insert_word(&mut self, map_index: usize, mask: S) {
+        let mask = mask & bit_range_mask(0, S::WIDTH - 1);
+        if mask != S::ZERO {
+            self.bit_sets.update(map_index, |set| *set |= mask);
+        }
+        self.debug_assert_invariants();
+    }
+
+    /// Remove the indices of the bits set in `mask` from the
+    /// bit set at `map_index` of this [`BTreeIndexSet`].
+    ///
+    /// See [`BTreeIndexSet::insert_word`] for the indices
+    /// held in each bit set.
+    pub fn remove_word(&mut self, map_index: usize, mask: S) {
+        self.bit_sets.update(map_index, |set| *set &= !mask);
+        self.debug_assert_invariants();
+    }
+
+    /// Flip the membership of `index` in this [`BTreeIndexSet`],
+    /// returning whether it is now present.
+    pub fn toggle(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let mask = S::from_usize(1 << bit_set_index);
+        let mut present = false;
+        self.bit_sets.update(map_index, |set| {
+            *set = flip_bits(*set, mask);
+            present = *set & mask != S::ZERO;
+        });
+        self.debug_assert_invariants();
+        present
+    }
+
+    /// Flip the membership of every index lower than `upto`
+    /// in this [`BTreeIndexSet`], leaving other indices as is.
+    ///
+    /// Every bit set up to `upto` is visited, including
+    /// those not currently stored in this set.
+    pub fn invert(&mut self, upto: usize) {
+        let Some(last) = upto.checked_sub(1) else {
+            return;
+        };
+        let (end_map_index, end_bit_set_index) = calculate_map_and_set_indices::<S>(last);
+
+        for map_index in 0..=end_map_index {
+            let last_bit = if map_index == end_map_index {
+                end_bit_set_index
+            } else {
+                S::WIDTH - 1
+            };
+            let mask = bit_range_mask(0, last_bit);
+            self.bit_sets
+                .update(map_index, |set| *set = flip_bits(*set, mask));
+        }
+
+        self.debug_assert_invariants();
+    }
+
+    /// Remove all indices from this [`BTreeIndexSet`], returning
+    /// them in ascending order.
+    ///
+    /// The set is left empty, even if the returned iterator is
+    /// not fully consumed. The storage of the drained bit sets
+    /// is freed as the iterator advances.
+    pub fn drain(&mut self) -> impl FusedIterator<Item = usize> + '_ {
+        let mut bit_sets = core::mem::take(&mut self.bit_sets);
+        Drain::new(core::iter::from_fn(move || bit_sets.pop_first()).fuse())
+    }
+
+    /// Return a handle to the bit set at `map_index`, allowing
+    /// several operations on its bits with a single lookup.
+    ///
+    /// The bit set holds the indices from `map_index * W` up to
+    /// `map_index * W + W - 1`, where `W` is the width of the bit set.
+    /// Changes are written back to this set when the handle is dropped.
+    #[inline]
+    pub fn block_entry(&mut self, map_index: usize) -> BlockEntry<'_, S> {
+        let set = self.get_block(map_index).unwrap_or(S::ZERO);
+        BlockEntry {
+            bit_sets: &mut self.bit_sets,
+            map_index,
+            set,
+        }
+    }
+
+    /// Return the bit set at `map_index`, if any.
+    #[inline]
+    fn get_block(&self, map_index: usize) -> Option<S> {
+        self.bit_sets.get(map_index).copied()
+    }
+
+    /// Assert the invariants of this [`BTreeIndexSet`], with the
+    /// `strict-invariants` feature.
+    ///
+    /// This is a no-op in release builds.
+    #[inline]
+    #[track_caller]
+    fn debug_assert_invariants(&self) {
+        super::debug_assert_invariants(self.blocks());
+    }
+
+    /// Iterate over the map indices and bit sets of
+    /// this [`BTreeIndexSet`], in ascending order.
+    #[inline]
+    pub(crate) fn blocks(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + ExactSizeIterator + FusedIterator + Clone + '_
+    {
+        self.bit_sets
+            .iter()
+            .map(|(map_index, &set)| (map_index, set))
+    }
+
+    /// Iterate over the map indices and bit sets of this [`BTreeIndexSet`]
+    /// whose map indices lie within `map_range`, in ascending order.
+    #[inline]
+    fn blocks_in(
+        &self,
+        map_range: RangeInclusive<usize>,
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + '_ {
+        self.bit_sets
+            .range(map_range)
+            .map(|(map_index, &set)| (map_index, set))
+    }
+
+    /// Merge non-empty `blocks` into this [`BTreeIndexSet`].
+    pub(crate) fn merge_blocks<I>(&mut self, blocks: I)
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        for (map_index, other_set) in blocks {
+            self.bit_sets.update(map_index, |set| *set |= other_set);
+        }
+        self.debug_assert_invariants();
+    }
+
+    /// Merge non-empty `blocks` into this [`BTreeIndexSet`], as with
+    /// [`Self::merge_blocks`].
+    ///
+    /// This never fails, since the memory used by this set only
+    /// grows with the number of bit sets merged, but matches the
+    /// fallible merge of [`DenseIndexSet`](crate::dense::DenseIndexSet).
+    #[inline]
+    pub(crate) fn try_merge_blocks<I>(&mut self, blocks: I) -> Result<(), super::error::Error>
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        self.merge_blocks(blocks);
+        Ok(())
+    }
+
+    /// Update each bit set of this [`BTreeIndexSet`] with `f`, in
+    /// ascending order of map indices, removing those left empty.
+    pub(crate) fn retain_blocks<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &mut S),
+    {
+        self.bit_sets.retain(|map_index, set| {
+            f(map_index, set);
+            *set != S::ZERO
+        });
+        self.debug_assert_invariants();
+    }
+
+    /// Append a non-empty bit set at `map_index`, which must be
+    /// greater than the map indices of all other bit sets.
+    #[inline]
+    pub(crate) fn push_block(&mut self, (map_index, set): (usize, S)) {
+        debug_assert!(set != S::ZERO);
+        self.bit_sets.push(map_index, set);
+    }
+}
+
+/// Handle to a bit set of a [`BTreeIndexSet`], created
+/// with [`BTreeIndexSet::block_entry`].
+///
+/// Bits are addressed by their position in the bit set, rather than
+/// by the index they represent in the set.
+pub struct BlockEntry<'a, S: storage::Storage> {
+    /// The map holding the bit set, written back on drop.
+    bit_sets: &'a mut BlockMap<S>,
+    /// The map index of the bit set.
+    map_index: usize,
+    /// The current value of the bit set.
+    set: S,
+}
+
+impl<S: storage::Storage> BlockEntry<'_, S> {
+    /// Return the map index of this bit set.
+    #[inline]
+    pub fn map_index(&self) -> usize {
+        self.map_index
+    }
+
+    /// Return the index represented by the first bit of this bit set.
+    #[inline]
+    pub fn start_index(&self) -> usize {
+        self.map_index() * S::WIDTH
+    }
+
+    /// Return the current value of this bit set.
+    #[inline]
+    pub fn bits(&self) -> S {
+        self.set
+    }
+
+    /// Overwrite the value of this bit set.
+    #[inline]
+    pub fn set_bits(&mut self, set: S) {
+        self.set = set;
+    }
+
+    /// Set the bit at position `bit` of this bit set.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `bit` is not lower than the width of the bit set.
+    #[inline]
+    pub fn insert(&mut self, bit: usize) {
+        self.set |= Self::mask(bit);
+    }
+
+    /// Clear the bit at position `bit` of this bit set.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `bit` is not lower than the width of the bit set.
+    #[inline]
+    pub fn remove(&mut self, bit: usize) {
+        self.set &= !Self::mask(bit);
+    }
+
+    /// Check if the bit at position `bit` of this bit set is set.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `bit` is not lower than the width of the bit set.
+    #[inline]
+    pub fn contains(&self, bit: usize) -> bool {
+        self.set & Self::mask(bit) != S::ZERO
+    }
+
+    /// Return a bit set with only the bit at position `bit` set.
+    #[inline]
+    fn mask(bit: usize) -> S {
+        assert!(bit < S::WIDTH, "bit position out of bounds");
+        S::from_usize(1 << bit)
+    }
+}
+
+impl<S: storage::Storage> Drop for BlockEntry<'_, S> {
+    fn drop(&mut self) {
+        let set = self.set;
+        self.bit_sets.update(self.map_index, |old| *old = set);
+    }
+}
+
+/// Wipe the map indices and bit sets of this [`BTreeIndexSet`],
+/// leaving it empty.
+///
+/// Memory released as the set grows or shrinks is not wiped.
+///
+/// Wrap the set in [`Zeroizing`] to wipe it when it is dropped.
+///
+/// [`Zeroizing`]: zeroize::Zeroizing
+#[cfg(feature = "zeroize")]
+impl<S> zeroize::Zeroize for BTreeIndexSet<S> {
+    #[inline]
+    fn zeroize(&mut self) {
+        self.bit_sets.zeroize();
+    }
+}
+
+impl<S: storage::Storage> fmt::Debug for BTreeIndexSet<S> {
+    /// Format this [`BTreeIndexSet`].
+    ///
+    /// The alternate form (`{:#?}`) renders the indices stored
+    /// in the set, eliding them past a fixed cap, whereas the
+    /// default form renders the raw bit sets.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            debug_indices(self, f)
+        } else {
+            f.debug_struct("BTreeIndexSet")
+                .field("bit_sets", &self.bit_sets)
+                .finish()
+        }
+    }
+}
+
 impl<S: storage::Storage> IndexSet for BTreeIndexSet<S> {
     #[inline]
     fn len(&self) -> usize {
         self.bit_sets
-            .values()
-            .map(|set| set.num_of_high_bits())
+            .iter()
+            .map(|(_, set)| set.num_of_high_bits())
             .sum::<usize>()
     }
 
@@ -98,57 +596,174 @@ impl<S: storage::Storage> IndexSet for BTreeIndexSet<S> {
         self.bit_sets.is_empty()
     }
 
-    fn insert(&mut self, index: usize) {
+    fn insert(&mut self, index: usize) -> bool {
         let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
-        let set = self.bit_sets.entry(map_index).or_insert(S::ZERO);
-        *set |= S::from_usize(1 << bit_set_index);
+        let mask = S::from_usize(1 << bit_set_index);
+        let mut added = false;
+        self.bit_sets.update(map_index, |set| {
+            added = *set & mask == S::ZERO;
+            *set |= mask;
+        });
+        self.debug_assert_invariants();
+        added
     }
 
-    fn remove(&mut self, index: usize) {
+    fn remove(&mut self, index: usize) -> bool {
         let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
-        let entry = self.bit_sets.entry(map_index).and_modify(|set| {
-            *set &= !S::from_usize(1 << bit_set_index);
+        let mask = S::from_usize(1 << bit_set_index);
+        let mut removed = false;
+        self.bit_sets.update(map_index, |set| {
+            removed = *set & mask != S::ZERO;
+            *set &= !mask;
         });
-        match entry {
-            Entry::Occupied(e) if *e.get() == S::ZERO => {
-                e.remove();
-            }
-            _ => {}
-        }
+        self.debug_assert_invariants();
+        removed
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.bit_sets.clear();
     }
 
     fn contains(&self, index: usize) -> bool {
         let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
         self.bit_sets
-            .get(&map_index)
+            .get(map_index)
             .map(|&set| set & S::from_usize(1 << bit_set_index) != S::ZERO)
             .unwrap_or(false)
     }
 
     #[inline]
-    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
-        self.bit_sets.iter().flat_map(|(&map_index, &set)| {
-            (0..S::WIDTH).filter_map(move |bit_set_index| {
-                let is_bit_set = (set & S::from_usize(1 << bit_set_index)) != S::ZERO;
-                if is_bit_set {
-                    Some(map_index * S::WIDTH + bit_set_index)
-                } else {
-                    None
-                }
-            })
-        })
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        Indices::new(self.blocks())
     }
 
     #[inline]
     fn union(&mut self, other: &BTreeIndexSet<S>) {
-        for (&map_index, &other_set) in other.bit_sets.iter() {
-            let set = self.bit_sets.entry(map_index).or_insert(S::ZERO);
-            *set |= other_set;
-        }
+        self.merge_blocks(other.blocks());
+    }
+
+    #[inline]
+    fn successor(&self, index: usize) -> Option<usize> {
+        self.successor_in_blocks(index)
+    }
+
+    #[inline]
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        self.predecessor_in_blocks(index)
     }
 }
 
+index_set_impl_adaptive!(crate::btree::BTreeIndexSet);
+index_set_impl_bit_set!(crate::btree::BTreeIndexSet);
+index_set_impl_bitmap!(crate::btree::BTreeIndexSet);
+index_set_impl_bools!(crate::btree::BTreeIndexSet);
+index_set_impl_borsh_compat!(crate::btree::BTreeIndexSet);
+index_set_impl_cbor!(crate::btree::BTreeIndexSet);
+index_set_impl_compact!(crate::btree::BTreeIndexSet);
+index_set_impl_contains_many!(crate::btree::BTreeIndexSet);
+index_set_impl_cross_width!(crate::btree::BTreeIndexSet);
+index_set_impl_hamming_distance!(crate::btree::BTreeIndexSet);
+index_set_impl_iter_u64_blocks!(crate::btree::BTreeIndexSet);
+index_set_impl_layout!(crate::btree::BTreeIndexSet);
+index_set_impl_lenient!(crate::btree::BTreeIndexSet);
+index_set_impl_map_monotonic!(crate::btree::BTreeIndexSet);
+index_set_impl_merkle!(crate::btree::BTreeIndexSet);
+index_set_impl_neighbors!(crate::btree::BTreeIndexSet);
+index_set_impl_range_serialize!(crate::btree::BTreeIndexSet);
+index_set_impl_reserve!(crate::btree::BTreeIndexSet);
+index_set_impl_roaring!(crate::btree::BTreeIndexSet);
+index_set_impl_scale!(crate::btree::BTreeIndexSet);
+index_set_impl_select!(crate::btree::BTreeIndexSet);
+index_set_impl_slice!(crate::btree::BTreeIndexSet);
+index_set_impl_stats!(BTree, crate::btree::BTreeIndexSet);
+index_set_impl_sync!(crate::btree::BTreeIndexSet);
+index_set_impl_partition!(crate::btree::BTreeIndexSet);
+index_set_impl_fixedbitset!(crate::btree::BTreeIndexSet);
 index_set_impl_from!(crate::btree::BTreeIndexSet);
 index_set_impl_from_iterator!(crate::btree::BTreeIndexSet);
+index_set_impl_from_str!(crate::btree::BTreeIndexSet);
+index_set_impl_hibitset!(crate::btree::BTreeIndexSet);
+index_set_impl_inclusion!(crate::btree::BTreeIndexSet);
+index_set_impl_index!(crate::btree::BTreeIndexSet);
+index_set_impl_partial_eq!(crate::btree::BTreeIndexSet);
+index_set_impl_prost!(crate::btree::BTreeIndexSet);
+index_set_impl_quickcheck!(crate::btree::BTreeIndexSet);
+index_set_impl_rayon!(crate::btree::BTreeIndexSet);
 index_set_impl_extend!(crate::btree::BTreeIndexSet);
 index_set_tests!(crate::btree::BTreeIndexSet);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test building sets from validated bit sets.
+    #[test]
+    fn test_btree_index_set_try_from_blocks() {
+        use crate::error::FromBlocksErrorKind;
+
+        let set = BTreeIndexSet::<u16>::try_from_blocks([(0, 0b10), (50, 0b01)].into()).unwrap();
+        assert_eq!(set, [1, 100]);
+
+        let error = BTreeIndexSet::<u16>::try_from_blocks([(0, 1), (3, 0)].into()).unwrap_err();
+        assert_eq!(error.kind(), FromBlocksErrorKind::EmptyBlock);
+
+        let error =
+            BTreeIndexSet::<u64>::try_from_blocks([(usize::MAX / 8, 1 << 63)].into()).unwrap_err();
+        assert_eq!(error.kind(), FromBlocksErrorKind::InvalidBits);
+    }
+
+    /// Test several bit operations through a block entry.
+    #[test]
+    fn test_btree_index_set_block_entry() {
+        let mut set: BTreeIndexSet<u64> = [1, 9, 100].into_iter().collect();
+
+        let mut entry = set.block_entry(1);
+        assert_eq!(entry.start_index(), 8);
+        assert!(entry.contains(1) && !entry.contains(0));
+        entry.insert(0);
+        entry.insert(7);
+        entry.remove(1);
+        drop(entry);
+
+        let mut entry = set.block_entry(40);
+        entry.insert(2);
+        drop(entry);
+
+        let mut entry = set.block_entry(12);
+        entry.set_bits(0);
+        drop(entry);
+
+        set.block_entry(0).remove(1);
+        set.block_entry(2);
+
+        assert_eq!(set, [8, 15, 322]);
+        assert_eq!(set.blocks().len(), 2);
+    }
+
+    /// Test wiping a set.
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_btree_index_set_zeroize() {
+        use zeroize::Zeroize;
+
+        let mut set: BTreeIndexSet<u16> = (0..1000).step_by(3).collect();
+        set.zeroize();
+        assert!(set.is_empty() && set.blocks().next().is_none());
+
+        set.insert(7);
+        assert_eq!(set, [7]);
+    }
+
+    /// Test wiping a set on drop, by wrapping it in [`Zeroizing`].
+    ///
+    /// [`Zeroizing`]: zeroize::Zeroizing
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_btree_index_set_zeroizing() {
+        let mut set = zeroize::Zeroizing::new(BTreeIndexSet::<u16>::new());
+        set.extend([3, 70]);
+        assert_eq!(*set, [3, 70]);
+        drop(set);
+    }
+}
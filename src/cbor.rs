@@ -0,0 +1,159 @@
+//! Canonical CBOR representation of index sets.
+//!
+//! Index sets are encoded following the deterministic encoding
+//! requirements of [RFC 8949], section 4.2.1, such that equal sets
+//! always encode to identical bytes, regardless of their backend or
+//! storage width. This makes the encoding suitable as the input
+//! of hash functions and signature schemes.
+//!
+//! An index set is encoded as an array of pairs, in the CDDL
+//! notation of [RFC 8610]:
+//!
+//! ```text
+//! index-set = [* block]
+//! block = [block-index: uint, bits: uint .ne 0]
+//! ```
+//!
+//! Block indices are strictly increasing, and bit `B` of the
+//! block at block index `I` marks the presence of index `64 * I + B`.
+//!
+//! [RFC 8949]: https://www.rfc-editor.org/rfc/rfc8949
+//! [RFC 8610]: https://www.rfc-editor.org/rfc/rfc8610
+
+use alloc::vec::Vec;
+
+use super::error::Error;
+
+/// Number of indices in each block of an encoded index set.
+const BLOCK_BITS: usize = u64::BITS as usize;
+
+/// CBOR major type of unsigned integers.
+const MAJOR_UNSIGNED: u8 = 0;
+
+/// CBOR major type of arrays.
+const MAJOR_ARRAY: u8 = 4;
+
+/// Append the head of a data item to `out`,
+/// in its shortest form.
+fn write_head(out: &mut Vec<u8>, major: u8, value: u64) {
+    let major = major << 5;
+    match value {
+        0..=23 => out.push(major | value as u8),
+        24..=0xff => out.extend_from_slice(&[major | 24, value as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+}
+
+/// Encode pairs of start indices and 64-bit blocks,
+/// in ascending order, as a canonical CBOR index set.
+pub(crate) fn encode<I>(blocks: I) -> Vec<u8>
+where
+    I: IntoIterator<Item = (usize, u64)>,
+{
+    let blocks: Vec<_> = blocks.into_iter().collect();
+    let mut out = Vec::new();
+
+    write_head(&mut out, MAJOR_ARRAY, blocks.len() as u64);
+    for (start_index, block) in blocks {
+        write_head(&mut out, MAJOR_ARRAY, 2);
+        write_head(&mut out, MAJOR_UNSIGNED, (start_index / BLOCK_BITS) as u64);
+        write_head(&mut out, MAJOR_UNSIGNED, block);
+    }
+
+    out
+}
+
+/// Reads data items from canonical CBOR input.
+struct Reader<'a> {
+    /// The remaining input.
+    bytes: &'a [u8],
+}
+
+impl Reader<'_> {
+    /// Read `N` bytes from the input.
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (taken, rest) = self
+            .bytes
+            .split_first_chunk()
+            .ok_or(Error::InvalidEncoding)?;
+        self.bytes = rest;
+        Ok(*taken)
+    }
+
+    /// Read the head of a data item of the `expected` major
+    /// type, and return its argument.
+    fn read_head(&mut self, expected: u8) -> Result<u64, Error> {
+        let [initial] = self.take()?;
+        if initial >> 5 != expected {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let (value, min) = match initial & 0x1f {
+            info @ 0..=23 => return Ok(u64::from(info)),
+            24 => (u64::from(u8::from_be_bytes(self.take()?)), 24),
+            25 => (u64::from(u16::from_be_bytes(self.take()?)), 0x100),
+            26 => (u64::from(u32::from_be_bytes(self.take()?)), 0x1_0000),
+            27 => (u64::from_be_bytes(self.take()?), 0x1_0000_0000),
+            31 => return Err(Error::NonCanonicalEncoding),
+            _ => return Err(Error::InvalidEncoding),
+        };
+
+        if value < min {
+            return Err(Error::NonCanonicalEncoding);
+        }
+        Ok(value)
+    }
+}
+
+/// Decode a canonical CBOR index set, and return
+/// the indices it carries, in ascending order.
+pub(crate) fn decode(bytes: &[u8]) -> Result<Vec<usize>, Error> {
+    let mut reader = Reader { bytes };
+    let num_of_blocks = reader.read_head(MAJOR_ARRAY)?;
+
+    let max_block_index = (usize::MAX / BLOCK_BITS) as u64;
+    let mut last_block_index = None;
+    let mut indices = Vec::new();
+
+    for _ in 0..num_of_blocks {
+        if reader.read_head(MAJOR_ARRAY)? != 2 {
+            return Err(Error::InvalidEncoding);
+        }
+        let block_index = reader.read_head(MAJOR_UNSIGNED)?;
+        let block = reader.read_head(MAJOR_UNSIGNED)?;
+
+        if last_block_index.is_some_and(|last| last >= block_index) {
+            return Err(Error::UnsortedBlocks);
+        }
+        if block == 0 {
+            return Err(Error::ZeroBlock);
+        }
+        if block_index > max_block_index {
+            return Err(Error::IndexOutOfBounds);
+        }
+        last_block_index = Some(block_index);
+
+        let start_index = block_index as usize * BLOCK_BITS;
+        indices.extend(
+            (0..BLOCK_BITS)
+                .filter(|&bit| block & (1 << bit) != 0)
+                .map(|bit| start_index + bit),
+        );
+    }
+
+    if !reader.bytes.is_empty() {
+        return Err(Error::InvalidEncoding);
+    }
+    Ok(indices)
+}
@@ -0,0 +1,148 @@
+//! Dense renumbering of the indices of sparse sets.
+//!
+//! Compacting a set maps each of its indices to its rank within the
+//! set, i.e. the number of smaller indices in the set, such that the
+//! indices of a set of `n` indices are renumbered from `0` up to `n - 1`,
+//! in the same order. The resulting [`Compaction`] translates indices
+//! back and forth, along with other sets and maps keyed by indices.
+//!
+//! ```
+//! # #[cfg(feature = "vec")] {
+//! use index_set::vec::VecIndexSet;
+//! use index_set::IndexSet;
+//!
+//! let ids: VecIndexSet = [3, 70, 1_000, 65_536].into_iter().collect();
+//! let (compaction, dense) = ids.compact();
+//!
+//! assert_eq!(dense, [0, 1, 2, 3]);
+//! assert_eq!(compaction.new_index(1_000), Some(2));
+//! assert_eq!(compaction.old_index(3), Some(65_536));
+//!
+//! let selected: VecIndexSet = [70, 65_536, 99].into_iter().collect();
+//! assert_eq!(compaction.translate(&selected), [1, 3]);
+//! # }
+//! ```
+
+use alloc::vec::Vec;
+
+use super::IndexSet;
+
+/// Number of indices in each lane of a [`Compaction`].
+const LANE_BITS: usize = u64::BITS as usize;
+
+/// Mapping of the indices of a set to their ranks within the set,
+/// created by the `compact` method of index sets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Compaction {
+    /// The non-empty 64-bit lanes of the original set, along
+    /// with their start indices, in ascending order.
+    lanes: Vec<(usize, u64)>,
+    /// The number of indices before each lane, matching `lanes`.
+    ranks: Vec<usize>,
+    /// The number of indices in the original set.
+    len: usize,
+}
+
+impl Compaction {
+    /// Build a [`Compaction`] of the given 64-bit lanes of
+    /// indices, as yielded by `iter_u64_blocks`.
+    #[cfg(any(
+        feature = "vec",
+        feature = "btree",
+        feature = "dense",
+        feature = "paged"
+    ))]
+    pub(crate) fn from_lanes<I>(lanes: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, u64)>,
+    {
+        let lanes: Vec<_> = lanes.into_iter().collect();
+        let mut ranks = Vec::with_capacity(lanes.len());
+        let mut len = 0;
+        for &(_, lane) in &lanes {
+            ranks.push(len);
+            len += lane.count_ones() as usize;
+        }
+        Self { lanes, ranks, len }
+    }
+
+    /// Return the number of indices in the compacted set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check if the compacted set is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Return the new index of `old_index`, if
+    /// it is present in the compacted set.
+    pub fn new_index(&self, old_index: usize) -> Option<usize> {
+        let start_index = old_index - old_index % LANE_BITS;
+        let position = self
+            .lanes
+            .binary_search_by_key(&start_index, |&(start_index, _)| start_index)
+            .ok()?;
+        let (_, lane) = self.lanes[position];
+        let bit = old_index % LANE_BITS;
+        if lane & (1 << bit) == 0 {
+            return None;
+        }
+        let below = lane & ((1 << bit) - 1);
+        Some(self.ranks[position] + below.count_ones() as usize)
+    }
+
+    /// Return the old index whose new index is `new_index`, if any.
+    pub fn old_index(&self, new_index: usize) -> Option<usize> {
+        if new_index >= self.len {
+            return None;
+        }
+        let position = self.ranks.partition_point(|&rank| rank <= new_index) - 1;
+        let (start_index, mut lane) = self.lanes[position];
+        for _ in 0..new_index - self.ranks[position] {
+            lane &= lane - 1;
+        }
+        Some(start_index + lane.trailing_zeros() as usize)
+    }
+
+    /// Iterate over the pairs of old and new indices
+    /// of the compacted set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.lanes
+            .iter()
+            .flat_map(|&(start_index, lane)| {
+                (0..LANE_BITS)
+                    .filter(move |&bit| lane & (1 << bit) != 0)
+                    .map(move |bit| start_index + bit)
+            })
+            .zip(0..self.len)
+    }
+
+    /// Translate the indices of `set` to their new indices,
+    /// dropping those absent from the compacted set.
+    pub fn translate<T>(&self, set: &T) -> T
+    where
+        T: IndexSet + FromIterator<usize>,
+    {
+        set.iter()
+            .filter_map(|old_index| self.new_index(old_index))
+            .collect()
+    }
+
+    /// Translate the keys of `entries` to their new indices,
+    /// dropping the entries whose keys are absent from the
+    /// compacted set.
+    pub fn translate_map<'a, I, V>(&'a self, entries: I) -> impl Iterator<Item = (usize, V)> + 'a
+    where
+        I: IntoIterator<Item = (usize, V)>,
+        I::IntoIter: 'a,
+    {
+        entries.into_iter().filter_map(|(old_index, value)| {
+            self.new_index(old_index)
+                .map(|new_index| (new_index, value))
+        })
+    }
+}
@@ -0,0 +1,209 @@
+//! Grow-only set CRDTs built on index sets.
+
+use core::fmt;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+#[cfg(feature = "btree")]
+use super::btree::BTreeIndexSet;
+use super::calculate_map_and_set_indices;
+use super::storage;
+use super::vec::VecIndexSet;
+use super::IndexSet;
+
+/// Join-semilattice, whose states can be merged in any
+/// order, any number of times, converging to the same state.
+pub trait Lattice {
+    /// Merge `other` into this state, leaving the least
+    /// upper bound of both states.
+    fn join(&mut self, other: &Self);
+}
+
+impl<S: storage::Storage> Lattice for VecIndexSet<S> {
+    #[inline]
+    fn join(&mut self, other: &Self) {
+        self.union(other);
+    }
+}
+
+#[cfg(feature = "btree")]
+impl<S: storage::Storage> Lattice for BTreeIndexSet<S> {
+    #[inline]
+    fn join(&mut self, other: &Self) {
+        self.union(other);
+    }
+}
+
+/// Causal marker of a [`DeltaGSet`], identifying the
+/// changes observed up to some point.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Marker(u64);
+
+/// Grow-only set CRDT backed by a [`VecIndexSet`], which
+/// records the bit sets changed by each update, such that
+/// delta states can be extracted from it.
+#[derive(Clone)]
+pub struct DeltaGSet<S = u64> {
+    /// The indices in this set.
+    set: VecIndexSet<S>,
+    /// Version of the latest change to this set.
+    version: u64,
+    /// Map indices of the changed bit sets, keyed by
+    /// the version of their latest change.
+    changes: BTreeMap<u64, usize>,
+    /// Version of the latest change to each bit set.
+    versions: BTreeMap<usize, u64>,
+}
+
+impl<S: storage::Storage> fmt::Debug for DeltaGSet<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeltaGSet")
+            .field("set", &self.set)
+            .field("marker", &self.marker())
+            .finish()
+    }
+}
+
+impl<S: storage::Storage> Default for DeltaGSet<S> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: storage::Storage> DeltaGSet<S> {
+    /// Create a new, empty [`DeltaGSet`].
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            set: VecIndexSet::new(),
+            version: 0,
+            changes: BTreeMap::new(),
+            versions: BTreeMap::new(),
+        }
+    }
+
+    /// Return the indices in this [`DeltaGSet`].
+    #[inline]
+    pub fn as_set(&self) -> &VecIndexSet<S> {
+        &self.set
+    }
+
+    /// Return the indices in this [`DeltaGSet`], discarding
+    /// its change history.
+    #[inline]
+    pub fn into_inner(self) -> VecIndexSet<S> {
+        self.set
+    }
+
+    /// Return a [`Marker`] covering all the changes
+    /// made to this [`DeltaGSet`] so far.
+    #[inline]
+    pub fn marker(&self) -> Marker {
+        Marker(self.version)
+    }
+
+    /// Add a new index to this [`DeltaGSet`].
+    pub fn insert(&mut self, index: usize) {
+        if self.set.insert(index) {
+            self.record_change(calculate_map_and_set_indices::<S>(index).0);
+        }
+    }
+
+    /// Merge a delta state, or a full state, into this [`DeltaGSet`].
+    ///
+    /// Only the bit sets which gain new indices are recorded
+    /// as changed, such that deltas propagate transitively.
+    pub fn apply_delta(&mut self, delta: &VecIndexSet<S>) {
+        for (map_index, set) in delta.blocks() {
+            let old_set = self.set.get_block(map_index).unwrap_or(S::ZERO);
+            if set & !old_set != S::ZERO {
+                self.set.insert_word(map_index, set);
+                self.record_change(map_index);
+            }
+        }
+    }
+
+    /// Extract a delta state with the bit sets changed after
+    /// `marker`, holding at most `max_blocks` bit sets.
+    ///
+    /// Returns the delta state along with the [`Marker`] of the
+    /// last change it covers, to extract the following changes.
+    pub fn delta_since(&self, marker: Marker, max_blocks: usize) -> (VecIndexSet<S>, Marker) {
+        let mut next_marker = marker;
+        let mut blocks: Vec<_> = self
+            .changes
+            .range(marker.0.saturating_add(1)..)
+            .take(max_blocks)
+            .map(|(&version, &map_index)| {
+                next_marker = Marker(version);
+                let set = self.set.get_block(map_index);
+                (map_index, set.expect("changed bit sets are never removed"))
+            })
+            .collect();
+        blocks.sort_unstable_by_key(|&(map_index, _)| map_index);
+
+        let mut delta = VecIndexSet::with_capacity(blocks.len());
+        delta.merge_blocks(blocks);
+        (delta, next_marker)
+    }
+
+    /// Record a change to the bit set at `map_index`.
+    fn record_change(&mut self, map_index: usize) {
+        self.version += 1;
+        if let Some(old_version) = self.versions.insert(map_index, self.version) {
+            self.changes.remove(&old_version);
+        }
+        self.changes.insert(self.version, map_index);
+    }
+}
+
+impl<S: storage::Storage> Lattice for DeltaGSet<S> {
+    #[inline]
+    fn join(&mut self, other: &Self) {
+        self.apply_delta(&other.set);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that replicas converge by exchanging bounded deltas.
+    #[test]
+    fn test_delta_g_set_sync() {
+        let mut a = DeltaGSet::<u64>::new();
+        let mut b = DeltaGSet::<u64>::new();
+        for index in [1, 64, 600, 1 << 20] {
+            a.insert(index);
+        }
+        b.insert(5);
+
+        let mut marker = Marker::default();
+        loop {
+            let (delta, next_marker) = a.delta_since(marker, 1);
+            if next_marker == marker {
+                break;
+            }
+            assert!(delta.num_blocks() <= 1);
+            b.apply_delta(&delta);
+            marker = next_marker;
+        }
+        assert_eq!(marker, a.marker());
+        assert_eq!(b.as_set(), &[1, 5, 64, 600, 1 << 20]);
+
+        a.insert(2);
+        let (delta, marker) = a.delta_since(marker, usize::MAX);
+        assert_eq!(delta, [1, 2]);
+        assert_eq!(marker, a.marker());
+
+        b.join(&a);
+        a.join(&b);
+        assert_eq!(a.as_set(), b.as_set());
+
+        let mut set: VecIndexSet = [1, 2].into_iter().collect();
+        set.join(&[3].into_iter().collect());
+        assert_eq!(set, [1, 2, 3]);
+    }
+}
@@ -0,0 +1,108 @@
+//! Cursors over the indices of index sets.
+
+use super::IndexSet;
+
+/// Position of a [`CursorMut`].
+#[derive(Debug, Clone, Copy)]
+enum Position {
+    /// Before the first index of the set.
+    Start,
+    /// At an index, which may have been removed from the set.
+    At(usize),
+    /// Past the last index of the set.
+    End,
+}
+
+/// Cursor walking over the indices of an [`IndexSet`], in ascending
+/// order, which can insert and remove indices as it goes.
+///
+/// The cursor starts before the first index of the set. Removing
+/// the current index does not move the cursor, such that the next
+/// call to [`CursorMut::move_next`] yields the index that followed
+/// the removed one.
+#[derive(Debug)]
+pub struct CursorMut<'a, T: ?Sized> {
+    /// The set being walked over.
+    set: &'a mut T,
+    /// The position of the cursor in the set.
+    position: Position,
+}
+
+impl<'a, T: IndexSet + ?Sized> CursorMut<'a, T> {
+    /// Create a new [`CursorMut`], positioned before
+    /// the first index of `set`.
+    #[inline]
+    pub fn new(set: &'a mut T) -> Self {
+        Self {
+            set,
+            position: Position::Start,
+        }
+    }
+
+    /// Return the index at the position of this cursor, unless
+    /// it lies outside of the set, or has been removed.
+    #[inline]
+    pub fn current(&self) -> Option<usize> {
+        match self.position {
+            Position::At(index) if self.set.contains(index) => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Move this cursor to the next index of the set, returning it.
+    ///
+    /// Once the cursor moves past the last index of the set,
+    /// it stays there, and [`None`] is returned.
+    pub fn move_next(&mut self) -> Option<usize> {
+        let next = match self.position {
+            Position::Start => self.set.iter().next(),
+            Position::At(index) => self.set.successor(index),
+            Position::End => None,
+        };
+        self.position = next.map_or(Position::End, Position::At);
+        next
+    }
+
+    /// Remove the index at the position of this cursor,
+    /// returning it, if it was present in the set.
+    #[inline]
+    pub fn remove_current(&mut self) -> Option<usize> {
+        let index = self.current()?;
+        self.set.remove(index);
+        Some(index)
+    }
+
+    /// Insert an index lower than the index at the position
+    /// of this cursor, which will not be visited by it.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `index` is not lower than the position of
+    /// this cursor, or if the cursor is before the first index.
+    pub fn insert_before(&mut self, index: usize) {
+        let is_before = match self.position {
+            Position::Start => false,
+            Position::At(current) => index < current,
+            Position::End => true,
+        };
+        assert!(is_before, "index out of order");
+        self.set.insert(index);
+    }
+
+    /// Insert an index greater than the index at the position
+    /// of this cursor, which will be visited by it.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `index` is not greater than the position of
+    /// this cursor, or if the cursor is past the last index.
+    pub fn insert_after(&mut self, index: usize) {
+        let is_after = match self.position {
+            Position::Start => true,
+            Position::At(current) => index > current,
+            Position::End => false,
+        };
+        assert!(is_after, "index out of order");
+        self.set.insert(index);
+    }
+}
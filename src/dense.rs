@@ -0,0 +1,473 @@
+//! Index set backed by a plain bit vector.
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::ops::RangeInclusive;
+
+use alloc::vec::Vec;
+
+use super::calculate_map_and_set_indices;
+use super::debug_indices;
+use super::iter::Indices;
+use super::macros::*;
+use super::storage;
+use super::IndexSet;
+
+/// Index set backed by a plain bit vector.
+///
+/// The bit set holding an index is found directly at position
+/// `index / W` of the vector, where `W` is the width of the
+/// bit sets, without storing any map indices. All the bit sets
+/// up to the largest index in the set are kept in memory, hence
+/// this backend suits dense sets of indices within a bounded
+/// range, where it is both smaller and faster than a
+/// [`VecIndexSet`](crate::vec::VecIndexSet).
+///
+/// Inserting a large index allocates every bit set below it,
+/// which may exhaust the available memory.
+#[derive(Default, Clone, PartialEq, Eq, Hash)]
+#[repr(transparent)]
+pub struct DenseIndexSet<S = u64> {
+    /// Bit vectors, containing the actual boolean values to be
+    /// asserted. The last bit vector is never empty.
+    ///
+    /// If the bit `B` is set, at the bit vector with index `S`, then
+    /// the index `S::WIDTH * S + B` is in the set.
+    words: Vec<S>,
+}
+
+impl<S> DenseIndexSet<S> {
+    /// Create a new [`DenseIndexSet`].
+    pub const fn new() -> Self {
+        Self { words: Vec::new() }
+    }
+
+    /// Create a new [`DenseIndexSet`] with room for
+    /// at least `capacity` bit sets.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            words: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Return the number of bit sets this [`DenseIndexSet`]
+    /// can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.words.capacity()
+    }
+
+    /// Shrink the capacity of this [`DenseIndexSet`]
+    /// as much as possible.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.words.shrink_to_fit();
+    }
+}
+
+impl<S: storage::Storage> DenseIndexSet<S> {
+    /// Remove the empty bit sets at the end of this [`DenseIndexSet`].
+    #[inline]
+    fn trim(&mut self) {
+        while self.words.last() == Some(&S::ZERO) {
+            self.words.pop();
+        }
+    }
+
+    /// Return the bit set at `map_index`, if any.
+    #[inline]
+    fn get_block(&self, map_index: usize) -> Option<S> {
+        self.words
+            .get(map_index)
+            .copied()
+            .filter(|&set| set != S::ZERO)
+    }
+
+    /// Assert the invariants of this [`DenseIndexSet`], with the
+    /// `strict-invariants` feature.
+    ///
+    /// This is a no-op in release builds.
+    #[inline]
+    #[track_caller]
+    fn debug_assert_invariants(&self) {
+        debug_assert!(self.words.last() != Some(&S::ZERO));
+        super::debug_assert_invariants(self.blocks());
+    }
+
+    /// Iterate over the map indices and non-empty bit sets
+    /// of this [`DenseIndexSet`], in ascending order.
+    #[inline]
+    pub(crate) fn blocks(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + FusedIterator + Clone + '_ {
+        Self::blocks_from(0, &self.words)
+    }
+
+    /// Iterate over the map indices and non-empty bit sets of this
+    /// [`DenseIndexSet`] whose map indices lie within `map_range`,
+    /// in ascending order.
+    #[inline]
+    fn blocks_in(
+        &self,
+        map_range: RangeInclusive<usize>,
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + '_ {
+        let end = map_range.end().saturating_add(1).min(self.words.len());
+        let start = (*map_range.start()).min(end);
+        Self::blocks_from(start, &self.words[start..end])
+    }
+
+    /// Iterate over the non-empty bit sets of `words`,
+    /// whose first map index is `start`.
+    #[inline]
+    fn blocks_from<'a>(
+        start: usize,
+        words: &'a [S],
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + FusedIterator + Clone + 'a {
+        words
+            .iter()
+            .enumerate()
+            .filter(|&(_, &set)| set != S::ZERO)
+            .map(move |(i, &set)| (start + i, set))
+    }
+
+    /// Merge non-empty `blocks` into this [`DenseIndexSet`].
+    pub(crate) fn merge_blocks<I>(&mut self, blocks: I)
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        for (map_index, other_set) in blocks {
+            if map_index >= self.words.len() {
+                self.words.resize(map_index + 1, S::ZERO);
+            }
+            self.words[map_index] |= other_set;
+        }
+        self.debug_assert_invariants();
+    }
+
+    /// Merge non-empty `blocks` into this [`DenseIndexSet`], as with
+    /// [`Self::merge_blocks`], returning an error if the bit sets up
+    /// to the greatest map index of `blocks` can't be allocated.
+    ///
+    /// The set is left unchanged on error. Decoders of untrusted
+    /// input merge through this method, such that a single huge
+    /// index can't abort the process.
+    pub(crate) fn try_merge_blocks<I>(&mut self, blocks: I) -> Result<(), super::error::Error>
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        let blocks: Vec<_> = blocks.into_iter().collect();
+        if let Some(len) = blocks.iter().map(|&(map_index, _)| map_index + 1).max() {
+            self.words
+                .try_reserve(len.saturating_sub(self.words.len()))?;
+        }
+        self.merge_blocks(blocks);
+        Ok(())
+    }
+
+    /// Update each non-empty bit set of this [`DenseIndexSet`]
+    /// with `f`, in ascending order of map indices.
+    pub(crate) fn retain_blocks<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &mut S),
+    {
+        for (map_index, set) in self.words.iter_mut().enumerate() {
+            if *set != S::ZERO {
+                f(map_index, set);
+            }
+        }
+        self.trim();
+        self.debug_assert_invariants();
+    }
+
+    /// Append a non-empty bit set at `map_index`, which must be
+    /// greater than the map indices of all other bit sets.
+    #[inline]
+    fn push_block(&mut self, (map_index, set): (usize, S)) {
+        debug_assert!(self.words.len() <= map_index);
+        debug_assert!(set != S::ZERO);
+        self.words.resize(map_index, S::ZERO);
+        self.words.push(set);
+    }
+}
+
+impl<S: storage::Storage> fmt::Debug for DenseIndexSet<S> {
+    /// Format this [`DenseIndexSet`].
+    ///
+    /// The alternate form (`{:#?}`) renders the indices stored
+    /// in the set, eliding them past a fixed cap, whereas the
+    /// default form renders the raw bit sets.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            debug_indices(self, f)
+        } else {
+            f.debug_struct("DenseIndexSet")
+                .field("words", &self.words)
+                .finish()
+        }
+    }
+}
+
+impl<S: storage::Storage> IndexSet for DenseIndexSet<S> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|set| set.num_of_high_bits())
+            .sum::<usize>()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    fn insert(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        if map_index >= self.words.len() {
+            self.words.resize(map_index + 1, S::ZERO);
+        }
+        let mask = S::from_usize(1 << bit_set_index);
+        let added = self.words[map_index] & mask == S::ZERO;
+        self.words[map_index] |= mask;
+        self.debug_assert_invariants();
+        added
+    }
+
+    fn remove(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let mut removed = false;
+        if let Some(set) = self.words.get_mut(map_index) {
+            let mask = S::from_usize(1 << bit_set_index);
+            removed = *set & mask != S::ZERO;
+            *set &= !mask;
+            self.trim();
+        }
+        self.debug_assert_invariants();
+        removed
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.words.clear();
+    }
+
+    #[inline]
+    fn contains(&self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        self.words
+            .get(map_index)
+            .is_some_and(|&set| set & S::from_usize(1 << bit_set_index) != S::ZERO)
+    }
+
+    #[inline]
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        Indices::new(self.blocks())
+    }
+
+    fn union(&mut self, other: &DenseIndexSet<S>) {
+        if other.words.len() > self.words.len() {
+            self.words.resize(other.words.len(), S::ZERO);
+        }
+        for (set, &other_set) in self.words.iter_mut().zip(&other.words) {
+            *set |= other_set;
+        }
+        self.debug_assert_invariants();
+    }
+
+    #[inline]
+    fn reserve(&mut self, size: usize) {
+        self.words.reserve(size);
+    }
+
+    #[inline]
+    fn successor(&self, index: usize) -> Option<usize> {
+        self.successor_in_blocks(index)
+    }
+
+    #[inline]
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        self.predecessor_in_blocks(index)
+    }
+}
+
+#[cfg(feature = "vec")]
+impl<S: storage::Storage> From<&crate::vec::VecIndexSet<S>> for DenseIndexSet<S> {
+    /// Convert a [`VecIndexSet`](crate::vec::VecIndexSet)
+    /// into a [`DenseIndexSet`].
+    fn from(set: &crate::vec::VecIndexSet<S>) -> Self {
+        let mut dense = Self::new();
+        for block in set.blocks() {
+            dense.push_block(block);
+        }
+        dense
+    }
+}
+
+#[cfg(feature = "vec")]
+impl<S: storage::Storage> From<&DenseIndexSet<S>> for crate::vec::VecIndexSet<S> {
+    /// Convert a [`DenseIndexSet`] into a
+    /// [`VecIndexSet`](crate::vec::VecIndexSet).
+    fn from(set: &DenseIndexSet<S>) -> Self {
+        let mut sparse = Self::new();
+        for block in set.blocks() {
+            sparse.push_block(block);
+        }
+        sparse
+    }
+}
+
+#[cfg(feature = "btree")]
+impl<S: storage::Storage> From<&crate::btree::BTreeIndexSet<S>> for DenseIndexSet<S> {
+    /// Convert a [`BTreeIndexSet`](crate::btree::BTreeIndexSet)
+    /// into a [`DenseIndexSet`].
+    fn from(set: &crate::btree::BTreeIndexSet<S>) -> Self {
+        let mut dense = Self::new();
+        for block in set.blocks() {
+            dense.push_block(block);
+        }
+        dense
+    }
+}
+
+#[cfg(feature = "btree")]
+impl<S: storage::Storage> From<&DenseIndexSet<S>> for crate::btree::BTreeIndexSet<S> {
+    /// Convert a [`DenseIndexSet`] into a
+    /// [`BTreeIndexSet`](crate::btree::BTreeIndexSet).
+    fn from(set: &DenseIndexSet<S>) -> Self {
+        let mut sparse = Self::new();
+        for block in set.blocks() {
+            sparse.push_block(block);
+        }
+        sparse
+    }
+}
+
+index_set_impl_adaptive!(crate::dense::DenseIndexSet);
+index_set_impl_bit_set!(crate::dense::DenseIndexSet);
+index_set_impl_bitmap!(crate::dense::DenseIndexSet);
+index_set_impl_bools!(crate::dense::DenseIndexSet);
+index_set_impl_compact!(crate::dense::DenseIndexSet);
+index_set_impl_contains_many!(crate::dense::DenseIndexSet);
+index_set_impl_cross_width!(crate::dense::DenseIndexSet);
+index_set_impl_hamming_distance!(crate::dense::DenseIndexSet);
+index_set_impl_iter_u64_blocks!(crate::dense::DenseIndexSet);
+index_set_impl_map_monotonic!(crate::dense::DenseIndexSet);
+index_set_impl_merkle!(crate::dense::DenseIndexSet);
+index_set_impl_neighbors!(crate::dense::DenseIndexSet);
+index_set_impl_range_serialize!(crate::dense::DenseIndexSet);
+index_set_impl_roaring!(crate::dense::DenseIndexSet);
+index_set_impl_select!(crate::dense::DenseIndexSet);
+index_set_impl_slice!(crate::dense::DenseIndexSet);
+index_set_impl_sync!(crate::dense::DenseIndexSet);
+index_set_impl_partition!(crate::dense::DenseIndexSet);
+index_set_impl_fixedbitset!(crate::dense::DenseIndexSet);
+index_set_impl_from!(crate::dense::DenseIndexSet);
+index_set_impl_from_iterator!(crate::dense::DenseIndexSet);
+index_set_impl_from_str!(crate::dense::DenseIndexSet);
+index_set_impl_hibitset!(crate::dense::DenseIndexSet);
+index_set_impl_inclusion!(crate::dense::DenseIndexSet);
+index_set_impl_index!(crate::dense::DenseIndexSet);
+index_set_impl_partial_eq!(crate::dense::DenseIndexSet);
+index_set_impl_quickcheck!(crate::dense::DenseIndexSet);
+index_set_impl_rayon!(crate::dense::DenseIndexSet);
+index_set_impl_extend!(crate::dense::DenseIndexSet);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test inserting and removing indices, trimming
+    /// the empty bit sets at the end of the vector.
+    #[test]
+    fn test_dense_index_set_insert_remove() {
+        let mut set = DenseIndexSet::<u16>::new();
+        for index in [5, 0, 9, 2, 9] {
+            set.insert(index);
+        }
+        assert_eq!(set, [0, 2, 5, 9]);
+        assert_eq!(set.len(), 4);
+        assert_eq!(set.words.len(), 5);
+        assert!(set.contains(5) && !set.contains(6) && !set.contains(1 << 40));
+
+        set.remove(9);
+        set.remove(1 << 40);
+        assert_eq!(set.words.len(), 3);
+        assert_eq!(set.successor(2), Some(5));
+        assert_eq!(set.predecessor(2), Some(0));
+
+        for index in [0, 2, 5] {
+            set.remove(index);
+        }
+        assert!(set.is_empty());
+        assert_eq!(set, DenseIndexSet::new());
+    }
+
+    /// Test merging and intersecting sets.
+    #[test]
+    fn test_dense_index_set_union_intersection() {
+        let mut a: DenseIndexSet<u8> = [0, 3, 100].into_iter().collect();
+        let b: DenseIndexSet<u8> = [1, 3, 300].into_iter().collect();
+        a.union(&b);
+        assert_eq!(a, [0, 1, 3, 100, 300]);
+
+        a.intersect_with(&DenseIndexSet::<u64>::from([0, 3, 4]));
+        assert_eq!(a, [0, 3]);
+        assert_eq!(a.words.len(), 4);
+    }
+
+    /// Test converting sets from and into the sparse backends.
+    #[test]
+    #[cfg(any(feature = "vec", feature = "btree"))]
+    fn test_dense_index_set_sparse_conversions() {
+        let indices = [1, 2, 64, 65, 1000, 4095];
+
+        #[cfg(feature = "vec")]
+        {
+            let sparse: crate::vec::VecIndexSet<u32> = indices.into_iter().collect();
+            let dense = DenseIndexSet::from(&sparse);
+            assert_eq!(dense, indices);
+            assert_eq!(crate::vec::VecIndexSet::from(&dense), sparse);
+        }
+
+        #[cfg(feature = "btree")]
+        {
+            let sparse: crate::btree::BTreeIndexSet<u32> = indices.into_iter().collect();
+            let dense = DenseIndexSet::from(&sparse);
+            assert_eq!(dense, indices);
+            assert_eq!(crate::btree::BTreeIndexSet::from(&dense), sparse);
+        }
+    }
+
+    /// Test rejecting adaptive encodings of indices too large to allocate.
+    #[test]
+    fn test_dense_index_set_adaptive_huge_index() {
+        let bytes = crate::adaptive::encode([(1 << 62, 1)]);
+        assert_eq!(
+            DenseIndexSet::<u64>::from_adaptive_bytes(&bytes),
+            Err(crate::error::Error::AllocError)
+        );
+    }
+
+    /// Test rejecting pages holding indices too large to allocate.
+    #[test]
+    #[cfg(feature = "serialize-borsh")]
+    fn test_dense_index_set_deserialize_huge_range() {
+        let index = 1u64 << 62;
+        let mut bytes = alloc::vec::Vec::new();
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(&index.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&(index / 8).to_le_bytes());
+        bytes.extend_from_slice(&1u64.to_le_bytes());
+        assert_eq!(bytes.len(), 36);
+
+        let mut set: DenseIndexSet = [1, 2].into_iter().collect();
+        let error = set.deserialize_into_range(&mut &bytes[..]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            crate::error::Error::AllocError.to_string()
+        );
+        assert_eq!(set, [1, 2]);
+    }
+}
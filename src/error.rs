@@ -0,0 +1,209 @@
+//! Errors returned when building or reading index sets.
+//!
+//! Fallible operations return an [`Error`], such that callers can
+//! match on failure causes across operations. Building an index set
+//! from its bit sets returns a [`FromBlocksError`], which converts
+//! into an [`Error`].
+
+use core::fmt;
+
+use alloc::collections::TryReserveError;
+
+/// Cause of a failure of any fallible operation of this crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Error {
+    /// The map indices of the bit sets are not in ascending order.
+    UnsortedBlocks,
+    /// A map index appears more than once.
+    DuplicateBlock,
+    /// A bit set has no bits set.
+    ZeroBlock,
+    /// A bit set has bits set past the width of the bit sets.
+    InvalidBits,
+    /// An index lies past [`usize::MAX`].
+    IndexOutOfBounds,
+    /// An encoded index set is malformed, such as
+    /// when its length does not match its contents.
+    InvalidEncoding,
+    /// An encoded index set is valid, but not in the
+    /// canonical form required by its decoder.
+    NonCanonicalEncoding,
+    /// Memory could not be allocated.
+    AllocError,
+    /// An index set exceeds the limits of an encoding,
+    /// such as the number of bit sets it can hold.
+    LimitExceeded,
+}
+
+impl Error {
+    /// Return a description of this error.
+    pub(crate) const fn message(&self) -> &'static str {
+        match self {
+            Self::UnsortedBlocks => "map indices should have been sorted",
+            Self::DuplicateBlock => "map indices should have been unique",
+            Self::ZeroBlock => "bit sets should have been non-empty",
+            Self::InvalidBits => "bit sets should only hold bits within their width",
+            Self::IndexOutOfBounds => "bit sets should not hold indices past usize::MAX",
+            Self::InvalidEncoding => "encoded index set is malformed",
+            Self::NonCanonicalEncoding => "encoded index set is not canonically encoded",
+            Self::AllocError => "memory allocation failed",
+            Self::LimitExceeded => "index set exceeds the limits of its encoding",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl From<Error> for alloc::string::String {
+    #[inline]
+    fn from(error: Error) -> Self {
+        error.message().into()
+    }
+}
+
+/// Wrap an [`Error`] in a borsh I/O error.
+///
+/// Without the `std` feature of `borsh`, its I/O errors only
+/// hold a message. Otherwise, the [`Error`] can be recovered
+/// with `std::io::Error::get_ref` and a downcast.
+#[cfg(feature = "serialize-borsh")]
+impl From<Error> for borsh::io::Error {
+    fn from(error: Error) -> Self {
+        let kind = match error {
+            Error::AllocError => borsh::io::ErrorKind::OutOfMemory,
+            Error::LimitExceeded => borsh::io::ErrorKind::InvalidInput,
+            _ => borsh::io::ErrorKind::InvalidData,
+        };
+        borsh::io::Error::new(kind, error)
+    }
+}
+
+impl From<FromBlocksError> for Error {
+    fn from(error: FromBlocksError) -> Self {
+        match error.kind {
+            FromBlocksErrorKind::Unsorted => Self::UnsortedBlocks,
+            FromBlocksErrorKind::Duplicate => Self::DuplicateBlock,
+            FromBlocksErrorKind::EmptyBlock => Self::ZeroBlock,
+            FromBlocksErrorKind::InvalidBits => Self::InvalidBits,
+            FromBlocksErrorKind::IndexOverflow => Self::IndexOutOfBounds,
+        }
+    }
+}
+
+impl From<TryReserveError> for Error {
+    #[inline]
+    fn from(_: TryReserveError) -> Self {
+        Self::AllocError
+    }
+}
+
+/// Error returned when building an index set from
+/// pairs of map indices and bit sets fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FromBlocksError {
+    /// The kind of error that was encountered.
+    kind: FromBlocksErrorKind,
+}
+
+/// The kind of error encountered while building an index
+/// set from pairs of map indices and bit sets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FromBlocksErrorKind {
+    /// The map indices are not in ascending order.
+    Unsorted,
+    /// A map index appears more than once.
+    Duplicate,
+    /// A bit set has no bits set.
+    EmptyBlock,
+    /// A bit set has bits set past the width of the bit sets,
+    /// which hold no index.
+    InvalidBits,
+    /// A bit set holds indices past [`usize::MAX`].
+    IndexOverflow,
+}
+
+impl FromBlocksError {
+    /// Create a new [`FromBlocksError`] of the given `kind`.
+    #[cfg(any(
+        feature = "vec",
+        feature = "btree",
+        feature = "dense",
+        feature = "paged",
+        feature = "hashbrown"
+    ))]
+    #[inline]
+    pub(crate) const fn new(kind: FromBlocksErrorKind) -> Self {
+        Self { kind }
+    }
+
+    /// Return the kind of error that was encountered.
+    #[inline]
+    pub fn kind(&self) -> FromBlocksErrorKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for FromBlocksError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        Error::from(self.clone()).fmt(f)
+    }
+}
+
+impl core::error::Error for FromBlocksError {}
+
+/// Error returned when reading an index set from
+/// an [`embedded_io::Read`] implementation fails.
+#[cfg(all(feature = "embedded-io", feature = "vec"))]
+#[derive(Debug)]
+pub enum ReadError<E> {
+    /// Reading from the underlying reader failed.
+    Read(embedded_io::ReadExactError<E>),
+    /// The data read is not a valid encoded index set.
+    Decode(Error),
+}
+
+#[cfg(all(feature = "embedded-io", feature = "vec"))]
+impl<E: fmt::Debug> fmt::Display for ReadError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(error) => write!(f, "failed to read index set: {error}"),
+            Self::Decode(error) => error.fmt(f),
+        }
+    }
+}
+
+#[cfg(all(feature = "embedded-io", feature = "vec"))]
+impl<E: fmt::Debug> core::error::Error for ReadError<E> {}
+
+/// Error returned when writing an index set to
+/// an [`embedded_io::Write`] implementation fails.
+#[cfg(all(feature = "embedded-io", feature = "vec"))]
+#[derive(Debug)]
+pub enum WriteError<E> {
+    /// Writing to the underlying writer failed.
+    Write(E),
+    /// The index set cannot be encoded.
+    Encode(Error),
+}
+
+#[cfg(all(feature = "embedded-io", feature = "vec"))]
+impl<E: fmt::Debug> fmt::Display for WriteError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Write(error) => write!(f, "failed to write index set: {error:?}"),
+            Self::Encode(error) => error.fmt(f),
+        }
+    }
+}
+
+#[cfg(all(feature = "embedded-io", feature = "vec"))]
+impl<E: fmt::Debug> core::error::Error for WriteError<E> {}
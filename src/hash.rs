@@ -0,0 +1,328 @@
+//! Index set backed by a hash table.
+
+use core::fmt;
+use core::hash::{BuildHasherDefault, Hasher};
+use core::iter::FusedIterator;
+
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use hashbrown::hash_map::Entry;
+use hashbrown::HashMap;
+
+use super::calculate_map_and_set_indices;
+use super::debug_indices;
+use super::iter::Indices;
+use super::macros::*;
+use super::storage;
+use super::IndexSet;
+
+/// Index set backed by a [`HashMap`].
+///
+/// Inserting, removing and looking up indices takes constant
+/// time, regardless of the number of bit sets in the set.
+/// In exchange, iterating over the set in ascending order
+/// sorts its bit sets first, which allocates.
+///
+/// This trade off favors huge, extremely sparse sets accessed
+/// at random indices, where the lookups of the other backends
+/// dominate. The bit sets are hashed with a fast, unkeyed
+/// hasher, hence this backend should not store indices
+/// chosen by an adversary.
+#[derive(Default, Clone, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct HashIndexSet<S = u64> {
+    /// Map of indices to bit vectors, containing the actual boolean
+    /// values to be asserted.
+    ///
+    /// If the bit `B` is set, at the bit vector with index `S`, then
+    /// the index `S::WIDTH * S + B` is in the set.
+    bit_sets: HashMap<usize, S, BuildHasherDefault<BlockHasher>>,
+}
+
+/// Hasher of the map indices of a [`HashIndexSet`].
+///
+/// Map indices are mixed with the finalizer of SplitMix64,
+/// such that neighboring bit sets land in unrelated buckets.
+#[derive(Default, Clone, Copy)]
+struct BlockHasher {
+    state: u64,
+}
+
+impl Hasher for BlockHasher {
+    #[inline]
+    fn finish(&self) -> u64 {
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state = self.state.rotate_left(8) ^ u64::from(byte);
+        }
+    }
+
+    #[inline]
+    fn write_usize(&mut self, i: usize) {
+        self.state ^= i as u64;
+    }
+}
+
+impl<S> HashIndexSet<S> {
+    /// Create a new [`HashIndexSet`].
+    pub const fn new() -> Self {
+        Self {
+            bit_sets: HashMap::with_hasher(BuildHasherDefault::new()),
+        }
+    }
+
+    /// Create a new [`HashIndexSet`] with room for
+    /// at least `capacity` bit sets.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bit_sets: HashMap::with_capacity_and_hasher(capacity, BuildHasherDefault::new()),
+        }
+    }
+
+    /// Return the number of bit sets this [`HashIndexSet`]
+    /// can hold without reallocating.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.bit_sets.capacity()
+    }
+
+    /// Shrink the capacity of this [`HashIndexSet`]
+    /// as much as possible.
+    #[inline]
+    pub fn shrink_to_fit(&mut self) {
+        self.bit_sets.shrink_to_fit();
+    }
+}
+
+impl<S: storage::Storage> HashIndexSet<S> {
+    /// Assert the invariants of this [`HashIndexSet`], with the
+    /// `strict-invariants` feature.
+    ///
+    /// This is a no-op in release builds. The bit sets are only
+    /// sorted when the invariants are checked, such that updates
+    /// otherwise remain constant time.
+    #[inline]
+    #[track_caller]
+    fn debug_assert_invariants(&self) {
+        if cfg!(all(feature = "strict-invariants", debug_assertions)) {
+            super::debug_assert_invariants(self.blocks());
+        }
+    }
+
+    /// Iterate over the map indices and bit sets of
+    /// this [`HashIndexSet`], in ascending order.
+    ///
+    /// The bit sets are sorted up front, which allocates.
+    fn blocks(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + ExactSizeIterator + FusedIterator + Clone
+    {
+        let mut blocks: Vec<_> = self
+            .bit_sets
+            .iter()
+            .map(|(&map_index, &set)| (map_index, set))
+            .collect();
+        blocks.sort_unstable_by_key(|&(map_index, _)| map_index);
+        blocks.into_iter()
+    }
+
+    /// Merge non-empty `blocks` into this [`HashIndexSet`].
+    pub(crate) fn merge_blocks<I>(&mut self, blocks: I)
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        for (map_index, other_set) in blocks {
+            *self.bit_sets.entry(map_index).or_insert(S::ZERO) |= other_set;
+        }
+        self.debug_assert_invariants();
+    }
+}
+
+impl<S: storage::Storage> fmt::Debug for HashIndexSet<S> {
+    /// Format this [`HashIndexSet`].
+    ///
+    /// The alternate form (`{:#?}`) renders the indices stored
+    /// in the set, eliding them past a fixed cap, whereas the
+    /// default form renders the raw bit sets, in ascending order.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            debug_indices(self, f)
+        } else {
+            f.debug_struct("HashIndexSet")
+                .field("bit_sets", &self.blocks().collect::<BTreeMap<_, _>>())
+                .finish()
+        }
+    }
+}
+
+impl<S: storage::Storage> IndexSet for HashIndexSet<S> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.bit_sets
+            .values()
+            .map(|set| set.num_of_high_bits())
+            .sum::<usize>()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.bit_sets.is_empty()
+    }
+
+    fn insert(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let mask = S::from_usize(1 << bit_set_index);
+        let set = self.bit_sets.entry(map_index).or_insert(S::ZERO);
+        let added = *set & mask == S::ZERO;
+        *set |= mask;
+        self.debug_assert_invariants();
+        added
+    }
+
+    fn remove(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let mut removed = false;
+        if let Entry::Occupied(mut e) = self.bit_sets.entry(map_index) {
+            let mask = S::from_usize(1 << bit_set_index);
+            removed = *e.get() & mask != S::ZERO;
+            let set = *e.get() & !mask;
+            if set == S::ZERO {
+                e.remove();
+            } else {
+                e.insert(set);
+            }
+        }
+        self.debug_assert_invariants();
+        removed
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.bit_sets.clear();
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        self.bit_sets
+            .get(&map_index)
+            .map(|&set| set & S::from_usize(1 << bit_set_index) != S::ZERO)
+            .unwrap_or(false)
+    }
+
+    #[inline]
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        Indices::new(self.blocks())
+    }
+
+    fn union(&mut self, other: &HashIndexSet<S>) {
+        self.bit_sets.reserve(other.bit_sets.len());
+        for (&map_index, &set) in &other.bit_sets {
+            *self.bit_sets.entry(map_index).or_insert(S::ZERO) |= set;
+        }
+        self.debug_assert_invariants();
+    }
+
+    #[inline]
+    fn reserve(&mut self, size: usize) {
+        self.bit_sets.reserve(size);
+    }
+}
+
+index_set_impl_from!(crate::hash::HashIndexSet);
+index_set_impl_from_iterator!(crate::hash::HashIndexSet);
+index_set_impl_from_str!(crate::hash::HashIndexSet);
+index_set_impl_hamming_distance!(crate::hash::HashIndexSet);
+index_set_impl_inclusion!(crate::hash::HashIndexSet);
+index_set_impl_index!(crate::hash::HashIndexSet);
+index_set_impl_partial_eq!(crate::hash::HashIndexSet);
+index_set_impl_extend!(crate::hash::HashIndexSet);
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeSet;
+
+    use super::*;
+
+    /// Test that updates do not sort the bit sets, which
+    /// would make inserting many sparse indices quadratic.
+    #[test]
+    #[cfg(not(all(feature = "strict-invariants", debug_assertions)))]
+    fn test_hash_index_set_many_sparse_updates() {
+        let mut set = HashIndexSet::<u64>::new();
+        let indices = (0..200_000usize).map(|i| i * 1031);
+        for index in indices.clone() {
+            set.insert(index);
+        }
+        assert_eq!(set.bit_sets.len(), 200_000);
+
+        for index in indices {
+            set.remove(index);
+        }
+        assert!(set.is_empty());
+    }
+
+    /// Test inserting, removing and looking up indices.
+    #[test]
+    fn test_hash_index_set_random_access() {
+        let mut set = HashIndexSet::<u16>::new();
+        let indices = [1 << 40, 7, usize::MAX, 3, 1 << 20, 8];
+        for index in indices {
+            set.insert(index);
+        }
+        set.insert(7);
+
+        assert_eq!(set.len(), indices.len());
+        assert!(indices.iter().all(|&index| set.contains(index)));
+        assert!(!set.contains(9) && !set.contains((1 << 40) + 1));
+        assert_eq!(set, [3, 7, 8, 1 << 20, 1 << 40, usize::MAX]);
+
+        set.remove(8);
+        set.remove(usize::MAX);
+        set.remove(9);
+        assert_eq!(set, [3, 7, 1 << 20, 1 << 40]);
+        assert_eq!(set.bit_sets.len(), 4);
+
+        for index in [3, 7, 1 << 20, 1 << 40] {
+            set.remove(index);
+        }
+        assert!(set.is_empty());
+    }
+
+    /// Test iterating over sparse sets in ascending order.
+    #[test]
+    fn test_hash_index_set_iter_sorted() {
+        let indices: BTreeSet<usize> = (0..2_000usize)
+            .map(|i| i.wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 20)
+            .collect();
+        let set: HashIndexSet = indices.iter().rev().copied().collect();
+
+        assert_eq!(set.len(), indices.len());
+        assert!(set.iter().eq(indices.iter().copied()));
+        assert_eq!(BTreeSet::from(&set), indices);
+    }
+
+    /// Test merging sets.
+    #[test]
+    fn test_hash_index_set_union() {
+        let mut a: HashIndexSet<u8> = [0, 3, 1000].into_iter().collect();
+        let b: HashIndexSet<u8> = [1, 3, 1 << 30].into_iter().collect();
+        a.union(&b);
+        assert_eq!(a, [0, 1, 3, 1000, 1 << 30]);
+        assert_eq!(
+            a.partial_cmp_by_inclusion(&b),
+            Some(core::cmp::Ordering::Greater)
+        );
+
+        a.extend([&HashIndexSet::from([5])]);
+        assert_eq!(a, [0, 1, 3, 5, 1000, 1 << 30]);
+        assert_eq!(format!("{a:#?}"), format!("{:#?}", BTreeSet::from(&a)));
+    }
+}
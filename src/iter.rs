@@ -0,0 +1,241 @@
+//! Iterators over the indices of index sets.
+
+use core::iter::FusedIterator;
+
+use super::bit_range_mask;
+use super::bit_set_indices;
+use super::storage::Storage;
+
+/// Iterator over the indices held in a sequence of bit sets,
+/// given in ascending order of their map indices.
+#[derive(Clone)]
+pub(crate) struct Indices<B, S> {
+    /// The bit sets left to iterate over.
+    blocks: B,
+    /// The first index of the bit set being iterated
+    /// over, along with its bits left to yield.
+    current: Option<(usize, S)>,
+    /// The number of indices left to yield, once counted.
+    remaining: Option<usize>,
+}
+
+impl<B, S> Indices<B, S>
+where
+    B: Iterator<Item = (usize, S)> + Clone,
+    S: Storage,
+{
+    /// Create a new [`Indices`] iterator over `blocks`.
+    ///
+    /// The indices they hold are only counted when first needed,
+    /// such that iterating over a prefix of a large set is cheap.
+    #[inline]
+    pub(crate) const fn new(blocks: B) -> Self {
+        Self {
+            blocks,
+            current: None,
+            remaining: None,
+        }
+    }
+
+    /// Return the number of indices left to yield,
+    /// counting them if they were not counted yet.
+    fn remaining(&self) -> usize {
+        self.remaining.unwrap_or_else(|| {
+            let current = self.current.map_or(0, |(_, set)| set.num_of_high_bits());
+            let blocks: usize = self
+                .blocks
+                .clone()
+                .map(|(_, set)| Self::index_bits(set).num_of_high_bits())
+                .sum();
+            current + blocks
+        })
+    }
+}
+
+impl<B, S: Storage> Indices<B, S> {
+    /// Return the bits of `set` that represent indices.
+    #[inline]
+    fn index_bits(set: S) -> S {
+        set & bit_range_mask(0, S::WIDTH - 1)
+    }
+}
+
+impl<B, S> Iterator for Indices<B, S>
+where
+    B: Iterator<Item = (usize, S)> + Clone,
+    S: Storage,
+{
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            if let Some((start_index, set)) = &mut self.current {
+                if *set != S::ZERO {
+                    let index = *start_index + set.trailing_zeros();
+                    *set = set.without_lowest_bit();
+                    if let Some(remaining) = &mut self.remaining {
+                        *remaining -= 1;
+                    }
+                    return Some(index);
+                }
+            }
+            let (map_index, set) = self.blocks.next()?;
+            self.current = Some((map_index * S::WIDTH, Self::index_bits(set)));
+        }
+    }
+
+    /// Return the exact number of indices left to yield, which
+    /// scans the bit sets left to iterate over, unless counted.
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.remaining();
+        (remaining, Some(remaining))
+    }
+
+    #[inline]
+    fn count(self) -> usize {
+        self.remaining()
+    }
+
+    /// Return the last index left to yield, only reading
+    /// the bits of the last non-empty bit set.
+    fn last(self) -> Option<usize> {
+        let (start_index, set) = self
+            .current
+            .into_iter()
+            .chain(
+                self.blocks
+                    .map(|(map_index, set)| (map_index * S::WIDTH, Self::index_bits(set))),
+            )
+            .filter(|&(_, set)| set != S::ZERO)
+            .last()?;
+        bit_set_indices(set)
+            .next_back()
+            .map(|bit_set_index| start_index + bit_set_index)
+    }
+
+    /// Return the `n`th index left to yield, skipping whole
+    /// bit sets by counting their bits.
+    fn nth(&mut self, mut n: usize) -> Option<usize> {
+        if let Some(remaining) = &mut self.remaining {
+            *remaining = remaining.saturating_sub(n);
+        }
+        loop {
+            if let Some((_, set)) = &mut self.current {
+                let num_of_high_bits = set.num_of_high_bits();
+                if n < num_of_high_bits {
+                    for _ in 0..n {
+                        *set = set.without_lowest_bit();
+                    }
+                    return self.next();
+                }
+                n -= num_of_high_bits;
+                self.current = None;
+            }
+            let (map_index, set) = self.blocks.next()?;
+            self.current = Some((map_index * S::WIDTH, Self::index_bits(set)));
+        }
+    }
+
+    /// Fold the indices left to yield, one bit set at a time.
+    fn fold<A, F>(self, init: A, mut f: F) -> A
+    where
+        F: FnMut(A, usize) -> A,
+    {
+        let blocks = self
+            .blocks
+            .map(|(map_index, set)| (map_index * S::WIDTH, Self::index_bits(set)));
+
+        let mut acc = init;
+        for (start_index, mut set) in self.current.into_iter().chain(blocks) {
+            while set != S::ZERO {
+                acc = f(acc, start_index + set.trailing_zeros());
+                set = set.without_lowest_bit();
+            }
+        }
+        acc
+    }
+}
+
+impl<B, S> ExactSizeIterator for Indices<B, S>
+where
+    B: Iterator<Item = (usize, S)> + Clone,
+    S: Storage,
+{
+}
+
+impl<B, S> FusedIterator for Indices<B, S>
+where
+    B: FusedIterator<Item = (usize, S)> + Clone,
+    S: Storage,
+{
+}
+
+/// Iterator over the indices held in a sequence of bit sets,
+/// given in ascending order of their map indices, which are
+/// consumed as they are iterated over.
+#[cfg(any(feature = "vec", feature = "btree"))]
+pub(crate) struct Drain<B, S> {
+    /// The bit sets left to iterate over.
+    blocks: B,
+    /// The first index of the bit set being iterated
+    /// over, along with its bits left to yield.
+    current: Option<(usize, S)>,
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+impl<B, S: Storage> Drain<B, S> {
+    /// Create a new [`Drain`] iterator over `blocks`,
+    /// none of which may be empty.
+    #[inline]
+    pub(crate) const fn new(blocks: B) -> Self {
+        Self {
+            blocks,
+            current: None,
+        }
+    }
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+impl<B, S> Iterator for Drain<B, S>
+where
+    B: Iterator<Item = (usize, S)>,
+    S: Storage,
+{
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        loop {
+            if let Some((start_index, set)) = &mut self.current {
+                if *set != S::ZERO {
+                    let index = *start_index + set.trailing_zeros();
+                    *set = set.without_lowest_bit();
+                    return Some(index);
+                }
+            }
+            let (map_index, set) = self.blocks.next()?;
+            self.current = Some((map_index * S::WIDTH, Indices::<B, S>::index_bits(set)));
+        }
+    }
+
+    /// Bound the number of indices left to yield, given that
+    /// each of the remaining bit sets holds at least one index.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let current = self.current.map_or(0, |(_, set)| set.num_of_high_bits());
+        let (num_of_blocks, max_num_of_blocks) = self.blocks.size_hint();
+        let upper = max_num_of_blocks
+            .and_then(|n| n.checked_mul(S::WIDTH))
+            .and_then(|n| n.checked_add(current));
+        (current.saturating_add(num_of_blocks), upper)
+    }
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+impl<B, S> FusedIterator for Drain<B, S>
+where
+    B: FusedIterator<Item = (usize, S)>,
+    S: Storage,
+{
+}
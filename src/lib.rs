@@ -3,11 +3,136 @@
 #![cfg_attr(not(test), no_std)]
 
 extern crate alloc;
+#[cfg(all(feature = "std", not(test)))]
+extern crate std;
 
+use core::iter::FusedIterator;
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+use core::ops::{Bound, RangeBounds};
+
+pub mod adapter;
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+pub mod adaptive;
+pub mod array;
+#[cfg(all(feature = "async-io", feature = "vec"))]
+mod async_io;
+pub mod bitmap;
+#[cfg(feature = "btree")]
+mod block_map;
+#[cfg(feature = "vec")]
+pub mod borrowed;
+#[cfg(feature = "btree")]
 pub mod btree;
+#[cfg(all(feature = "serialize-cbor", any(feature = "vec", feature = "btree")))]
+pub mod cbor;
+pub mod compact;
+#[cfg(feature = "crdt")]
+pub mod crdt;
+pub mod cursor;
+#[cfg(feature = "dense")]
+pub mod dense;
+pub mod error;
+#[cfg(feature = "hashbrown")]
+pub mod hash;
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+mod iter;
 mod macros;
+pub mod merkle;
+#[cfg(all(feature = "serialize-serde-bytes", feature = "vec"))]
+pub mod packed;
+#[cfg(feature = "paged")]
+pub mod paged;
+pub mod parse;
+#[cfg(feature = "vec")]
+pub mod pool;
+pub mod prelude;
+#[cfg(feature = "proptest")]
+pub mod proptest;
+#[cfg(feature = "prost")]
+pub mod proto;
+#[cfg(all(feature = "serialize-serde", any(feature = "vec", feature = "btree")))]
+mod serde_compat;
+pub mod stats;
 mod storage;
+pub mod sync;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "vec")]
+pub mod typed;
+#[cfg(feature = "vec")]
 pub mod vec;
+pub mod view;
+#[cfg(all(feature = "wasm", feature = "vec"))]
+pub mod wasm;
+
+#[doc(hidden)]
+pub mod __private {
+    //! Implementation details of exported macros.
+
+    use core::ops::{Range, RangeInclusive};
+
+    use super::IndexSet;
+
+    /// Argument of the [`index_set`](crate::index_set) macro.
+    pub trait IndexSetMacroArg {
+        /// Insert the indices represented by this argument
+        /// into `set`.
+        fn insert_into<T: IndexSet>(self, set: &mut T);
+    }
+
+    impl IndexSetMacroArg for usize {
+        #[inline]
+        fn insert_into<T: IndexSet>(self, set: &mut T) {
+            set.insert(self);
+        }
+    }
+
+    impl IndexSetMacroArg for Range<usize> {
+        #[inline]
+        fn insert_into<T: IndexSet>(self, set: &mut T) {
+            set.reserve(crate::safe_iter_reserve_cap(&self));
+            for index in self {
+                set.insert(index);
+            }
+        }
+    }
+
+    impl IndexSetMacroArg for RangeInclusive<usize> {
+        #[inline]
+        fn insert_into<T: IndexSet>(self, set: &mut T) {
+            set.reserve(crate::safe_iter_reserve_cap(&self));
+            for index in self {
+                set.insert(index);
+            }
+        }
+    }
+
+    /// Insert the indices represented by `arg` into `set`.
+    #[inline]
+    pub fn insert_into<T, A>(set: &mut T, arg: A)
+    where
+        T: IndexSet,
+        A: IndexSetMacroArg,
+    {
+        arg.insert_into(set);
+    }
+}
 
 /// Public interface of any index set implementation.
 pub trait IndexSet {
@@ -20,17 +145,30 @@ pub trait IndexSet {
     fn is_empty(&self) -> bool;
 
     /// Add a new index to this [`IndexSet`].
-    fn insert(&mut self, index: usize);
+    ///
+    /// Returns `true` if the index was not already present.
+    fn insert(&mut self, index: usize) -> bool;
 
     /// Remove an index from this [`IndexSet`].
-    fn remove(&mut self, index: usize);
+    ///
+    /// Returns `true` if the index was present.
+    fn remove(&mut self, index: usize) -> bool;
+
+    /// Remove all indices from this [`IndexSet`].
+    ///
+    /// Backends keep the allocation of their bit sets where
+    /// possible, such that it may be reused by later inserts.
+    fn clear(&mut self);
 
     /// Check the presence of an index in this [`IndexSet`].
     fn contains(&self, index: usize) -> bool;
 
     /// Return an iterator over the indices in
     /// this [`IndexSet`], in ascending order.
-    fn iter(&self) -> impl Iterator<Item = usize> + '_;
+    ///
+    /// Once exhausted, the iterator never yields
+    /// any more indices.
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_;
 
     /// Merge two [`IndexSet`] instances.
     ///
@@ -39,10 +177,244 @@ pub trait IndexSet {
     fn union(&mut self, other: &Self);
 
     /// Attempt to reserve space for the specified
-    /// number of additional [`usize`] elements.
+    /// number of additional bit sets.
+    ///
+    /// Each bit set stores a fixed number of consecutive indices,
+    /// such that reserving `size` bit sets is enough to insert
+    /// at least `size` additional [`usize`] elements, scattered
+    /// across the set, without reallocating. Backends also provide
+    /// a `reserve_for_index` method, which reserves enough space
+    /// to store all indices up to a given maximum index.
+    ///
+    /// Like [`Vec::reserve`](alloc::vec::Vec::reserve), backends may
+    /// reserve more space than requested, to amortize reallocations.
+    /// [`VecIndexSet::reserve_exact`](crate::vec::VecIndexSet::reserve_exact)
+    /// reserves no more than requested, while backends which allocate
+    /// each bit set on its own, such as
+    /// [`BTreeIndexSet`](crate::btree::BTreeIndexSet), ignore this call.
     fn reserve(&mut self, _size: usize) {
         // NOOP
     }
+
+    /// Return the lowest index in this [`IndexSet`]
+    /// that is greater than `index`, if any.
+    fn successor(&self, index: usize) -> Option<usize> {
+        self.iter().find(|&i| i > index)
+    }
+
+    /// Return the greatest index in this [`IndexSet`]
+    /// that is lower than `index`, if any.
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        self.iter().take_while(|&i| i < index).last()
+    }
+
+    /// Return the index in this [`IndexSet`] closest to `index`,
+    /// if any, favoring the lower index when two are equally close.
+    fn nearest(&self, index: usize) -> Option<usize> {
+        if self.contains(index) {
+            return Some(index);
+        }
+        match (self.predecessor(index), self.successor(index)) {
+            (Some(lower), Some(upper)) => Some(if index - lower <= upper - index {
+                lower
+            } else {
+                upper
+            }),
+            (lower, upper) => lower.or(upper),
+        }
+    }
+
+    /// Return a cursor over the indices in this [`IndexSet`],
+    /// which can insert and remove indices as it walks the set.
+    #[inline]
+    fn cursor_mut(&mut self) -> cursor::CursorMut<'_, Self> {
+        cursor::CursorMut::new(self)
+    }
+
+    /// Compare the indices of two [`IndexSet`] instances,
+    /// lexicographically, in ascending order.
+    ///
+    /// Unlike [`Ord`], the result is independent of the
+    /// backend and storage width of either set.
+    fn cmp_contents<T>(&self, other: &T) -> core::cmp::Ordering
+    where
+        T: IndexSet + ?Sized,
+    {
+        self.iter().cmp(other.iter())
+    }
+
+    /// Return an iterator over the indices in this [`IndexSet`],
+    /// in ascending order, in chunks of `chunk_size` indices.
+    ///
+    /// The last chunk may hold fewer than `chunk_size` indices.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    fn iter_chunks(&self, chunk_size: usize) -> impl Iterator<Item = alloc::vec::Vec<usize>> + '_ {
+        assert!(chunk_size != 0, "chunk size must be non-zero");
+
+        let mut indices = self.iter();
+        core::iter::from_fn(move || {
+            let chunk: alloc::vec::Vec<_> = indices.by_ref().take(chunk_size).collect();
+            (!chunk.is_empty()).then_some(chunk)
+        })
+    }
+}
+
+macro_rules! impl_index_set_for_pointer {
+    ($($Pointer:tt)*) => {
+        impl<T: IndexSet + ?Sized> IndexSet for $($Pointer)* {
+            #[inline]
+            fn len(&self) -> usize {
+                (**self).len()
+            }
+
+            #[inline]
+            fn is_empty(&self) -> bool {
+                (**self).is_empty()
+            }
+
+            #[inline]
+            fn insert(&mut self, index: usize) -> bool {
+                (**self).insert(index)
+            }
+
+            #[inline]
+            fn remove(&mut self, index: usize) -> bool {
+                (**self).remove(index)
+            }
+
+            #[inline]
+            fn clear(&mut self) {
+                (**self).clear();
+            }
+
+            #[inline]
+            fn contains(&self, index: usize) -> bool {
+                (**self).contains(index)
+            }
+
+            #[inline]
+            fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+                (**self).iter()
+            }
+
+            #[inline]
+            fn union(&mut self, other: &Self) {
+                (**self).union(&**other);
+            }
+
+            #[inline]
+            fn reserve(&mut self, size: usize) {
+                (**self).reserve(size);
+            }
+
+            #[inline]
+            fn successor(&self, index: usize) -> Option<usize> {
+                (**self).successor(index)
+            }
+
+            #[inline]
+            fn predecessor(&self, index: usize) -> Option<usize> {
+                (**self).predecessor(index)
+            }
+        }
+    };
+}
+
+impl_index_set_for_pointer!(&mut T);
+impl_index_set_for_pointer!(alloc::boxed::Box<T>);
+
+/// Read-only interface of any index set implementation.
+///
+/// This is implemented by shared references to any [`IndexSet`],
+/// such that generic code which only queries a set can take an
+/// [`IndexSetRef`] by value, and be handed a `&T`.
+pub trait IndexSetRef {
+    /// Return the number of [`usize`] values present
+    /// in this [`IndexSetRef`].
+    fn len(&self) -> usize;
+
+    /// Checks if this [`IndexSetRef`] has no inner indexes
+    /// stored within.
+    fn is_empty(&self) -> bool;
+
+    /// Check the presence of an index in this [`IndexSetRef`].
+    fn contains(&self, index: usize) -> bool;
+
+    /// Return an iterator over the indices in
+    /// this [`IndexSetRef`], in ascending order.
+    ///
+    /// Once exhausted, the iterator never yields
+    /// any more indices.
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_;
+
+    /// Return the lowest index in this [`IndexSetRef`]
+    /// that is greater than `index`, if any.
+    fn successor(&self, index: usize) -> Option<usize> {
+        self.iter().find(|&i| i > index)
+    }
+
+    /// Return the greatest index in this [`IndexSetRef`]
+    /// that is lower than `index`, if any.
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        self.iter().take_while(|&i| i < index).last()
+    }
+}
+
+impl<T: IndexSet + ?Sized> IndexSetRef for &T {
+    #[inline]
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        (**self).is_empty()
+    }
+
+    #[inline]
+    fn contains(&self, index: usize) -> bool {
+        (**self).contains(index)
+    }
+
+    #[inline]
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        (**self).iter()
+    }
+
+    #[inline]
+    fn successor(&self, index: usize) -> Option<usize> {
+        (**self).successor(index)
+    }
+
+    #[inline]
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        (**self).predecessor(index)
+    }
+}
+
+/// Maximum number of indices rendered by the alternate
+/// [`Debug`](core::fmt::Debug) form of an [`IndexSet`].
+const DEBUG_INDICES_CAP: usize = 64;
+
+/// Render the indices in `set` as a set, eliding any
+/// indices past [`DEBUG_INDICES_CAP`].
+fn debug_indices<T>(set: &T, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result
+where
+    T: IndexSet,
+{
+    let mut debug_set = f.debug_set();
+    let mut indices = set.iter();
+
+    debug_set.entries(indices.by_ref().take(DEBUG_INDICES_CAP));
+
+    if indices.next().is_some() {
+        debug_set.finish_non_exhaustive()
+    } else {
+        debug_set.finish()
+    }
 }
 
 #[inline]
@@ -57,6 +429,308 @@ where
     min_cap.min(256)
 }
 
+/// Packs indices, given in ascending order, into bit sets.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+struct BlockPacker<S> {
+    /// The bit set currently being filled, along with its map index.
+    current: Option<(usize, S)>,
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+impl<S: storage::Storage> BlockPacker<S> {
+    /// Create a new [`BlockPacker`].
+    #[inline]
+    const fn new() -> Self {
+        Self { current: None }
+    }
+
+    /// Add an index to this [`BlockPacker`], which must not be lower
+    /// than any of the indices previously added.
+    ///
+    /// Returns the previous bit set, once `index` falls in a new one.
+    #[inline]
+    fn push(&mut self, index: usize) -> Option<(usize, S)> {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let bit = S::from_usize(1 << bit_set_index);
+
+        match &mut self.current {
+            Some((current_map_index, set)) if *current_map_index == map_index => {
+                *set |= bit;
+                None
+            }
+            current => current.replace((map_index, bit)),
+        }
+    }
+
+    /// Return the last bit set filled by this [`BlockPacker`].
+    #[inline]
+    fn finish(self) -> Option<(usize, S)> {
+        self.current
+    }
+}
+
+/// Pack indices, given in ascending order, into pairs
+/// of map indices and bit sets.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+#[inline]
+fn pack_sorted<S, I>(iter: I) -> impl Iterator<Item = (usize, S)>
+where
+    S: storage::Storage,
+    I: IntoIterator<Item = usize>,
+{
+    let mut iter = iter.into_iter();
+    let mut packer = Some(BlockPacker::new());
+
+    core::iter::from_fn(move || loop {
+        let Some(index) = iter.next() else {
+            return packer.take()?.finish();
+        };
+        if let Some(block) = packer.as_mut()?.push(index) {
+            return Some(block);
+        }
+    })
+}
+
+/// Iterate over the positions of the bits set in `set`,
+/// in ascending order.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+#[inline]
+fn bit_set_indices<S>(set: S) -> impl DoubleEndedIterator<Item = usize>
+where
+    S: storage::Storage,
+{
+    (0..S::WIDTH).filter(move |&bit_set_index| set & S::from_usize(1 << bit_set_index) != S::ZERO)
+}
+
+/// Split 64-bit lanes of indices, as yielded by `iter_u64_blocks`,
+/// into the non-empty bit sets of type `S` covering them, in
+/// ascending order.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+fn blocks_from_u64_lanes<S, I>(lanes: I) -> impl Iterator<Item = (usize, S)>
+where
+    S: storage::Storage,
+    I: IntoIterator<Item = (usize, u64)>,
+{
+    const LANE_WIDTH: usize = u64::BITS as usize;
+
+    let width_mask = u64::MAX >> (LANE_WIDTH - S::WIDTH);
+    lanes.into_iter().flat_map(move |(start_index, lane)| {
+        (0..LANE_WIDTH).step_by(S::WIDTH).filter_map(move |offset| {
+            let bits = (lane >> offset) & width_mask;
+            (bits != 0).then(|| {
+                (
+                    (start_index + offset) / S::WIDTH,
+                    S::from_usize(bits as usize),
+                )
+            })
+        })
+    })
+}
+
+/// Return the greatest map index of a bit set of type `S`,
+/// such that all of its indices fit in a [`usize`].
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+#[inline]
+fn max_map_index<S>() -> usize {
+    usize::MAX.checked_div(size_of::<S>()).unwrap_or(usize::MAX)
+}
+
+/// Check the invariants of the bit sets of an index set, given
+/// in ascending order: their map indices must be strictly
+/// increasing and in range, and none of them may be empty.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+fn check_invariants<S, I>(blocks: I) -> Result<(), error::FromBlocksError>
+where
+    S: storage::Storage,
+    I: IntoIterator<Item = (usize, S)>,
+{
+    use error::{FromBlocksError, FromBlocksErrorKind};
+
+    let mut last_map_index = None;
+    for (map_index, set) in blocks {
+        match last_map_index.map(|last: usize| last.cmp(&map_index)) {
+            Some(core::cmp::Ordering::Greater) => {
+                return Err(FromBlocksError::new(FromBlocksErrorKind::Unsorted));
+            }
+            Some(core::cmp::Ordering::Equal) => {
+                return Err(FromBlocksError::new(FromBlocksErrorKind::Duplicate));
+            }
+            _ => {}
+        }
+        if set == S::ZERO {
+            return Err(FromBlocksError::new(FromBlocksErrorKind::EmptyBlock));
+        }
+        if set & !bit_range_mask::<S>(0, S::WIDTH - 1) != S::ZERO {
+            return Err(FromBlocksError::new(FromBlocksErrorKind::InvalidBits));
+        }
+        if map_index > max_map_index::<S>() {
+            return Err(FromBlocksError::new(FromBlocksErrorKind::IndexOverflow));
+        }
+        last_map_index = Some(map_index);
+    }
+    Ok(())
+}
+
+/// Assert the invariants of the bit sets of an index set, given
+/// in ascending order, with the `strict-invariants` feature.
+///
+/// This is a no-op in release builds.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+#[inline]
+#[track_caller]
+fn debug_assert_invariants<S, I>(blocks: I)
+where
+    S: storage::Storage,
+    I: IntoIterator<Item = (usize, S)>,
+{
+    if cfg!(all(feature = "strict-invariants", debug_assertions)) {
+        if let Err(error) = check_invariants(blocks) {
+            panic!("index set invariant violated: {error}");
+        }
+    }
+}
+
+/// Convert arbitrary range bounds into an inclusive pair of
+/// start and end indices, or [`None`] if the range is empty.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+#[inline]
+fn inclusive_range_bounds<R>(range: R) -> Option<(usize, usize)>
+where
+    R: RangeBounds<usize>,
+{
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.checked_add(1)?,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end,
+        Bound::Excluded(&end) => end.checked_sub(1)?,
+        Bound::Unbounded => usize::MAX,
+    };
+    (start <= end).then_some((start, end))
+}
+
+/// Return a bit set with all bits between `start_bit` and
+/// `end_bit` set, inclusively.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+#[inline]
+fn bit_range_mask<S>(start_bit: usize, end_bit: usize) -> S
+where
+    S: storage::Storage,
+{
+    debug_assert!(start_bit <= end_bit && end_bit < S::WIDTH);
+    let upper = usize::MAX >> (usize::BITS as usize - 1 - end_bit);
+    let lower = usize::MAX << start_bit;
+    S::from_usize(upper & lower)
+}
+
+/// Return the bit sets holding all the indices within the
+/// non-empty `range`, in ascending order of map indices.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+fn range_blocks<S>(range: core::ops::RangeInclusive<usize>) -> impl Iterator<Item = (usize, S)>
+where
+    S: storage::Storage,
+{
+    let (start, end) = range.into_inner();
+    debug_assert!(start <= end);
+    let (start_map_index, start_bit) = calculate_map_and_set_indices::<S>(start);
+    let (end_map_index, end_bit) = calculate_map_and_set_indices::<S>(end);
+
+    (start_map_index..=end_map_index).map(move |map_index| {
+        let first_bit = if map_index == start_map_index {
+            start_bit
+        } else {
+            0
+        };
+        let last_bit = if map_index == end_map_index {
+            end_bit
+        } else {
+            S::WIDTH - 1
+        };
+        (map_index, bit_range_mask(first_bit, last_bit))
+    })
+}
+
+/// Flip the bits of `set` which are set in `mask`.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+#[inline]
+fn flip_bits<S>(set: S, mask: S) -> S
+where
+    S: storage::Storage,
+{
+    let mut flipped = !set & mask;
+    flipped |= set & !mask;
+    flipped
+}
+
 #[inline]
 const fn calculate_map_and_set_indices<S>(index: usize) -> (usize, usize)
 where
@@ -69,3 +743,15 @@ where
 
     (map_index, bit_set_index)
 }
+
+/// Return the number of bit sets needed to store
+/// all indices up to `max_index`, inclusive.
+#[cfg(any(feature = "vec", feature = "btree"))]
+#[inline]
+const fn num_of_blocks_up_to<S>(max_index: usize) -> usize
+where
+    S: storage::Storage,
+{
+    let (map_index, _) = calculate_map_and_set_indices::<S>(max_index);
+    map_index.saturating_add(1)
+}
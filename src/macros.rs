@@ -1,355 +1,3802 @@
 //! Macro definitions.
 
-macro_rules! index_set_impl_from_iterator {
+/// Create an index set containing the given indices
+/// and ranges of indices.
+///
+/// The type of the set is inferred from the context,
+/// and must implement [`Default`] and [`IndexSet`](crate::IndexSet).
+///
+/// ## Example
+///
+/// ```
+/// # #[cfg(feature = "vec")] {
+/// use index_set::index_set;
+/// use index_set::vec::VecIndexSet;
+/// use index_set::IndexSet;
+///
+/// let set: VecIndexSet = index_set![1, 4, 7..20, 42];
+///
+/// assert!(set.contains(4));
+/// assert!(set.contains(19));
+/// assert!(!set.contains(20));
+/// assert_eq!(set.len(), 16);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! index_set {
+    () => {
+        ::core::default::Default::default()
+    };
+    ($($arg:expr),+ $(,)?) => {{
+        let mut set = ::core::default::Default::default();
+        $(
+            $crate::__private::insert_into(&mut set, $arg);
+        )+
+        set
+    }};
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_adaptive {
     ($($Set:tt)*) => {
-        impl<S: crate::storage::Storage> FromIterator<usize>
-            for $($Set)*<S>
-        {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Encode this set in its adaptive binary representation,
+            /// either as a list of indices or as a list of bitmap
+            /// lanes, whichever is shorter.
+            ///
+            /// Refer to the [`adaptive`](crate::adaptive) module
+            /// for a description of the encoding.
             #[inline]
-            fn from_iter<T>(iter: T) -> Self
+            pub fn to_adaptive_bytes(&self) -> alloc::vec::Vec<u8> {
+                crate::adaptive::encode(self.iter_u64_blocks())
+            }
+
+            /// Decode a set from its adaptive binary representation.
+            ///
+            /// Returns [`Error::AllocError`](crate::error::Error) if the
+            /// memory to hold the decoded set can't be allocated.
+            pub fn from_adaptive_bytes(bytes: &[u8]) -> Result<Self, crate::error::Error> {
+                let lanes = crate::adaptive::decode(bytes)?;
+                let mut set = Self::new();
+                set.try_merge_blocks(crate::blocks_from_u64_lanes::<S, _>(lanes))?;
+                Ok(set)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_bit_set {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "bit-set")]
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a new set from the bits set in a
+            /// [`BitSet`](::bit_set::BitSet), one block at a time.
+            ///
+            /// NB: this is not a [`From`] conversion, since it
+            /// would overlap with the conversion from iterators.
+            pub fn from_bit_set(bit_set: &::bit_set::BitSet) -> Self {
+                const BLOCK_BITS: usize = u32::BITS as usize;
+
+                let indices = bit_set
+                    .get_ref()
+                    .blocks()
+                    .enumerate()
+                    .filter(|&(_, block)| block != 0)
+                    .flat_map(|(block_index, block)| {
+                        let start_index = block_index * BLOCK_BITS;
+                        (0..BLOCK_BITS)
+                            .filter(move |&bit| block & (1 << bit) != 0)
+                            .map(move |bit| start_index + bit)
+                    });
+
+                let mut set = Self::new();
+                for block in crate::pack_sorted(indices) {
+                    set.push_block(block);
+                }
+                set
+            }
+
+            /// Convert this set into a [`BitSet`](::bit_set::BitSet),
+            /// one 64-bit block at a time.
+            pub fn to_bit_set(&self) -> ::bit_set::BitSet {
+                // NB: bit vectors are built from bytes whose most
+                // significant bit comes first
+                let bytes = self.to_bitmap_bytes(crate::bitmap::BitOrder::Msb0);
+                ::bit_set::BitSet::from_bytes(&bytes)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_bitmap {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a new set from a dense bitmap, whose bits
+            /// within each byte are laid out in the given `order`.
+            ///
+            /// The bitmap is read 64 bits at a time.
+            pub fn from_bitmap_bytes(bytes: &[u8], order: crate::bitmap::BitOrder) -> Self {
+                const LANE_BYTES: usize = size_of::<u64>();
+
+                let lanes = bytes
+                    .chunks(LANE_BYTES)
+                    .enumerate()
+                    .map(|(lane_index, chunk)| {
+                        let mut lane_bytes = [0u8; LANE_BYTES];
+                        for (lane_byte, &byte) in lane_bytes.iter_mut().zip(chunk) {
+                            *lane_byte = order.convert_byte(byte);
+                        }
+                        (lane_index * u64::BITS as usize, u64::from_le_bytes(lane_bytes))
+                    })
+                    .filter(|&(_, lane)| lane != 0);
+
+                let mut set = Self::new();
+                for block in crate::blocks_from_u64_lanes::<S, _>(lanes) {
+                    set.push_block(block);
+                }
+                set
+            }
+
+            /// Convert this set into a dense bitmap, whose bits
+            /// within each byte are laid out in the given `order`.
+            ///
+            /// The bitmap is as short as possible, ending with the
+            /// byte holding the greatest index of this set.
+            pub fn to_bitmap_bytes(&self, order: crate::bitmap::BitOrder) -> alloc::vec::Vec<u8> {
+                let Some(max_index) = self.largest(1).next() else {
+                    return alloc::vec::Vec::new();
+                };
+
+                let mut bytes = alloc::vec![0u8; max_index / 8 + 1];
+                for (start_index, lane) in self.iter_u64_blocks() {
+                    let lane_bytes = lane.to_le_bytes().map(|byte| order.convert_byte(byte));
+                    let lane_start = start_index / 8;
+                    let lane_end = (lane_start + lane_bytes.len()).min(bytes.len());
+                    bytes[lane_start..lane_end].copy_from_slice(&lane_bytes[..lane_end - lane_start]);
+                }
+                bytes
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_bools {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a set holding the positions of the `true`
+            /// values of `bools`, packing them one bit set at a time.
+            pub fn from_bools(bools: &[bool]) -> Self {
+                let mut set = Self::new();
+                for (map_index, chunk) in bools.chunks(S::WIDTH).enumerate() {
+                    let bits = chunk
+                        .iter()
+                        .rev()
+                        .fold(0usize, |bits, &value| (bits << 1) | usize::from(value));
+                    if bits != 0 {
+                        set.push_block((map_index, S::from_usize(bits)));
+                    }
+                }
+                set
+            }
+
+            /// Convert this set into a dense vector of `len` booleans,
+            /// which are `true` at the positions of its indices.
+            ///
+            /// Indices greater than or equal to `len` are left out.
+            pub fn to_bool_vec(&self, len: usize) -> alloc::vec::Vec<bool> {
+                let mut bools = alloc::vec![false; len];
+                let Some(last) = len.checked_sub(1) else {
+                    return bools;
+                };
+                let (end_map_index, _) = crate::calculate_map_and_set_indices::<S>(last);
+
+                for (map_index, set) in self.blocks_in(0..=end_map_index) {
+                    for bit in crate::bit_set_indices(set) {
+                        if let Some(value) = bools.get_mut(map_index * S::WIDTH + bit) {
+                            *value = true;
+                        }
+                    }
+                }
+                bools
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_impl_borsh_compat {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "serialize-borsh")]
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Deserialize a set from the borsh encoding of a
+            /// [`BTreeSet<usize>`](alloc::collections::BTreeSet).
+            ///
+            /// Fields previously stored as a `BTreeSet<usize>` can
+            /// be read into this set without migrating them first,
+            /// with `#[borsh(deserialize_with = "...")]`.
+            ///
+            /// Indices are packed into bit sets as they are read, and
+            /// must be strictly increasing. Indices past [`usize::MAX`]
+            /// are rejected with [`Error::IndexOutOfBounds`](crate::error::Error).
+            pub fn deserialize_btreeset_reader<R>(reader: &mut R) -> borsh::io::Result<Self>
             where
-                T: IntoIterator<Item = usize>
+                R: borsh::io::Read,
+            {
+                use borsh::BorshDeserialize;
+                use crate::error::Error;
+
+                let len = u32::deserialize_reader(reader)?;
+                let mut set = Self::new();
+                let mut packer = crate::BlockPacker::new();
+                let mut last = None;
+
+                for _ in 0..len {
+                    let index = usize::try_from(u64::deserialize_reader(reader)?)
+                        .map_err(|_| Error::IndexOutOfBounds)?;
+                    if last.is_some_and(|last| last >= index) {
+                        return Err(Error::UnsortedBlocks.into());
+                    }
+                    last = Some(index);
+                    if let Some(block) = packer.push(index) {
+                        set.push_block(block);
+                    }
+                }
+                if let Some(block) = packer.finish() {
+                    set.push_block(block);
+                }
+
+                Ok(set)
+            }
+
+            /// Deserialize a set from the borsh encoding of a
+            /// [`BTreeSet<usize>`](alloc::collections::BTreeSet),
+            /// which must span all of `bytes`.
+            pub fn from_btreeset_bytes(mut bytes: &[u8]) -> borsh::io::Result<Self> {
+                let set = Self::deserialize_btreeset_reader(&mut bytes)?;
+                if !bytes.is_empty() {
+                    return Err(crate::error::Error::InvalidEncoding.into());
+                }
+                Ok(set)
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_impl_cbor {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "serialize-cbor")]
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Encode this set as canonical CBOR.
+            ///
+            /// Equal sets always encode to identical bytes, regardless
+            /// of their backend or storage width. Refer to the
+            /// [`cbor`](crate::cbor) module for a description
+            /// of the encoding.
+            #[inline]
+            pub fn to_canonical_cbor(&self) -> alloc::vec::Vec<u8> {
+                crate::cbor::encode(self.iter_u64_blocks())
+            }
+
+            /// Decode a set from canonical CBOR.
+            ///
+            /// Any input which is not the canonical encoding
+            /// of an index set is rejected.
+            pub fn from_canonical_cbor(bytes: &[u8]) -> Result<Self, crate::error::Error> {
+                let mut set = Self::new();
+                for block in crate::pack_sorted(crate::cbor::decode(bytes)?) {
+                    set.push_block(block);
+                }
+                Ok(set)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_compact {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Renumber the indices of this set densely, by their
+            /// rank within the set, returning the mapping of old to
+            /// new indices, along with the renumbered set.
+            ///
+            /// Refer to the [`compact`](crate::compact) module
+            /// for more details.
+            pub fn compact(&self) -> (crate::compact::Compaction, Self) {
+                let compaction = crate::compact::Compaction::from_lanes(self.iter_u64_blocks());
+                let mut set = Self::new();
+                for block in crate::pack_sorted(0..compaction.len()) {
+                    set.push_block(block);
+                }
+                (compaction, set)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_contains_many {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Check if all the given indices are present in this set.
+            ///
+            /// Consecutive indices within the same bit set are
+            /// checked with a single lookup. Returns early on the
+            /// first absent index.
+            pub fn contains_all<I>(&self, indices: I) -> bool
+            where
+                I: IntoIterator<Item = usize>,
+            {
+                let mut cached_block = None;
+                indices
+                    .into_iter()
+                    .all(|index| self.contains_cached(&mut cached_block, index))
+            }
+
+            /// Check if any of the given indices is present in this set.
+            ///
+            /// Consecutive indices within the same bit set are
+            /// checked with a single lookup. Returns early on the
+            /// first present index.
+            pub fn contains_any<I>(&self, indices: I) -> bool
+            where
+                I: IntoIterator<Item = usize>,
+            {
+                let mut cached_block = None;
+                indices
+                    .into_iter()
+                    .any(|index| self.contains_cached(&mut cached_block, index))
+            }
+
+            /// Check the presence of each of the given indices in this set,
+            /// returning the answers in the same order as `queries`.
+            ///
+            /// Queries are sorted internally, such that each bit set
+            /// is looked up once, regardless of how many queries
+            /// fall within it.
+            pub fn contains_batch(&self, queries: &[usize]) -> alloc::vec::Vec<bool> {
+                let mut order: alloc::vec::Vec<usize> = (0..queries.len()).collect();
+                order.sort_unstable_by_key(|&position| queries[position]);
+
+                let mut answers = alloc::vec![false; queries.len()];
+                let mut cached_block = None;
+                for position in order {
+                    answers[position] = self.contains_cached(&mut cached_block, queries[position]);
+                }
+                answers
+            }
+
+            /// Check the presence of `index` in this set, only looking up
+            /// its bit set if it differs from the last looked up bit set,
+            /// stored in `cached_block`.
+            #[inline]
+            fn contains_cached(
+                &self,
+                cached_block: &mut Option<(usize, Option<S>)>,
+                index: usize,
+            ) -> bool {
+                let (map_index, bit_set_index) =
+                    crate::calculate_map_and_set_indices::<S>(index);
+                let set = match *cached_block {
+                    Some((cached_map_index, set)) if cached_map_index == map_index => set,
+                    _ => {
+                        let set = self.get_block(map_index);
+                        *cached_block = Some((map_index, set));
+                        set
+                    }
+                };
+                set.is_some_and(|set| set & S::from_usize(1 << bit_set_index) != S::ZERO)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+macro_rules! index_set_impl_hamming_distance {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Return the number of indices in either this set
+            /// or `other`, but not in both.
+            ///
+            /// The bit sets of both sets are compared in a single
+            /// pass, without building their symmetric difference.
+            pub fn hamming_distance(&self, other: &Self) -> usize {
+                use core::cmp::Ordering;
+
+                let mut blocks_a = self.blocks().peekable();
+                let mut blocks_b = other.blocks().peekable();
+                let mut distance = 0;
+
+                loop {
+                    let set = match (blocks_a.peek(), blocks_b.peek()) {
+                        (Some(&(a, set_a)), Some(&(b, set_b))) => match a.cmp(&b) {
+                            Ordering::Less => {
+                                blocks_a.next();
+                                set_a
+                            }
+                            Ordering::Greater => {
+                                blocks_b.next();
+                                set_b
+                            }
+                            Ordering::Equal => {
+                                blocks_a.next();
+                                blocks_b.next();
+                                crate::flip_bits(set_a, set_b)
+                            }
+                        },
+                        (Some(&(_, set_a)), None) => {
+                            blocks_a.next();
+                            set_a
+                        }
+                        (None, Some(&(_, set_b))) => {
+                            blocks_b.next();
+                            set_b
+                        }
+                        (None, None) => break,
+                    };
+                    distance += set.num_of_high_bits();
+                }
+
+                distance
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+macro_rules! index_set_impl_inclusion {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Compare this set with `other` by inclusion, in a single
+            /// pass over the bit sets of both.
+            ///
+            /// Returns [`Ordering::Less`](core::cmp::Ordering::Less) if
+            /// this set is a strict subset of `other`,
+            /// [`Ordering::Greater`](core::cmp::Ordering::Greater) if it
+            /// is a strict superset, [`Ordering::Equal`](core::cmp::Ordering::Equal)
+            /// if both sets hold the same indices, and [`None`] otherwise.
+            /// Unlike [`Ord`], this is only a partial order.
+            pub fn partial_cmp_by_inclusion(&self, other: &Self) -> Option<core::cmp::Ordering> {
+                use core::cmp::Ordering;
+
+                let mut blocks_a = self.blocks().peekable();
+                let mut blocks_b = other.blocks().peekable();
+                let mut is_subset = true;
+                let mut is_superset = true;
+
+                while is_subset || is_superset {
+                    match (blocks_a.peek(), blocks_b.peek()) {
+                        (Some(&(a, set_a)), Some(&(b, set_b))) => match a.cmp(&b) {
+                            Ordering::Less => {
+                                is_subset = false;
+                                blocks_a.next();
+                            }
+                            Ordering::Greater => {
+                                is_superset = false;
+                                blocks_b.next();
+                            }
+                            Ordering::Equal => {
+                                is_subset &= set_a & !set_b == S::ZERO;
+                                is_superset &= set_b & !set_a == S::ZERO;
+                                blocks_a.next();
+                                blocks_b.next();
+                            }
+                        },
+                        (Some(_), None) => {
+                            is_subset = false;
+                            break;
+                        }
+                        (None, Some(_)) => {
+                            is_superset = false;
+                            break;
+                        }
+                        (None, None) => break,
+                    }
+                }
+
+                match (is_subset, is_superset) {
+                    (true, true) => Some(Ordering::Equal),
+                    (true, false) => Some(Ordering::Less),
+                    (false, true) => Some(Ordering::Greater),
+                    (false, false) => None,
+                }
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_merkle {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a [`MerkleTree`](crate::merkle::MerkleTree) over
+            /// the bit sets of this set, hashed with `H`, to prove the
+            /// presence or absence of its indices.
+            pub fn merkle_tree<H>(&self) -> crate::merkle::MerkleTree<S, H>
+            where
+                H: crate::merkle::MerkleHasher,
+            {
+                crate::merkle::MerkleTree::from_blocks(self.blocks())
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_neighbors {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Return the lowest index in this set that is greater
+            /// than `index`, only scanning the bit sets from the
+            /// one holding `index + 1` onwards.
+            fn successor_in_blocks(&self, index: usize) -> Option<usize> {
+                let (map_index, bit_set_index) =
+                    crate::calculate_map_and_set_indices::<S>(index.checked_add(1)?);
+
+                self.blocks_in(map_index..=usize::MAX).find_map(|(i, mut set)| {
+                    if i == map_index {
+                        set &= crate::bit_range_mask(bit_set_index, S::WIDTH - 1);
+                    }
+                    crate::bit_set_indices(set).next().map(|bit| i * S::WIDTH + bit)
+                })
+            }
+
+            /// Return the greatest index in this set that is lower
+            /// than `index`, only scanning the bit sets up to the
+            /// one holding `index - 1`.
+            fn predecessor_in_blocks(&self, index: usize) -> Option<usize> {
+                let (map_index, bit_set_index) =
+                    crate::calculate_map_and_set_indices::<S>(index.checked_sub(1)?);
+
+                self.blocks_in(0..=map_index).rev().find_map(|(i, mut set)| {
+                    if i == map_index {
+                        set &= crate::bit_range_mask(0, bit_set_index);
+                    }
+                    crate::bit_set_indices(set).next_back().map(|bit| i * S::WIDTH + bit)
+                })
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_iter_u64_blocks {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Iterate over the indices in this set, as pairs of
+            /// start indices and [`u64`] bit sets, regardless of
+            /// the storage width of this set.
+            ///
+            /// Start indices are multiples of 64, yielded in ascending
+            /// order. The bit `B` of the bit set starting at index `I`
+            /// is set if the index `I + B` is present in this set.
+            pub fn iter_u64_blocks(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+                const LANE_WIDTH: usize = u64::BITS as usize;
+
+                let width_mask = u64::MAX >> (LANE_WIDTH - S::WIDTH);
+                let mut blocks = self.blocks().peekable();
+
+                core::iter::from_fn(move || loop {
+                    let (map_index, set) = blocks.next()?;
+
+                    let start_index = map_index * S::WIDTH;
+                    let lane_start_index = start_index - start_index % LANE_WIDTH;
+                    let mut lane = (set.to_u64() & width_mask) << (start_index % LANE_WIDTH);
+
+                    while let Some(&(map_index, set)) = blocks.peek() {
+                        let start_index = map_index * S::WIDTH;
+                        if start_index - start_index % LANE_WIDTH != lane_start_index {
+                            break;
+                        }
+                        lane |= (set.to_u64() & width_mask) << (start_index % LANE_WIDTH);
+                        blocks.next();
+                    }
+
+                    if lane != 0 {
+                        return Some((lane_start_index, lane));
+                    }
+                })
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_impl_layout {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Return the number of bit sets stored in this set.
+            #[inline]
+            pub fn num_blocks(&self) -> usize {
+                self.blocks().len()
+            }
+
+            /// Return the greatest index that fits in the bit sets
+            /// stored in this set, that is the last index of its last
+            /// bit set, or [`None`] if it is empty.
+            ///
+            /// This bounds the stored bit sets, not their capacity:
+            /// inserting an index past this one always adds a bit set,
+            /// and so may inserting one between sparse bit sets.
+            #[inline]
+            pub fn max_index_in_blocks(&self) -> Option<usize> {
+                let (map_index, _) = self.blocks().next_back()?;
+                Some((map_index * S::WIDTH).saturating_add(S::WIDTH - 1))
+            }
+
+            /// Return the range spanning from the lowest to the
+            /// greatest index in this set, or [`None`] if it is empty.
+            pub fn index_span(&self) -> Option<core::ops::RangeInclusive<usize>> {
+                let min = self.blocks().find_map(|(i, set)| {
+                    crate::bit_set_indices(set).next().map(|bit| i * S::WIDTH + bit)
+                })?;
+                let max = self.blocks().rev().find_map(|(i, set)| {
+                    crate::bit_set_indices(set).next_back().map(|bit| i * S::WIDTH + bit)
+                })?;
+                Some(min..=max)
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_impl_lenient {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a set from pairs of map indices and bit sets,
+            /// repairing them rather than rejecting them.
+            ///
+            /// Pairs are sorted by map index, the bit sets of repeated
+            /// map indices are merged, bits past the width of the bit
+            /// sets are cleared, and empty bit sets, as well as those
+            /// holding indices past [`usize::MAX`], are dropped.
+            pub fn from_blocks_lenient<I>(blocks: I) -> Self
+            where
+                I: IntoIterator<Item = (usize, S)>,
+            {
+                let width_mask = crate::bit_range_mask::<S>(0, S::WIDTH - 1);
+                let mut blocks: alloc::vec::Vec<_> = blocks
+                    .into_iter()
+                    .map(|(map_index, set)| (map_index, set & width_mask))
+                    .filter(|&(map_index, set)| {
+                        set != S::ZERO && map_index <= crate::max_map_index::<S>()
+                    })
+                    .collect();
+                blocks.sort_unstable_by_key(|&(map_index, _)| map_index);
+
+                let mut set = Self::with_capacity(blocks.len());
+                let mut blocks = blocks.into_iter().peekable();
+                while let Some((map_index, mut bit_set)) = blocks.next() {
+                    while let Some((_, other)) = blocks.next_if(|&(i, _)| i == map_index) {
+                        bit_set |= other;
+                    }
+                    set.push_block((map_index, bit_set));
+                }
+
+                set
+            }
+
+            /// Deserialize a set from borsh data, repairing
+            /// non-canonical bit sets rather than rejecting them,
+            /// as with [`Self::from_blocks_lenient`].
+            ///
+            /// Data produced by older or buggy writers can be read
+            /// with `#[borsh(deserialize_with = "...")]`. Bit sets
+            /// holding indices past [`usize::MAX`] are still rejected,
+            /// rather than dropped.
+            #[cfg(feature = "serialize-borsh")]
+            pub fn deserialize_lenient_reader<R>(reader: &mut R) -> borsh::io::Result<Self>
+            where
+                R: borsh::io::Read,
+            {
+                use borsh::BorshDeserialize;
+
+                let len = u32::deserialize_reader(reader)?;
+                let mut blocks = alloc::vec::Vec::new();
+                let mut word = alloc::vec![0; S::WIDTH];
+
+                for _ in 0..len {
+                    let map_index = usize::try_from(u64::deserialize_reader(reader)?)
+                        .ok()
+                        .filter(|&map_index| map_index <= crate::max_map_index::<S>())
+                        .ok_or(crate::error::Error::IndexOutOfBounds)?;
+                    reader.read_exact(&mut word)?;
+                    blocks.push((map_index, S::from_le_slice(&word)));
+                }
+
+                Ok(Self::from_blocks_lenient(blocks))
+            }
+
+            /// Deserialize a set from borsh data, and re-validate all
+            /// of its invariants: map indices must be strictly increasing,
+            /// and none of the bit sets may be empty.
+            ///
+            /// Data read from untrusted sources can be checked
+            /// with `#[borsh(deserialize_with = "...")]`.
+            #[cfg(feature = "serialize-borsh")]
+            pub fn deserialize_strict_reader<R>(reader: &mut R) -> borsh::io::Result<Self>
+            where
+                R: borsh::io::Read,
+                S: borsh::BorshDeserialize,
+            {
+                use borsh::BorshDeserialize;
+
+                let blocks = alloc::vec::Vec::<(usize, S)>::deserialize_reader(reader)?;
+                crate::check_invariants(blocks.iter().copied()).map_err(crate::error::Error::from)?;
+
+                let mut set = Self::new();
+                for block in blocks {
+                    set.push_block(block);
+                }
+                Ok(set)
+            }
+
+            /// Deserialize a set from serde data, and re-validate all
+            /// of its invariants, as with [`Self::deserialize_strict_reader`].
+            ///
+            /// Data read from untrusted sources can be checked
+            /// with `#[serde(deserialize_with = "...")]`.
+            #[cfg(feature = "serialize-serde")]
+            pub fn deserialize_strict<'de, D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+                S: serde::Deserialize<'de>,
+            {
+                let set = <Self as serde::Deserialize>::deserialize(deserializer)?;
+                crate::check_invariants(set.blocks()).map_err(serde::de::Error::custom)?;
+                Ok(set)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_map_monotonic {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a new set, with the indices of this set
+            /// mapped through `f`.
+            ///
+            /// If `f` is strictly increasing, the new set is bulk
+            /// loaded in a single pass. Otherwise, the remaining
+            /// indices are inserted one at a time, from the first
+            /// index where `f` stops increasing.
+            pub fn map_monotonic<F>(&self, f: F) -> Self
+            where
+                F: Fn(usize) -> usize,
             {
                 use crate::IndexSet;
 
-                let iter = iter.into_iter();
-                let bounded_cap = crate::safe_iter_reserve_cap(
-                    &iter,
-                );
+                let mut set = Self::new();
+                let mut packer = crate::BlockPacker::new();
+                let mut last_index = None;
+                let mut indices = self.iter().map(f);
 
-                let mut set = Self::with_capacity(bounded_cap);
+                for index in indices.by_ref() {
+                    if last_index.is_some_and(|last_index| index < last_index) {
+                        if let Some(block) = packer.finish() {
+                            set.push_block(block);
+                        }
+                        set.insert(index);
+                        for index in indices {
+                            set.insert(index);
+                        }
+                        return set;
+                    }
+                    last_index = Some(index);
+                    if let Some(block) = packer.push(index) {
+                        set.push_block(block);
+                    }
+                }
+                if let Some(block) = packer.finish() {
+                    set.push_block(block);
+                }
+
+                set
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_impl_reserve {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Create a new set with enough capacity to store all
+            /// indices up to `max_index`, inclusive.
+            #[inline]
+            pub fn with_capacity_for_max_index(max_index: usize) -> Self {
+                Self::with_capacity(crate::num_of_blocks_up_to::<S>(max_index))
+            }
+
+            /// Reserve enough capacity to store all indices
+            /// up to `max_index`, inclusive.
+            #[inline]
+            pub fn reserve_for_index(&mut self, max_index: usize) {
+                use crate::IndexSet;
+
+                let num_of_blocks = crate::num_of_blocks_up_to::<S>(max_index);
+                let additional = num_of_blocks.saturating_sub(self.blocks().len());
+                self.reserve(additional);
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_rayon {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "rayon")]
+        impl<S> ::rayon::iter::FromParallelIterator<usize> for $($Set)*<S>
+        where
+            S: crate::storage::Storage + Send,
+        {
+            /// Build a set from a parallel iterator of indices.
+            ///
+            /// The indices are sorted in parallel, then split into
+            /// shards along bit set boundaries, which are packed into
+            /// bit sets on separate threads. The shards are finally
+            /// appended to the set in order, one bit set at a time.
+            fn from_par_iter<I>(par_iter: I) -> Self
+            where
+                I: ::rayon::iter::IntoParallelIterator<Item = usize>,
+            {
+                use ::rayon::prelude::*;
+
+                let mut indices: alloc::vec::Vec<usize> = par_iter.into_par_iter().collect();
+                indices.par_sort_unstable();
+
+                let map_index = |index| crate::calculate_map_and_set_indices::<S>(index).0;
+                let shard_len = indices.len().div_ceil(::rayon::current_num_threads()).max(1);
+                let mut shards = alloc::vec::Vec::new();
+                let mut rest = indices.as_slice();
+                while !rest.is_empty() {
+                    let mut end = shard_len.min(rest.len());
+                    // NB: extend the shard up to the end of the bit set
+                    // holding its last index, such that no bit set is
+                    // split across shards
+                    let last_map_index = map_index(rest[end - 1]);
+                    end += rest[end..].partition_point(|&i| map_index(i) == last_map_index);
+                    let (shard, tail) = rest.split_at(end);
+                    shards.push(shard);
+                    rest = tail;
+                }
+
+                let shards: alloc::vec::Vec<alloc::vec::Vec<(usize, S)>> = shards
+                    .into_par_iter()
+                    .map(|shard| crate::pack_sorted(shard.iter().copied()).collect())
+                    .collect();
+
+                let mut set = Self::with_capacity(shards.iter().map(|shard| shard.len()).sum());
+                for block in shards.into_iter().flatten() {
+                    set.push_block(block);
+                }
+                set
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_roaring {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "roaring")]
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a new set from the indices in a
+            /// [`RoaringBitmap`](::roaring::RoaringBitmap).
+            ///
+            /// NB: this is not a [`From`] conversion, since it
+            /// would overlap with the conversion from iterators.
+            pub fn from_roaring_bitmap(bitmap: &::roaring::RoaringBitmap) -> Self {
+                let mut set = Self::new();
+                for block in crate::pack_sorted(bitmap.iter().map(|index| index as usize)) {
+                    set.push_block(block);
+                }
+                set
+            }
+
+            /// Build a new set from the indices in a
+            /// [`RoaringTreemap`](::roaring::RoaringTreemap).
+            ///
+            /// Fails if the treemap holds an index past [`usize::MAX`].
+            pub fn try_from_roaring_treemap(
+                treemap: &::roaring::RoaringTreemap,
+            ) -> Result<Self, crate::error::Error> {
+                if let Some(max_index) = treemap.max() {
+                    usize::try_from(max_index).map_err(|_| crate::error::Error::IndexOutOfBounds)?;
+                }
+
+                let mut set = Self::new();
+                for block in crate::pack_sorted(treemap.iter().map(|index| index as usize)) {
+                    set.push_block(block);
+                }
+                Ok(set)
+            }
+        }
+
+        #[cfg(feature = "roaring")]
+        impl<S: crate::storage::Storage> TryFrom<&$($Set)*<S>> for ::roaring::RoaringBitmap {
+            type Error = crate::error::Error;
+
+            /// Fails if the set holds an index past [`u32::MAX`].
+            fn try_from(set: &$($Set)*<S>) -> Result<Self, Self::Error> {
+                if let Some(max_index) = set.largest(1).next() {
+                    u32::try_from(max_index).map_err(|_| crate::error::Error::LimitExceeded)?;
+                }
+
+                Ok(Self::from_sorted_iter(set.iter().map(|index| index as u32))
+                    .expect("index sets iterate in ascending order"))
+            }
+        }
+
+        #[cfg(feature = "roaring")]
+        impl<S: crate::storage::Storage> From<&$($Set)*<S>> for ::roaring::RoaringTreemap {
+            fn from(set: &$($Set)*<S>) -> Self {
+                Self::from_sorted_iter(set.iter().map(|index| index as u64))
+                    .expect("index sets iterate in ascending order")
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_impl_scale {
+    ($($Set:tt)*) => {
+        /// Encodes this set as a SCALE vector of pairs of
+        /// map indices, as [`u64`] values, and bit sets.
+        #[cfg(feature = "scale")]
+        impl<S> ::parity_scale_codec::Encode for $($Set)*<S>
+        where
+            S: crate::storage::Storage + ::parity_scale_codec::Encode,
+        {
+            fn size_hint(&self) -> usize {
+                let num_of_blocks = self.blocks().len();
+                ::parity_scale_codec::Compact(num_of_blocks as u32).size_hint()
+                    + num_of_blocks * (size_of::<u64>() + S::WIDTH)
+            }
+
+            fn encode_to<O>(&self, dest: &mut O)
+            where
+                O: ::parity_scale_codec::Output + ?Sized,
+            {
+                ::parity_scale_codec::Compact(self.blocks().len() as u32).encode_to(dest);
+                for (map_index, set) in self.blocks() {
+                    (map_index as u64).encode_to(dest);
+                    set.encode_to(dest);
+                }
+            }
+        }
+
+        #[cfg(feature = "scale")]
+        impl<S> ::parity_scale_codec::EncodeLike for $($Set)*<S> where
+            S: crate::storage::Storage + ::parity_scale_codec::Encode
+        {
+        }
+
+        /// Decodes a set encoded as a SCALE vector of pairs of map
+        /// indices and bit sets, whose map indices must be sorted.
+        #[cfg(feature = "scale")]
+        impl<S> ::parity_scale_codec::Decode for $($Set)*<S>
+        where
+            S: crate::storage::Storage + ::parity_scale_codec::Decode,
+        {
+            fn decode<I>(input: &mut I) -> Result<Self, ::parity_scale_codec::Error>
+            where
+                I: ::parity_scale_codec::Input,
+            {
+                let ::parity_scale_codec::Compact(num_of_blocks) =
+                    <::parity_scale_codec::Compact<u32>>::decode(input)?;
+
+                let mut set = Self::new();
+                let mut last_map_index = None;
+
+                for _ in 0..num_of_blocks {
+                    let map_index = usize::try_from(u64::decode(input)?)
+                        .ok()
+                        .filter(|&map_index| map_index <= crate::max_map_index::<S>())
+                        .ok_or(crate::error::Error::IndexOutOfBounds.message())?;
+                    let bits = S::decode(input)?;
+
+                    if last_map_index.is_some_and(|last| last >= map_index) {
+                        return Err(crate::error::Error::UnsortedBlocks.message().into());
+                    }
+                    last_map_index = Some(map_index);
+
+                    if bits != S::ZERO {
+                        set.push_block((map_index, bits));
+                    }
+                }
+
+                Ok(set)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_select {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Iterate over the `k` smallest indices in this set,
+            /// in ascending order.
+            ///
+            /// Only the bit sets holding these indices are scanned.
+            pub fn smallest(&self, k: usize) -> impl Iterator<Item = usize> + '_ {
+                self.blocks()
+                    .flat_map(|(map_index, set)| {
+                        crate::bit_set_indices(set)
+                            .map(move |bit_set_index| map_index * S::WIDTH + bit_set_index)
+                    })
+                    .take(k)
+            }
+
+            /// Iterate over the `k` largest indices in this set,
+            /// in descending order.
+            ///
+            /// Only the bit sets holding these indices are scanned.
+            pub fn largest(&self, k: usize) -> impl Iterator<Item = usize> + '_ {
+                self.blocks()
+                    .rev()
+                    .flat_map(|(map_index, set)| {
+                        crate::bit_set_indices(set)
+                            .rev()
+                            .map(move |bit_set_index| map_index * S::WIDTH + bit_set_index)
+                    })
+                    .take(k)
+            }
+
+            /// Collect the `k` smallest indices in this set,
+            /// in ascending order.
+            #[inline]
+            pub fn take_smallest(&self, k: usize) -> alloc::vec::Vec<usize> {
+                self.smallest(k).collect()
+            }
+
+            /// Collect the `k` largest indices in this set,
+            /// in descending order.
+            #[inline]
+            pub fn take_largest(&self, k: usize) -> alloc::vec::Vec<usize> {
+                self.largest(k).collect()
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_slice {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a new set, with the indices of this set
+            /// that lie within `range`.
+            ///
+            /// Only the bit sets intersecting `range` are visited.
+            pub fn slice<R>(&self, range: R) -> Self
+            where
+                R: core::ops::RangeBounds<usize>,
+            {
+                let mut set = Self::new();
+
+                let Some((start, end)) = crate::inclusive_range_bounds(range) else {
+                    return set;
+                };
+                let (start_map_index, start_bit_set_index) =
+                    crate::calculate_map_and_set_indices::<S>(start);
+                let (end_map_index, end_bit_set_index) =
+                    crate::calculate_map_and_set_indices::<S>(end);
+
+                for (map_index, mut bit_set) in self.blocks_in(start_map_index..=end_map_index) {
+                    let first_bit = if map_index == start_map_index {
+                        start_bit_set_index
+                    } else {
+                        0
+                    };
+                    let last_bit = if map_index == end_map_index {
+                        end_bit_set_index
+                    } else {
+                        S::WIDTH - 1
+                    };
+                    bit_set &= crate::bit_range_mask(first_bit, last_bit);
+                    if bit_set != S::ZERO {
+                        set.push_block((map_index, bit_set));
+                    }
+                }
+
+                set
+            }
+
+            /// Iterate over every index within `range`, in ascending
+            /// order, along with whether it is present in this set.
+            ///
+            /// Presence is read off one bit set at a time, rather
+            /// than looking up each index in this set.
+            pub fn iter_presence<R>(
+                &self,
+                range: R,
+            ) -> impl core::iter::FusedIterator<Item = (usize, bool)> + '_
+            where
+                R: core::ops::RangeBounds<usize>,
+            {
+                let bounds = crate::inclusive_range_bounds(range);
+                let map_range = match bounds {
+                    Some((start, end)) => {
+                        crate::calculate_map_and_set_indices::<S>(start).0
+                            ..=crate::calculate_map_and_set_indices::<S>(end).0
+                    }
+                    None => 0..=0,
+                };
+
+                let mut blocks = self.blocks_in(map_range).peekable();
+                let mut current = None;
+                bounds
+                    .into_iter()
+                    .flat_map(|(start, end)| start..=end)
+                    .map(move |index| {
+                        let (map_index, bit_set_index) =
+                            crate::calculate_map_and_set_indices::<S>(index);
+                        let bit_set = match current {
+                            Some((current_map_index, bit_set)) if current_map_index == map_index => {
+                                bit_set
+                            }
+                            _ => {
+                                let bit_set = blocks
+                                    .next_if(|&(next_map_index, _)| next_map_index == map_index)
+                                    .map_or(S::ZERO, |(_, bit_set)| bit_set);
+                                current = Some((map_index, bit_set));
+                                bit_set
+                            }
+                        };
+                        (index, bit_set & S::from_usize(1 << bit_set_index) != S::ZERO)
+                    })
+            }
+
+            /// Build a new set, with the indices of this set
+            /// that lie within `range`, shifted down such that
+            /// the start of `range` becomes index zero.
+            pub fn slice_rebased<R>(&self, range: R) -> Self
+            where
+                R: core::ops::RangeBounds<usize>,
+            {
+                use crate::IndexSet;
+
+                let Some((start, end)) = crate::inclusive_range_bounds(range) else {
+                    return Self::new();
+                };
+                let slice = self.slice(start..=end);
+
+                let (start_map_index, start_bit_set_index) =
+                    crate::calculate_map_and_set_indices::<S>(start);
+
+                let mut set = Self::new();
+
+                if start_bit_set_index == 0 {
+                    // NB: the range is aligned to the start of a bit set,
+                    // so we only need to shift the map indices
+                    for (map_index, bit_set) in slice.blocks() {
+                        set.push_block((map_index - start_map_index, bit_set));
+                    }
+                } else {
+                    for block in crate::pack_sorted(slice.iter().map(|index| index - start)) {
+                        set.push_block(block);
+                    }
+                }
+
+                set
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_range_serialize {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "serialize-borsh")]
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Serialize the indices of this set that lie within
+            /// `range` to `writer`, in borsh format, such that a
+            /// huge set can be shipped in several pages.
+            ///
+            /// Only the bit sets intersecting `range` are encoded,
+            /// along with the bounds of `range`. Pages are read back
+            /// with [`Self::deserialize_into_range`].
+            pub fn serialize_range<W, R>(&self, writer: &mut W, range: R) -> borsh::io::Result<()>
+            where
+                W: borsh::io::Write,
+                R: core::ops::RangeBounds<usize>,
+            {
+                let (start, end) = crate::inclusive_range_bounds(range).unwrap_or((1, 0));
+                let blocks = self.slice(start..=end);
+                let len = u32::try_from(blocks.blocks().count())
+                    .map_err(|_| crate::error::Error::LimitExceeded)?;
+
+                writer.write_all(&(start as u64).to_le_bytes())?;
+                writer.write_all(&(end as u64).to_le_bytes())?;
+                writer.write_all(&len.to_le_bytes())?;
+
+                let mut word = alloc::vec::Vec::with_capacity(S::WIDTH);
+                for (map_index, bit_set) in blocks.blocks() {
+                    writer.write_all(&(map_index as u64).to_le_bytes())?;
+                    word.clear();
+                    bit_set.extend_le_bytes(&mut word);
+                    writer.write_all(&word)?;
+                }
+
+                Ok(())
+            }
+
+            /// Deserialize a page of indices written with
+            /// [`Self::serialize_range`], merging them into this set.
+            ///
+            /// Returns the range of indices covered by the page. The
+            /// page is fully validated before it is merged, leaving
+            /// this set unchanged if it is malformed.
+            pub fn deserialize_into_range<R>(
+                &mut self,
+                reader: &mut R,
+            ) -> borsh::io::Result<core::ops::RangeInclusive<usize>>
+            where
+                R: borsh::io::Read,
+            {
+                use borsh::BorshDeserialize;
+                use crate::error::Error;
+
+                let invalid = borsh::io::Error::from;
+                let read_usize = |reader: &mut R| {
+                    usize::try_from(u64::deserialize_reader(reader)?)
+                        .map_err(|_| invalid(Error::IndexOutOfBounds))
+                };
+
+                let start = read_usize(reader)?;
+                let end = read_usize(reader)?;
+                let len = u32::deserialize_reader(reader)?;
+
+                // NB: the page is collected into pairs of map indices
+                // and bit sets, rather than into a set, such that it is
+                // only merged, and allocated for, once fully validated
+                let mut page = alloc::vec::Vec::new();
+                let mut last_map_index = None;
+                let mut word = alloc::vec![0; S::WIDTH];
+
+                for _ in 0..len {
+                    let map_index = read_usize(reader)?;
+                    reader.read_exact(&mut word)?;
+                    let bit_set = S::from_le_slice(&word);
+
+                    if last_map_index.is_some_and(|last| last >= map_index) {
+                        return Err(invalid(Error::UnsortedBlocks));
+                    }
+                    if bit_set == S::ZERO {
+                        return Err(invalid(Error::ZeroBlock));
+                    }
+                    if start > end || map_index < start / S::WIDTH || map_index > end / S::WIDTH {
+                        return Err(invalid(Error::IndexOutOfBounds));
+                    }
+                    let start_bit = if map_index == start / S::WIDTH { start % S::WIDTH } else { 0 };
+                    let end_bit = if map_index == end / S::WIDTH { end % S::WIDTH } else { S::WIDTH - 1 };
+                    if bit_set & !crate::bit_range_mask::<S>(start_bit, end_bit) != S::ZERO {
+                        return Err(invalid(Error::IndexOutOfBounds));
+                    }
+                    last_map_index = Some(map_index);
+                    page.push((map_index, bit_set));
+                }
+
+                self.try_merge_blocks(page).map_err(invalid)?;
+
+                Ok(start..=end)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_partition {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Split the indices of this set into two new sets,
+            /// of the indices that satisfy `pred` and of those
+            /// that do not, respectively.
+            pub fn partition<F>(&self, mut pred: F) -> (Self, Self)
+            where
+                F: FnMut(usize) -> bool,
+            {
+                let mut matching = Self::new();
+                let mut rest = Self::new();
+
+                for (map_index, bit_set) in self.blocks() {
+                    let mut matching_bit_set = S::ZERO;
+
+                    for bit_set_index in crate::bit_set_indices(bit_set) {
+                        if pred(map_index * S::WIDTH + bit_set_index) {
+                            matching_bit_set |= S::from_usize(1 << bit_set_index);
+                        }
+                    }
+
+                    let rest_bit_set = bit_set & !matching_bit_set;
+
+                    if matching_bit_set != S::ZERO {
+                        matching.push_block((map_index, matching_bit_set));
+                    }
+                    if rest_bit_set != S::ZERO {
+                        rest.push_block((map_index, rest_bit_set));
+                    }
+                }
+
+                (matching, rest)
+            }
+
+            /// Return a new set with the indices of this set
+            /// that satisfy `pred`, leaving this set intact.
+            pub fn filter<F>(&self, mut pred: F) -> Self
+            where
+                F: FnMut(usize) -> bool,
+            {
+                let mut set = Self::new();
+
+                for (map_index, bit_set) in self.blocks() {
+                    let mut matching_bit_set = S::ZERO;
+
+                    for bit_set_index in crate::bit_set_indices(bit_set) {
+                        if pred(map_index * S::WIDTH + bit_set_index) {
+                            matching_bit_set |= S::from_usize(1 << bit_set_index);
+                        }
+                    }
+
+                    if matching_bit_set != S::ZERO {
+                        set.push_block((map_index, matching_bit_set));
+                    }
+                }
+
+                set
+            }
+
+            /// Remove the indices of this set that do not satisfy `pred`,
+            /// in ascending order.
+            ///
+            /// Bits are cleared in place, a bit set at a time, and bit
+            /// sets left empty are removed.
+            pub fn retain<F>(&mut self, mut pred: F)
+            where
+                F: FnMut(usize) -> bool,
+            {
+                self.retain_blocks(|map_index, bit_set| {
+                    for bit_set_index in crate::bit_set_indices(*bit_set) {
+                        if !pred(map_index * S::WIDTH + bit_set_index) {
+                            *bit_set &= !S::from_usize(1 << bit_set_index);
+                        }
+                    }
+                });
+            }
+
+            /// Split the index space of this set into at most `n`
+            /// consecutive ranges, each holding roughly `len() / n`
+            /// of the indices in this set.
+            ///
+            /// Ranges start at zero, and end on bit set boundaries,
+            /// such that the number of indices in each range is off
+            /// by at most the width of a bit set. No empty ranges
+            /// are returned, and an empty set yields no ranges.
+            pub fn partition_chunks(&self, n: usize) -> alloc::vec::Vec<core::ops::Range<usize>> {
+                use crate::IndexSet;
+
+                let total = self.len() as u128;
+                let mut chunks = alloc::vec::Vec::new();
+                if n == 0 || total == 0 {
+                    return chunks;
+                }
+
+                let mut start = 0;
+                let mut seen = 0u128;
+                for (map_index, bit_set) in self.blocks() {
+                    seen += bit_set.num_of_high_bits() as u128;
+                    // NB: the last bit set always closes the last chunk
+                    if seen * n as u128 >= total * (chunks.len() as u128 + 1) {
+                        let end = (map_index + 1).saturating_mul(S::WIDTH);
+                        chunks.push(start..end);
+                        start = end;
+                    }
+                }
+
+                chunks
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_impl_stats {
+    ($backend:ident, $($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Compute statistics about the occupancy of
+            /// the bit sets of this set.
+            pub fn stats(&self) -> crate::stats::Stats {
+                crate::stats::Stats::from_blocks(self.blocks())
+            }
+
+            /// Recommend the most memory efficient storage width
+            /// and backend, for the indices currently in this set.
+            ///
+            /// A set can be converted to the recommended storage
+            /// width with [`Self::repack`].
+            pub fn recommend_storage(&self) -> crate::stats::Recommendation {
+                use crate::IndexSet;
+
+                crate::stats::Recommendation::from_indices(
+                    self.iter(),
+                    crate::stats::StorageWidth::of::<S>(),
+                    crate::stats::Backend::$backend,
+                )
+            }
+
+            /// Iterate over the differences between consecutive
+            /// indices of this set, in ascending order.
+            ///
+            /// A set with `n` indices yields `n - 1` differences,
+            /// without collecting its indices.
+            pub fn deltas(&self) -> impl core::iter::FusedIterator<Item = usize> + '_ {
+                use crate::IndexSet;
+
+                let mut indices = self.iter();
+                // NB: an empty set yields no further indices, so
+                // the initial value is never read
+                let mut previous = indices.next().unwrap_or(0);
+                indices.map(move |index| {
+                    let delta = index - previous;
+                    previous = index;
+                    delta
+                })
+            }
+
+            /// Build a new set with the indices of this set,
+            /// stored with a different storage width.
+            pub fn repack<T>(&self) -> $($Set)*<T>
+            where
+                T: crate::storage::Storage,
+            {
+                use crate::IndexSet;
+
+                let mut set = $($Set)*::new();
+                for block in crate::pack_sorted(self.iter()) {
+                    set.push_block(block);
+                }
+                set
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_sync {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Summarize the indices of this set, in buckets of
+            /// `1 << bucket_bits` indices, to start reconciling
+            /// it with the set of another node.
+            ///
+            /// See the [`sync`](crate::sync) module for
+            /// an overview of the protocol.
+            pub fn sync_summary(&self, bucket_bits: u32) -> crate::sync::Summary {
+                crate::sync::Summary::from_lanes(bucket_bits, self.iter_u64_blocks())
+            }
+
+            /// Compare the `summary` of the set of another node
+            /// against this set, returning the indices of this set
+            /// within the buckets which differ.
+            pub fn sync_delta(&self, summary: &crate::sync::Summary) -> crate::sync::Delta {
+                let ours =
+                    crate::sync::Summary::from_lanes(summary.bucket_bits(), self.iter_u64_blocks());
+                crate::sync::Delta::from_lanes(
+                    ours.bucket_bits(),
+                    ours.differing_buckets(summary),
+                    self.iter_u64_blocks(),
+                )
+            }
+
+            /// Return the indices of this set within the buckets
+            /// listed in the `delta` of another node.
+            pub fn sync_reply(&self, delta: &crate::sync::Delta) -> crate::sync::Delta {
+                crate::sync::Delta::from_lanes(
+                    delta.bucket_bits(),
+                    delta.buckets().to_vec(),
+                    self.iter_u64_blocks(),
+                )
+            }
+
+            /// Merge the indices in the `delta` of another
+            /// node into this set.
+            ///
+            /// The delta is validated before it is merged,
+            /// leaving this set unchanged if it is malformed, or
+            /// if the memory to merge it can't be allocated.
+            pub fn sync_merge(
+                &mut self,
+                delta: &crate::sync::Delta,
+            ) -> Result<(), crate::error::Error> {
+                let lanes = delta.lanes()?;
+                self.try_merge_blocks(crate::blocks_from_u64_lanes::<S, _>(lanes.iter().copied()))
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+macro_rules! index_set_impl_from_iterator {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> FromIterator<usize>
+            for $($Set)*<S>
+        {
+            #[inline]
+            fn from_iter<T>(iter: T) -> Self
+            where
+                T: IntoIterator<Item = usize>
+            {
+                use crate::IndexSet;
+
+                let iter = iter.into_iter();
+                let bounded_cap = crate::safe_iter_reserve_cap(
+                    &iter,
+                );
+
+                let mut set = Self::with_capacity(bounded_cap);
+
+                for item in iter {
+                    set.insert(item);
+                }
+
+                set
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_cross_width {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Merge the indices of `other`, whose bit sets may have
+            /// a different width, into this set.
+            ///
+            /// The bit sets of `other` are split or joined into bit
+            /// sets of this set as they are merged.
+            pub fn union_with<T>(&mut self, other: &$($Set)*<T>)
+            where
+                T: crate::storage::Storage,
+            {
+                self.merge_blocks(crate::blocks_from_u64_lanes::<S, _>(other.iter_u64_blocks()));
+            }
+
+            /// Remove the indices of this set which are not in `other`,
+            /// whose bit sets may have a different width.
+            ///
+            /// The bit sets of `other` are split or joined into bit
+            /// sets of this set as they are compared.
+            pub fn intersect_with<T>(&mut self, other: &$($Set)*<T>)
+            where
+                T: crate::storage::Storage,
+            {
+                let mut other_blocks =
+                    crate::blocks_from_u64_lanes::<S, _>(other.iter_u64_blocks()).peekable();
+
+                self.retain_blocks(|map_index, set| {
+                    while other_blocks.next_if(|&(j, _)| j < map_index).is_some() {}
+                    match other_blocks.next_if(|&(j, _)| j == map_index) {
+                        Some((_, other_set)) => *set &= other_set,
+                        None => *set = S::ZERO,
+                    }
+                });
+            }
+
+            /// Check if this set holds the same indices as `other`,
+            /// whose bit sets may have a different width.
+            pub fn contents_eq<T>(&self, other: &$($Set)*<T>) -> bool
+            where
+                T: crate::storage::Storage,
+            {
+                self.iter_u64_blocks().eq(other.iter_u64_blocks())
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+macro_rules! index_set_impl_extend {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> Extend<usize> for $($Set)*<S> {
+            #[inline]
+            fn extend<T>(&mut self, iter: T)
+            where
+                T: IntoIterator<Item = usize>
+            {
+                use crate::IndexSet;
+
+                let iter = iter.into_iter();
+                let bounded_cap = crate::safe_iter_reserve_cap(
+                    &iter,
+                );
+
+                self.reserve(bounded_cap);
+
+                for item in iter {
+                    self.insert(item);
+                }
+            }
+        }
+
+        impl<'a, S: crate::storage::Storage> Extend<&'a $($Set)*<S>> for $($Set)*<S> {
+            /// Merge each of the sets in `iter` into this set,
+            /// one bit set at a time.
+            #[inline]
+            fn extend<T>(&mut self, iter: T)
+            where
+                T: IntoIterator<Item = &'a $($Set)*<S>>
+            {
+                use crate::IndexSet;
+
+                for other in iter {
+                    self.union(other);
+                }
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_fixedbitset {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "fixedbitset")]
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Build a new set from the bits set in a
+            /// [`FixedBitSet`](::fixedbitset::FixedBitSet).
+            ///
+            /// NB: this is not a [`From`] conversion, since it
+            /// would overlap with the conversion from iterators.
+            pub fn from_fixedbitset(bit_set: &::fixedbitset::FixedBitSet) -> Self {
+                const WORD_BITS: usize = usize::BITS as usize;
+
+                let indices = bit_set
+                    .as_slice()
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &word)| word != 0)
+                    .flat_map(|(word_index, &word)| {
+                        let start_index = word_index * WORD_BITS;
+                        (0..WORD_BITS)
+                            .filter(move |&bit| word & (1 << bit) != 0)
+                            .map(move |bit| start_index + bit)
+                    })
+                    .take_while(|&index| index < bit_set.len());
+
+                let mut set = Self::new();
+                for block in crate::pack_sorted(indices) {
+                    set.push_block(block);
+                }
+                set
+            }
+
+            /// Convert this set into a [`FixedBitSet`](::fixedbitset::FixedBitSet)
+            /// holding `len` bits.
+            ///
+            /// Indices greater than or equal to `len` are left out.
+            pub fn to_fixedbitset(&self, len: usize) -> ::fixedbitset::FixedBitSet {
+                const WORD_BITS: usize = usize::BITS as usize;
+
+                let mut words = alloc::vec![0usize; len.div_ceil(WORD_BITS)];
+
+                for (mut start_index, mut lane) in self.iter_u64_blocks() {
+                    while lane != 0 {
+                        let Some(word) = words.get_mut(start_index / WORD_BITS) else {
+                            break;
+                        };
+                        *word |= lane as usize;
+                        lane = lane.checked_shr(WORD_BITS as u32).unwrap_or(0);
+                        start_index += WORD_BITS;
+                    }
+                }
+                if let (Some(last), tail_bits @ 1..) = (words.last_mut(), len % WORD_BITS) {
+                    *last &= usize::MAX >> (WORD_BITS - tail_bits);
+                }
+
+                ::fixedbitset::FixedBitSet::with_capacity_and_blocks(len, words)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+macro_rules! index_set_impl_from {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> From<$($Set)*<S>>
+            for alloc::collections::BTreeSet<usize>
+        {
+            #[inline]
+            fn from(index_set: $($Set)*<S>) -> Self {
+                Self::from(&index_set)
+            }
+        }
+
+        impl<S: crate::storage::Storage> From<&$($Set)*<S>>
+            for alloc::collections::BTreeSet<usize>
+        {
+            fn from(index_set: &$($Set)*<S>) -> Self {
+                use crate::IndexSet;
+
+                let mut btree_set = Self::new();
+
+                for index in index_set.iter() {
+                    btree_set.insert(index);
+                }
+
+                btree_set
+            }
+        }
+
+        impl<S: crate::storage::Storage> From<$($Set)*<S>>
+            for alloc::vec::Vec<usize>
+        {
+            #[inline]
+            fn from(index_set: $($Set)*<S>) -> Self {
+                Self::from(&index_set)
+            }
+        }
+
+        impl<S: crate::storage::Storage> From<&$($Set)*<S>>
+            for alloc::vec::Vec<usize>
+        {
+            fn from(index_set: &$($Set)*<S>) -> Self {
+                use crate::IndexSet;
+
+                let mut vec = Self::new();
+
+                for index in index_set.iter() {
+                    vec.push(index);
+                }
+
+                vec
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl<S, H> From<$($Set)*<S>> for ::std::collections::HashSet<usize, H>
+        where
+            S: crate::storage::Storage,
+            H: core::hash::BuildHasher + Default,
+        {
+            #[inline]
+            fn from(index_set: $($Set)*<S>) -> Self {
+                Self::from(&index_set)
+            }
+        }
+
+        #[cfg(feature = "std")]
+        impl<S, H> From<&$($Set)*<S>> for ::std::collections::HashSet<usize, H>
+        where
+            S: crate::storage::Storage,
+            H: core::hash::BuildHasher + Default,
+        {
+            fn from(index_set: &$($Set)*<S>) -> Self {
+                use crate::IndexSet;
+
+                let mut hash_set = Self::with_capacity_and_hasher(index_set.len(), H::default());
+
+                for index in index_set.iter() {
+                    hash_set.insert(index);
+                }
+
+                hash_set
+            }
+        }
+
+        impl<I, S> From<I> for $($Set)*<S>
+        where
+            I: IntoIterator<Item = usize>,
+            S: crate::storage::Storage,
+        {
+            #[inline]
+            fn from(iter: I) -> Self {
+                Self::from_iter(iter)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+macro_rules! index_set_impl_from_str {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> core::str::FromStr for $($Set)*<S> {
+            type Err = crate::parse::ParseIndexSetError;
+
+            /// Parse a set of comma separated indices and inclusive
+            /// ranges of indices, such as `1,4-6,10`.
+            ///
+            /// Ranges are filled in whole bit sets at a time,
+            /// rather than one index at a time.
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                let mut set = Self::new();
+
+                for range in crate::parse::parse_ranges(s) {
+                    set.merge_blocks(crate::range_blocks::<S>(range?));
+                }
+
+                Ok(set)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_hibitset {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "hibitset")]
+        impl<S: crate::storage::Storage> $($Set)*<S> {
+            /// Return word `i` of layer `layer` of the hierarchical
+            /// bit set view of this set, as laid out by
+            /// [`BitSetLike`](::hibitset::BitSetLike).
+            fn hibitset_layer(&self, layer: u32, i: usize) -> usize {
+                const LAYER_BITS: u32 = usize::BITS.trailing_zeros();
+
+                let shift = layer * LAYER_BITS;
+                let start_index = i << (shift + LAYER_BITS);
+                let end_index = start_index + ((1 << (shift + LAYER_BITS)) - 1);
+
+                let (start_map_index, _) = crate::calculate_map_and_set_indices::<S>(start_index);
+                let (end_map_index, _) = crate::calculate_map_and_set_indices::<S>(end_index);
+
+                self.blocks_in(start_map_index..=end_map_index)
+                    .flat_map(|(map_index, set)| {
+                        crate::bit_set_indices(set)
+                            .map(move |bit_set_index| map_index * S::WIDTH + bit_set_index)
+                    })
+                    .fold(0, |word, index| word | 1 << ((index - start_index) >> shift))
+            }
+        }
+
+        /// Exposes the indices of this set to the joins of
+        /// [`hibitset`](::hibitset), without copying them.
+        ///
+        /// Only indices within the capacity of a
+        /// [`BitSet`](::hibitset::BitSet) are visible.
+        #[cfg(feature = "hibitset")]
+        impl<S: crate::storage::Storage> ::hibitset::BitSetLike for $($Set)*<S> {
+            #[inline]
+            fn layer3(&self) -> usize {
+                self.hibitset_layer(3, 0)
+            }
+
+            #[inline]
+            fn layer2(&self, i: usize) -> usize {
+                self.hibitset_layer(2, i)
+            }
+
+            #[inline]
+            fn layer1(&self, i: usize) -> usize {
+                self.hibitset_layer(1, i)
+            }
+
+            #[inline]
+            fn layer0(&self, i: usize) -> usize {
+                self.hibitset_layer(0, i)
+            }
+
+            #[inline]
+            fn contains(&self, i: u32) -> bool {
+                crate::IndexSet::contains(self, i as usize)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+macro_rules! index_set_impl_index {
+    ($($Set:tt)*) => {
+        impl<S: crate::storage::Storage> core::ops::Index<usize> for $($Set)*<S> {
+            type Output = bool;
+
+            /// Check the presence of an index in this set,
+            /// such that `set[index]` is equivalent to
+            /// `set.contains(index)`.
+            #[inline]
+            fn index(&self, index: usize) -> &bool {
+                use crate::IndexSet;
+
+                if self.contains(index) {
+                    &true
+                } else {
+                    &false
+                }
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+macro_rules! index_set_impl_partial_eq {
+    ($($Set:tt)*) => {
+        /// Compare a set against a slice of indices, which is
+        /// interpreted as a list of unique indices sorted in
+        /// ascending order.
+        impl<S: crate::storage::Storage> PartialEq<[usize]> for $($Set)*<S> {
+            #[inline]
+            fn eq(&self, other: &[usize]) -> bool {
+                use crate::IndexSet;
+
+                self.iter().eq(other.iter().copied())
+            }
+        }
+
+        /// Compare a set against an array of indices, which is
+        /// interpreted as a list of unique indices sorted in
+        /// ascending order.
+        impl<S: crate::storage::Storage, const N: usize> PartialEq<[usize; N]> for $($Set)*<S> {
+            #[inline]
+            fn eq(&self, other: &[usize; N]) -> bool {
+                self == other.as_slice()
+            }
+        }
+
+        /// Compare a set against a vector of indices, which is
+        /// interpreted as a list of unique indices sorted in
+        /// ascending order.
+        impl<S: crate::storage::Storage> PartialEq<alloc::vec::Vec<usize>> for $($Set)*<S> {
+            #[inline]
+            fn eq(&self, other: &alloc::vec::Vec<usize>) -> bool {
+                self == other.as_slice()
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_impl_prost {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "prost")]
+        impl<S: crate::storage::Storage> From<&$($Set)*<S>> for crate::proto::IndexSetProto {
+            #[inline]
+            fn from(set: &$($Set)*<S>) -> Self {
+                Self::from_u64_blocks(set.iter_u64_blocks())
+            }
+        }
+
+        #[cfg(feature = "prost")]
+        impl<S: crate::storage::Storage> TryFrom<&crate::proto::IndexSetProto> for $($Set)*<S> {
+            type Error = crate::error::Error;
+
+            fn try_from(proto: &crate::proto::IndexSetProto) -> Result<Self, Self::Error> {
+                let mut set = Self::new();
+                for block in crate::pack_sorted(proto.indices()?) {
+                    set.push_block(block);
+                }
+                Ok(set)
+            }
+        }
+
+        #[cfg(feature = "prost")]
+        impl<S: crate::storage::Storage> TryFrom<crate::proto::IndexSetProto> for $($Set)*<S> {
+            type Error = crate::error::Error;
+
+            #[inline]
+            fn try_from(proto: crate::proto::IndexSetProto) -> Result<Self, Self::Error> {
+                Self::try_from(&proto)
+            }
+        }
+    };
+}
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+macro_rules! index_set_impl_quickcheck {
+    ($($Set:tt)*) => {
+        #[cfg(feature = "quickcheck")]
+        impl<S> quickcheck::Arbitrary for $($Set)*<S>
+        where
+            S: crate::storage::Storage + 'static,
+        {
+            #[inline]
+            fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+                let indices: alloc::vec::Vec<usize> = quickcheck::Arbitrary::arbitrary(g);
+                Self::from_iter(indices)
+            }
+
+            fn shrink(&self) -> alloc::boxed::Box<dyn Iterator<Item = Self>> {
+                let indices: alloc::vec::Vec<usize> = self.into();
+                alloc::boxed::Box::new(indices.shrink().map(Self::from_iter))
+            }
+        }
+    };
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+macro_rules! index_set_tests_for {
+    ($type:ident, $($Set:tt)*) => {
+        #[cfg(test)]
+        mod $type {
+            use crate::IndexSet;
+
+            type Set = $($Set)* :: <$type>;
+
+            crate::macros::index_set_core_test_fns!($type, $($Set)*);
+
+            /// Test truncating index sets.
+            #[test]
+            fn test_index_set_truncate() {
+                let indices = [1, 4, 6, 31, 32, 33, 100, 123, 128];
+                let set: Set = indices.into_iter().collect();
+
+                for max in [0, 1, 2, 5, 6, 7, 32, 33, 34, 100, 129, 1000] {
+                    let mut truncated = set.clone();
+                    truncated.truncate(max);
+
+                    let expected: Set = indices.into_iter().filter(|&i| i < max).collect();
+                    assert_eq!(truncated, expected);
+                }
+            }
+
+            /// Test popping the lowest and greatest indices of index sets.
+            #[test]
+            fn test_index_set_pop_smallest_and_largest() {
+                let indices = [1, 4, 6, 31, 32, 33, 100, 123, 128];
+                let set: Set = indices.into_iter().collect();
+
+                for n in 0..=indices.len() + 1 {
+                    let mut popped_set = set.clone();
+                    let popped = popped_set.pop_smallest(n);
+                    let n = n.min(indices.len());
+                    assert_eq!(popped, indices[..n]);
+                    assert!(popped_set.iter().eq(indices[n..].iter().copied()));
+
+                    let mut popped_set = set.clone();
+                    let popped = popped_set.pop_largest(n);
+                    assert!(popped.into_iter().eq(indices.into_iter().rev().take(n)));
+                    assert!(popped_set.iter().eq(indices[..indices.len() - n].iter().copied()));
+                }
+
+                assert!(Set::new().pop_smallest(3).is_empty());
+                assert!(Set::new().pop_largest(3).is_empty());
+            }
+
+            /// Test inserting and removing whole bit sets at once.
+            #[test]
+            fn test_index_set_insert_and_remove_word() {
+                let width = <$type as crate::storage::Storage>::WIDTH;
+                let mut set: Set = [0].into_iter().collect();
+
+                set.insert_word(3, 1);
+                set.insert_word(5, 0);
+                set.insert_word(0, !0);
+                let expected: Set = (0..width).chain([3 * width]).collect();
+                assert_eq!(set, expected);
+
+                set.remove_word(0, !1);
+                set.remove_word(3, 1);
+                set.remove_word(7, !0);
+                let expected: Set = [0].into_iter().collect();
+                assert_eq!(set, expected);
+                assert_eq!(set.len(), 1);
+
+                set.remove_word(0, 1);
+                assert!(set.is_empty());
+            }
+
+            /// Test the accessors of the layout of index sets.
+            #[test]
+            fn test_index_set_layout() {
+                let width = <$type as crate::storage::Storage>::WIDTH;
+                let set: Set = [3, 4, 100, 1000].into_iter().collect();
+
+                let blocks: std::collections::BTreeSet<_> =
+                    set.iter().map(|index| index / width).collect();
+                assert_eq!(set.num_blocks(), blocks.len());
+                assert_eq!(set.max_index_in_blocks(), Some(1000 / width * width + width - 1));
+                assert_eq!(set.index_span(), Some(3..=1000));
+
+                let empty = Set::new();
+                assert_eq!(empty.num_blocks(), 0);
+                assert_eq!(empty.max_index_in_blocks(), None);
+                assert_eq!(empty.index_span(), None);
+            }
+
+            /// Test flipping the membership of single indices.
+            #[test]
+            fn test_index_set_toggle() {
+                let mut set = Set::new();
+                let mut expected = ::std::collections::BTreeSet::new();
+
+                for i in [1, 4, 6, 1, 100, 123, 4, 6, 0, 123, 1, 4] {
+                    let present = expected.insert(i) || !expected.remove(&i);
+                    assert_eq!(set.toggle(i), present);
+                    assert_eq!(set.contains(i), present);
+                }
+                assert!(set.iter().eq(expected.iter().copied()));
+
+                // toggling the remaining indices leaves no empty bit sets
+                for i in expected {
+                    assert!(!set.toggle(i));
+                }
+                assert!(set.is_empty());
+                assert_eq!(set.blocks().count(), 0);
+            }
+
+            /// Test complementing index sets up to a bound.
+            #[test]
+            fn test_index_set_invert() {
+                let indices = [1, 4, 6, 31, 32, 33, 100, 123, 128];
+                let set: Set = indices.into_iter().collect();
+
+                for upto in [0, 1, 2, 5, 6, 7, 32, 33, 34, 100, 129, 300] {
+                    let mut inverted = set.clone();
+                    inverted.invert(upto);
+
+                    let expected: Set = (0..upto.max(129))
+                        .filter(|&i| (i < upto) != indices.contains(&i))
+                        .collect();
+                    assert_eq!(inverted, expected);
+
+                    inverted.invert(upto);
+                    assert_eq!(inverted, set);
+                }
+            }
+
+            /// Test splitting sets at an index.
+            #[test]
+            fn test_index_set_split_off() {
+                let indices = [1, 4, 6, 31, 32, 33, 100, 123, 128, 1 << 20];
+                let set: Set = indices.into_iter().collect();
+
+                for at in [0, 1, 2, 5, 6, 7, 32, 33, 34, 100, 129, 1 << 20, usize::MAX] {
+                    let mut head = set.clone();
+                    let tail = head.split_off(at);
+
+                    let expected_head: Set = indices.into_iter().filter(|&i| i < at).collect();
+                    let expected_tail: Set = indices.into_iter().filter(|&i| i >= at).collect();
+                    assert_eq!(head, expected_head);
+                    assert_eq!(tail, expected_tail);
+                    assert!(head.blocks().chain(tail.blocks()).all(|(_, bit_set)| bit_set != 0));
+
+                    head.union(&tail);
+                    assert_eq!(head, set);
+                }
+            }
+
+            /// Test moving the indices of sets into other sets.
+            #[test]
+            fn test_index_set_append() {
+                let cases: [(&[usize], &[usize]); 6] = [
+                    (&[], &[]),
+                    (&[], &[1, 4, 100]),
+                    (&[1, 4, 100], &[]),
+                    (&[1, 4, 6], &[31, 100, 1 << 20]),
+                    (&[31, 100, 1 << 20], &[1, 4, 6]),
+                    (&[1, 32, 100, 128], &[0, 4, 33, 100, 123, 1 << 20]),
+                ];
+
+                for (a, b) in cases {
+                    let mut set: Set = a.iter().copied().collect();
+                    let mut other: Set = b.iter().copied().collect();
+                    set.append(&mut other);
+
+                    let expected: Set = a.iter().chain(b).copied().collect();
+                    assert_eq!(set, expected);
+                    assert!(other.is_empty());
+                    assert_eq!(other.blocks().count(), 0);
+
+                    other.insert(7);
+                    assert!(other.iter().eq([7]));
+                }
+            }
+
+            /// Test draining the indices of sets.
+            #[test]
+            fn test_index_set_drain() {
+                let indices = [1, 4, 6, 31, 32, 33, 100, 123, 128, 1 << 20];
+                let mut set: Set = indices.into_iter().collect();
+
+                let drained: alloc::vec::Vec<_> = set.drain().collect();
+                assert_eq!(drained, indices);
+                assert!(set.is_empty());
+                assert_eq!(set.blocks().count(), 0);
+
+                set.extend(indices);
+                let mut drain = set.drain();
+                let (lower, upper) = drain.size_hint();
+                assert!(lower <= indices.len());
+                assert!(upper.is_none_or(|n| n >= indices.len()));
+                assert_eq!(drain.next(), Some(1));
+                drop(drain);
+                assert!(set.is_empty());
+                assert_eq!(set.drain().next(), None);
+
+                set.insert(100);
+                assert!(set.iter().eq([100]));
+            }
+
+            /// Test computing statistics of index sets.
+            #[test]
+            fn test_index_set_stats() {
+                let width = <$type as crate::storage::Storage>::WIDTH;
+
+                let stats = Set::new().stats();
+                assert_eq!(stats.num_blocks, 0);
+                assert_eq!(stats.len, 0);
+                assert_eq!(stats.min_index, None);
+                assert_eq!(stats.max_index, None);
+                assert_eq!(stats.avg_bits_per_block(), 0.0);
+
+                let set: Set = (0..width).chain([width * 10, width * 20 + 1]).collect();
+                let stats = set.stats();
+
+                assert_eq!(stats.num_blocks, 3);
+                assert_eq!(stats.len, width + 2);
+                assert_eq!(stats.block_width, width);
+                assert_eq!(stats.min_index, Some(0));
+                assert_eq!(stats.max_index, Some(width * 20 + 1));
+                assert_eq!(stats.histogram.iter().sum::<usize>(), 3);
+                assert_eq!(stats.histogram[1], if width == 1 { 3 } else { 2 });
+                assert_eq!(stats.histogram[width], if width == 1 { 3 } else { 1 });
+                assert_eq!(stats.avg_bits_per_block(), (width + 2) as f64 / 3.0);
+            }
+
+            /// Test iterating over the gaps between indices of index sets.
+            #[test]
+            fn test_index_set_deltas() {
+                let set: Set = [1, 4, 6, 31, 32, 1000].into_iter().collect();
+                assert!(set.deltas().eq([3, 2, 25, 1, 968]));
+
+                assert_eq!(Set::new().deltas().count(), 0);
+                assert_eq!([7].into_iter().collect::<Set>().deltas().count(), 0);
+            }
+
+            /// Test storage recommendations and repacking of index sets.
+            #[test]
+            fn test_index_set_recommend_storage() {
+                use crate::stats::{Backend, StorageWidth};
+
+                let block_size = |width: StorageWidth| ::core::mem::size_of::<usize>() + width.size();
+
+                let sparse: Set = (0..64).map(|i| i * 1000).collect();
+                let recommendation = sparse.recommend_storage();
+                assert_eq!(recommendation.backend, Backend::Vec);
+                assert_eq!(recommendation.width, StorageWidth::U8);
+                assert_eq!(recommendation.estimated_bytes, 64 * block_size(StorageWidth::U8));
+                assert!(recommendation.estimated_bytes <= recommendation.current_bytes);
+
+                let dense: Set = (0..1024).collect();
+                let recommendation = dense.recommend_storage();
+                assert_eq!(recommendation.backend, Backend::Vec);
+                assert_eq!(recommendation.width, StorageWidth::U128);
+                assert_eq!(
+                    recommendation.estimated_bytes,
+                    1024 / 16 * block_size(StorageWidth::U128)
+                );
+
+                let repacked = dense.repack::<u8>();
+                assert!(repacked.iter().eq(dense.iter()));
+                assert_eq!(repacked.repack::<$type>(), dense);
+            }
+
+            /// Test reserving space up to a maximum index.
+            #[test]
+            fn test_index_set_reserve_for_index() {
+                let mut set = Set::with_capacity_for_max_index(1000);
+                set.extend(0..=1000);
+                set.reserve_for_index(2000);
+                set.reserve_for_index(10);
+                set.extend(1001..=2000);
+
+                assert!(set.iter().eq(0..=2000));
+                assert!(Set::with_capacity_for_max_index(0).is_empty());
+            }
+
+            /// Test canonical CBOR encoding.
+            #[test]
+            #[cfg(feature = "serialize-cbor")]
+            fn test_index_set_canonical_cbor() {
+                use crate::error::Error;
+
+                let set: Set = [1, 4, 6, 63, 64, 1000].into_iter().collect();
+                let bytes = set.to_canonical_cbor();
+                assert_eq!(
+                    bytes,
+                    [
+                        0x83, // array(3)
+                        0x82, 0x00, 0x1b, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x52,
+                        0x82, 0x01, 0x01,
+                        0x82, 0x0f, 0x1b, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00,
+                    ]
+                );
+                assert_eq!(Set::from_canonical_cbor(&bytes).unwrap(), set);
+                assert_eq!(Set::new().to_canonical_cbor(), [0x80]);
+
+                let invalid = |bytes: &[u8]| Set::from_canonical_cbor(bytes).unwrap_err();
+                assert_eq!(invalid(&[0x81, 0x82, 0x01]), Error::InvalidEncoding);
+                assert_eq!(invalid(&[0x80, 0x00]), Error::InvalidEncoding);
+                assert_eq!(invalid(&[0x81, 0x83, 0x01, 0x01, 0x01]), Error::InvalidEncoding);
+                assert_eq!(invalid(&[0x81, 0x82, 0x18, 0x01, 0x01]), Error::NonCanonicalEncoding);
+                assert_eq!(invalid(&[0x9f, 0xff]), Error::NonCanonicalEncoding);
+                assert_eq!(invalid(&[0x82, 0x82, 0x01, 0x01, 0x82, 0x01, 0x01]), Error::UnsortedBlocks);
+                assert_eq!(invalid(&[0x81, 0x82, 0x01, 0x00]), Error::ZeroBlock);
+            }
+
+            /// Test SCALE encoding.
+            #[test]
+            #[cfg(feature = "scale")]
+            fn test_index_set_scale() {
+                use ::parity_scale_codec::{Decode, Encode};
+
+                let width = <$type as crate::storage::Storage>::WIDTH;
+                let one = $type::try_from(1).unwrap();
+                let two = $type::try_from(2).unwrap();
+
+                let set: Set = [1, 4, 6, 63, 64, 1000].into_iter().collect();
+                let bytes = set.encode();
+                assert_eq!(bytes.len(), set.size_hint());
+                assert_eq!(Set::decode(&mut bytes.as_slice()).unwrap(), set);
+
+                let pairs: Vec<(u64, $type)> = vec![(0, one), (3, one)];
+                let expected: Set = [0, 3 * width].into_iter().collect();
+                assert_eq!(Set::decode(&mut pairs.encode().as_slice()).unwrap(), expected);
+                assert_eq!(expected.encode(), pairs.encode());
+
+                let unsorted: Vec<(u64, $type)> = vec![(3, one), (0, one)];
+                assert!(Set::decode(&mut unsorted.encode().as_slice()).is_err());
+                let duplicated: Vec<(u64, $type)> = vec![(3, one), (3, two)];
+                assert!(Set::decode(&mut duplicated.encode().as_slice()).is_err());
+            }
+
+            /// Test converting index sets to and from protobuf messages.
+            #[test]
+            #[cfg(feature = "prost")]
+            fn test_index_set_prost() {
+                use ::prost::Message;
+
+                use crate::error::Error;
+                use crate::proto::IndexSetProto;
+
+                let set: Set = [1, 4, 6, 63, 64, 1000].into_iter().collect();
+                let proto = IndexSetProto::from(&set);
+                assert_eq!(proto.block_indices, [0, 1, 15]);
+
+                let decoded = IndexSetProto::decode(proto.encode_to_vec().as_slice()).unwrap();
+                assert_eq!(Set::try_from(decoded).unwrap(), set);
+
+                let invalid = |block_indices: Vec<u64>, blocks: Vec<u64>| {
+                    let proto = IndexSetProto { block_indices, blocks };
+                    Set::try_from(&proto).unwrap_err()
+                };
+                assert_eq!(invalid(vec![0], vec![]), Error::InvalidEncoding);
+                assert_eq!(invalid(vec![1, 1], vec![1, 2]), Error::UnsortedBlocks);
+                assert_eq!(invalid(vec![1], vec![0]), Error::ZeroBlock);
+                assert_eq!(invalid(vec![u64::MAX], vec![1]), Error::IndexOutOfBounds);
+            }
+
+            /// Test that deserialized sets can't hold indices
+            /// past [`usize::MAX`].
+            #[test]
+            #[cfg(feature = "serialize-borsh")]
+            fn test_index_set_borsh_decode_overflow() {
+                use borsh::BorshDeserialize;
+
+                let width = <$type as crate::storage::Storage>::WIDTH;
+                let one = $type::try_from(1).unwrap();
+
+                let max = borsh::to_vec(&vec![(usize::MAX / width, one)]).unwrap();
+                assert!(Set::try_from_slice(&max).unwrap().contains(usize::MAX - width + 1));
+
+                if let Some(past_max) = (usize::MAX / width).checked_add(1) {
+                    let past_max = borsh::to_vec(&vec![(past_max, one)]).unwrap();
+                    assert!(Set::try_from_slice(&past_max).is_err());
+                }
+            }
+
+            /// Test that strict borsh deserialization
+            /// re-validates all invariants.
+            #[test]
+            #[cfg(feature = "serialize-borsh")]
+            fn test_index_set_strict_invariants_borsh() {
+                let zero = $type::try_from(0).unwrap();
+                let one = $type::try_from(1).unwrap();
+
+                let strict = |bytes: Vec<u8>| Set::deserialize_strict_reader(&mut bytes.as_slice());
+                let valid = borsh::to_vec(&vec![(0usize, one), (2, one)]).unwrap();
+                let empty_block = borsh::to_vec(&vec![(0usize, one), (2, zero)]).unwrap();
+                let duplicated = borsh::to_vec(&vec![(2usize, one), (2, one)]).unwrap();
+
+                assert_eq!(strict(valid).unwrap(), Set::from_iter([0, 2 * <$type as crate::storage::Storage>::WIDTH]));
+                assert_eq!(strict(empty_block).unwrap_err().to_string(), crate::error::Error::ZeroBlock.to_string());
+                assert!(strict(duplicated).is_err());
+            }
+
+            /// Test that strict serde deserialization
+            /// re-validates all invariants.
+            #[test]
+            #[cfg(feature = "serialize-serde")]
+            fn test_index_set_strict_invariants_serde() {
+                let strict = |json: &str| {
+                    Set::deserialize_strict(&mut serde_json::Deserializer::from_str(json))
+                };
+                let json = serde_json::to_string(&Set::from_iter([0])).unwrap();
+                assert!(strict(&json).is_ok());
+                assert!(strict(&json.replacen('1', "0", 1)).is_err());
+                assert!(strict("[1, 4, 6]").is_ok());
+            }
+
+            /// Test borsh serialization.
+            #[test]
+            #[cfg(feature = "serialize-borsh")]
+            fn test_index_set_borsh_decode() {
+                use borsh::BorshDeserialize;
+
+                let one = $type::try_from(1).unwrap();
+
+                let valid = (
+                    4u32,
+                    [
+                        (0usize, one),
+                        (1, one),
+                        (2, one),
+                        (3, one),
+                    ],
+                );
+                let invalid = (
+                    4u32,
+                    [
+                        (0usize, one),
+                        (1, one),
+                        (3, one),
+                        (2, one),
+                    ],
+                );
+
+                let valid = borsh::to_vec(&valid).unwrap();
+                let invalid = borsh::to_vec(&invalid).unwrap();
+
+                _ = Set::try_from_slice(&valid).unwrap();
+                _ = Set::try_from_slice(&invalid).unwrap_err();
+            }
+
+            /// Test deserializing either serialized index
+            /// sets or plain arrays of indices.
+            #[test]
+            #[cfg(feature = "serialize-serde")]
+            fn test_index_set_serde_decode_indices() {
+                let set: Set = [1, 4, 6, 100, 1000].into_iter().collect();
+                let repr = serde_json::to_string(&set).unwrap();
+
+                assert_eq!(serde_json::from_str::<Set>(&repr).unwrap(), set);
+                assert_eq!(serde_json::from_str::<Set>("[1,4,6,100,1000]").unwrap(), set);
+                assert_eq!(serde_json::from_str::<Set>("[]").unwrap(), Set::new());
+                assert!(serde_json::from_str::<Set>("[1,-4]").is_err());
+                assert!(serde_json::from_str::<Set>("1").is_err());
+            }
+
+            /// Test reading the borsh encoding of a `BTreeSet<usize>`.
+            #[test]
+            #[cfg(feature = "serialize-borsh")]
+            fn test_index_set_borsh_decode_btreeset() {
+                use std::collections::BTreeSet;
+
+                #[derive(borsh::BorshDeserialize)]
+                struct State {
+                    #[borsh(deserialize_with = "Set::deserialize_btreeset_reader")]
+                    set: Set,
+                }
+
+                let indices = BTreeSet::from([1usize, 4, 6, 100, 1000]);
+                let bytes = borsh::to_vec(&indices).unwrap();
+                let expected: Set = indices.iter().copied().collect();
+
+                assert_eq!(Set::from_btreeset_bytes(&bytes).unwrap(), expected);
+                assert_eq!(borsh::from_slice::<State>(&bytes).unwrap().set, expected);
+
+                let unsorted = borsh::to_vec(&vec![4u64, 1]).unwrap();
+                let duplicated = borsh::to_vec(&vec![4u64, 4]).unwrap();
+                let mut trailing = bytes.clone();
+                trailing.push(0);
+                let error = |bytes: &[u8]| Set::from_btreeset_bytes(bytes).unwrap_err().to_string();
+                assert_eq!(error(&unsorted), crate::error::Error::UnsortedBlocks.to_string());
+                assert_eq!(error(&duplicated), crate::error::Error::UnsortedBlocks.to_string());
+                assert_eq!(error(&trailing), crate::error::Error::InvalidEncoding.to_string());
+                assert!(Set::from_btreeset_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+                let dense: BTreeSet<usize> = (0..300).chain([usize::MAX]).collect();
+                let set = Set::from_btreeset_bytes(&borsh::to_vec(&dense).unwrap()).unwrap();
+                assert!(set.iter().eq(dense.iter().copied()));
+            }
+
+            /// Test repairing non-canonical bit sets.
+            #[test]
+            fn test_index_set_from_blocks_lenient() {
+                let one = $type::try_from(1).unwrap();
+                let width = <$type as crate::storage::Storage>::WIDTH;
+
+                let set = Set::from_blocks_lenient([(5, one), (0, one), (5, one), (3, 0), (0, one)]);
+                let expected: Set = [0, width * 5].into_iter().collect();
+                assert_eq!(set, expected);
+                assert_eq!(set.num_blocks(), 2);
+                assert!(Set::from_blocks_lenient([]).is_empty());
+                if width > 1 {
+                    assert!(Set::from_blocks_lenient([(usize::MAX, one)]).is_empty());
+                }
+            }
+
+            /// Test reading non-canonical borsh data leniently.
+            #[test]
+            #[cfg(feature = "serialize-borsh")]
+            fn test_index_set_borsh_decode_lenient() {
+                #[derive(borsh::BorshDeserialize)]
+                struct State {
+                    #[borsh(deserialize_with = "Set::deserialize_lenient_reader")]
+                    set: Set,
+                }
+
+                let one = $type::try_from(1).unwrap();
+                let pairs: alloc::vec::Vec<(u64, $type)> =
+                    vec![(4, one), (1, one), (4, 0), (1, one)];
+                let bytes = borsh::to_vec(&pairs).unwrap();
+
+                let expected = Set::from_blocks_lenient([(1, one), (4, one)]);
+                assert_eq!(borsh::from_slice::<State>(&bytes).unwrap().set, expected);
+                assert!(Set::deserialize_lenient_reader(&mut &bytes[..bytes.len() - 1]).is_err());
+
+                if crate::max_map_index::<$type>() < usize::MAX {
+                    let past_max = borsh::to_vec(&vec![(u64::MAX, one)]).unwrap();
+                    let error = Set::deserialize_lenient_reader(&mut past_max.as_slice()).unwrap_err();
+                    assert_eq!(error.to_string(), crate::error::Error::IndexOutOfBounds.to_string());
+                }
+            }
+
+            /// Test serde serialization.
+            #[test]
+            #[cfg(feature = "serialize-serde")]
+            fn test_index_set_serde_decode() {
+                use std::collections::HashMap;
+
+                if !std::any::type_name::<Set>().contains("VecIndexSet") {
+                    // NB: we're only testing VecIndexSet
+                    return;
+                }
+
+                let one = $type::try_from(1).unwrap();
+
+                let valid = HashMap::from([(
+                    "bit_sets".to_string(),
+                    [
+                        (0usize, one),
+                        (1, one),
+                        (2, one),
+                        (3, one),
+                    ],
+                )]);
+                let invalid = HashMap::from([(
+                    "bit_sets".to_string(),
+                    [
+                        (0usize, one),
+                        (1, one),
+                        (3, one),
+                        (2, one),
+                    ],
+                )]);
+
+                let valid = serde_json::to_string(&valid).unwrap();
+                let invalid = serde_json::to_string(&invalid).unwrap();
+
+                _ = serde_json::from_str::<Set>(&valid).unwrap();
+                _ = serde_json::from_str::<Set>(&invalid).unwrap_err();
+            }
+
+        }
+    };
+}
+
+#[cfg(feature = "paged")]
+macro_rules! index_set_core_tests_for {
+    ($type:ident, $($Set:tt)*) => {
+        #[cfg(test)]
+        mod $type {
+            use crate::IndexSet;
+
+            type Set = $($Set)* :: <$type>;
+
+            crate::macros::index_set_core_test_fns!($type, $($Set)*);
+        }
+    };
+}
+
+#[cfg(all(test, any(feature = "vec", feature = "btree", feature = "paged")))]
+macro_rules! index_set_core_test_fns {
+    ($type:ident, $($Set:tt)*) => {
+        /// Test index insert ops.
+        #[test]
+        fn test_index_set_insert() {
+            let mut set = Set::new();
+            let mut indices = vec![1, 4, 6, 3, 1, 100, 123, 12, 3];
+
+            // insert some elements into the set
+            for i in indices.iter().copied() {
+                set.insert(i);
+            }
+
+            // check if the set contains the same elements
+            // we inserted, in ascending order
+            indices.sort_unstable();
+            indices.dedup();
+
+            let set_indices: Vec<_> = set.iter().collect();
+            assert_eq!(indices, set_indices);
+
+            // check that the no. of storage elements used is lower
+            // than the max no. of bitsets we would otherwise need
+            let storage_elements_max =
+                indices[indices.len() - 1] / <u64 as $crate::storage::Storage>::WIDTH;
+            assert!(set.blocks().count() <= storage_elements_max);
+        }
+
+        /// Test that insert ops report newly added indices.
+        #[test]
+        fn test_index_set_insert_reports_new_indices() {
+            let mut set = Set::new();
+            let mut expected = ::std::collections::BTreeSet::new();
+
+            for i in [1, 4, 6, 3, 1, 100, 123, 12, 3, 4, 0, 100] {
+                assert_eq!(set.insert(i), expected.insert(i));
+            }
+            assert!(set.iter().eq(expected.iter().copied()));
+        }
+
+        /// Test that remove ops report removed indices.
+        #[test]
+        fn test_index_set_remove_reports_removed_indices() {
+            let mut set: Set = [1, 4, 6, 3, 100, 123, 12].into_iter().collect();
+            let mut expected: ::std::collections::BTreeSet<_> = set.iter().collect();
+
+            for i in [100, 6, 100, 12, 123, 3, 5, 1_000, 3, 4] {
+                assert_eq!(set.remove(i), expected.remove(&i));
+            }
+            assert!(set.iter().eq(expected.iter().copied()));
+        }
+
+        /// Test clearing sets.
+        #[test]
+        fn test_index_set_clear() {
+            let mut set: Set = [1, 4, 6, 3, 100, 123, 12].into_iter().collect();
+            set.clear();
+            assert!(set.is_empty());
+            assert_eq!(set.len(), 0);
+            assert_eq!(set.iter().next(), None);
+            assert_eq!(set, Set::new());
+
+            assert!(set.insert(100));
+            assert!(set.iter().eq([100]));
+        }
+
+        /// Test index remove ops.
+        #[test]
+        fn test_index_set_remove() {
+            let mut set = Set::new();
+            let indices = [1, 4, 6, 3, 1, 100, 123, 12, 3];
+            let remove = [100, 6, 100, 12, 123, 3];
+
+            // insert some elements into the set
+            for i in indices.iter().copied() {
+                set.insert(i);
+            }
+
+            // remove elements from the set
+            for i in remove.iter().copied() {
+                set.remove(i);
+            }
+
+            let expected: ::std::collections::HashSet<_> = {
+                let indices: ::std::collections::HashSet<_> = indices.into_iter().collect();
+                let remove: ::std::collections::HashSet<_> = remove.into_iter().collect();
+                indices.difference(&remove).copied().collect()
+            };
+            let got: ::std::collections::HashSet<_> = set.iter().collect();
+
+            assert_eq!(expected, got);
+        }
+
+        /// Test creating an index from an iterator.
+        #[test]
+        fn test_index_set_from_iter() {
+            let indices = [1, 4, 6, 3, 1, 100, 123, 12, 3];
+
+            let got: Set = indices.iter().copied().collect();
+            let expected: ::std::collections::BTreeSet<_> = indices.iter().copied().collect();
+
+            assert_eq!(expected, got.into());
+        }
+
+        /// Test index set length related ops.
+        #[test]
+        fn test_index_set_len_and_is_empty() {
+            let indices_1 = [1, 4, 6, 3];
+            let indices_2 = [2, 100, 123, 12, 5];
+
+            let mut set = Set::new();
+
+            assert!(set.is_empty());
+
+            set.extend(indices_1.iter().copied());
+            assert!(!set.is_empty());
+            assert_eq!(set.len(), indices_1.len());
+
+            set.extend(indices_2.iter().copied());
+            assert!(!set.is_empty());
+            assert_eq!(set.len(), indices_1.len() + indices_2.len());
+
+            for item in indices_1.iter().copied() {
+                set.remove(item);
+            }
+            assert!(!set.is_empty());
+            assert_eq!(set.len(), indices_2.len());
+
+            for item in indices_2.iter().copied() {
+                set.remove(item);
+            }
+            assert!(set.is_empty());
+            assert_eq!(set.len(), 0);
+        }
+
+        /// Test the contains method of index sets.
+        #[test]
+        fn test_index_set_contains() {
+            let indices = [1, 4, 6, 3, 2, 100, 123, 12, 5];
+            let not_in_set = [50, 200, 150];
+
+            let set: Set = indices
+                .iter()
+                .copied()
+                .collect();
+
+            for index in indices {
+                assert!(set.contains(index));
+            }
+
+            for index in not_in_set {
+                assert!(!set.contains(index));
+            }
+
+            for index in indices {
+                assert!(set[index]);
+            }
+
+            for index in not_in_set {
+                assert!(!set[index]);
+            }
+        }
+
+        /// Test extending index sets with other sets.
+        #[test]
+        fn test_index_set_extend_with_sets() {
+            let a: Set = [1, 4, 6, 3, 2].into_iter().collect();
+            let b: Set = [100, 123, 12, 5, 4].into_iter().collect();
+            let c: Set = [1000].into_iter().collect();
+
+            let mut set = Set::new();
+            set.extend([&a, &b, &c]);
+
+            let expected: Set = a.iter().chain(b.iter()).chain(c.iter()).collect();
+            assert_eq!(set, expected);
+        }
+
+        /// Test using borrowed and boxed index sets
+        /// through the [`IndexSet`] trait.
+        #[test]
+        fn test_index_set_through_pointers() {
+            fn insert_all(mut set: impl IndexSet, indices: &[usize]) -> usize {
+                for &index in indices {
+                    set.insert(index);
+                }
+                set.len()
+            }
+
+            let mut set = Set::new();
+            assert_eq!(insert_all(&mut set, &[1, 4, 100]), 3);
+            assert_eq!(set, [1, 4, 100].into_iter().collect::<Set>());
+
+            let mut boxed = Box::new(set.clone());
+            boxed.union(&Box::new([6].into_iter().collect()));
+            assert_eq!(IndexSet::successor(&boxed, 4), Some(6));
+            assert_eq!(insert_all(boxed, &[1000]), 5);
+
+            fn summarize(set: impl crate::IndexSetRef) -> (usize, bool, Option<usize>) {
+                (set.len(), set.contains(4), set.iter().last())
+            }
+
+            assert_eq!(summarize(&set), (3, true, Some(100)));
+            assert_eq!(crate::IndexSetRef::predecessor(&&set, 100), Some(4));
+            assert_eq!(summarize(&Set::new()), (0, false, None));
+        }
+
+        /// Test the union method of index sets.
+        #[test]
+        fn test_index_set_union() {
+            let indices_1 = [1, 4, 6, 3, 2];
+            let indices_2 = [100, 123, 12, 5];
+
+            let expected = {
+                let mut set = Set::new();
 
-                for item in iter {
-                    set.insert(item);
+                for index in indices_1.iter().copied() {
+                    set.insert(index);
+                }
+                for index in indices_2.iter().copied() {
+                    set.insert(index);
                 }
 
                 set
-            }
+            };
+
+            let mut set: Set = indices_1
+                .iter()
+                .copied()
+                .collect();
+            let other: Set = indices_2
+                .iter()
+                .copied()
+                .collect();
+
+            set.union(&other);
+            assert_eq!(set, expected);
+
+            let mut set: Set = (0..300).step_by(3).collect();
+            set.union(&(100..400).step_by(5).collect());
+            set.union(&Set::new());
+            let expected: Set = (0..300).step_by(3).chain((100..400).step_by(5)).collect();
+            assert_eq!(set, expected);
         }
-    };
-}
 
-macro_rules! index_set_impl_extend {
-    ($($Set:tt)*) => {
-        impl<S: crate::storage::Storage> Extend<usize> for $($Set)*<S> {
-            #[inline]
-            fn extend<T>(&mut self, iter: T)
-            where
-                T: IntoIterator<Item = usize>
-            {
-                use crate::IndexSet;
+        /// Test parsing index sets from strings.
+        #[test]
+        fn test_index_set_from_str() {
+            use crate::parse::ParseIndexSetErrorKind;
 
-                let iter = iter.into_iter();
-                let bounded_cap = crate::safe_iter_reserve_cap(
-                    &iter,
-                );
+            let set: Set = "1,4, 6-10 ,42".parse().unwrap();
+            let expected: Set = [1, 4, 6, 7, 8, 9, 10, 42].into_iter().collect();
+            assert_eq!(set, expected);
 
-                self.reserve(bounded_cap);
+            let set: Set = "20-30, 3-25,5".parse().unwrap();
+            assert_eq!(set, (3..=30).collect::<Set>());
 
-                for item in iter {
-                    self.insert(item);
-                }
-            }
+            let set: Set = "7-200006".parse().unwrap();
+            assert_eq!(set.len(), 200_000);
+            assert!(set.iter().eq(7..=200_006));
+
+            let set: Set = "".parse().unwrap();
+            assert!(set.is_empty());
+
+            let err = "1,4,x,42".parse::<Set>().unwrap_err();
+            assert_eq!(err.kind(), ParseIndexSetErrorKind::InvalidIndex);
+            assert_eq!(err.span(), 4..5);
+
+            let err = "1,,2".parse::<Set>().unwrap_err();
+            assert_eq!(err.kind(), ParseIndexSetErrorKind::EmptyToken);
+            assert_eq!(err.span(), 2..2);
+
+            let err = "1, 10-6".parse::<Set>().unwrap_err();
+            assert_eq!(err.kind(), ParseIndexSetErrorKind::InvalidRange);
+            assert_eq!(err.span(), 3..7);
         }
-    };
-}
 
-macro_rules! index_set_impl_from {
-    ($($Set:tt)*) => {
-        impl<S: crate::storage::Storage> From<$($Set)*<S>>
-            for alloc::collections::BTreeSet<usize>
-        {
-            #[inline]
-            fn from(index_set: $($Set)*<S>) -> Self {
-                Self::from(&index_set)
-            }
+        /// Test the default and alternate debug representations.
+        #[test]
+        fn test_index_set_debug() {
+            let set: Set = [1, 4, 6].into_iter().collect();
+
+            assert!(format!("{set:?}").contains("bit_sets"));
+            let expected: ::std::collections::BTreeSet<usize> = [1, 4, 6].into();
+            assert_eq!(format!("{set:#?}"), format!("{expected:#?}"));
+
+            let set: Set = (0..1000).collect();
+            let rendered = format!("{set:#?}");
+
+            assert!(rendered.contains("63,"));
+            assert!(!rendered.contains("64,"));
+            assert!(rendered.ends_with("..\n}"));
         }
 
-        impl<S: crate::storage::Storage> From<&$($Set)*<S>>
-            for alloc::collections::BTreeSet<usize>
-        {
-            fn from(index_set: &$($Set)*<S>) -> Self {
-                use crate::IndexSet;
+        /// Test the index set constructor macro.
+        #[test]
+        fn test_index_set_macro() {
+            let set: Set = crate::index_set![1, 4, 7..10, 12..=13, 42,];
+            let expected: Set = [1, 4, 7, 8, 9, 12, 13, 42].into_iter().collect();
+            assert_eq!(set, expected);
 
-                let mut btree_set = Self::new();
+            let set: Set = crate::index_set![];
+            assert!(set.is_empty());
+        }
 
-                for index in index_set.iter() {
-                    btree_set.insert(index);
+        /// Test the proptest strategy of index sets.
+        #[cfg(feature = "proptest")]
+        mod proptest {
+            use ::proptest::prelude::*;
+
+            use super::*;
+
+            ::proptest::proptest! {
+                #[test]
+                fn test_index_set_proptest_strategy(
+                    set in crate::proptest::index_set::<Set>(500, 16),
+                ) {
+                    prop_assert!(set.len() <= 16);
+                    prop_assert!(set.iter().all(|index| index <= 500));
                 }
+            }
+        }
 
-                btree_set
+        /// Test the quickcheck implementation of index sets.
+        #[test]
+        #[cfg(feature = "quickcheck")]
+        fn test_index_set_quickcheck_arbitrary() {
+            use quickcheck::Arbitrary;
+
+            fn prop_len_matches_iter(set: Set) -> bool {
+                set.len() == set.iter().count()
+            }
+
+            quickcheck::quickcheck(prop_len_matches_iter as fn(Set) -> bool);
+
+            let set: Set = [1, 4, 6, 100].into_iter().collect();
+            for shrunk in set.shrink() {
+                assert!(shrunk.len() <= set.len());
             }
         }
 
-        impl<S: crate::storage::Storage> From<$($Set)*<S>>
-            for alloc::vec::Vec<usize>
-        {
-            #[inline]
-            fn from(index_set: $($Set)*<S>) -> Self {
-                Self::from(&index_set)
+        /// Test index sets against a reference implementation.
+        #[test]
+        #[cfg(feature = "test-util")]
+        fn test_index_set_against_model() {
+            for seed in 0..8 {
+                crate::test_util::check_random_ops::<Set>(seed, 500, 300);
             }
         }
 
-        impl<S: crate::storage::Storage> From<&$($Set)*<S>>
-            for alloc::vec::Vec<usize>
-        {
-            fn from(index_set: &$($Set)*<S>) -> Self {
-                use crate::IndexSet;
+        /// Test mapping the indices of index sets.
+        #[test]
+        fn test_index_set_map_monotonic() {
+            let set: Set = [1, 4, 6, 100, 123].into_iter().collect();
 
-                let mut vec = Self::new();
+            let mapped = set.map_monotonic(|index| index * 3 + 2);
+            let expected: Set = [5, 14, 20, 302, 371].into_iter().collect();
+            assert_eq!(mapped, expected);
 
-                for index in index_set.iter() {
-                    vec.push(index);
-                }
+            let mapped = set.map_monotonic(|index| 200 - index);
+            let expected: Set = [199, 196, 194, 100, 77].into_iter().collect();
+            assert_eq!(mapped, expected);
 
-                vec
+            let mapped = set.map_monotonic(|index| index / 4);
+            let expected: Set = [0, 1, 25, 30].into_iter().collect();
+            assert_eq!(mapped, expected);
+        }
+
+        /// Test extracting sub-ranges of index sets.
+        #[test]
+        fn test_index_set_slice() {
+            let set: Set = [1, 4, 6, 31, 32, 33, 100, 123, 128].into_iter().collect();
+
+            let expected: Set = [4, 6, 31, 32].into_iter().collect();
+            assert_eq!(set.slice(3..=32), expected);
+            assert_eq!(set.slice(3..33), expected);
+
+            let expected: Set = [0, 2, 27, 28].into_iter().collect();
+            assert_eq!(set.slice_rebased(4..33), expected);
+
+            let expected: Set = [0, 1, 68, 91, 96].into_iter().collect();
+            assert_eq!(set.slice_rebased(32..), expected);
+
+            assert_eq!(set.slice(..), set);
+            assert_eq!(set.slice_rebased(..), set);
+            assert!(set.slice(7..31).is_empty());
+            assert!(set.slice(7..7).is_empty());
+            assert!(set.slice_rebased(200..).is_empty());
+        }
+
+        /// Test iterating over the presence of indices in a range.
+        #[test]
+        fn test_index_set_iter_presence() {
+            let set: Set = [1, 4, 6, 31, 32, 33, 100].into_iter().collect();
+
+            let presence: alloc::vec::Vec<_> = set.iter_presence(0..40).collect();
+            assert_eq!(presence.len(), 40);
+            for (index, present) in presence {
+                assert_eq!(present, set.contains(index), "index {index}");
             }
+
+            let expected = [(99, false), (100, true), (101, false)];
+            assert!(set.iter_presence(99..=101).eq(expected));
+            assert!(set.iter_presence(usize::MAX..).eq([(usize::MAX, false)]));
+            assert_eq!(set.iter_presence(7..7).count(), 0);
         }
 
-        impl<I, S> From<I> for $($Set)*<S>
-        where
-            I: IntoIterator<Item = usize>,
-            S: crate::storage::Storage,
-        {
-            #[inline]
-            fn from(iter: I) -> Self {
-                Self::from_iter(iter)
+        /// Test counting the indices in either of two sets, but not both.
+        #[test]
+        fn test_index_set_hamming_distance() {
+            let a: Set = [1, 4, 6, 31, 32, 100, 1000].into_iter().collect();
+            let b: Set = [1, 5, 6, 32, 33, 200, 1000].into_iter().collect();
+
+            assert_eq!(a.hamming_distance(&b), 6);
+            assert_eq!(b.hamming_distance(&a), 6);
+            assert_eq!(a.hamming_distance(&a), 0);
+            assert_eq!(a.hamming_distance(&Set::new()), a.len());
+            assert_eq!(Set::new().hamming_distance(&b), b.len());
+        }
+
+        /// Test set operations between sets of different widths.
+        #[test]
+        fn test_index_set_cross_width_ops() {
+            let a: Set = [1, 4, 6, 31, 32, 100, 1000].into_iter().collect();
+            let b_u8: $($Set)*<u8> = [1, 5, 6, 32, 33, 200, 1000].into_iter().collect();
+            let b_u128: $($Set)*<u128> = b_u8.iter().collect();
+
+            let mut expected = a.clone();
+            expected.extend(b_u8.iter());
+            let mut union = a.clone();
+            union.union_with(&b_u8);
+            assert_eq!(union, expected);
+            let mut union = a.clone();
+            union.union_with(&b_u128);
+            assert_eq!(union, expected);
+
+            let expected: Set = a.iter().filter(|&i| b_u8.contains(i)).collect();
+            let mut intersection = a.clone();
+            intersection.intersect_with(&b_u8);
+            assert_eq!(intersection, expected);
+            let mut intersection = a.clone();
+            intersection.intersect_with(&b_u128);
+            assert_eq!(intersection, expected);
+
+            assert!(a.contents_eq(&a.iter().collect::<$($Set)*<u8>>()));
+            assert!(a.contents_eq(&a.iter().collect::<$($Set)*<u128>>()));
+            assert!(!a.contents_eq(&b_u8));
+            assert!(!a.contents_eq(&b_u128));
+        }
+
+        /// Test conversions from and to dense byte bitmaps,
+        /// in both bit orders.
+        #[test]
+        fn test_index_set_bitmap_bytes() {
+            use crate::bitmap::BitOrder;
+
+            let set: Set = [0, 9, 15].into_iter().collect();
+            assert_eq!(set.to_bitmap_bytes(BitOrder::Lsb0), [0x01, 0x82]);
+            assert_eq!(set.to_bitmap_bytes(BitOrder::Msb0), [0x80, 0x41]);
+            assert_eq!(Set::from_bitmap_bytes(&[0x01, 0x82], BitOrder::Lsb0), set);
+            assert_eq!(Set::from_bitmap_bytes(&[0x80, 0x41], BitOrder::Msb0), set);
+
+            let set: Set = [1, 4, 6, 31, 32, 33, 100, 1000].into_iter().collect();
+            for order in [BitOrder::Lsb0, BitOrder::Msb0] {
+                let bytes = set.to_bitmap_bytes(order);
+                assert_eq!(bytes.len(), 1000 / 8 + 1);
+                assert_eq!(Set::from_bitmap_bytes(&bytes, order), set);
             }
+            assert!(Set::new().to_bitmap_bytes(BitOrder::Msb0).is_empty());
+            assert_eq!(Set::from_bitmap_bytes(&[0; 20], BitOrder::Lsb0), Set::new());
         }
-    };
-}
 
-macro_rules! index_set_tests_for {
-    ($type:ident, $($Set:tt)*) => {
-        #[cfg(test)]
-        mod $type {
-            use crate::IndexSet;
+        /// Test conversions from and to dense boolean vectors.
+        #[test]
+        fn test_index_set_bools() {
+            let indices = [1, 4, 6, 31, 32, 33, 100];
+            let bools: Vec<bool> = (0..128).map(|i| indices.contains(&i)).collect();
+            let set = Set::from_bools(&bools);
+
+            assert_eq!(set, indices.into_iter().collect::<Set>());
+            assert_eq!(set.to_bool_vec(128), bools);
+            assert_eq!(set.to_bool_vec(33), &bools[..33]);
+            assert!(set.to_bool_vec(0).is_empty());
+            assert_eq!(Set::from_bools(&[false; 10]), Set::new());
+        }
 
-            type Set = $($Set)* :: <$type>;
+        /// Test comparing index sets by inclusion.
+        #[test]
+        fn test_index_set_partial_cmp_by_inclusion() {
+            use ::core::cmp::Ordering;
+
+            let set: Set = [1, 4, 6, 100, 1000].into_iter().collect();
+            let subset: Set = [1, 6, 1000].into_iter().collect();
+            let other: Set = [1, 5, 1000].into_iter().collect();
+
+            assert_eq!(set.partial_cmp_by_inclusion(&set), Some(Ordering::Equal));
+            assert_eq!(subset.partial_cmp_by_inclusion(&set), Some(Ordering::Less));
+            assert_eq!(set.partial_cmp_by_inclusion(&subset), Some(Ordering::Greater));
+            assert_eq!(set.partial_cmp_by_inclusion(&other), None);
+            assert_eq!(other.partial_cmp_by_inclusion(&subset), None);
+            assert_eq!(Set::new().partial_cmp_by_inclusion(&set), Some(Ordering::Less));
+            assert_eq!(
+                Set::new().partial_cmp_by_inclusion(&Set::new()),
+                Some(Ordering::Equal)
+            );
+        }
 
-            /// Test index insert ops.
-            #[test]
-            fn test_index_set_insert() {
-                let mut set = Set::new();
-                let mut indices = vec![1, 4, 6, 3, 1, 100, 123, 12, 3];
+        /// Test partitioning index sets by a predicate.
+        #[test]
+        fn test_index_set_partition() {
+            let set: Set = [1, 4, 6, 31, 32, 33, 100, 123, 128].into_iter().collect();
 
-                // insert some elements into the set
-                for i in indices.iter().copied() {
-                    set.insert(i);
-                }
+            let (even, odd) = set.partition(|index| index % 2 == 0);
+            let expected_even: Set = [4, 6, 32, 100, 128].into_iter().collect();
+            let expected_odd: Set = [1, 31, 33, 123].into_iter().collect();
+            assert_eq!(even, expected_even);
+            assert_eq!(odd, expected_odd);
 
-                // check if the set contains the same elements
-                // we inserted, in ascending order
-                indices.sort_unstable();
-                indices.dedup();
+            let (all, none) = set.partition(|_| true);
+            assert_eq!(all, set);
+            assert!(none.is_empty());
+        }
 
-                let set_indices: Vec<_> = set.iter().collect();
-                assert_eq!(indices, set_indices);
+        /// Test retaining the indices that satisfy a predicate.
+        #[test]
+        fn test_index_set_retain() {
+            let indices = [1, 4, 6, 31, 32, 33, 100, 123, 128];
+            let mut set: Set = indices.into_iter().collect();
+
+            let mut visited = alloc::vec::Vec::new();
+            set.retain(|index| {
+                visited.push(index);
+                index > 4 && index != 123
+            });
+            assert_eq!(visited, indices);
+            assert_eq!(set, [6, 31, 32, 33, 100, 128]);
+
+            set.retain(|index| index < 32);
+            assert_eq!(set, [6, 31]);
+            assert!(set.blocks().all(|(_, bit_set)| bit_set != 0));
+
+            set.retain(|_| false);
+            assert!(set.is_empty());
+            assert_eq!(set.blocks().count(), 0);
+        }
 
-                // check that the no. of storage elements used is lower
-                // than the max no. of bitsets we would otherwise need
-                let storage_elements_max =
-                    indices[indices.len() - 1] / <u64 as $crate::storage::Storage>::WIDTH;
-                assert!(set.bit_sets.len() <= storage_elements_max);
+        /// Test the specialized methods of index iterators
+        /// against stepping through the indices one by one.
+        #[test]
+        fn test_index_set_iter_specializations() {
+            let set: Set = [0, 1, 5, 31, 32, 33, 100, 127, 128, 1 << 20]
+                .into_iter()
+                .collect();
+
+            let mut expected = alloc::vec::Vec::new();
+            let mut iter = set.iter();
+            while let Some(index) = iter.next() {
+                expected.push(index);
             }
 
-            /// Test index remove ops.
-            #[test]
-            fn test_index_set_remove() {
-                let mut set = Set::new();
-                let indices = [1, 4, 6, 3, 1, 100, 123, 12, 3];
-                let remove = [100, 6, 100, 12, 123, 3];
+            for skip in 0..=expected.len() {
+                let skipped = || {
+                    let mut iter = set.iter();
+                    if skip > 0 {
+                        assert_eq!(iter.nth(skip - 1), Some(expected[skip - 1]));
+                    }
+                    iter
+                };
+                let rest = &expected[skip..];
+
+                assert_eq!(skipped().count(), rest.len());
+                assert_eq!(skipped().size_hint(), (rest.len(), Some(rest.len())));
+                assert_eq!(skipped().last(), rest.last().copied());
+                assert_eq!(skipped().nth(2), rest.get(2).copied());
+                let folded = skipped().fold(alloc::vec::Vec::new(), |mut acc, index| {
+                    acc.push(index);
+                    acc
+                });
+                assert_eq!(folded, rest);
+            }
 
-                // insert some elements into the set
-                for i in indices.iter().copied() {
-                    set.insert(i);
-                }
+            assert_eq!(Set::new().iter().count(), 0);
+            assert_eq!(Set::new().iter().last(), None);
+            assert_eq!(set.iter().nth(expected.len()), None);
 
-                // remove elements from the set
-                for i in remove.iter().copied() {
-                    set.remove(i);
-                }
+            let mut iter = set.iter();
+            assert_eq!(iter.nth(expected.len() + 1), None);
+            assert_eq!(iter.size_hint(), (0, Some(0)));
+            assert!(iter.next().is_none());
+        }
 
-                let expected: ::std::collections::HashSet<_> = {
-                    let indices: ::std::collections::HashSet<_> = indices.into_iter().collect();
-                    let remove: ::std::collections::HashSet<_> = remove.into_iter().collect();
-                    indices.difference(&remove).copied().collect()
-                };
-                let got: ::std::collections::HashSet<_> = set.iter().collect();
+        /// Test building filtered copies of index sets.
+        #[test]
+        fn test_index_set_filter() {
+            let set: Set = [1, 4, 6, 31, 32, 33, 100, 123, 128].into_iter().collect();
 
-                assert_eq!(expected, got);
-            }
+            let even = set.filter(|index| index % 2 == 0);
+            let expected: Set = [4, 6, 32, 100, 128].into_iter().collect();
+            assert_eq!(even, expected);
+            assert_eq!(set.len(), 9);
 
-            /// Test creating an index from an iterator.
-            #[test]
-            fn test_index_set_from_iter() {
-                let indices = [1, 4, 6, 3, 1, 100, 123, 12, 3];
+            assert_eq!(set.filter(|_| true), set);
+            assert!(set.filter(|index| index > 1000).is_empty());
+        }
 
-                let got: Set = indices.iter().copied().collect();
-                let expected: ::std::collections::BTreeSet<_> = indices.iter().copied().collect();
+        /// Test reconciling index sets which differ
+        /// in a few buckets.
+        #[test]
+        fn test_index_set_sync() {
+            let common = (0..1_000).map(|i| i * 37);
+            let mut a: Set = common.clone().chain([50_000, 1 << 30]).collect();
+            let mut b: Set = common.chain([500, 70_000]).collect();
+            let expected: Set = a.iter().chain(b.iter()).collect();
+
+            let summary = a.sync_summary(crate::sync::DEFAULT_BUCKET_BITS);
+            let delta = b.sync_delta(&summary);
+            assert_eq!(delta.buckets(), [0, 12, 17, 1 << 18]);
+            let reply = a.sync_reply(&delta);
+            a.sync_merge(&delta).unwrap();
+            b.sync_merge(&reply).unwrap();
+            assert_eq!(a, expected);
+            assert_eq!(b, expected);
+
+            let summary = a.sync_summary(crate::sync::DEFAULT_BUCKET_BITS);
+            assert!(b.sync_delta(&summary).is_empty());
+        }
 
-                assert_eq!(expected, got.into());
-            }
+        /// Test iterating over normalized 64-bit blocks of index sets.
+        #[test]
+        fn test_index_set_iter_u64_blocks() {
+            let set: Set = [1, 4, 6, 63, 64, 130, 191, 1000].into_iter().collect();
+
+            let blocks: Vec<_> = set.iter_u64_blocks().collect();
+            assert_eq!(
+                blocks,
+                [
+                    (0, (1 << 1) | (1 << 4) | (1 << 6) | (1 << 63)),
+                    (64, 1),
+                    (128, (1 << 2) | (1 << 63)),
+                    (960, 1 << 40),
+                ]
+            );
+            assert!(Set::new().iter_u64_blocks().next().is_none());
+        }
 
-            /// Test index set length related ops.
-            #[test]
-            fn test_index_set_len_and_is_empty() {
-                let indices_1 = [1, 4, 6, 3];
-                let indices_2 = [2, 100, 123, 12, 5];
+        /// Test checking the presence of many indices at once.
+        #[test]
+        fn test_index_set_contains_all_and_any() {
+            let set: Set = [1, 2, 3, 4, 100, 123].into_iter().collect();
 
-                let mut set = Set::new();
+            assert!(set.contains_all([1, 2, 3, 4, 100]));
+            assert!(set.contains_all([]));
+            assert!(!set.contains_all([1, 2, 5]));
+            assert!(!set.contains_all([200, 1]));
 
-                assert!(set.is_empty());
+            assert!(set.contains_any([5, 6, 123]));
+            assert!(!set.contains_any([5, 6, 7, 200]));
+            assert!(!set.contains_any([]));
+        }
 
-                set.extend(indices_1.iter().copied());
-                assert!(!set.is_empty());
-                assert_eq!(set.len(), indices_1.len());
+        /// Test answering a batch of membership queries.
+        #[test]
+        fn test_index_set_contains_batch() {
+            let set: Set = [1, 2, 3, 4, 100, 123].into_iter().collect();
+
+            assert_eq!(
+                set.contains_batch(&[123, 5, 1, 200, 4, 1, 0]),
+                [true, false, true, false, true, true, false]
+            );
+            assert!(set.contains_batch(&[]).is_empty());
+            assert_eq!(Set::new().contains_batch(&[0, 7]), [false, false]);
+        }
 
-                set.extend(indices_2.iter().copied());
-                assert!(!set.is_empty());
-                assert_eq!(set.len(), indices_1.len() + indices_2.len());
+        /// Test comparing index sets against lists of indices.
+        #[test]
+        fn test_index_set_eq_indices() {
+            let set: Set = [6, 1, 4, 100].into_iter().collect();
+
+            assert_eq!(set, [1, 4, 6, 100]);
+            assert_eq!(set, vec![1, 4, 6, 100]);
+            assert_eq!(set, *[1, 4, 6, 100].as_slice());
+            assert_ne!(set, [6, 1, 4, 100]);
+            assert_ne!(set, [1, 4, 6]);
+            assert_ne!(set, [1, 4, 4, 6, 100]);
+            assert_eq!(Set::new(), []);
+        }
 
-                for item in indices_1.iter().copied() {
-                    set.remove(item);
-                }
-                assert!(!set.is_empty());
-                assert_eq!(set.len(), indices_2.len());
+        /// Test querying the smallest and largest indices.
+        #[test]
+        fn test_index_set_smallest_and_largest() {
+            let set: Set = [1, 4, 6, 63, 64, 130, 1000].into_iter().collect();
+
+            assert!(set.smallest(3).eq([1, 4, 6]));
+            assert!(set.largest(3).eq([1000, 130, 64]));
+            assert_eq!(set.take_smallest(100), [1, 4, 6, 63, 64, 130, 1000]);
+            assert_eq!(set.take_largest(2), [1000, 130]);
+            assert!(set.take_smallest(0).is_empty());
+            assert!(Set::new().largest(5).next().is_none());
+        }
 
-                for item in indices_2.iter().copied() {
-                    set.remove(item);
-                }
-                assert!(set.is_empty());
-                assert_eq!(set.len(), 0);
+        /// Test comparing the indices of index sets.
+        #[test]
+        fn test_index_set_cmp_contents() {
+            use ::core::cmp::Ordering;
+
+            use crate::array::ArrayIndexSet;
+
+            let set: Set = [1, 4, 100].into_iter().collect();
+            let same: ArrayIndexSet<2> = [1, 4, 100].into_iter().collect();
+            let greater: ArrayIndexSet<2> = [1, 5].into_iter().collect();
+            let prefix: ArrayIndexSet<2> = [1, 4].into_iter().collect();
+
+            assert_eq!(set.cmp_contents(&same), Ordering::Equal);
+            assert_eq!(set.cmp_contents(&greater), Ordering::Less);
+            assert_eq!(set.cmp_contents(&prefix), Ordering::Greater);
+            assert_eq!(Set::new().cmp_contents(&prefix), Ordering::Less);
+        }
+
+        /// Test splitting index sets into balanced chunks.
+        #[test]
+        fn test_index_set_partition_chunks() {
+            let width = <$type as $crate::storage::Storage>::WIDTH;
+            let set: Set = (0..1000).chain(5000..5200).collect();
+
+            let chunks = set.partition_chunks(4);
+            assert_eq!(chunks.len(), 4);
+            assert_eq!(chunks[0].start, 0);
+            assert!(chunks.windows(2).all(|pair| pair[0].end == pair[1].start));
+            assert!(chunks[3].end > 5199);
+            for chunk in chunks {
+                let count = set.iter().filter(|index| chunk.contains(index)).count();
+                assert!(count.abs_diff(300) <= width, "{chunk:?} holds {count}");
             }
 
-            /// Test the contains method of index sets.
-            #[test]
-            fn test_index_set_contains() {
-                let indices = [1, 4, 6, 3, 2, 100, 123, 12, 5];
-                let not_in_set = [50, 200, 150];
+            assert_eq!(set.partition_chunks(1).len(), 1);
+            assert!(set.partition_chunks(0).is_empty());
+            assert!(Set::new().partition_chunks(3).is_empty());
+        }
 
-                let set: Set = indices
-                    .iter()
-                    .copied()
-                    .collect();
+        /// Test iterating over chunks of indices.
+        #[test]
+        fn test_index_set_iter_chunks() {
+            let set: Set = [1, 4, 6, 63, 64, 130, 1000].into_iter().collect();
 
-                for index in indices {
-                    assert!(set.contains(index));
-                }
+            assert!(set
+                .iter_chunks(3)
+                .eq([vec![1, 4, 6], vec![63, 64, 130], vec![1000]]));
+            assert!(set.iter_chunks(7).eq([set.iter().collect::<Vec<_>>()]));
+            assert!(Set::new().iter_chunks(2).next().is_none());
+        }
+
+        /// Test that index iterators report their exact
+        /// length, and stay exhausted once done.
+        #[test]
+        fn test_index_set_iter_size_hint_and_fused() {
+            let set: Set = [1, 4, 6, 63, 64, 130, 1000].into_iter().collect();
+            let mut iter = set.iter();
+
+            for remaining in (0..set.len()).rev() {
+                assert_eq!(iter.size_hint(), (remaining + 1, Some(remaining + 1)));
+                assert!(iter.next().is_some());
+            }
+            assert_eq!(iter.size_hint(), (0, Some(0)));
+            assert!(iter.next().is_none());
+            assert!(iter.next().is_none());
+            assert_eq!(Set::new().iter().size_hint(), (0, Some(0)));
+        }
+
+        /// Test looking up the neighbors of indices.
+        #[test]
+        fn test_index_set_successor_and_predecessor() {
+            let set: Set = [1, 4, 6, 63, 64, 1000].into_iter().collect();
+
+            assert_eq!(set.successor(0), Some(1));
+            assert_eq!(set.successor(1), Some(4));
+            assert_eq!(set.successor(6), Some(63));
+            assert_eq!(set.successor(64), Some(1000));
+            assert_eq!(set.successor(1000), None);
+            assert_eq!(set.successor(usize::MAX), None);
+
+            assert_eq!(set.predecessor(1000), Some(64));
+            assert_eq!(set.predecessor(64), Some(63));
+            assert_eq!(set.predecessor(5), Some(4));
+            assert_eq!(set.predecessor(1), None);
+            assert_eq!(set.predecessor(0), None);
+            assert_eq!(set.predecessor(usize::MAX), Some(1000));
+        }
+
+        /// Test finding the closest indices in index sets.
+        #[test]
+        fn test_index_set_nearest() {
+            let set: Set = [4, 6, 63, 1000].into_iter().collect();
+
+            assert_eq!(set.nearest(0), Some(4));
+            assert_eq!(set.nearest(5), Some(4));
+            assert_eq!(set.nearest(6), Some(6));
+            assert_eq!(set.nearest(34), Some(6));
+            assert_eq!(set.nearest(35), Some(63));
+            assert_eq!(set.nearest(usize::MAX), Some(1000));
+            assert_eq!(Set::new().nearest(7), None);
+        }
 
-                for index in not_in_set {
-                    assert!(!set.contains(index));
+        /// Test mutating index sets through a cursor.
+        #[test]
+        fn test_index_set_cursor_mut() {
+            let mut set: Set = (0..20).collect();
+            let mut visited = vec![];
+
+            let mut cursor = set.cursor_mut();
+            assert_eq!(cursor.current(), None);
+            while let Some(index) = cursor.move_next() {
+                visited.push(index);
+                if index % 3 == 0 {
+                    assert_eq!(cursor.remove_current(), Some(index));
+                    assert_eq!(cursor.current(), None);
+                }
+                if index == 10 {
+                    cursor.insert_after(100);
+                    cursor.insert_before(5);
                 }
             }
+            assert_eq!(cursor.current(), None);
+            assert_eq!(cursor.remove_current(), None);
+            cursor.insert_before(200);
 
-            /// Test the union method of index sets.
-            #[test]
-            fn test_index_set_union() {
-                let indices_1 = [1, 4, 6, 3, 2];
-                let indices_2 = [100, 123, 12, 5];
+            let expected: Set = (0..20).filter(|i| i % 3 != 0).chain([100, 200]).collect();
+            assert_eq!(set, expected);
+            assert_eq!(visited, (0..20).chain([100]).collect::<Vec<_>>());
+        }
 
-                let expected = {
-                    let mut set = Set::new();
+        /// Test converting index sets to and from bit sets.
+        #[test]
+        #[cfg(feature = "bit-set")]
+        fn test_index_set_bit_set() {
+            let set: Set = [1, 4, 6, 31, 32, 63, 64, 1000].into_iter().collect();
 
-                    for index in indices_1.iter().copied() {
-                        set.insert(index);
-                    }
-                    for index in indices_2.iter().copied() {
-                        set.insert(index);
-                    }
+            let bit_set = set.to_bit_set();
+            assert!(bit_set.iter().eq(set.iter()));
+            assert_eq!(Set::from_bit_set(&bit_set), set);
 
-                    set
-                };
+            assert!(Set::new().to_bit_set().is_empty());
+            assert_eq!(Set::from_bit_set(&::bit_set::BitSet::new()), Set::new());
+        }
 
-                let mut set: Set = indices_1
-                    .iter()
-                    .copied()
-                    .collect();
-                let other: Set = indices_2
-                    .iter()
-                    .copied()
-                    .collect();
+        /// Test converting index sets to and from hash sets.
+        #[test]
+        #[cfg(feature = "std")]
+        fn test_index_set_hash_set() {
+            use ::std::collections::HashSet;
 
-                set.union(&other);
-                assert_eq!(set, expected);
+            let set: Set = [1, 4, 6, 63, 64, 1000].into_iter().collect();
+
+            let hash_set = HashSet::<usize>::from(&set);
+            assert_eq!(hash_set, HashSet::from([1, 4, 6, 63, 64, 1000]));
+            assert_eq!(Set::from_iter(hash_set.iter().copied()), set);
+            assert_eq!(Set::from(hash_set), set);
+        }
+
+        /// Test the adaptive binary encoding of sparse and dense sets.
+        #[test]
+        fn test_index_set_adaptive_bytes() {
+            use crate::error::Error;
+
+            let sparse: Set = [1, 4, 200].into_iter().collect();
+            let bytes = sparse.to_adaptive_bytes();
+            assert_eq!(bytes, [0x00, 0x03, 0x01, 0x02, 0xc3, 0x01]);
+            assert_eq!(Set::from_adaptive_bytes(&bytes).unwrap(), sparse);
+
+            let dense: Set = (0..60).chain(128..130).collect();
+            let bytes = dense.to_adaptive_bytes();
+            assert_eq!(bytes[..3], [0x01, 0x02, 0x00]);
+            assert_eq!(bytes.len(), 2 * 9 + 2);
+            assert_eq!(Set::from_adaptive_bytes(&bytes).unwrap(), dense);
+
+            assert_eq!(Set::new().to_adaptive_bytes(), [0x00, 0x00]);
+            assert_eq!(Set::from_adaptive_bytes(&[0x00, 0x00]).unwrap(), Set::new());
+
+            let invalid = |bytes: &[u8]| Set::from_adaptive_bytes(bytes).unwrap_err();
+            assert_eq!(invalid(&[]), Error::InvalidEncoding);
+            assert_eq!(invalid(&[0x02, 0x00]), Error::InvalidEncoding);
+            assert_eq!(invalid(&[0x00, 0x01]), Error::InvalidEncoding);
+            assert_eq!(invalid(&[0x00, 0x00, 0x00]), Error::InvalidEncoding);
+            assert_eq!(invalid(&[0x00, 0x01, 0x81, 0x00]), Error::InvalidEncoding);
+            assert_eq!(invalid(&[0x01, 0x01, 0x00, 0, 0, 0, 0, 0, 0, 0, 0]), Error::ZeroBlock);
+            assert_eq!(
+                invalid(&[0x00, 0x02, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x00]),
+                Error::IndexOutOfBounds,
+            );
+        }
+
+        /// Test compacting a set, and translating other sets through it.
+        #[test]
+        fn test_index_set_compact() {
+            let set: Set = [3, 64, 65, 200, 1000].into_iter().collect();
+            let (compaction, dense) = set.compact();
+
+            assert_eq!(dense, [0, 1, 2, 3, 4]);
+            assert_eq!(compaction.len(), 5);
+            for (new_index, old_index) in set.iter().enumerate() {
+                assert_eq!(compaction.new_index(old_index), Some(new_index));
+                assert_eq!(compaction.old_index(new_index), Some(old_index));
             }
+            assert!(compaction.iter().eq(set.iter().zip(0..)));
+            assert_eq!(compaction.new_index(4), None);
+            assert_eq!(compaction.new_index(10_000), None);
+            assert_eq!(compaction.old_index(5), None);
+
+            let other: Set = [1, 65, 1000, 2000].into_iter().collect();
+            assert_eq!(compaction.translate(&other), [2, 4]);
+            let names = [(200, "a"), (7, "b"), (3, "c")];
+            assert!(compaction.translate_map(names).eq([(3, "a"), (0, "c")]));
+
+            let (compaction, dense) = Set::new().compact();
+            assert!(compaction.is_empty() && dense.is_empty());
+        }
 
-            /// Test borsh serialization.
-            #[test]
-            #[cfg(feature = "serialize-borsh")]
-            fn test_index_set_borsh_decode() {
-                use borsh::BorshDeserialize;
+        /// Test converting index sets to and from fixed bit sets.
+        #[test]
+        #[cfg(feature = "fixedbitset")]
+        fn test_index_set_fixedbitset() {
+            use ::fixedbitset::FixedBitSet;
 
-                let one = $type::try_from(1).unwrap();
+            let set: Set = [1, 4, 6, 63, 64, 1000].into_iter().collect();
 
-                let valid = (
-                    4u32,
-                    [
-                        (0usize, one),
-                        (1, one),
-                        (2, one),
-                        (3, one),
-                    ],
-                );
-                let invalid = (
-                    4u32,
-                    [
-                        (0usize, one),
-                        (1, one),
-                        (3, one),
-                        (2, one),
-                    ],
+            let bit_set = set.to_fixedbitset(1001);
+            assert_eq!(bit_set.len(), 1001);
+            assert!(bit_set.ones().eq(set.iter()));
+            assert_eq!(Set::from_fixedbitset(&bit_set), set);
+
+            let truncated = set.to_fixedbitset(64);
+            assert!(truncated.ones().eq([1, 4, 6, 63]));
+            assert_eq!(set.to_fixedbitset(0).ones().next(), None);
+
+            let mut bit_set = FixedBitSet::with_capacity(70);
+            bit_set.insert_range(60..70);
+            let expected: Set = (60..70).collect();
+            assert_eq!(Set::from_fixedbitset(&bit_set), expected);
+        }
+
+        /// Test converting index sets to and from roaring bitmaps.
+        #[test]
+        #[cfg(feature = "roaring")]
+        fn test_index_set_roaring() {
+            use ::roaring::{RoaringBitmap, RoaringTreemap};
+
+            let set: Set = [1, 4, 6, 63, 64, 1000, 1 << 20].into_iter().collect();
+
+            let bitmap = RoaringBitmap::try_from(&set).unwrap();
+            assert!(bitmap.iter().map(|index| index as usize).eq(set.iter()));
+            assert_eq!(Set::from_roaring_bitmap(&bitmap), set);
+
+            let treemap = RoaringTreemap::from(&set);
+            assert!(treemap.iter().map(|index| index as usize).eq(set.iter()));
+            assert_eq!(Set::try_from_roaring_treemap(&treemap).unwrap(), set);
+
+            #[cfg(target_pointer_width = "64")]
+            {
+                let set: Set = [1, 1 << 40].into_iter().collect();
+                assert_eq!(
+                    RoaringBitmap::try_from(&set).unwrap_err(),
+                    crate::error::Error::LimitExceeded
                 );
+                assert_eq!(Set::try_from_roaring_treemap(&RoaringTreemap::from(&set)).unwrap(), set);
+            }
+        }
 
-                let valid = borsh::to_vec(&valid).unwrap();
-                let invalid = borsh::to_vec(&invalid).unwrap();
+        /// Test joining index sets with hierarchical bit sets.
+        #[test]
+        #[cfg(feature = "hibitset")]
+        fn test_index_set_hibitset() {
+            use ::hibitset::{BitSet, BitSetAnd, BitSetLike};
 
-                _ = Set::try_from_slice(&valid).unwrap();
-                _ = Set::try_from_slice(&invalid).unwrap_err();
+            let indices = [1, 4, 6, 63, 64, 1000, 4096, 1 << 18];
+            let set: Set = indices.into_iter().collect();
+            assert!(BitSetLike::iter(&set).map(|index| index as usize).eq(indices));
+
+            let mut other = BitSet::new();
+            for index in [4, 5, 64, 4096, 4097] {
+                other.add(index);
             }
+            assert!(BitSetAnd(&set, &other).iter().eq([4, 64, 4096]));
 
-            /// Test serde serialization.
-            #[test]
-            #[cfg(feature = "serialize-serde")]
-            fn test_index_set_serde_decode() {
-                use std::collections::HashMap;
+            let mut set = set;
+            set.insert(usize::MAX);
+            assert!(BitSetLike::iter(&set).map(|index| index as usize).eq(indices));
+        }
 
-                if !std::any::type_name::<Set>().contains("VecIndexSet") {
-                    // NB: we're only testing VecIndexSet
-                    return;
-                }
+        /// Test shipping index sets in range-restricted pages.
+        #[test]
+        #[cfg(feature = "serialize-borsh")]
+        fn test_index_set_serialize_range() {
+            let set: Set = (0..300).step_by(7).chain([1 << 40]).collect();
+
+            let mut reassembled = Set::new();
+            for start in (0..400).step_by(100) {
+                let mut bytes = alloc::vec::Vec::new();
+                set.serialize_range(&mut bytes, start..start + 100).unwrap();
+                let range = reassembled.deserialize_into_range(&mut &bytes[..]).unwrap();
+                assert_eq!(range, start..=start + 99);
+            }
+            assert_eq!(reassembled, set.slice(..400));
+
+            let mut bytes = alloc::vec::Vec::new();
+            set.serialize_range(&mut bytes, 1 << 40..).unwrap();
+            reassembled.deserialize_into_range(&mut &bytes[..]).unwrap();
+            assert_eq!(reassembled, set);
+
+            let mut bytes = alloc::vec::Vec::new();
+            set.serialize_range(&mut bytes, 5..5).unwrap();
+            let range = reassembled.deserialize_into_range(&mut &bytes[..]).unwrap();
+            assert!(range.is_empty());
+
+            // NB: a page holding indices past its range is rejected
+            let mut bytes = alloc::vec::Vec::new();
+            set.serialize_range(&mut bytes, 7..=14).unwrap();
+            bytes[..8].copy_from_slice(&8u64.to_le_bytes());
+            let mut other = Set::new();
+            assert!(other.deserialize_into_range(&mut &bytes[..]).is_err());
+            assert!(other.is_empty());
+        }
 
-                let one = $type::try_from(1).unwrap();
+        /// Test building index sets from parallel iterators.
+        #[test]
+        #[cfg(feature = "rayon")]
+        fn test_index_set_from_par_iter() {
+            use ::rayon::prelude::*;
 
-                let valid = HashMap::from([(
-                    "bit_sets".to_string(),
-                    [
-                        (0usize, one),
-                        (1, one),
-                        (2, one),
-                        (3, one),
-                    ],
-                )]);
-                let invalid = HashMap::from([(
-                    "bit_sets".to_string(),
-                    [
-                        (0usize, one),
-                        (1, one),
-                        (3, one),
-                        (2, one),
-                    ],
-                )]);
+            let indices: alloc::vec::Vec<usize> = (0..2_000)
+                .map(|i: usize| i.wrapping_mul(7919) % 5_000)
+                .chain([0, 1, 1, 1 << 40])
+                .collect();
 
-                let valid = serde_json::to_string(&valid).unwrap();
-                let invalid = serde_json::to_string(&invalid).unwrap();
+            let expected: Set = indices.iter().copied().collect();
+            let set: Set = indices.par_iter().copied().collect();
+            assert_eq!(set, expected);
 
-                _ = serde_json::from_str::<Set>(&valid).unwrap();
-                _ = serde_json::from_str::<Set>(&invalid).unwrap_err();
-            }
+            let set: Set = alloc::vec::Vec::<usize>::new().into_par_iter().collect();
+            assert!(set.is_empty());
         }
     };
 }
 
+#[cfg(any(feature = "vec", feature = "btree"))]
 macro_rules! index_set_tests {
     ($($Set:tt)*) => {
         index_set_tests_for!(u8, $($Set)*);
@@ -360,8 +3807,53 @@ macro_rules! index_set_tests {
     };
 }
 
-pub(crate) use index_set_impl_extend;
-pub(crate) use index_set_impl_from;
-pub(crate) use index_set_impl_from_iterator;
-pub(crate) use index_set_tests;
-pub(crate) use index_set_tests_for;
+#[cfg(feature = "paged")]
+macro_rules! index_set_core_tests {
+    ($($Set:tt)*) => {
+        index_set_core_tests_for!(u8, $($Set)*);
+        index_set_core_tests_for!(u16, $($Set)*);
+        index_set_core_tests_for!(u32, $($Set)*);
+        index_set_core_tests_for!(u64, $($Set)*);
+        index_set_core_tests_for!(u128, $($Set)*);
+    };
+}
+
+#[cfg(all(test, any(feature = "vec", feature = "btree", feature = "paged")))]
+pub(crate) use index_set_core_test_fns;
+#[cfg(feature = "paged")]
+pub(crate) use {index_set_core_tests, index_set_core_tests_for};
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+pub(crate) use {
+    index_set_impl_adaptive, index_set_impl_bit_set, index_set_impl_bitmap, index_set_impl_bools,
+    index_set_impl_compact, index_set_impl_contains_many, index_set_impl_cross_width,
+    index_set_impl_fixedbitset, index_set_impl_hibitset, index_set_impl_iter_u64_blocks,
+    index_set_impl_map_monotonic, index_set_impl_merkle, index_set_impl_neighbors,
+    index_set_impl_partition, index_set_impl_quickcheck, index_set_impl_range_serialize,
+    index_set_impl_rayon, index_set_impl_roaring, index_set_impl_select, index_set_impl_slice,
+    index_set_impl_sync,
+};
+#[cfg(any(feature = "vec", feature = "btree"))]
+pub(crate) use {
+    index_set_impl_borsh_compat, index_set_impl_cbor, index_set_impl_layout,
+    index_set_impl_lenient, index_set_impl_prost, index_set_impl_reserve, index_set_impl_scale,
+    index_set_impl_stats,
+};
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+pub(crate) use {
+    index_set_impl_extend, index_set_impl_from, index_set_impl_from_iterator,
+    index_set_impl_from_str, index_set_impl_hamming_distance, index_set_impl_inclusion,
+    index_set_impl_index, index_set_impl_partial_eq,
+};
+#[cfg(any(feature = "vec", feature = "btree"))]
+pub(crate) use {index_set_tests, index_set_tests_for};
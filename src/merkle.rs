@@ -0,0 +1,367 @@
+//! Merkle commitments over the bit sets of index sets.
+//!
+//! A [`MerkleTree`] commits to the canonical sequence of bit sets
+//! of an index set, in ascending order of their map indices. Light
+//! clients holding only its [`root`](MerkleTree::root) can then check
+//! [`Proof`]s of the presence or absence of any index in the set.
+//!
+//! The hash function is supplied by implementing [`MerkleHasher`],
+//! such that this crate does not depend on any particular one.
+//!
+//! Proofs may be serialized and sent to light clients. Decoded proofs
+//! are untrusted: [`MerkleTree::verify`] rejects malformed ones.
+
+use core::fmt;
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "serialize-borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serialize-serde")]
+use serde::{Deserialize, Serialize};
+
+use super::calculate_map_and_set_indices;
+use super::storage;
+
+/// Hash function of a [`MerkleTree`].
+///
+/// Implementations should rely on a cryptographic hash function,
+/// and separate the domains of leaves and inner nodes.
+pub trait MerkleHasher {
+    /// The output of the hash function.
+    type Hash: Clone + Eq + fmt::Debug;
+
+    /// Hash the encoding of a leaf of the tree.
+    fn hash_leaf(data: &[u8]) -> Self::Hash;
+
+    /// Hash two adjacent nodes of the tree.
+    fn hash_node(left: &Self::Hash, right: &Self::Hash) -> Self::Hash;
+}
+
+/// Merkle tree over the bit sets of an index set.
+///
+/// The leaves of the tree are the bit sets of the set, in ascending
+/// order of their map indices. Each leaf is encoded as its map index,
+/// as a little endian [`u64`], followed by its bit set, in little
+/// endian order. A node without a sibling is carried up to the next
+/// level as is. The root commits to the number of leaves as well.
+pub struct MerkleTree<S, H: MerkleHasher> {
+    /// The leaves of the tree.
+    leaves: Vec<(usize, S)>,
+    /// The levels of the tree, from the hashes of
+    /// the leaves up to the root of the tree.
+    levels: Vec<Vec<H::Hash>>,
+}
+
+impl<S: storage::Storage + fmt::Debug, H: MerkleHasher> fmt::Debug for MerkleTree<S, H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MerkleTree")
+            .field("leaves", &self.leaves)
+            .field("root", &self.root())
+            .finish()
+    }
+}
+
+/// Proof of the presence or absence of an index
+/// in the set committed to by a [`MerkleTree`].
+///
+/// A proof opens the leaf holding the index, if any. Otherwise,
+/// it opens the leaves right before and after the missing leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize-borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serialize-serde", derive(Serialize, Deserialize))]
+pub struct Proof<S, T> {
+    /// The number of leaves in the tree.
+    num_leaves: usize,
+    /// The opened leaves, in ascending order of their positions.
+    openings: Vec<Opening<S, T>>,
+}
+
+/// Leaf of a [`MerkleTree`], along with the
+/// hashes needed to recompute the root from it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize-borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serialize-serde", derive(Serialize, Deserialize))]
+struct Opening<S, T> {
+    /// The position of the leaf in the tree.
+    position: usize,
+    /// The map index of the bit set in the leaf.
+    map_index: usize,
+    /// The bit set in the leaf.
+    set: S,
+    /// The hashes of the siblings of the nodes on
+    /// the path from the leaf up to the root.
+    siblings: Vec<T>,
+}
+
+impl<S: storage::Storage, H: MerkleHasher> MerkleTree<S, H> {
+    /// Build a [`MerkleTree`] over non-empty bit sets,
+    /// in ascending order of their map indices.
+    #[cfg(any(
+        feature = "vec",
+        feature = "btree",
+        feature = "dense",
+        feature = "paged"
+    ))]
+    pub(crate) fn from_blocks<I>(blocks: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        let leaves: Vec<_> = blocks.into_iter().collect();
+        let mut level: Vec<_> = leaves.iter().map(|&leaf| hash_leaf::<S, H>(leaf)).collect();
+        let mut levels = Vec::new();
+
+        while level.len() > 1 {
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => H::hash_node(left, right),
+                    [node] => node.clone(),
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(core::mem::replace(&mut level, next));
+        }
+        levels.push(level);
+
+        Self { leaves, levels }
+    }
+
+    /// Return the root of this [`MerkleTree`].
+    pub fn root(&self) -> H::Hash {
+        commit_len::<H>(
+            self.leaves.len(),
+            self.levels.last().and_then(|l| l.first()),
+        )
+    }
+
+    /// Prove the presence or absence of `index` in the
+    /// set committed to by this [`MerkleTree`].
+    pub fn prove(&self, index: usize) -> Proof<S, H::Hash> {
+        let (map_index, _) = calculate_map_and_set_indices::<S>(index);
+        let positions = match self.leaves.binary_search_by_key(&map_index, |&(i, _)| i) {
+            Ok(position) => position..position + 1,
+            Err(position) => position.saturating_sub(1)..(position + 1).min(self.leaves.len()),
+        };
+
+        Proof {
+            num_leaves: self.leaves.len(),
+            openings: positions.map(|position| self.open(position)).collect(),
+        }
+    }
+
+    /// Open the leaf at `position`.
+    fn open(&self, position: usize) -> Opening<S, H::Hash> {
+        let (map_index, set) = self.leaves[position];
+        let mut siblings = Vec::new();
+        let mut node = position;
+
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(node ^ 1) {
+                siblings.push(sibling.clone());
+            }
+            node /= 2;
+        }
+
+        Opening {
+            position,
+            map_index,
+            set,
+            siblings,
+        }
+    }
+
+    /// Check the `proof` of the presence, or absence, of `index`
+    /// in the set committed to by `root`.
+    ///
+    /// Returns `true` if the proof is valid, and shows that `index`
+    /// is present in the set if `present` is `true`, or absent from
+    /// the set otherwise. Malformed proofs, such as those decoded
+    /// from corrupted bytes, are rejected.
+    pub fn verify(root: &H::Hash, proof: &Proof<S, H::Hash>, index: usize, present: bool) -> bool {
+        let Some(found) = proof.check::<H>(root, index) else {
+            return false;
+        };
+        found == present
+    }
+}
+
+impl<S: storage::Storage, T: Clone + Eq> Proof<S, T> {
+    /// Check this [`Proof`] against `root`, returning the
+    /// presence of `index` in the set, if the proof is valid.
+    fn check<H>(&self, root: &T, index: usize) -> Option<bool>
+    where
+        H: MerkleHasher<Hash = T>,
+    {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+
+        for opening in &self.openings {
+            if opening.position >= self.num_leaves || opening.set == S::ZERO {
+                return None;
+            }
+            let node = opening.root::<H>(self.num_leaves)?;
+            if commit_len::<H>(self.num_leaves, Some(&node)) != *root {
+                return None;
+            }
+        }
+
+        match self.openings.as_slice() {
+            [] => (self.num_leaves == 0 && commit_len::<H>(0, None) == *root).then_some(false),
+            [leaf] if leaf.map_index == map_index => {
+                Some(leaf.set & S::from_usize(1 << bit_set_index) != S::ZERO)
+            }
+            [leaf] => {
+                let below_first = leaf.position == 0 && map_index < leaf.map_index;
+                let above_last = leaf.position == self.num_leaves - 1 && map_index > leaf.map_index;
+                (below_first || above_last).then_some(false)
+            }
+            [low, high] => (low.position + 1 == high.position
+                && low.map_index < map_index
+                && map_index < high.map_index)
+                .then_some(false),
+            _ => None,
+        }
+    }
+}
+
+impl<S: storage::Storage, T: Clone> Opening<S, T> {
+    /// Recompute the root of a tree with `num_leaves`
+    /// leaves from this [`Opening`].
+    fn root<H>(&self, num_leaves: usize) -> Option<T>
+    where
+        H: MerkleHasher<Hash = T>,
+    {
+        let mut node = hash_leaf::<S, H>((self.map_index, self.set));
+        let mut siblings = self.siblings.iter();
+        let (mut position, mut width) = (self.position, num_leaves);
+
+        while width > 1 {
+            if position % 2 == 1 {
+                node = H::hash_node(siblings.next()?, &node);
+            } else if position + 1 < width {
+                node = H::hash_node(&node, siblings.next()?);
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+
+        siblings.next().is_none().then_some(node)
+    }
+}
+
+/// Hash the leaf holding the bit set `set`, at `map_index`.
+fn hash_leaf<S, H>((map_index, set): (usize, S)) -> H::Hash
+where
+    S: storage::Storage,
+    H: MerkleHasher,
+{
+    let mut data = Vec::with_capacity(8 + core::mem::size_of::<S>());
+    data.extend_from_slice(&(map_index as u64).to_le_bytes());
+    set.extend_le_bytes(&mut data);
+    H::hash_leaf(&data)
+}
+
+/// Commit to the number of leaves of a tree, along with its top node.
+fn commit_len<H: MerkleHasher>(num_leaves: usize, top: Option<&H::Hash>) -> H::Hash {
+    let len = H::hash_leaf(&(num_leaves as u64).to_le_bytes());
+    match top {
+        Some(top) => H::hash_node(&len, top),
+        None => len,
+    }
+}
+
+#[cfg(all(test, feature = "vec"))]
+mod tests {
+    use super::*;
+    use crate::vec::VecIndexSet;
+    use crate::IndexSet;
+
+    /// Non-cryptographic hasher, only fit for tests.
+    struct TestHasher;
+
+    impl MerkleHasher for TestHasher {
+        type Hash = u64;
+
+        fn hash_leaf(data: &[u8]) -> u64 {
+            data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+            })
+        }
+
+        fn hash_node(left: &u64, right: &u64) -> u64 {
+            Self::hash_leaf(&[left.to_le_bytes(), right.to_le_bytes(), [1; 8]].concat())
+        }
+    }
+
+    type Tree = MerkleTree<u16, TestHasher>;
+
+    /// Test proving the presence and absence of indices.
+    #[test]
+    fn test_merkle_tree_proofs() {
+        for len in [0, 1, 2, 5, 8, 13] {
+            let set: VecIndexSet<u16> = (0..len).map(|i| i * 7 + 3).collect();
+            let tree = set.merkle_tree::<TestHasher>();
+            let root = tree.root();
+
+            for index in 0..len * 7 + 10 {
+                let present = set.contains(index);
+                let proof = tree.prove(index);
+                assert!(Tree::verify(&root, &proof, index, present), "{len} {index}");
+                assert!(!Tree::verify(&root, &proof, index, !present));
+                assert!(!Tree::verify(&root.wrapping_add(1), &proof, index, present));
+            }
+        }
+    }
+
+    /// Test rejecting proofs which were tampered with.
+    #[test]
+    fn test_merkle_tree_forged_proofs() {
+        let set: VecIndexSet<u16> = [1, 10, 20, 30, 40].into_iter().collect();
+        let tree = set.merkle_tree::<TestHasher>();
+        let root = tree.root();
+
+        let mut proof = tree.prove(20);
+        proof.openings[0].set = 0b11;
+        assert!(!Tree::verify(&root, &proof, 21, true));
+
+        let mut proof = tree.prove(15);
+        proof.openings.remove(1);
+        assert!(!Tree::verify(&root, &proof, 15, false));
+
+        let mut proof = tree.prove(1);
+        proof.num_leaves = 1;
+        assert!(!Tree::verify(&root, &proof, 1, true));
+
+        let other: VecIndexSet<u16> = [1, 10, 30, 40].into_iter().collect();
+        let proof = other.merkle_tree::<TestHasher>().prove(20);
+        assert!(!Tree::verify(&root, &proof, 20, false));
+    }
+
+    /// Test verifying proofs which were sent as bytes.
+    #[cfg(all(feature = "serialize-borsh", feature = "serialize-serde"))]
+    #[test]
+    fn test_merkle_tree_proof_round_trip() {
+        let set: VecIndexSet<u16> = [1, 10, 20, 30, 40].into_iter().collect();
+        let tree = set.merkle_tree::<TestHasher>();
+        let root = tree.root();
+
+        for (index, present) in [(20, true), (15, false), (50, false)] {
+            let proof = tree.prove(index);
+
+            let bytes = borsh::to_vec(&proof).unwrap();
+            let decoded: Proof<u16, u64> = borsh::from_slice(&bytes).unwrap();
+            assert_eq!(decoded, proof);
+            assert!(Tree::verify(&root, &decoded, index, present));
+
+            let json = serde_json::to_string(&proof).unwrap();
+            let decoded: Proof<u16, u64> = serde_json::from_str(&json).unwrap();
+            assert_eq!(decoded, proof);
+            assert!(Tree::verify(&root, &decoded, index, present));
+
+            let mut corrupted = bytes.clone();
+            *corrupted.last_mut().unwrap() ^= 1;
+            let decoded: Proof<u16, u64> = borsh::from_slice(&corrupted).unwrap();
+            assert!(!Tree::verify(&root, &decoded, index, present));
+        }
+    }
+}
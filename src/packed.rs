@@ -0,0 +1,72 @@
+//! Packed binary serde representation of a [`VecIndexSet`].
+//!
+//! Sets are serialized as a single byte buffer, holding the
+//! encoding of [`VecIndexSet::to_bytes`], rather than as a
+//! sequence of pairs of map indices and bit sets. Use it on
+//! fields with `#[serde(with = "index_set::packed")]`.
+
+use serde::{Deserialize, Deserializer, Serializer};
+use serde_bytes::ByteBuf;
+
+use super::borrowed::VecIndexSetRef;
+use super::storage;
+use super::vec::VecIndexSet;
+
+/// Serialize `set` as a packed byte buffer.
+#[inline]
+pub fn serialize<S, Z>(set: &VecIndexSet<S>, serializer: Z) -> Result<Z::Ok, Z::Error>
+where
+    S: storage::Storage,
+    Z: Serializer,
+{
+    let bytes = set.to_bytes().map_err(serde::ser::Error::custom)?;
+    serializer.serialize_bytes(&bytes)
+}
+
+/// Deserialize a [`VecIndexSet`] from a packed byte buffer.
+pub fn deserialize<'de, S, D>(deserializer: D) -> Result<VecIndexSet<S>, D::Error>
+where
+    S: storage::Storage,
+    D: Deserializer<'de>,
+{
+    let bytes = ByteBuf::deserialize(deserializer)?;
+    VecIndexSetRef::<S>::from_bytes(&bytes)
+        .map(|set| set.to_owned())
+        .map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    /// Index set field using the packed representation.
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Packed {
+        #[serde(with = "crate::packed")]
+        set: VecIndexSet<u32>,
+    }
+
+    /// Test serializing sets as packed byte buffers.
+    #[test]
+    fn test_packed_round_trip() {
+        let packed = Packed {
+            set: [1, 4, 6, 100, 1 << 40].into_iter().collect(),
+        };
+        let bytes = packed.set.to_bytes().unwrap();
+
+        let json = serde_json::to_string(&packed).unwrap();
+        assert_eq!(
+            json,
+            format!("{{\"set\":{}}}", serde_json::to_string(&bytes).unwrap())
+        );
+        assert_eq!(serde_json::from_str::<Packed>(&json).unwrap(), packed);
+
+        let invalid = format!(
+            "{{\"set\":{}}}",
+            serde_json::to_string(&bytes[1..]).unwrap()
+        );
+        assert!(serde_json::from_str::<Packed>(&invalid).is_err());
+    }
+}
@@ -0,0 +1,739 @@
+//! Index set backed by a sparse directory of pages of bit sets.
+
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::ops::RangeInclusive;
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+
+use super::calculate_map_and_set_indices;
+use super::debug_indices;
+use super::iter::Indices;
+use super::macros::*;
+use super::storage;
+use super::IndexSet;
+
+/// Number of bit sets in each page of a [`PagedIndexSet`].
+pub const PAGE_LEN: usize = 512;
+
+/// Number of bits of a page index resolved by each
+/// level of the directory of a [`PagedIndexSet`].
+const DIR_BITS: u32 = 8;
+
+/// Number of children of each inner node of the
+/// directory of a [`PagedIndexSet`].
+const DIR_LEN: usize = 1 << DIR_BITS;
+
+/// Index set backed by a sparse directory of pages of bit sets.
+///
+/// The bit set at map index `M` is stored in the page with key
+/// `M / PAGE_LEN` of the directory, at position `M % PAGE_LEN` of
+/// that page. Pages are only allocated once they hold a non-empty
+/// bit set, and freed once they are left empty.
+///
+/// This backend suits huge index spaces with clustered occupancy,
+/// where a [`DenseIndexSet`](crate::dense::DenseIndexSet) would
+/// be too large, and the lookups of the sparse backends dominate.
+/// The directory is a radix tree of arrays of pages, which is only
+/// as tall as the largest index in the set requires, such that a
+/// bit set is found in a bounded number of steps, regardless of
+/// the number of pages, and inner nodes are only allocated along
+/// the paths to the allocated pages.
+#[derive(Default, Clone, PartialEq, Eq, Hash)]
+pub struct PagedIndexSet<S = u64> {
+    /// Pages of bit sets, containing the actual boolean values to
+    /// be asserted, keyed by their position. Empty pages are never
+    /// stored.
+    ///
+    /// If the bit `B` is set, at the bit vector with index `S`, then
+    /// the index `S::WIDTH * S + B` is in the set.
+    pages: Directory<S>,
+}
+
+/// Radix tree of the pages of a [`PagedIndexSet`],
+/// keyed by their position.
+///
+/// Each inner level resolves [`DIR_BITS`] bits of a page index,
+/// starting from the most significant ones. The positions of the
+/// allocated pages are also kept in ascending order, to walk the
+/// pages in order without visiting the inner nodes.
+struct Directory<S> {
+    /// Root of the tree, if any page is allocated.
+    root: Option<Node<S>>,
+    /// Number of levels of inner nodes above the pages.
+    height: u32,
+    /// Positions of the allocated pages, in ascending order.
+    keys: Vec<usize>,
+}
+
+/// Node of the [`Directory`] of a [`PagedIndexSet`].
+#[derive(Clone)]
+enum Node<S> {
+    /// Inner node, whose children cover consecutive
+    /// ranges of page positions.
+    Inner(Box<[Option<Node<S>>; DIR_LEN]>),
+    /// Page of bit sets.
+    Page(Box<Page<S>>),
+}
+
+impl<S> Node<S> {
+    /// Create a new inner node, whose first child is `first`.
+    #[inline]
+    fn inner(first: Option<Self>) -> Self {
+        let mut children = Box::new([const { None }; DIR_LEN]);
+        children[0] = first;
+        Self::Inner(children)
+    }
+}
+
+impl<S> Directory<S> {
+    /// Create a new, empty [`Directory`].
+    #[inline]
+    const fn new() -> Self {
+        Self {
+            root: None,
+            height: 0,
+            keys: Vec::new(),
+        }
+    }
+
+    /// Return the position of the child holding the page at
+    /// `page_index` in an inner node `level + 1` levels above
+    /// the pages.
+    #[inline]
+    const fn slot(page_index: usize, level: u32) -> usize {
+        (page_index >> (level * DIR_BITS)) & (DIR_LEN - 1)
+    }
+
+    /// Check whether the tree is tall enough to hold
+    /// the page at `page_index`.
+    #[inline]
+    fn covers(&self, page_index: usize) -> bool {
+        page_index
+            .checked_shr(self.height * DIR_BITS)
+            .is_none_or(|rest| rest == 0)
+    }
+
+    /// Return the number of allocated pages.
+    #[inline]
+    fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Check whether no page is allocated.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Free all the pages.
+    #[inline]
+    fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Return the page at `page_index`, if any.
+    fn get(&self, page_index: usize) -> Option<&Page<S>> {
+        if !self.covers(page_index) {
+            return None;
+        }
+        let mut node = self.root.as_ref()?;
+        for level in (0..self.height).rev() {
+            node = match node {
+                Node::Inner(children) => children[Self::slot(page_index, level)].as_ref()?,
+                Node::Page(_) => return None,
+            };
+        }
+        match node {
+            Node::Page(page) => Some(page),
+            Node::Inner(_) => None,
+        }
+    }
+
+    /// Return the page at `page_index` mutably, if any.
+    fn get_mut(&mut self, page_index: usize) -> Option<&mut Page<S>> {
+        if !self.covers(page_index) {
+            return None;
+        }
+        let mut node = self.root.as_mut()?;
+        for level in (0..self.height).rev() {
+            node = match node {
+                Node::Inner(children) => children[Self::slot(page_index, level)].as_mut()?,
+                Node::Page(_) => return None,
+            };
+        }
+        match node {
+            Node::Page(page) => Some(page),
+            Node::Inner(_) => None,
+        }
+    }
+
+    /// Free the page at `page_index`, if any, along with
+    /// the inner nodes left without children.
+    fn remove(&mut self, page_index: usize) {
+        if !self.covers(page_index) {
+            return;
+        }
+        if Self::remove_from(&mut self.root, page_index, self.height) {
+            if let Ok(at) = self.keys.binary_search(&page_index) {
+                self.keys.remove(at);
+            }
+            if self.keys.is_empty() {
+                self.height = 0;
+            }
+        }
+    }
+
+    /// Free the page at `page_index` from the subtree at `node`,
+    /// which lies `level` levels above the pages, along with the
+    /// inner nodes left without children, returning whether the
+    /// page was found.
+    fn remove_from(node: &mut Option<Node<S>>, page_index: usize, level: u32) -> bool {
+        match node {
+            Some(Node::Page(_)) if level == 0 => {
+                *node = None;
+                true
+            }
+            Some(Node::Inner(children)) if level > 0 => {
+                let child = &mut children[Self::slot(page_index, level - 1)];
+                let removed = Self::remove_from(child, page_index, level - 1);
+                if children.iter().all(Option::is_none) {
+                    *node = None;
+                }
+                removed
+            }
+            _ => false,
+        }
+    }
+
+    /// Iterate over the positions and pages whose positions
+    /// lie within `page_range`, in ascending order.
+    fn range(
+        &self,
+        page_range: RangeInclusive<usize>,
+    ) -> impl DoubleEndedIterator<Item = (usize, &Page<S>)> + FusedIterator + Clone + '_ {
+        let start = self.keys.partition_point(|&k| k < *page_range.start());
+        let end = self.keys.partition_point(|&k| k <= *page_range.end());
+        self.keys[start..end.max(start)]
+            .iter()
+            .filter_map(move |&page_index| Some((page_index, self.get(page_index)?)))
+    }
+
+    /// Update each page with `f`, in ascending order of positions,
+    /// freeing those for which it returns `false`.
+    fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &mut Page<S>) -> bool,
+    {
+        let mut keys = core::mem::take(&mut self.keys);
+        keys.retain(|&page_index| {
+            let keep = self
+                .get_mut(page_index)
+                .is_some_and(|page| f(page_index, page));
+            if !keep {
+                Self::remove_from(&mut self.root, page_index, self.height);
+            }
+            keep
+        });
+        if keys.is_empty() {
+            self.height = 0;
+        }
+        self.keys = keys;
+    }
+}
+
+impl<S: storage::Storage> Directory<S> {
+    /// Return the page at `page_index`, allocating it, along
+    /// with the inner nodes leading to it, if missing.
+    fn get_or_insert(&mut self, page_index: usize) -> &mut Page<S> {
+        while !self.covers(page_index) {
+            self.root = self.root.take().map(|root| Node::inner(Some(root)));
+            self.height += 1;
+        }
+
+        let mut node = &mut self.root;
+        for level in (0..self.height).rev() {
+            node = match node.get_or_insert_with(|| Node::inner(None)) {
+                Node::Inner(children) => &mut children[Self::slot(page_index, level)],
+                Node::Page(_) => unreachable!(),
+            };
+        }
+        if node.is_none() {
+            let at = self.keys.partition_point(|&k| k < page_index);
+            self.keys.insert(at, page_index);
+        }
+        match node.get_or_insert_with(|| Node::Page(Page::new())) {
+            Node::Page(page) => page,
+            Node::Inner(_) => unreachable!(),
+        }
+    }
+}
+
+impl<S: Clone> Clone for Directory<S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            height: self.height,
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+impl<S> Default for Directory<S> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: PartialEq> PartialEq for Directory<S> {
+    /// Compare the pages of two [`Directory`] instances,
+    /// regardless of the height of their trees.
+    fn eq(&self, other: &Self) -> bool {
+        self.keys == other.keys
+            && self
+                .range(0..=usize::MAX)
+                .zip(other.range(0..=usize::MAX))
+                .all(|((_, a), (_, b))| a == b)
+    }
+}
+
+impl<S: Eq> Eq for Directory<S> {}
+
+impl<S: Hash> Hash for Directory<S> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.keys.hash(state);
+        for (_, page) in self.range(0..=usize::MAX) {
+            page.hash(state);
+        }
+    }
+}
+
+/// Page of bit sets of a [`PagedIndexSet`].
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Page<S> {
+    /// The bit sets in this page, empty or not.
+    words: [S; PAGE_LEN],
+    /// The number of non-empty bit sets in this page.
+    num_blocks: usize,
+}
+
+impl<S: storage::Storage> Page<S> {
+    /// Create a new, empty [`Page`].
+    #[inline]
+    fn new() -> Box<Self> {
+        Box::new(Self {
+            words: [S::ZERO; PAGE_LEN],
+            num_blocks: 0,
+        })
+    }
+
+    /// Replace the bit set at `slot` with `set`, keeping track
+    /// of the number of non-empty bit sets.
+    #[inline]
+    fn set(&mut self, slot: usize, set: S) {
+        let word = &mut self.words[slot];
+        match (*word == S::ZERO, set == S::ZERO) {
+            (true, false) => self.num_blocks += 1,
+            (false, true) => self.num_blocks -= 1,
+            _ => {}
+        }
+        *word = set;
+    }
+}
+
+impl<S> PagedIndexSet<S> {
+    /// Create a new [`PagedIndexSet`].
+    pub const fn new() -> Self {
+        Self {
+            pages: Directory::new(),
+        }
+    }
+
+    /// Create a new [`PagedIndexSet`] with room for
+    /// at least `capacity` bit sets.
+    ///
+    /// ## Warning
+    ///
+    /// In the current implementation, this method is a stub.
+    /// Pages are allocated on demand, such that it doesn't
+    /// provide any benefit over calling [`PagedIndexSet::new`].
+    #[inline]
+    pub fn with_capacity(_capacity: usize) -> Self {
+        Self::new()
+    }
+
+    /// Return the number of pages allocated by this [`PagedIndexSet`].
+    #[inline]
+    pub fn num_pages(&self) -> usize {
+        self.pages.len()
+    }
+}
+
+impl<S: storage::Storage> PagedIndexSet<S> {
+    /// Return the page and the position within the page
+    /// of the bit set at `map_index`.
+    #[inline]
+    const fn page_and_slot(map_index: usize) -> (usize, usize) {
+        (map_index / PAGE_LEN, map_index % PAGE_LEN)
+    }
+
+    /// Return the page at `page_index`, allocating it if missing.
+    #[inline]
+    fn page_mut(&mut self, page_index: usize) -> &mut Page<S> {
+        self.pages.get_or_insert(page_index)
+    }
+
+    /// Free the page at `page_index` if it is empty.
+    #[inline]
+    fn release_page(&mut self, page_index: usize) {
+        if self
+            .pages
+            .get(page_index)
+            .is_some_and(|page| page.num_blocks == 0)
+        {
+            self.pages.remove(page_index);
+        }
+    }
+
+    /// Return the bit set at `map_index`, if any.
+    #[inline]
+    fn get_block(&self, map_index: usize) -> Option<S> {
+        let (page_index, slot) = Self::page_and_slot(map_index);
+        let set = self.pages.get(page_index)?.words[slot];
+        (set != S::ZERO).then_some(set)
+    }
+
+    /// Assert the invariants of this [`PagedIndexSet`], with the
+    /// `strict-invariants` feature.
+    ///
+    /// This is a no-op in release builds.
+    #[inline]
+    #[track_caller]
+    fn debug_assert_invariants(&self) {
+        debug_assert!(self
+            .pages
+            .range(0..=usize::MAX)
+            .all(|(_, page)| page.num_blocks > 0));
+        super::debug_assert_invariants(self.blocks());
+    }
+
+    /// Iterate over the map indices and non-empty bit sets
+    /// of this [`PagedIndexSet`], in ascending order.
+    #[inline]
+    fn blocks(&self) -> impl DoubleEndedIterator<Item = (usize, S)> + FusedIterator + Clone + '_ {
+        self.blocks_in(0..=usize::MAX)
+    }
+
+    /// Iterate over the map indices and non-empty bit sets of this
+    /// [`PagedIndexSet`] whose map indices lie within `map_range`,
+    /// in ascending order.
+    fn blocks_in(
+        &self,
+        map_range: RangeInclusive<usize>,
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + FusedIterator + Clone + '_ {
+        let (start, end) = (*map_range.start(), *map_range.end());
+        // NB: when `start > end`, the pages are filtered out below
+        self.pages
+            .range(start / PAGE_LEN..=end / PAGE_LEN)
+            .flat_map(|(page_index, page)| {
+                page.words
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &set)| set != S::ZERO)
+                    .map(move |(slot, &set)| (page_index * PAGE_LEN + slot, set))
+            })
+            .filter(move |&(map_index, _)| start <= map_index && map_index <= end)
+    }
+
+    /// Merge non-empty `blocks` into this [`PagedIndexSet`].
+    pub(crate) fn merge_blocks<I>(&mut self, blocks: I)
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        for (map_index, other_set) in blocks {
+            let (page_index, slot) = Self::page_and_slot(map_index);
+            let page = self.page_mut(page_index);
+            let mut set = page.words[slot];
+            set |= other_set;
+            page.set(slot, set);
+        }
+        self.debug_assert_invariants();
+    }
+
+    /// Merge non-empty `blocks` into this [`PagedIndexSet`], as with
+    /// [`Self::merge_blocks`].
+    ///
+    /// This never fails, since the memory used by this set only
+    /// grows with the number of bit sets merged, but matches the
+    /// fallible merge of [`DenseIndexSet`](crate::dense::DenseIndexSet).
+    #[inline]
+    pub(crate) fn try_merge_blocks<I>(&mut self, blocks: I) -> Result<(), super::error::Error>
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        self.merge_blocks(blocks);
+        Ok(())
+    }
+
+    /// Update each non-empty bit set of this [`PagedIndexSet`] with
+    /// `f`, in ascending order of map indices, freeing the pages
+    /// left empty.
+    pub(crate) fn retain_blocks<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &mut S),
+    {
+        self.pages.retain(|page_index, page| {
+            for slot in 0..PAGE_LEN {
+                let mut set = page.words[slot];
+                if set != S::ZERO {
+                    f(page_index * PAGE_LEN + slot, &mut set);
+                    page.set(slot, set);
+                }
+            }
+            page.num_blocks > 0
+        });
+        self.debug_assert_invariants();
+    }
+
+    /// Append a non-empty bit set at `map_index`, which must be
+    /// greater than the map indices of all other bit sets.
+    #[inline]
+    fn push_block(&mut self, (map_index, set): (usize, S)) {
+        debug_assert!(self.blocks().next_back().is_none_or(|(i, _)| i < map_index));
+        debug_assert!(set != S::ZERO);
+        let (page_index, slot) = Self::page_and_slot(map_index);
+        self.page_mut(page_index).set(slot, set);
+    }
+}
+
+impl<S: storage::Storage> fmt::Debug for PagedIndexSet<S> {
+    /// Format this [`PagedIndexSet`].
+    ///
+    /// The alternate form (`{:#?}`) renders the indices stored
+    /// in the set, eliding them past a fixed cap, whereas the
+    /// default form renders the raw non-empty bit sets.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            debug_indices(self, f)
+        } else {
+            f.debug_struct("PagedIndexSet")
+                .field("bit_sets", &self.blocks().collect::<BTreeMap<_, _>>())
+                .finish()
+        }
+    }
+}
+
+impl<S: storage::Storage> IndexSet for PagedIndexSet<S> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.blocks()
+            .map(|(_, set)| set.num_of_high_bits())
+            .sum::<usize>()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.pages.is_empty()
+    }
+
+    fn insert(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let (page_index, slot) = Self::page_and_slot(map_index);
+        let page = self.page_mut(page_index);
+        let mask = S::from_usize(1 << bit_set_index);
+        let mut set = page.words[slot];
+        let added = set & mask == S::ZERO;
+        set |= mask;
+        page.set(slot, set);
+        self.debug_assert_invariants();
+        added
+    }
+
+    fn remove(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let (page_index, slot) = Self::page_and_slot(map_index);
+        let mut removed = false;
+        if let Some(page) = self.pages.get_mut(page_index) {
+            let mask = S::from_usize(1 << bit_set_index);
+            let mut set = page.words[slot];
+            removed = set & mask != S::ZERO;
+            set &= !mask;
+            page.set(slot, set);
+            self.release_page(page_index);
+        }
+        self.debug_assert_invariants();
+        removed
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.pages.clear();
+    }
+
+    #[inline]
+    fn contains(&self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        self.get_block(map_index)
+            .is_some_and(|set| set & S::from_usize(1 << bit_set_index) != S::ZERO)
+    }
+
+    #[inline]
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        Indices::new(self.blocks())
+    }
+
+    #[inline]
+    fn union(&mut self, other: &PagedIndexSet<S>) {
+        self.merge_blocks(other.blocks());
+    }
+
+    #[inline]
+    fn successor(&self, index: usize) -> Option<usize> {
+        self.successor_in_blocks(index)
+    }
+
+    #[inline]
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        self.predecessor_in_blocks(index)
+    }
+}
+
+index_set_impl_adaptive!(crate::paged::PagedIndexSet);
+index_set_impl_bit_set!(crate::paged::PagedIndexSet);
+index_set_impl_bitmap!(crate::paged::PagedIndexSet);
+index_set_impl_bools!(crate::paged::PagedIndexSet);
+index_set_impl_compact!(crate::paged::PagedIndexSet);
+index_set_impl_contains_many!(crate::paged::PagedIndexSet);
+index_set_impl_cross_width!(crate::paged::PagedIndexSet);
+index_set_impl_hamming_distance!(crate::paged::PagedIndexSet);
+index_set_impl_iter_u64_blocks!(crate::paged::PagedIndexSet);
+index_set_impl_map_monotonic!(crate::paged::PagedIndexSet);
+index_set_impl_merkle!(crate::paged::PagedIndexSet);
+index_set_impl_neighbors!(crate::paged::PagedIndexSet);
+index_set_impl_range_serialize!(crate::paged::PagedIndexSet);
+index_set_impl_roaring!(crate::paged::PagedIndexSet);
+index_set_impl_select!(crate::paged::PagedIndexSet);
+index_set_impl_slice!(crate::paged::PagedIndexSet);
+index_set_impl_sync!(crate::paged::PagedIndexSet);
+index_set_impl_partition!(crate::paged::PagedIndexSet);
+index_set_impl_fixedbitset!(crate::paged::PagedIndexSet);
+index_set_impl_from!(crate::paged::PagedIndexSet);
+index_set_impl_from_iterator!(crate::paged::PagedIndexSet);
+index_set_impl_from_str!(crate::paged::PagedIndexSet);
+index_set_impl_hibitset!(crate::paged::PagedIndexSet);
+index_set_impl_inclusion!(crate::paged::PagedIndexSet);
+index_set_impl_index!(crate::paged::PagedIndexSet);
+index_set_impl_partial_eq!(crate::paged::PagedIndexSet);
+index_set_impl_quickcheck!(crate::paged::PagedIndexSet);
+index_set_impl_rayon!(crate::paged::PagedIndexSet);
+index_set_impl_extend!(crate::paged::PagedIndexSet);
+
+index_set_core_tests!(crate::paged::PagedIndexSet);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test inserting and removing clustered indices,
+    /// far apart in a huge index space.
+    #[test]
+    fn test_paged_index_set_insert_remove() {
+        let mut set = PagedIndexSet::<u64>::new();
+        let clusters = [0, 1 << 24, (1 << 28) - 64];
+        for start in clusters {
+            for index in start..start + 100 {
+                set.insert(index);
+            }
+        }
+        assert_eq!(set.len(), 300);
+        assert_eq!(set.num_pages(), 4);
+        assert!(set.contains((1 << 24) + 99) && !set.contains((1 << 24) + 100));
+        assert_eq!(set.successor(99), Some(1 << 24));
+        assert_eq!(set.predecessor(1 << 24), Some(99));
+        assert!(set
+            .iter()
+            .eq(clusters.iter().flat_map(|&start| start..start + 100)));
+
+        for index in (1 << 28) - 64..(1 << 28) + 36 {
+            set.remove(index);
+        }
+        assert_eq!(set.num_pages(), 2);
+
+        for index in (0..100).chain((1 << 24)..(1 << 24) + 100) {
+            set.remove(index);
+        }
+        assert!(set.is_empty());
+        assert_eq!(set, PagedIndexSet::new());
+    }
+
+    /// Test merging and intersecting sets across pages.
+    #[test]
+    fn test_paged_index_set_union_intersection() {
+        let mut a: PagedIndexSet<u8> = [0, 3, 10_000].into_iter().collect();
+        let b: PagedIndexSet<u8> = [1, 3, 5_000].into_iter().collect();
+        a.union(&b);
+        assert_eq!(a, [0, 1, 3, 5_000, 10_000]);
+
+        a.intersect_with(&PagedIndexSet::<u64>::from([0, 3, 4]));
+        assert_eq!(a, [0, 3]);
+        assert_eq!(a.num_pages(), 1);
+    }
+
+    /// Test that the directory only grows with the allocated
+    /// pages, up to the greatest index.
+    #[test]
+    fn test_paged_index_set_huge_indices() {
+        let mut set = PagedIndexSet::<u64>::new();
+        let indices = [3, 1 << 40, usize::MAX - 1, usize::MAX];
+        for index in indices {
+            assert!(set.insert(index));
+        }
+        assert_eq!(set.num_pages(), 3);
+        assert!(set.iter().eq(indices));
+        assert_eq!(set.predecessor(usize::MAX), Some(usize::MAX - 1));
+        assert_eq!(set.successor(3), Some(1 << 40));
+
+        assert!(set.remove(usize::MAX));
+        assert!(set.remove(usize::MAX - 1));
+        assert_eq!(set.num_pages(), 2);
+        assert!(set.iter().eq([3, 1 << 40]));
+    }
+
+    /// Test that the directory only grows as tall as the greatest
+    /// page requires, and frees the inner nodes left empty.
+    #[test]
+    fn test_paged_index_set_directory() {
+        use core::hash::BuildHasher;
+
+        let mut set = PagedIndexSet::<u64>::new();
+        set.insert(1);
+        assert_eq!(set.pages.height, 0);
+
+        // NB: the page of index 1 << 40 is at position 1 << 28,
+        // which takes four levels of eight bits to resolve
+        set.insert(1 << 40);
+        assert_eq!(set.pages.height, 4);
+        assert!(set.contains(1) && set.contains(1 << 40));
+        assert!(set.remove(1 << 40));
+        assert_eq!(set, [1]);
+
+        let state = std::hash::RandomState::new();
+        let other: PagedIndexSet<u64> = [1].into_iter().collect();
+        assert_eq!(set, other);
+        assert_eq!(state.hash_one(&set), state.hash_one(&other));
+
+        let Some(Node::Inner(children)) = &set.pages.root else {
+            panic!("the root should have been an inner node");
+        };
+        assert_eq!(children.iter().filter(|child| child.is_some()).count(), 1);
+
+        assert!(set.remove(1));
+        assert!(set.pages.root.is_none());
+        assert_eq!(set.pages.height, 0);
+    }
+}
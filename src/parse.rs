@@ -0,0 +1,135 @@
+//! Parsing of index sets from their textual range syntax.
+
+use core::fmt;
+use core::ops::Range;
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+use core::ops::RangeInclusive;
+
+/// Error returned when parsing an index set from a string fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseIndexSetError {
+    /// The kind of error that was encountered.
+    kind: ParseIndexSetErrorKind,
+    /// Byte span of the offending token, in the input string.
+    span: Range<usize>,
+}
+
+/// The kind of error encountered while parsing an index set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseIndexSetErrorKind {
+    /// A comma separated token was empty.
+    EmptyToken,
+    /// A token could not be parsed as a [`usize`].
+    InvalidIndex,
+    /// The start of a range was greater than its end.
+    InvalidRange,
+}
+
+impl ParseIndexSetError {
+    /// Return the kind of error that was encountered.
+    #[inline]
+    pub fn kind(&self) -> ParseIndexSetErrorKind {
+        self.kind
+    }
+
+    /// Return the byte span of the offending token,
+    /// in the parsed input string.
+    #[inline]
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+}
+
+impl fmt::Display for ParseIndexSetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let what = match self.kind {
+            ParseIndexSetErrorKind::EmptyToken => "empty token",
+            ParseIndexSetErrorKind::InvalidIndex => "invalid index",
+            ParseIndexSetErrorKind::InvalidRange => "range start is greater than its end",
+        };
+        write!(
+            f,
+            "{what} at byte {}..{} of index set",
+            self.span.start, self.span.end
+        )
+    }
+}
+
+impl core::error::Error for ParseIndexSetError {}
+
+/// Parse a string of comma separated indices and inclusive
+/// ranges, such as `"1,4,6-10,42"`, into ranges of indices.
+///
+/// Whitespace around tokens is ignored, and an empty (or
+/// blank) string yields no ranges.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+pub(crate) fn parse_ranges(
+    s: &str,
+) -> impl Iterator<Item = Result<RangeInclusive<usize>, ParseIndexSetError>> + '_ {
+    let is_blank = s.trim().is_empty();
+    let mut offset = 0;
+
+    s.split(',').filter(move |_| !is_blank).map(move |token| {
+        let token_start = offset;
+        offset += token.len() + 1;
+        parse_token(token, token_start)
+    })
+}
+
+/// Parse a single index or range token, starting at
+/// byte `offset` of the original input.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged",
+    feature = "hashbrown"
+))]
+fn parse_token(token: &str, offset: usize) -> Result<RangeInclusive<usize>, ParseIndexSetError> {
+    let span_of = |sub: &str| {
+        // NB: `sub` is always a subslice of `token`
+        let start = offset + (sub.as_ptr() as usize - token.as_ptr() as usize);
+        start..start + sub.len()
+    };
+    let parse_index = |sub: &str| {
+        let sub = sub.trim();
+        sub.parse::<usize>().map_err(|_| ParseIndexSetError {
+            kind: if sub.is_empty() {
+                ParseIndexSetErrorKind::EmptyToken
+            } else {
+                ParseIndexSetErrorKind::InvalidIndex
+            },
+            span: span_of(sub),
+        })
+    };
+
+    match token.split_once('-') {
+        None => {
+            let index = parse_index(token)?;
+            Ok(index..=index)
+        }
+        Some((start, end)) => {
+            let start_index = parse_index(start)?;
+            let end_index = parse_index(end)?;
+            if start_index > end_index {
+                return Err(ParseIndexSetError {
+                    kind: ParseIndexSetErrorKind::InvalidRange,
+                    span: span_of(token.trim()),
+                });
+            }
+            Ok(start_index..=end_index)
+        }
+    }
+}
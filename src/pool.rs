@@ -0,0 +1,116 @@
+//! Recycling of the allocations of short-lived index sets.
+
+use core::fmt;
+
+use alloc::vec::Vec;
+
+use super::storage;
+use super::vec::VecIndexSet;
+
+/// Pool of empty [`VecIndexSet`]s, which retain the
+/// capacity of the bit sets they held before being
+/// recycled.
+///
+/// Taking sets from a pool, rather than creating new
+/// ones, avoids allocating on every short-lived set.
+#[derive(Clone)]
+pub struct Pool<S = u64> {
+    /// The recycled sets, ready to be taken.
+    sets: Vec<VecIndexSet<S>>,
+    /// The maximum number of sets kept in the pool.
+    max_sets: usize,
+}
+
+impl<S> fmt::Debug for Pool<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("len", &self.sets.len())
+            .field("max_sets", &self.max_sets)
+            .finish()
+    }
+}
+
+impl<S> Default for Pool<S> {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> Pool<S> {
+    /// Create a new, empty [`Pool`], keeping an
+    /// unbounded number of recycled sets.
+    #[inline]
+    pub const fn new() -> Self {
+        Self::with_max_sets(usize::MAX)
+    }
+
+    /// Create a new, empty [`Pool`], keeping at most
+    /// `max_sets` recycled sets. Sets recycled past
+    /// this limit are dropped.
+    #[inline]
+    pub const fn with_max_sets(max_sets: usize) -> Self {
+        Self {
+            sets: Vec::new(),
+            max_sets,
+        }
+    }
+
+    /// Return the number of recycled sets in this [`Pool`].
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Check if this [`Pool`] holds no recycled sets.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+}
+
+impl<S: storage::Storage> Pool<S> {
+    /// Take an empty [`VecIndexSet`] from this [`Pool`], or
+    /// create a new one, if there are no recycled sets.
+    #[inline]
+    pub fn take(&mut self) -> VecIndexSet<S> {
+        self.sets.pop().unwrap_or_else(VecIndexSet::new)
+    }
+
+    /// Clear `set` and return it to this [`Pool`], to be
+    /// taken again along with its allocation.
+    #[inline]
+    pub fn recycle(&mut self, mut set: VecIndexSet<S>) {
+        if self.sets.len() < self.max_sets {
+            set.clear_blocks();
+            self.sets.push(set);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IndexSet;
+
+    /// Test that recycled sets are handed out empty, with their
+    /// capacity, up to the maximum number of sets in the pool.
+    #[test]
+    fn test_pool_recycle() {
+        let mut pool: Pool = Pool::with_max_sets(1);
+        assert!(pool.is_empty());
+
+        let mut set = pool.take();
+        set.extend([1, 64, 600, 1 << 20]);
+        let capacity = set.capacity();
+        pool.recycle(set);
+        pool.recycle([5].into_iter().collect());
+        assert_eq!(pool.len(), 1);
+
+        let set = pool.take();
+        assert!(set.is_empty());
+        assert_eq!(set.capacity(), capacity);
+        assert!(pool.is_empty());
+        assert_eq!(pool.take().capacity(), 0);
+    }
+}
@@ -0,0 +1,41 @@
+//! Commonly used items of this crate, along with
+//! convenience aliases of its index set types.
+//!
+//! ```
+//! # #[cfg(feature = "vec")] {
+//! use index_set::prelude::*;
+//!
+//! let mut set = IndexSet64::new();
+//! set.insert(42);
+//! assert!(set.contains(42));
+//! # }
+//! ```
+
+pub use crate::array::ArrayIndexSet;
+#[cfg(feature = "btree")]
+pub use crate::btree::BTreeIndexSet;
+#[cfg(feature = "dense")]
+pub use crate::dense::DenseIndexSet;
+#[cfg(feature = "hashbrown")]
+pub use crate::hash::HashIndexSet;
+pub use crate::index_set;
+#[cfg(feature = "paged")]
+pub use crate::paged::PagedIndexSet;
+#[cfg(feature = "vec")]
+pub use crate::typed::{VecIndexSet32, VecIndexSet64};
+#[cfg(feature = "vec")]
+pub use crate::vec::VecIndexSet;
+pub use crate::IndexSet;
+
+/// [`VecIndexSet`] storing its bits in [`u64`] integers.
+#[cfg(feature = "vec")]
+pub type IndexSet64 = VecIndexSet<u64>;
+
+/// [`VecIndexSet`] storing its bits in [`u128`] integers.
+#[cfg(feature = "vec")]
+pub type IndexSet128 = VecIndexSet<u128>;
+
+/// [`BTreeIndexSet`] storing its bits in [`u64`] integers,
+/// suited for very sparse sets with frequent insertions.
+#[cfg(feature = "btree")]
+pub type SparseIndexSet = BTreeIndexSet<u64>;
@@ -0,0 +1,64 @@
+//! [`proptest`](::proptest) strategies to generate index sets.
+//!
+//! Generated sets shrink by removing indices, and by
+//! lowering the values of the remaining indices.
+//!
+//! ```
+//! # #[cfg(feature = "vec")] {
+//! use index_set::proptest::vec_index_set;
+//! use index_set::IndexSet;
+//! use proptest::prelude::*;
+//!
+//! proptest! {
+//!     fn bounded(set in vec_index_set::<u64>(1000, 32)) {
+//!         prop_assert!(set.len() <= 32);
+//!         prop_assert!(set.iter().all(|index| index <= 1000));
+//!     }
+//! }
+//! # bounded();
+//! # }
+//! ```
+
+use core::fmt::Debug;
+
+use ::proptest::collection;
+use ::proptest::strategy::Strategy;
+
+#[cfg(feature = "btree")]
+use super::btree::BTreeIndexSet;
+#[cfg(any(feature = "vec", feature = "btree"))]
+use super::storage;
+#[cfg(feature = "vec")]
+use super::vec::VecIndexSet;
+
+/// Strategy generating index sets of type `T`, containing
+/// at most `max_len` indices no greater than `max_index`.
+pub fn index_set<T>(max_index: usize, max_len: usize) -> impl Strategy<Value = T>
+where
+    T: FromIterator<usize> + Debug,
+{
+    collection::vec(0..=max_index, 0..=max_len).prop_map(T::from_iter)
+}
+
+/// Strategy generating [`VecIndexSet`] instances, containing
+/// at most `max_len` indices no greater than `max_index`.
+#[cfg(feature = "vec")]
+pub fn vec_index_set<S>(max_index: usize, max_len: usize) -> impl Strategy<Value = VecIndexSet<S>>
+where
+    S: storage::Storage,
+{
+    index_set(max_index, max_len)
+}
+
+/// Strategy generating [`BTreeIndexSet`] instances, containing
+/// at most `max_len` indices no greater than `max_index`.
+#[cfg(feature = "btree")]
+pub fn btree_index_set<S>(
+    max_index: usize,
+    max_len: usize,
+) -> impl Strategy<Value = BTreeIndexSet<S>>
+where
+    S: storage::Storage,
+{
+    index_set(max_index, max_len)
+}
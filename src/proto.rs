@@ -0,0 +1,90 @@
+//! Protocol buffers representation of index sets.
+//!
+//! An index set is carried as an [`IndexSetProto`] message,
+//! which corresponds to the following definition:
+//!
+//! ```protobuf
+//! message IndexSet {
+//!   // Strictly increasing indices of 64-bit blocks.
+//!   repeated uint64 block_indices = 1;
+//!   // Non-zero 64-bit blocks, such that bit B of the block
+//!   // at block index I marks the presence of index 64 * I + B.
+//!   repeated fixed64 blocks = 2;
+//! }
+//! ```
+//!
+//! The message does not depend on the storage width
+//! or backend of the sets it is converted from.
+
+use alloc::vec::Vec;
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+use super::error::Error;
+
+/// Number of indices in each block of an [`IndexSetProto`].
+#[cfg(any(feature = "vec", feature = "btree"))]
+const BLOCK_BITS: usize = u64::BITS as usize;
+
+/// Protocol buffers message carrying an index set.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IndexSetProto {
+    /// Strictly increasing indices of 64-bit blocks.
+    #[prost(uint64, repeated, tag = "1")]
+    pub block_indices: Vec<u64>,
+    /// Non-zero 64-bit blocks, such that bit `B` of the block at
+    /// block index `I` marks the presence of index `64 * I + B`.
+    #[prost(fixed64, repeated, tag = "2")]
+    pub blocks: Vec<u64>,
+}
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+impl IndexSetProto {
+    /// Build a new [`IndexSetProto`] from pairs of start
+    /// indices and 64-bit blocks, in ascending order.
+    pub(crate) fn from_u64_blocks<I>(blocks: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, u64)>,
+    {
+        let (block_indices, blocks) = blocks
+            .into_iter()
+            .map(|(start_index, block)| ((start_index / BLOCK_BITS) as u64, block))
+            .unzip();
+        Self {
+            block_indices,
+            blocks,
+        }
+    }
+
+    /// Validate this [`IndexSetProto`], and return an iterator
+    /// over the indices it carries, in ascending order.
+    pub(crate) fn indices(&self) -> Result<impl Iterator<Item = usize> + '_, Error> {
+        if self.block_indices.len() != self.blocks.len() {
+            return Err(Error::InvalidEncoding);
+        }
+        if self.block_indices.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(Error::UnsortedBlocks);
+        }
+        if self.blocks.contains(&0) {
+            return Err(Error::ZeroBlock);
+        }
+        let max_block_index = (usize::MAX / BLOCK_BITS) as u64;
+        if self
+            .block_indices
+            .last()
+            .is_some_and(|&block_index| block_index > max_block_index)
+        {
+            return Err(Error::IndexOutOfBounds);
+        }
+
+        Ok(self
+            .block_indices
+            .iter()
+            .zip(&self.blocks)
+            .flat_map(|(&block_index, &block)| {
+                let start_index = block_index as usize * BLOCK_BITS;
+                (0..BLOCK_BITS)
+                    .filter(move |&bit| block & (1 << bit) != 0)
+                    .map(move |bit| start_index + bit)
+            }))
+    }
+}
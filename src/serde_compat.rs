@@ -0,0 +1,56 @@
+//! Compatibility between the serde representations of index sets.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::marker::PhantomData;
+
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+
+/// Serde data of an index set, in one of its accepted shapes.
+pub(crate) enum Shape<R> {
+    /// The internal representation `R` of the index set.
+    Repr(R),
+    /// A plain array of indices.
+    Indices(Vec<usize>),
+}
+
+/// Deserialize the serde data of an index set, whose
+/// internal representation is `R`.
+///
+/// Self-describing, human readable formats may hold either
+/// the internal representation or a plain array of indices,
+/// told apart by the shape of the data. Other formats always
+/// hold the internal representation.
+pub(crate) fn deserialize<'de, D, R>(deserializer: D) -> Result<Shape<R>, D::Error>
+where
+    D: Deserializer<'de>,
+    R: Deserialize<'de>,
+{
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(ShapeVisitor(PhantomData))
+    } else {
+        R::deserialize(deserializer).map(Shape::Repr)
+    }
+}
+
+/// Serde visitor dispatching on the [`Shape`] of index set data.
+struct ShapeVisitor<R>(PhantomData<R>);
+
+impl<'de, R: Deserialize<'de>> Visitor<'de> for ShapeVisitor<R> {
+    type Value = Shape<R>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an index set, or an array of indices")
+    }
+
+    #[inline]
+    fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        Vec::deserialize(SeqAccessDeserializer::new(seq)).map(Shape::Indices)
+    }
+
+    #[inline]
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        R::deserialize(MapAccessDeserializer::new(map)).map(Shape::Repr)
+    }
+}
@@ -0,0 +1,241 @@
+//! Statistics about the occupancy of index sets.
+
+use core::mem::size_of;
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[cfg(any(feature = "vec", feature = "btree"))]
+use super::bit_set_indices;
+use super::storage;
+
+/// Statistics about the bit sets of an index set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of bit sets stored in the index set.
+    pub num_blocks: usize,
+    /// Number of indices stored in the index set.
+    pub len: usize,
+    /// Number of indices each bit set is able to hold.
+    pub block_width: usize,
+    /// The lowest index stored in the index set.
+    pub min_index: Option<usize>,
+    /// The highest index stored in the index set.
+    pub max_index: Option<usize>,
+    /// Density histogram of the bit sets, such that
+    /// `histogram[n]` is the number of bit sets with
+    /// exactly `n` indices stored in them.
+    pub histogram: Vec<usize>,
+}
+
+impl Stats {
+    /// Compute the [`Stats`] of the given bit sets,
+    /// sorted in ascending order of their map index.
+    #[cfg(any(feature = "vec", feature = "btree"))]
+    pub(crate) fn from_blocks<S, I>(blocks: I) -> Self
+    where
+        S: storage::Storage,
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        let mut stats = Self {
+            num_blocks: 0,
+            len: 0,
+            block_width: S::WIDTH,
+            min_index: None,
+            max_index: None,
+            histogram: vec![0; S::WIDTH + 1],
+        };
+
+        for (map_index, set) in blocks {
+            let high_bits = set.num_of_high_bits();
+
+            stats.num_blocks += 1;
+            stats.len += high_bits;
+            stats.histogram[high_bits.min(S::WIDTH)] += 1;
+
+            if stats.min_index.is_none() {
+                stats.min_index = bit_set_indices(set)
+                    .next()
+                    .map(|bit_set_index| map_index * S::WIDTH + bit_set_index);
+            }
+            stats.max_index = bit_set_indices(set)
+                .last()
+                .map(|bit_set_index| map_index * S::WIDTH + bit_set_index)
+                .or(stats.max_index);
+        }
+
+        stats
+    }
+
+    /// Return the average number of indices stored in each bit set.
+    pub fn avg_bits_per_block(&self) -> f64 {
+        if self.num_blocks == 0 {
+            0.0
+        } else {
+            self.len as f64 / self.num_blocks as f64
+        }
+    }
+
+    /// Return the average fraction of each bit set that is
+    /// occupied, between `0.0` and `1.0`.
+    pub fn density(&self) -> f64 {
+        self.avg_bits_per_block() / self.block_width as f64
+    }
+}
+
+/// Storage integer type of the bit sets of an index set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StorageWidth {
+    /// Bit sets stored in [`u8`] integers.
+    U8,
+    /// Bit sets stored in [`u16`] integers.
+    U16,
+    /// Bit sets stored in [`u32`] integers.
+    U32,
+    /// Bit sets stored in [`u64`] integers.
+    U64,
+    /// Bit sets stored in [`u128`] integers.
+    U128,
+}
+
+impl StorageWidth {
+    /// All the available [`StorageWidth`] values.
+    pub const ALL: [Self; 5] = [Self::U8, Self::U16, Self::U32, Self::U64, Self::U128];
+
+    /// Return the [`StorageWidth`] of the storage integer `S`.
+    #[cfg(any(feature = "vec", feature = "btree"))]
+    pub(crate) fn of<S: storage::Storage>() -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|width| width.block_width() == S::WIDTH)
+            .expect("all storage integers have a matching width")
+    }
+
+    /// Return the number of indices each bit set
+    /// is able to hold, with this [`StorageWidth`].
+    pub const fn block_width(self) -> usize {
+        match self {
+            Self::U8 => <u8 as storage::Storage>::WIDTH,
+            Self::U16 => <u16 as storage::Storage>::WIDTH,
+            Self::U32 => <u32 as storage::Storage>::WIDTH,
+            Self::U64 => <u64 as storage::Storage>::WIDTH,
+            Self::U128 => <u128 as storage::Storage>::WIDTH,
+        }
+    }
+
+    /// Return the size, in bytes, of the integer type
+    /// of this [`StorageWidth`].
+    pub const fn size(self) -> usize {
+        match self {
+            Self::U8 => size_of::<u8>(),
+            Self::U16 => size_of::<u16>(),
+            Self::U32 => size_of::<u32>(),
+            Self::U64 => size_of::<u64>(),
+            Self::U128 => size_of::<u128>(),
+        }
+    }
+}
+
+/// Backend of an index set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Backend {
+    /// A [`VecIndexSet`](crate::vec::VecIndexSet).
+    Vec,
+    /// A [`BTreeIndexSet`](crate::btree::BTreeIndexSet).
+    BTree,
+}
+
+impl Backend {
+    /// All the available [`Backend`] values.
+    pub const ALL: [Self; 2] = [Self::Vec, Self::BTree];
+
+    /// Estimate the memory used, in bytes, by `num_blocks` bit sets
+    /// of the given `width`, stored in this [`Backend`].
+    ///
+    /// The estimate of [`Backend::BTree`] only accounts for leaf
+    /// nodes, which are assumed to be about two thirds full.
+    pub const fn estimate_bytes(self, width: StorageWidth, num_blocks: usize) -> usize {
+        /// Maximum number of entries in a node of a [`BTreeMap`](alloc::collections::BTreeMap).
+        const BTREE_NODE_CAPACITY: usize = 11;
+        /// Assumed number of entries in a node of a [`BTreeMap`](alloc::collections::BTreeMap).
+        const BTREE_NODE_ENTRIES: usize = BTREE_NODE_CAPACITY * 2 / 3;
+
+        match self {
+            Self::Vec => num_blocks * (size_of::<usize>() + width.size()),
+            Self::BTree => {
+                let node_size = BTREE_NODE_CAPACITY * (size_of::<usize>() + width.size())
+                    + 2 * size_of::<usize>();
+                num_blocks.div_ceil(BTREE_NODE_ENTRIES) * node_size
+            }
+        }
+    }
+}
+
+/// Recommended storage of an index set, for its observed occupancy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recommendation {
+    /// The recommended storage width.
+    pub width: StorageWidth,
+    /// The recommended backend.
+    pub backend: Backend,
+    /// Estimated memory used by the recommended storage, in bytes.
+    pub estimated_bytes: usize,
+    /// Estimated memory used by the current storage, in bytes.
+    pub current_bytes: usize,
+}
+
+impl Recommendation {
+    /// Compute the most memory efficient storage for the given
+    /// `indices`, sorted in ascending order, currently stored with
+    /// `current_width` in `current_backend`.
+    #[cfg(any(feature = "vec", feature = "btree"))]
+    pub(crate) fn from_indices<I>(
+        indices: I,
+        current_width: StorageWidth,
+        current_backend: Backend,
+    ) -> Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut num_blocks = [0usize; StorageWidth::ALL.len()];
+        let mut last_map_indices = [None; StorageWidth::ALL.len()];
+
+        for index in indices {
+            for (i, width) in StorageWidth::ALL.into_iter().enumerate() {
+                let map_index = Some(index / width.block_width());
+                if last_map_indices[i] != map_index {
+                    last_map_indices[i] = map_index;
+                    num_blocks[i] += 1;
+                }
+            }
+        }
+
+        let num_blocks_of = |width: StorageWidth| {
+            let i = StorageWidth::ALL.iter().position(|&w| w == width).unwrap();
+            num_blocks[i]
+        };
+        let current_bytes =
+            current_backend.estimate_bytes(current_width, num_blocks_of(current_width));
+
+        let mut recommendation = Self {
+            width: current_width,
+            backend: current_backend,
+            estimated_bytes: current_bytes,
+            current_bytes,
+        };
+
+        for backend in Backend::ALL {
+            for width in StorageWidth::ALL {
+                let estimated_bytes = backend.estimate_bytes(width, num_blocks_of(width));
+                if estimated_bytes < recommendation.estimated_bytes {
+                    recommendation.width = width;
+                    recommendation.backend = backend;
+                    recommendation.estimated_bytes = estimated_bytes;
+                }
+            }
+        }
+
+        recommendation
+    }
+}
@@ -1,5 +1,6 @@
 //! Storage integers for a [`super::IndexSet`].
 
+use core::fmt::Debug;
 use core::hash::Hash;
 use core::ops::{BitAnd, BitAndAssign, BitOrAssign, Not};
 
@@ -13,10 +14,35 @@ macro_rules! impl_storage_for {
                 x as $primitive
             }
 
+            #[inline(always)]
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+
             #[inline(always)]
             fn num_of_high_bits(self) -> usize {
                 self.count_ones() as usize
             }
+
+            #[inline(always)]
+            fn trailing_zeros(self) -> usize {
+                <$primitive>::trailing_zeros(self) as usize
+            }
+
+            #[inline(always)]
+            fn without_lowest_bit(self) -> $primitive {
+                self & self.wrapping_sub(1)
+            }
+
+            #[inline(always)]
+            fn from_le_slice(bytes: &[u8]) -> $primitive {
+                <$primitive>::from_le_bytes(bytes.try_into().unwrap())
+            }
+
+            #[inline(always)]
+            fn extend_le_bytes(self, bytes: &mut alloc::vec::Vec<u8>) {
+                bytes.extend_from_slice(&self.to_le_bytes());
+            }
         }
     };
 }
@@ -32,6 +58,7 @@ impl_storage_for!(u128);
 /// Any primitive unsigned integer type will do.
 pub trait Storage:
     Sized
+    + Debug
     + Hash
     + PartialOrd
     + Ord
@@ -53,6 +80,23 @@ pub trait Storage:
     /// Convert a [`usize`] to a value of [`Self`].
     fn from_usize(x: usize) -> Self;
 
+    /// Convert a value of [`Self`] to a [`u64`], truncating
+    /// any bits that do not fit.
+    fn to_u64(self) -> u64;
+
     /// Count the number of bits set in [`Self`].
     fn num_of_high_bits(self) -> usize;
+
+    /// Count the number of trailing zero bits in [`Self`].
+    fn trailing_zeros(self) -> usize;
+
+    /// Clear the lowest bit set in [`Self`], if any.
+    fn without_lowest_bit(self) -> Self;
+
+    /// Decode a value of [`Self`] from its little endian bytes,
+    /// of which there must be exactly [`Storage::WIDTH`].
+    fn from_le_slice(bytes: &[u8]) -> Self;
+
+    /// Append the little endian bytes of [`Self`] to `bytes`.
+    fn extend_le_bytes(self, bytes: &mut alloc::vec::Vec<u8>);
 }
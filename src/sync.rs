@@ -0,0 +1,334 @@
+//! Reconciliation of index sets held by distinct nodes.
+//!
+//! Two nodes holding large, mostly identical sets converge to
+//! the union of both sets by exchanging data proportional to
+//! their difference, rather than full serializations:
+//!
+//! 1. The first node sends a [`Summary`] of its set, made of
+//!    fingerprints of the indices within fixed size buckets.
+//! 2. The second node compares the summary against its own set,
+//!    and replies with a [`Delta`] listing the buckets which
+//!    differ, along with its own indices within them.
+//! 3. The first node merges the delta into its set, and replies
+//!    with its own indices within the listed buckets.
+//! 4. The second node merges that last delta into its set.
+//!
+//! ```
+//! # #[cfg(feature = "vec")] {
+//! use index_set::vec::VecIndexSet;
+//! use index_set::IndexSet;
+//!
+//! let mut a: VecIndexSet = (0..10_000).chain([20_000]).collect();
+//! let mut b: VecIndexSet = (0..10_000).chain([30_000]).collect();
+//!
+//! let summary = a.sync_summary(index_set::sync::DEFAULT_BUCKET_BITS);
+//! let delta = b.sync_delta(&summary);
+//! let reply = a.sync_reply(&delta);
+//! a.sync_merge(&delta).unwrap();
+//! b.sync_merge(&reply).unwrap();
+//!
+//! assert_eq!(a, b);
+//! assert!(a.contains(20_000) && a.contains(30_000));
+//! # }
+//! ```
+//!
+//! Fingerprints are not cryptographic hashes, hence this protocol
+//! should only run between nodes which trust each other.
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "serialize-borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+#[cfg(feature = "serialize-serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+use super::error::Error;
+
+/// Default base 2 logarithm of the number of indices in each
+/// bucket of a [`Summary`], such that buckets span 4096 indices.
+pub const DEFAULT_BUCKET_BITS: u32 = 12;
+
+/// Lowest base 2 logarithm of the number of indices in each
+/// bucket, such that a 64-bit lane of indices never spans
+/// several buckets.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+const MIN_BUCKET_BITS: u32 = u64::BITS.trailing_zeros();
+
+/// Fingerprints of the indices of a set, grouped in buckets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize-borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serialize-serde", derive(Serialize, Deserialize))]
+pub struct Summary {
+    /// Base 2 logarithm of the number of indices in each bucket.
+    bucket_bits: u32,
+    /// Bucket numbers and fingerprints of the non-empty
+    /// buckets, in ascending order of bucket numbers.
+    fingerprints: Vec<(usize, u64)>,
+}
+
+impl Summary {
+    /// Compute the [`Summary`] of the given 64-bit lanes of
+    /// indices, as yielded by `iter_u64_blocks`.
+    #[cfg(any(
+        feature = "vec",
+        feature = "btree",
+        feature = "dense",
+        feature = "paged"
+    ))]
+    pub(crate) fn from_lanes<I>(bucket_bits: u32, lanes: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, u64)>,
+    {
+        let bucket_bits = bucket_bits.clamp(MIN_BUCKET_BITS, usize::BITS - 1);
+        let mut fingerprints: Vec<(usize, u64)> = Vec::new();
+
+        for (start_index, lane) in lanes {
+            let bucket = start_index >> bucket_bits;
+            let hash = mix(mix(start_index as u64) ^ lane);
+            match fingerprints.last_mut() {
+                Some((last, fingerprint)) if *last == bucket => {
+                    *fingerprint = mix(*fingerprint ^ hash);
+                }
+                _ => fingerprints.push((bucket, hash)),
+            }
+        }
+
+        Self {
+            bucket_bits,
+            fingerprints,
+        }
+    }
+
+    /// Return the base 2 logarithm of the number
+    /// of indices in each bucket of this [`Summary`].
+    #[inline]
+    pub fn bucket_bits(&self) -> u32 {
+        self.bucket_bits
+    }
+
+    /// Return the number of non-empty buckets in this [`Summary`].
+    #[inline]
+    pub fn num_buckets(&self) -> usize {
+        self.fingerprints.len()
+    }
+
+    /// Return the bucket numbers whose fingerprints differ between
+    /// this [`Summary`] and `other`, in ascending order.
+    #[cfg(any(
+        feature = "vec",
+        feature = "btree",
+        feature = "dense",
+        feature = "paged"
+    ))]
+    pub(crate) fn differing_buckets(&self, other: &Self) -> Vec<usize> {
+        let mut ours = self.fingerprints.iter().peekable();
+        let mut theirs = other.fingerprints.iter().peekable();
+        let mut buckets = Vec::new();
+
+        loop {
+            let bucket = match (ours.peek(), theirs.peek()) {
+                (None, None) => return buckets,
+                (Some(&&(a, _)), None) => {
+                    ours.next();
+                    a
+                }
+                (None, Some(&&(b, _))) => {
+                    theirs.next();
+                    b
+                }
+                (Some(&&(a, x)), Some(&&(b, y))) => {
+                    if a <= b {
+                        ours.next();
+                    }
+                    if b <= a {
+                        theirs.next();
+                    }
+                    if a == b && x == y {
+                        continue;
+                    }
+                    a.min(b)
+                }
+            };
+            buckets.push(bucket);
+        }
+    }
+}
+
+/// Indices of a set within the buckets where it
+/// differs from the set of another node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serialize-borsh", derive(BorshSerialize, BorshDeserialize))]
+#[cfg_attr(feature = "serialize-serde", derive(Serialize, Deserialize))]
+pub struct Delta {
+    /// Base 2 logarithm of the number of indices in each bucket.
+    bucket_bits: u32,
+    /// Bucket numbers of the differing buckets, in ascending order.
+    buckets: Vec<usize>,
+    /// The 64-bit lanes of indices within the differing buckets,
+    /// in ascending order of their start indices.
+    lanes: Vec<(usize, u64)>,
+}
+
+impl Delta {
+    /// Build a [`Delta`] out of the given 64-bit lanes of indices,
+    /// as yielded by `iter_u64_blocks`, which lie within `buckets`.
+    #[cfg(any(
+        feature = "vec",
+        feature = "btree",
+        feature = "dense",
+        feature = "paged"
+    ))]
+    pub(crate) fn from_lanes<I>(bucket_bits: u32, mut buckets: Vec<usize>, lanes: I) -> Self
+    where
+        I: IntoIterator<Item = (usize, u64)>,
+    {
+        let bucket_bits = bucket_bits.clamp(MIN_BUCKET_BITS, usize::BITS - 1);
+        buckets.sort_unstable();
+        buckets.dedup();
+
+        let lanes = if buckets.is_empty() {
+            Vec::new()
+        } else {
+            lanes
+                .into_iter()
+                .filter(|&(start_index, _)| {
+                    buckets.binary_search(&(start_index >> bucket_bits)).is_ok()
+                })
+                .collect()
+        };
+
+        Self {
+            bucket_bits,
+            buckets,
+            lanes,
+        }
+    }
+
+    /// Return the base 2 logarithm of the number
+    /// of indices in each bucket of this [`Delta`].
+    #[inline]
+    pub fn bucket_bits(&self) -> u32 {
+        self.bucket_bits
+    }
+
+    /// Return the bucket numbers listed in this [`Delta`],
+    /// in ascending order.
+    #[inline]
+    pub fn buckets(&self) -> &[usize] {
+        &self.buckets
+    }
+
+    /// Check if this [`Delta`] lists no differing buckets,
+    /// in which case both sets already hold the same indices.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+
+    /// Return the validated 64-bit lanes of indices of this [`Delta`].
+    #[cfg(any(
+        feature = "vec",
+        feature = "btree",
+        feature = "dense",
+        feature = "paged"
+    ))]
+    pub(crate) fn lanes(&self) -> Result<&[(usize, u64)], Error> {
+        if !(MIN_BUCKET_BITS..usize::BITS).contains(&self.bucket_bits) {
+            return Err(Error::InvalidEncoding);
+        }
+        if self.buckets.windows(2).any(|w| w[0] >= w[1])
+            || self.lanes.windows(2).any(|w| w[0].0 >= w[1].0)
+        {
+            return Err(Error::UnsortedBlocks);
+        }
+        for &(start_index, lane) in &self.lanes {
+            if lane == 0 {
+                return Err(Error::ZeroBlock);
+            }
+            if start_index % u64::BITS as usize != 0
+                || self
+                    .buckets
+                    .binary_search(&(start_index >> self.bucket_bits))
+                    .is_err()
+            {
+                return Err(Error::InvalidEncoding);
+            }
+        }
+        Ok(&self.lanes)
+    }
+}
+
+/// Mix the bits of `z`, with the finalizer of SplitMix64.
+#[cfg(any(
+    feature = "vec",
+    feature = "btree",
+    feature = "dense",
+    feature = "paged"
+))]
+#[inline]
+const fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(all(test, feature = "vec"))]
+mod tests {
+    use super::*;
+    use crate::vec::VecIndexSet;
+
+    /// Test rejecting malformed deltas.
+    #[test]
+    fn test_sync_merge_invalid() {
+        let mut set: VecIndexSet = [1, 2, 3].into_iter().collect();
+        let delta = |bucket_bits, buckets: &[usize], lanes: &[(usize, u64)]| Delta {
+            bucket_bits,
+            buckets: buckets.to_vec(),
+            lanes: lanes.to_vec(),
+        };
+
+        let cases = [
+            (delta(3, &[0], &[(0, 1)]), Error::InvalidEncoding),
+            (delta(12, &[1, 0], &[(0, 1)]), Error::UnsortedBlocks),
+            (delta(12, &[0], &[(64, 1), (0, 1)]), Error::UnsortedBlocks),
+            (delta(12, &[0], &[(0, 0)]), Error::ZeroBlock),
+            (delta(12, &[0], &[(1, 1)]), Error::InvalidEncoding),
+            (delta(12, &[0], &[(4096, 1)]), Error::InvalidEncoding),
+        ];
+        for (delta, error) in cases {
+            assert_eq!(set.sync_merge(&delta), Err(error));
+        }
+        assert_eq!(set, [1, 2, 3]);
+
+        set.sync_merge(&delta(12, &[0, 1], &[(4096, 0b101)]))
+            .unwrap();
+        assert_eq!(set, [1, 2, 3, 4096, 4098]);
+    }
+
+    /// Test rejecting deltas too large to merge into a dense set.
+    #[test]
+    #[cfg(feature = "dense")]
+    fn test_sync_merge_dense_huge_index() {
+        let mut set: crate::dense::DenseIndexSet = [1, 2, 3].into_iter().collect();
+        let start_index = 1 << 62;
+        let delta = Delta {
+            bucket_bits: 12,
+            buckets: alloc::vec![start_index >> 12],
+            lanes: alloc::vec![(start_index, 1)],
+        };
+
+        assert_eq!(set.sync_merge(&delta), Err(Error::AllocError));
+        assert_eq!(set, [1, 2, 3]);
+    }
+}
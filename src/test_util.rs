@@ -0,0 +1,135 @@
+//! Utilities to test [`IndexSet`] implementations.
+//!
+//! The helpers in this module apply sequences of operations
+//! to an [`IndexSet`] implementation and to a reference
+//! [`BTreeSet`], asserting that both always agree on the
+//! contents of the set.
+
+use alloc::collections::BTreeSet;
+use alloc::vec::Vec;
+
+use super::IndexSet;
+
+/// Operation applied to an [`IndexSet`] under test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// Insert an index.
+    Insert(usize),
+    /// Remove an index.
+    Remove(usize),
+    /// Check the presence of an index.
+    Contains(usize),
+    /// Merge the set with another set, built
+    /// from the given indices.
+    Union(Vec<usize>),
+    /// Reserve space for additional elements.
+    Reserve(usize),
+}
+
+/// Generate `count` pseudo-random operations, from the given
+/// `seed`, over indices no greater than `max_index`.
+///
+/// The same seed always yields the same sequence of operations.
+pub fn random_ops(seed: u64, count: usize, max_index: usize) -> Vec<Op> {
+    let mut rng = SplitMix64(seed);
+    let random_index =
+        |rng: &mut SplitMix64| (rng.next() % (max_index as u64).saturating_add(1)) as usize;
+
+    (0..count)
+        .map(|_| match rng.next() % 16 {
+            0..=6 => Op::Insert(random_index(&mut rng)),
+            7..=11 => Op::Remove(random_index(&mut rng)),
+            12..=13 => Op::Contains(random_index(&mut rng)),
+            14 => {
+                let len = rng.next() % 8;
+                Op::Union((0..len).map(|_| random_index(&mut rng)).collect())
+            }
+            _ => Op::Reserve((rng.next() % 8) as usize),
+        })
+        .collect()
+}
+
+/// Apply `ops` to a new set of type `T` and to a reference
+/// [`BTreeSet`], asserting their equivalence after each
+/// operation.
+///
+/// ## Panics
+///
+/// Panics with a description of the failing operation
+/// if the set under test diverges from the reference.
+pub fn check_ops<T>(ops: &[Op])
+where
+    T: IndexSet + Default + FromIterator<usize>,
+{
+    let mut set = T::default();
+    let mut model = BTreeSet::new();
+
+    for (step, op) in ops.iter().enumerate() {
+        match op {
+            &Op::Insert(index) => {
+                set.insert(index);
+                model.insert(index);
+            }
+            &Op::Remove(index) => {
+                set.remove(index);
+                model.remove(&index);
+            }
+            &Op::Contains(index) => {
+                assert_eq!(
+                    set.contains(index),
+                    model.contains(&index),
+                    "step {step}: {op:?} diverged from the model"
+                );
+            }
+            Op::Union(indices) => {
+                let other: T = indices.iter().copied().collect();
+                set.union(&other);
+                model.extend(indices.iter().copied());
+            }
+            &Op::Reserve(size) => {
+                set.reserve(size);
+            }
+        }
+
+        assert_eq!(
+            set.len(),
+            model.len(),
+            "step {step}: length diverged from the model after {op:?}"
+        );
+        assert_eq!(
+            set.is_empty(),
+            model.is_empty(),
+            "step {step}: emptiness diverged from the model after {op:?}"
+        );
+    }
+
+    assert!(
+        set.iter().eq(model.iter().copied()),
+        "contents diverged from the model"
+    );
+}
+
+/// Apply `count` pseudo-random operations, generated from
+/// `seed`, to a set of type `T` and to a reference [`BTreeSet`].
+///
+/// See [`random_ops`] and [`check_ops`].
+pub fn check_random_ops<T>(seed: u64, count: usize, max_index: usize)
+where
+    T: IndexSet + Default + FromIterator<usize>,
+{
+    check_ops::<T>(&random_ops(seed, count, max_index));
+}
+
+/// Minimal pseudo-random number generator, to avoid
+/// pulling in extra dependencies.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,707 @@
+//! Index sets over fixed width index types.
+//!
+//! The index sets in the rest of this crate store [`usize`] values,
+//! whose width depends on the target. A [`TypedIndexSet`] stores
+//! values of a fixed width integer type instead, such that its
+//! memory usage and wire encoding are the same on all targets.
+
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::Hash;
+
+use alloc::vec::Vec;
+#[cfg(feature = "serialize-borsh")]
+use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
+#[cfg(feature = "serialize-serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::bit_set_indices;
+use super::error::Error;
+use super::storage;
+use super::DEBUG_INDICES_CAP;
+
+mod sealed {
+    /// Prevent downstream implementations of [`super::IndexType`].
+    pub trait Sealed {}
+}
+
+macro_rules! impl_index_type_for {
+    ($primitive:ty) => {
+        impl sealed::Sealed for $primitive {}
+
+        impl IndexType for $primitive {
+            const MAX: $primitive = <$primitive>::MAX;
+
+            #[inline(always)]
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+
+            #[inline(always)]
+            fn from_u64(x: u64) -> $primitive {
+                x as $primitive
+            }
+        }
+    };
+}
+
+impl_index_type_for!(u32);
+impl_index_type_for!(u64);
+
+/// The integer type of the values stored in a [`TypedIndexSet`].
+///
+/// This trait is sealed, and implemented for [`u32`] and [`u64`].
+pub trait IndexType: sealed::Sealed + fmt::Debug + Hash + Ord + Copy {
+    /// The greatest value of this [`IndexType`].
+    const MAX: Self;
+
+    /// Convert a value of [`Self`] to a [`u64`].
+    fn to_u64(self) -> u64;
+
+    /// Convert a [`u64`] to a value of [`Self`], which
+    /// must fit in this [`IndexType`].
+    fn from_u64(x: u64) -> Self;
+}
+
+/// Check the pairs of indices and bit vectors of a decoded
+/// [`TypedIndexSet`], as with the checked constructors of the
+/// [`usize`] index sets.
+///
+/// The indices must be strictly increasing, such that all the values
+/// of their bit vectors fit in an `I`, and no bit vector may be empty
+/// or have bits set past its width.
+fn check_bit_sets<I, S>(bit_sets: &[(I, S)]) -> Result<(), Error>
+where
+    I: IndexType,
+    S: storage::Storage,
+{
+    let max_map_index = I::MAX.to_u64() / S::WIDTH as u64;
+    let width_mask = crate::bit_range_mask::<S>(0, S::WIDTH - 1);
+
+    for (i, &(map_index, set)) in bit_sets.iter().enumerate() {
+        match i
+            .checked_sub(1)
+            .map(|last| bit_sets[last].0.cmp(&map_index))
+        {
+            Some(Ordering::Greater) => return Err(Error::UnsortedBlocks),
+            Some(Ordering::Equal) => return Err(Error::DuplicateBlock),
+            _ => {}
+        }
+        if set == S::ZERO {
+            return Err(Error::ZeroBlock);
+        }
+        if set & !width_mask != S::ZERO {
+            return Err(Error::InvalidBits);
+        }
+        if map_index.to_u64() > max_map_index {
+            return Err(Error::IndexOutOfBounds);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "serialize-serde")]
+mod serde_deserialize {
+    use alloc::vec::Vec;
+
+    use serde::{Deserialize, Deserializer};
+
+    use super::{storage, IndexType};
+
+    /// Deserialize a [`TypedIndexSet`](super::TypedIndexSet) from serde data.
+    pub fn from<'de, D, I, S>(deserializer: D) -> Result<Vec<(I, S)>, D::Error>
+    where
+        D: Deserializer<'de>,
+        I: Deserialize<'de> + IndexType,
+        S: Deserialize<'de> + storage::Storage,
+    {
+        let bit_sets: Vec<(I, S)> = Deserialize::deserialize(deserializer)?;
+        super::check_bit_sets(&bit_sets).map_err(serde::de::Error::custom)?;
+        Ok(bit_sets)
+    }
+}
+
+#[cfg(feature = "serialize-borsh")]
+mod borsh_deserialize {
+    use alloc::vec::Vec;
+
+    use super::{storage, IndexType};
+
+    /// Deserialize a [`TypedIndexSet`](super::TypedIndexSet) from borsh data.
+    pub fn from<R, I, S>(reader: &mut R) -> Result<Vec<(I, S)>, borsh::io::Error>
+    where
+        R: borsh::io::Read,
+        I: borsh::de::BorshDeserialize + IndexType,
+        S: borsh::de::BorshDeserialize + storage::Storage,
+    {
+        let bit_sets: Vec<(I, S)> = borsh::BorshDeserialize::deserialize_reader(reader)?;
+        super::check_bit_sets(&bit_sets)?;
+        Ok(bit_sets)
+    }
+}
+
+#[cfg(any(feature = "serialize-borsh", feature = "serialize-serde"))]
+mod repr {
+    use alloc::vec::Vec;
+    #[cfg(feature = "serialize-borsh")]
+    use alloc::{format, string::ToString};
+
+    #[cfg(feature = "serialize-borsh")]
+    use borsh::{BorshDeserialize, BorshSchema};
+    #[cfg(feature = "serialize-serde")]
+    use serde::Deserialize;
+
+    /// Serialized representation of a [`TypedIndexSet`](super::TypedIndexSet),
+    /// as pairs of map indices and bit sets.
+    #[cfg_attr(feature = "serialize-borsh", derive(BorshDeserialize, BorshSchema))]
+    #[cfg_attr(feature = "serialize-serde", derive(Deserialize))]
+    pub struct TypedIndexSet<I, S> {
+        /// Pairs of indices to bit vectors, sorted by index.
+        #[cfg_attr(
+            feature = "serialize-borsh",
+            borsh(
+                deserialize_with = "super::borsh_deserialize::from",
+                bound(deserialize = "I: BorshDeserialize + super::IndexType, \
+                                   S: BorshDeserialize + crate::storage::Storage")
+            )
+        )]
+        #[cfg_attr(
+            feature = "serialize-serde",
+            serde(deserialize_with = "super::serde_deserialize::from")
+        )]
+        #[cfg_attr(
+            feature = "serialize-serde",
+            serde(bound(deserialize = "I: Deserialize<'de> + super::IndexType, \
+                               S: Deserialize<'de> + crate::storage::Storage"))
+        )]
+        pub bit_sets: Vec<(I, S)>,
+    }
+
+    impl<I, S> From<TypedIndexSet<I, S>> for super::TypedIndexSet<I, S> {
+        #[inline]
+        fn from(repr: TypedIndexSet<I, S>) -> Self {
+            let (keys, words) = repr.bit_sets.into_iter().unzip();
+            Self { keys, words }
+        }
+    }
+}
+
+/// Index set of `I` values, backed by a [`Vec`].
+///
+/// Unlike a [`VecIndexSet`](crate::vec::VecIndexSet), the indices
+/// of the bit vectors of this set are stored as `I` values, rather
+/// than as [`usize`] values. This set does not implement
+/// [`IndexSet`](crate::IndexSet), whose methods take and return
+/// [`usize`] values, but provides the same operations over `I`.
+#[derive(Default, Clone, PartialEq, Eq, Hash)]
+pub struct TypedIndexSet<I, S = u64> {
+    /// Indices of the bit vectors in `words`, sorted
+    /// in ascending order.
+    keys: Vec<I>,
+    /// Bit vectors containing the actual boolean values
+    /// to be asserted.
+    ///
+    /// If the bit `B` is set, at the bit vector `words[K]`, then
+    /// the value `S::WIDTH * keys[K] + B` is in the set.
+    words: Vec<S>,
+}
+
+/// [`TypedIndexSet`] of [`u32`] values.
+pub type VecIndexSet32<S = u64> = TypedIndexSet<u32, S>;
+
+/// [`TypedIndexSet`] of [`u64`] values.
+///
+/// Unlike a [`VecIndexSet`](crate::vec::VecIndexSet), this set can
+/// store values above [`u32::MAX`] on 32-bit targets, such as `wasm32`.
+/// Its borsh encoding matches that of a [`VecIndexSet`](crate::vec::VecIndexSet)
+/// with the same storage type, on 64-bit targets.
+pub type VecIndexSet64<S = u64> = TypedIndexSet<u64, S>;
+
+/// Pairs of indices to bit vectors of a [`TypedIndexSet`],
+/// in the order they are rendered and serialized.
+struct BitSets<'a, I, S>(&'a TypedIndexSet<I, S>);
+
+impl<I: fmt::Debug, S: fmt::Debug> fmt::Debug for BitSets<'_, I, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.keys.iter().zip(&self.0.words))
+            .finish()
+    }
+}
+
+#[cfg(feature = "serialize-serde")]
+impl<I: Serialize, S: Serialize> Serialize for BitSets<'_, I, S> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        serializer.collect_seq(self.0.keys.iter().zip(&self.0.words))
+    }
+}
+
+/// Split `index` into the index of its bit vector,
+/// and the position of its bit in that bit vector.
+#[inline]
+fn calculate_map_and_set_indices<I, S>(index: I) -> (I, usize)
+where
+    I: IndexType,
+    S: storage::Storage,
+{
+    let index = index.to_u64();
+    let width = S::WIDTH as u64;
+
+    (I::from_u64(index / width), (index % width) as usize)
+}
+
+impl<I, S> TypedIndexSet<I, S> {
+    /// Create a new [`TypedIndexSet`].
+    pub const fn new() -> Self {
+        Self {
+            keys: Vec::new(),
+            words: Vec::new(),
+        }
+    }
+
+    /// Create a new [`TypedIndexSet`] with the given capacity,
+    /// in bit vectors.
+    #[inline]
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            keys: Vec::with_capacity(capacity),
+            words: Vec::with_capacity(capacity),
+        }
+    }
+}
+
+impl<I: IndexType, S: storage::Storage> TypedIndexSet<I, S> {
+    /// Return the number of values present
+    /// in this [`TypedIndexSet`].
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|set| set.num_of_high_bits()).sum()
+    }
+
+    /// Checks if this [`TypedIndexSet`] has no values
+    /// stored within.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Add a new value to this [`TypedIndexSet`], returning
+    /// whether it was not present in the set yet.
+    pub fn insert(&mut self, index: I) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<I, S>(index);
+        let block_index = self.keys.binary_search(&map_index).unwrap_or_else(|at| {
+            self.keys.insert(at, map_index);
+            self.words.insert(at, S::ZERO);
+            at
+        });
+        let mask = S::from_usize(1 << bit_set_index);
+        let set = &mut self.words[block_index];
+        let added = *set & mask == S::ZERO;
+        *set |= mask;
+        added
+    }
+
+    /// Remove a value from this [`TypedIndexSet`], returning
+    /// whether it was present in the set.
+    pub fn remove(&mut self, index: I) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<I, S>(index);
+        let Ok(block_index) = self.keys.binary_search(&map_index) else {
+            return false;
+        };
+        let mask = S::from_usize(1 << bit_set_index);
+        let set = &mut self.words[block_index];
+        let removed = *set & mask != S::ZERO;
+        *set &= !mask;
+        if *set == S::ZERO {
+            self.keys.remove(block_index);
+            self.words.remove(block_index);
+        }
+        removed
+    }
+
+    /// Check the presence of a value in this [`TypedIndexSet`].
+    pub fn contains(&self, index: I) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<I, S>(index);
+        self.keys
+            .binary_search(&map_index)
+            .is_ok_and(|block_index| {
+                self.words[block_index] & S::from_usize(1 << bit_set_index) != S::ZERO
+            })
+    }
+
+    /// Return an iterator over the values in
+    /// this [`TypedIndexSet`], in ascending order.
+    ///
+    /// The values of each bit vector fit in an `I`, since decoded
+    /// sets are checked, hence computing them can't overflow.
+    pub fn iter(&self) -> impl Iterator<Item = I> + '_ {
+        self.keys
+            .iter()
+            .zip(&self.words)
+            .flat_map(|(&map_index, &set)| {
+                let start = map_index.to_u64() * S::WIDTH as u64;
+                bit_set_indices(set)
+                    .map(move |bit_set_index| I::from_u64(start + bit_set_index as u64))
+            })
+    }
+
+    /// Merge two [`TypedIndexSet`] instances, in a single pass.
+    pub fn union(&mut self, other: &Self) {
+        let old_keys = core::mem::take(&mut self.keys);
+        let old_words = core::mem::take(&mut self.words);
+        let mut old_blocks = old_keys.into_iter().zip(old_words).peekable();
+        let mut other_blocks = other
+            .keys
+            .iter()
+            .copied()
+            .zip(other.words.iter().copied())
+            .peekable();
+
+        let capacity = old_blocks.len() + other_blocks.len();
+        self.keys.reserve(capacity);
+        self.words.reserve(capacity);
+
+        loop {
+            let (map_index, set) = match (old_blocks.peek(), other_blocks.peek()) {
+                (Some(&(a, mut set_a)), Some(&(b, set_b))) => match a.cmp(&b) {
+                    Ordering::Less => old_blocks.next().unwrap(),
+                    Ordering::Greater => other_blocks.next().unwrap(),
+                    Ordering::Equal => {
+                        old_blocks.next();
+                        other_blocks.next();
+                        set_a |= set_b;
+                        (a, set_a)
+                    }
+                },
+                (Some(_), None) => old_blocks.next().unwrap(),
+                (None, Some(_)) => other_blocks.next().unwrap(),
+                (None, None) => break,
+            };
+            self.keys.push(map_index);
+            self.words.push(set);
+        }
+    }
+}
+
+impl<S: storage::Storage> VecIndexSet64<S> {
+    /// Encode this [`VecIndexSet64`] into the binary representation
+    /// of [`VecIndexSet::to_bytes`](crate::vec::VecIndexSet::to_bytes),
+    /// which is the same on all targets.
+    ///
+    /// Returns [`Error::LimitExceeded`] if this set holds
+    /// more than [`u32::MAX`] bit vectors.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let num_of_blocks = u32::try_from(self.keys.len()).map_err(|_| Error::LimitExceeded)?;
+        let mut bytes = Vec::with_capacity(4 + self.keys.len() * (8 + S::WIDTH));
+
+        bytes.extend_from_slice(&num_of_blocks.to_le_bytes());
+        for (&map_index, &set) in self.keys.iter().zip(&self.words) {
+            bytes.extend_from_slice(&map_index.to_le_bytes());
+            set.extend_le_bytes(&mut bytes);
+        }
+
+        Ok(bytes)
+    }
+
+    /// Decode a [`VecIndexSet64`] from the binary representation
+    /// of [`VecIndexSet64::to_bytes`], checking its bit vectors
+    /// as with its borsh decoding.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (header, encoded_blocks) = bytes
+            .split_first_chunk::<4>()
+            .ok_or(Error::InvalidEncoding)?;
+        let pair_size = 8 + S::WIDTH;
+        let num_of_blocks = u32::from_le_bytes(*header) as usize;
+        if num_of_blocks.checked_mul(pair_size) != Some(encoded_blocks.len()) {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let bit_sets: Vec<(u64, S)> = encoded_blocks
+            .chunks_exact(pair_size)
+            .map(|pair| {
+                let (key, set) = pair.split_at(8);
+                (
+                    u64::from_le_bytes(key.try_into().unwrap()),
+                    S::from_le_slice(set),
+                )
+            })
+            .collect();
+        check_bit_sets(&bit_sets)?;
+
+        let (keys, words) = bit_sets.into_iter().unzip();
+        Ok(Self { keys, words })
+    }
+}
+
+impl<I: IndexType, S: storage::Storage> fmt::Debug for TypedIndexSet<I, S> {
+    /// Format this [`TypedIndexSet`].
+    ///
+    /// The alternate form (`{:#?}`) renders the values stored
+    /// in the set, eliding them past a fixed cap, whereas the
+    /// default form renders the raw bit sets.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            let mut debug_set = f.debug_set();
+            let mut indices = self.iter();
+
+            debug_set.entries(indices.by_ref().take(DEBUG_INDICES_CAP));
+
+            if indices.next().is_some() {
+                debug_set.finish_non_exhaustive()
+            } else {
+                debug_set.finish()
+            }
+        } else {
+            f.debug_struct("TypedIndexSet")
+                .field("bit_sets", &BitSets(self))
+                .finish()
+        }
+    }
+}
+
+impl<I: IndexType, S: storage::Storage> FromIterator<I> for TypedIndexSet<I, S> {
+    #[inline]
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = I>,
+    {
+        let mut set = Self::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl<I: IndexType, S: storage::Storage> Extend<I> for TypedIndexSet<I, S> {
+    #[inline]
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = I>,
+    {
+        for index in iter {
+            self.insert(index);
+        }
+    }
+}
+
+#[cfg(feature = "serialize-serde")]
+impl<I: Serialize, S: Serialize> Serialize for TypedIndexSet<I, S> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("TypedIndexSet", 1)?;
+        state.serialize_field("bit_sets", &BitSets(self))?;
+        state.end()
+    }
+}
+
+#[cfg(feature = "serialize-serde")]
+impl<'de, I, S> Deserialize<'de> for TypedIndexSet<I, S>
+where
+    I: Deserialize<'de> + IndexType,
+    S: Deserialize<'de> + storage::Storage,
+{
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <repr::TypedIndexSet<I, S> as Deserialize>::deserialize(deserializer).map(Self::from)
+    }
+}
+
+#[cfg(feature = "serialize-borsh")]
+impl<I: BorshSerialize, S: BorshSerialize> BorshSerialize for TypedIndexSet<I, S> {
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        let len = u32::try_from(self.keys.len()).map_err(|_| Error::LimitExceeded)?;
+        BorshSerialize::serialize(&len, writer)?;
+        for (map_index, set) in self.keys.iter().zip(&self.words) {
+            BorshSerialize::serialize(map_index, writer)?;
+            BorshSerialize::serialize(set, writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serialize-borsh")]
+impl<I, S> BorshDeserialize for TypedIndexSet<I, S>
+where
+    I: BorshDeserialize + IndexType,
+    S: BorshDeserialize + storage::Storage,
+{
+    #[inline]
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        <repr::TypedIndexSet<I, S> as BorshDeserialize>::deserialize_reader(reader).map(Self::from)
+    }
+}
+
+#[cfg(feature = "serialize-borsh")]
+impl<I: BorshSchema, S: BorshSchema> BorshSchema for TypedIndexSet<I, S> {
+    #[inline]
+    fn add_definitions_recursively(
+        definitions: &mut alloc::collections::BTreeMap<
+            borsh::schema::Declaration,
+            borsh::schema::Definition,
+        >,
+    ) {
+        repr::TypedIndexSet::<I, S>::add_definitions_recursively(definitions)
+    }
+
+    #[inline]
+    fn declaration() -> borsh::schema::Declaration {
+        repr::TypedIndexSet::<I, S>::declaration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test basic operations on typed index sets.
+    #[test]
+    fn test_typed_index_set_ops() {
+        let mut set: VecIndexSet32 = [1, 4, 6, u32::MAX, 1000].into_iter().collect();
+
+        assert!(set.remove(6));
+        assert!(!set.remove(7));
+        assert!(set.insert(3));
+        assert!(!set.insert(3));
+
+        assert!(set.contains(u32::MAX) && set.contains(3) && !set.contains(6));
+        assert!(set.iter().eq([1, 3, 4, 1000, u32::MAX]));
+        assert_eq!(set.len(), 5);
+
+        let mut other: VecIndexSet32<u8> = [2, 3, 5000].into_iter().collect();
+        other.union(&[1, 5000, 6000].into_iter().collect());
+        assert!(other.iter().eq([1, 2, 3, 5000, 6000]));
+        assert!(VecIndexSet32::<u16>::new().is_empty());
+    }
+
+    /// Test typed index sets of values wider than 32 bits.
+    #[test]
+    fn test_typed_index_set_u64() {
+        let mut set: VecIndexSet64<u8> = [1 << 40, u64::MAX, 3].into_iter().collect();
+        set.union(&[u64::MAX - 1, 1 << 40].into_iter().collect());
+
+        assert!(set.contains(1 << 40) && !set.contains(1 << 41));
+        assert!(set.iter().eq([3, 1 << 40, u64::MAX - 1, u64::MAX]));
+    }
+
+    /// Test rejecting decoded sets of u64 values whose
+    /// bit vectors hold values past [`u64::MAX`].
+    #[test]
+    #[cfg(feature = "serialize-borsh")]
+    fn test_typed_index_set_u64_borsh_bounds() {
+        let decode = |pairs: Vec<(u64, u64)>| {
+            borsh::from_slice::<VecIndexSet64>(&borsh::to_vec(&pairs).unwrap())
+        };
+
+        let error = decode(vec![(u64::MAX, 1)]).unwrap_err();
+        assert_eq!(error.to_string(), Error::IndexOutOfBounds.to_string());
+        assert!(decode(vec![(u64::MAX / 8 + 1, 1)]).is_err());
+
+        let set = decode(vec![(u64::MAX / 8, 1 << 7)]).unwrap();
+        assert!(set.iter().eq([u64::MAX]));
+    }
+
+    /// Test encoding sets of u64 values into the binary
+    /// representation of sets of usize values.
+    #[test]
+    fn test_typed_index_set_u64_bytes() {
+        let set: VecIndexSet64 = [1, 100, 1 << 40, u64::MAX].into_iter().collect();
+        let bytes = set.to_bytes().unwrap();
+        assert_eq!(VecIndexSet64::from_bytes(&bytes).unwrap(), set);
+
+        #[cfg(target_pointer_width = "64")]
+        {
+            let expected: crate::vec::VecIndexSet =
+                set.iter().map(|index| index as usize).collect();
+            assert_eq!(bytes, expected.to_bytes().unwrap());
+        }
+
+        let encode = |pairs: &[(u64, u64)]| {
+            let mut bytes = (pairs.len() as u32).to_le_bytes().to_vec();
+            for (key, set) in pairs {
+                bytes.extend_from_slice(&key.to_le_bytes());
+                bytes.extend_from_slice(&set.to_le_bytes());
+            }
+            bytes
+        };
+        let cases: [(&[u8], _); 6] = [
+            (&[], Error::InvalidEncoding),
+            (&bytes[..bytes.len() - 1], Error::InvalidEncoding),
+            (&encode(&[(2, 1), (1, 1)]), Error::UnsortedBlocks),
+            (&encode(&[(1, 1), (1, 1)]), Error::DuplicateBlock),
+            (&encode(&[(1, 0)]), Error::ZeroBlock),
+            (&encode(&[(u64::MAX / 8 + 1, 1)]), Error::IndexOutOfBounds),
+        ];
+        for (bytes, error) in cases {
+            assert_eq!(VecIndexSet64::<u64>::from_bytes(bytes), Err(error));
+        }
+        assert_eq!(
+            VecIndexSet64::<u64>::from_bytes(&encode(&[(1, 1 << 8)])),
+            Err(Error::InvalidBits)
+        );
+    }
+
+    /// Test that sets of u64 values are encoded like
+    /// sets of usize values.
+    #[test]
+    #[cfg(all(feature = "serialize-borsh", target_pointer_width = "64"))]
+    fn test_typed_index_set_u64_borsh_encoding() {
+        let set: VecIndexSet64 = [1, 64, 1 << 40].into_iter().collect();
+        let expected: crate::vec::VecIndexSet = [1, 64, 1 << 40].into_iter().collect();
+
+        assert_eq!(
+            borsh::to_vec(&set).unwrap(),
+            borsh::to_vec(&expected).unwrap()
+        );
+    }
+
+    /// Test that the keys of typed index sets are
+    /// encoded with their own width.
+    #[test]
+    #[cfg(feature = "serialize-borsh")]
+    fn test_typed_index_set_borsh_encoding() {
+        let set: VecIndexSet32 = [1, 64, 200].into_iter().collect();
+        let pairs: Vec<(u32, u64)> = vec![(0, 1 << 1), (8, 1), (25, 1)];
+
+        let encoded = borsh::to_vec(&set).unwrap();
+        assert_eq!(encoded, borsh::to_vec(&pairs).unwrap());
+        assert_eq!(borsh::from_slice::<VecIndexSet32>(&encoded).unwrap(), set);
+
+        let error = |pairs: Vec<(u32, u64)>| {
+            let bytes = borsh::to_vec(&pairs).unwrap();
+            borsh::from_slice::<VecIndexSet32>(&bytes)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(vec![(8, 1), (0, 1)]),
+            Error::UnsortedBlocks.to_string()
+        );
+        assert_eq!(error(vec![(0, 0)]), Error::ZeroBlock.to_string());
+        assert_eq!(error(vec![(0, 1 << 8)]), Error::InvalidBits.to_string());
+        assert_eq!(
+            error(vec![(u32::MAX, 1)]),
+            Error::IndexOutOfBounds.to_string()
+        );
+
+        let last: Vec<(u32, u64)> = vec![(u32::MAX / 8, 1 << 7)];
+        let set = borsh::from_slice::<VecIndexSet32>(&borsh::to_vec(&last).unwrap()).unwrap();
+        assert!(set.iter().eq([u32::MAX]));
+    }
+
+    /// Test rejecting malformed serde encodings of typed index sets.
+    #[test]
+    #[cfg(feature = "serialize-serde")]
+    fn test_typed_index_set_serde_invalid() {
+        let set: VecIndexSet32 = [1, u32::MAX].into_iter().collect();
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(serde_json::from_str::<VecIndexSet32>(&json).unwrap(), set);
+
+        for json in [
+            r#"{"bit_sets":[[0,0]]}"#,
+            r#"{"bit_sets":[[4294967295,1]]}"#,
+            r#"{"bit_sets":[[1,1],[0,1]]}"#,
+        ] {
+            assert!(serde_json::from_str::<VecIndexSet32>(json).is_err());
+        }
+    }
+}
@@ -1,24 +1,46 @@
 //! Index set backed by a [`Vec`].
 
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::ops::RangeInclusive;
+
+use alloc::boxed::Box;
+use alloc::collections::TryReserveError;
 use alloc::vec::Vec;
 #[cfg(feature = "serialize-borsh")]
-use alloc::{format, string::ToString};
-#[cfg(feature = "serialize-borsh")]
 use borsh::{BorshDeserialize, BorshSchema, BorshSerialize};
 #[cfg(feature = "serialize-serde")]
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use super::bit_range_mask;
+use super::bit_set_indices;
 use super::calculate_map_and_set_indices;
+use super::debug_indices;
+use super::error::{Error, FromBlocksError};
+use super::flip_bits;
+use super::iter::{Drain, Indices};
 use super::macros::*;
+use super::max_map_index;
+#[cfg(feature = "serialize-serde")]
+use super::serde_compat::Shape;
 use super::storage;
 use super::IndexSet;
 
+/// Upper bound on the number of bit sets to preallocate room
+/// for when streaming a set in, regardless of its header.
+#[cfg(any(feature = "async-io", feature = "embedded-io"))]
+const MAX_PREALLOCATED_BLOCKS: usize = 1024;
+
 #[cfg(feature = "serialize-serde")]
 mod serde_deserialize {
     use alloc::vec::Vec;
 
     use serde::{Deserialize, Deserializer};
 
+    use crate::error::Error;
+
     /// Deserialize a [`VecIndexSet`] from serde data.
     pub fn from<'de, D, S>(deserializer: D) -> Result<Vec<(usize, S)>, D::Error>
     where
@@ -31,11 +53,15 @@ mod serde_deserialize {
                 unreachable!()
             };
             if a > b {
-                return Err(serde::de::Error::custom(
-                    "VecIndexSet should have been sorted",
-                ));
+                return Err(serde::de::Error::custom(Error::UnsortedBlocks));
             }
         }
+        if bit_sets
+            .last()
+            .is_some_and(|&(map_index, _)| map_index > crate::max_map_index::<S>())
+        {
+            return Err(serde::de::Error::custom(Error::IndexOutOfBounds));
+        }
         Ok(bit_sets)
     }
 }
@@ -56,50 +82,423 @@ mod borsh_deserialize {
                 unreachable!()
             };
             if a > b {
-                return Err(borsh::io::Error::new(
-                    borsh::io::ErrorKind::Other,
-                    "VecIndexSet should have been sorted",
-                ));
+                return Err(Error::UnsortedBlocks.into());
             }
         }
+        if bit_sets
+            .last()
+            .is_some_and(|&(map_index, _)| map_index > crate::max_map_index::<S>())
+        {
+            return Err(Error::IndexOutOfBounds.into());
+        }
         Ok(bit_sets)
     }
 }
 
+#[cfg(any(feature = "serialize-borsh", feature = "serialize-serde"))]
+mod repr {
+    use alloc::vec::Vec;
+    #[cfg(feature = "serialize-borsh")]
+    use alloc::{format, string::ToString};
+
+    #[cfg(feature = "serialize-borsh")]
+    use borsh::{BorshDeserialize, BorshSchema};
+    #[cfg(feature = "serialize-serde")]
+    use serde::Deserialize;
+
+    /// Serialized representation of a [`VecIndexSet`](super::VecIndexSet),
+    /// as pairs of map indices and bit sets.
+    #[cfg_attr(feature = "serialize-borsh", derive(BorshDeserialize, BorshSchema))]
+    #[cfg_attr(feature = "serialize-serde", derive(Deserialize))]
+    pub struct VecIndexSet<S> {
+        /// Pairs of indices to bit vectors, sorted by index.
+        #[cfg_attr(
+            feature = "serialize-borsh",
+            borsh(deserialize_with = "super::borsh_deserialize::from")
+        )]
+        #[cfg_attr(
+            feature = "serialize-serde",
+            serde(deserialize_with = "super::serde_deserialize::from")
+        )]
+        #[cfg_attr(
+            feature = "serialize-serde",
+            serde(bound(deserialize = "S: Deserialize<'de>"))
+        )]
+        pub bit_sets: Vec<(usize, S)>,
+    }
+
+    impl<S> From<VecIndexSet<S>> for super::VecIndexSet<S> {
+        #[inline]
+        fn from(repr: VecIndexSet<S>) -> Self {
+            let (keys, words) = repr.bit_sets.into_iter().unzip();
+            Self {
+                keys,
+                words,
+                ..Self::new()
+            }
+        }
+    }
+}
+
 /// Index set backed by a [`Vec`].
-#[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
-#[cfg_attr(
-    feature = "serialize-borsh",
-    derive(BorshSerialize, BorshDeserialize, BorshSchema)
-)]
-#[cfg_attr(feature = "serialize-serde", derive(Serialize, Deserialize))]
-#[repr(transparent)]
+///
+/// Lookups, such as [`IndexSet::contains`] and [`IndexSet::iter`],
+/// never panic nor allocate. Operations that may allocate have
+/// fallible `try_*` variants, which return an error rather than
+/// aborting when memory runs out.
+///
+/// Unlike the other index sets, this type is not `#[repr(transparent)]`,
+/// since its map indices and bit vectors are held in separate vectors,
+/// next to its lookup hint, summary and search index. Its layout is
+/// unspecified, and its serialized form is unaffected.
+#[derive(Default, Clone, PartialEq, Eq, Hash)]
 pub struct VecIndexSet<S = u64> {
-    /// Pairs of indices to bit vectors, containing the actual boolean
-    /// values to be asserted.
-    ///
-    /// If the bit `B` is set, at the bit vector with index `S`, then
-    /// the index `S::WIDTH * S + B` is in the set.
-    #[cfg_attr(
-        feature = "serialize-borsh",
-        borsh(deserialize_with = "borsh_deserialize::from")
-    )]
-    #[cfg_attr(
-        feature = "serialize-serde",
-        serde(deserialize_with = "serde_deserialize::from")
-    )]
-    #[cfg_attr(
-        feature = "serialize-serde",
-        serde(bound(deserialize = "S: Deserialize<'de>"))
-    )]
-    bit_sets: Vec<(usize, S)>,
+    /// Indices of the bit vectors in `words`, sorted
+    /// in ascending order.
+    ///
+    /// These are kept apart from the bit vectors, such
+    /// that lookups only touch a dense array of indices.
+    keys: Vec<usize>,
+    /// Bit vectors containing the actual boolean values
+    /// to be asserted.
+    ///
+    /// If the bit `B` is set, at the bit vector `words[I]`, then
+    /// the index `S::WIDTH * keys[I] + B` is in the set.
+    words: Vec<S>,
+    /// Position of the last bit vector found by a lookup.
+    hint: BlockHint,
+    /// Optional summary of the occupied ranges of indices.
+    summary: BlockSummary,
+    /// Optional cache friendly index of sampled map indices.
+    search: SearchIndex,
+}
+
+/// Position of the last bit vector found by a lookup in
+/// a [`VecIndexSet`], which is checked before searching
+/// its indices.
+///
+/// Only lookups made through `&mut self` move the hint, such
+/// that reads through `&self` never store to the set. Hints
+/// are never relied upon for correctness, and are ignored
+/// when comparing or hashing sets.
+#[derive(Default, Clone)]
+struct BlockHint(usize);
+
+impl BlockHint {
+    /// Create a new [`BlockHint`].
+    #[inline]
+    const fn new() -> Self {
+        Self(0)
+    }
+
+    /// Return the hinted position.
+    #[inline]
+    fn get(&self) -> usize {
+        self.0
+    }
+
+    /// Update the hinted position.
+    #[inline]
+    fn set(&mut self, position: usize) {
+        self.0 = position;
+    }
+}
+
+impl PartialEq for BlockHint {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for BlockHint {}
+
+impl Hash for BlockHint {
+    #[inline]
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+/// Number of consecutive map indices marked by each
+/// bit of a [`BlockSummary`].
+const SUMMARY_GRANULE: usize = 8;
+
+/// Number of words in a [`BlockSummary`].
+const SUMMARY_WORDS: usize = 16;
+
+/// Number of bits in a [`BlockSummary`].
+const SUMMARY_BITS: usize = SUMMARY_WORDS * u64::BITS as usize;
+
+/// Coarse summary of the bit vectors occupied in a [`VecIndexSet`],
+/// used to answer lookups of absent indices without searching.
+///
+/// Each bit of the summary marks a range of [`SUMMARY_GRANULE`]
+/// consecutive map indices, wrapping around every [`SUMMARY_BITS`]
+/// ranges. Bits are only cleared when the summary is rebuilt, once
+/// enough bit vectors have been removed, such that the summary
+/// always marks a superset of the occupied ranges.
+///
+/// Summaries are ignored when comparing or hashing sets.
+#[derive(Default, Clone)]
+struct BlockSummary(Option<Box<SummaryBits>>);
+
+/// Bits of an enabled [`BlockSummary`].
+#[derive(Clone)]
+struct SummaryBits {
+    /// The ranges of map indices which may be occupied.
+    words: [u64; SUMMARY_WORDS],
+    /// Number of bit vectors removed since the last rebuild.
+    stale: usize,
+}
+
+impl BlockSummary {
+    /// Create a new, disabled [`BlockSummary`].
+    #[inline]
+    const fn new() -> Self {
+        Self(None)
+    }
+
+    /// Return the word and bit marking `map_index`.
+    #[inline]
+    const fn position(map_index: usize) -> (usize, u64) {
+        let bit = (map_index / SUMMARY_GRANULE) % SUMMARY_BITS;
+        (bit / u64::BITS as usize, 1 << (bit % u64::BITS as usize))
+    }
+
+    /// Build a summary of the occupied map indices in `keys`.
+    fn rebuild(&mut self, keys: &[usize]) {
+        let summary = self.0.get_or_insert_with(|| {
+            Box::new(SummaryBits {
+                words: [0; SUMMARY_WORDS],
+                stale: 0,
+            })
+        });
+        summary.words = [0; SUMMARY_WORDS];
+        summary.stale = 0;
+        for &map_index in keys {
+            let (word, bit) = Self::position(map_index);
+            summary.words[word] |= bit;
+        }
+    }
+
+    /// Check if the bit vector at `map_index` may be occupied.
+    ///
+    /// Always returns true if the summary is disabled.
+    #[inline]
+    fn may_contain(&self, map_index: usize) -> bool {
+        self.0.as_ref().is_none_or(|summary| {
+            let (word, bit) = Self::position(map_index);
+            summary.words[word] & bit != 0
+        })
+    }
+
+    /// Mark the bit vector at `map_index` as occupied.
+    #[inline]
+    fn mark(&mut self, map_index: usize) {
+        if let Some(summary) = &mut self.0 {
+            let (word, bit) = Self::position(map_index);
+            summary.words[word] |= bit;
+        }
+    }
+
+    /// Record the removal of `count` bit vectors, rebuilding the
+    /// summary from the remaining `keys` once it has gone stale.
+    #[inline]
+    fn note_removals(&mut self, count: usize, keys: &[usize]) {
+        let Some(summary) = &mut self.0 else {
+            return;
+        };
+        summary.stale += count;
+        if summary.stale > keys.len().max(SUMMARY_BITS) {
+            self.rebuild(keys);
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for BlockSummary {
+    fn zeroize(&mut self) {
+        if let Some(summary) = &mut self.0 {
+            summary.words.zeroize();
+            summary.stale = 0;
+        }
+    }
+}
+
+impl PartialEq for BlockSummary {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for BlockSummary {}
+
+impl Hash for BlockSummary {
+    #[inline]
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+/// Number of bit vectors between consecutive map
+/// indices sampled by a [`SearchIndex`].
+const SEARCH_STRIDE: usize = 16;
+
+/// Index of every [`SEARCH_STRIDE`]-th map index of a [`VecIndexSet`],
+/// used to start lookups next to the bit vector being looked up.
+///
+/// The sampled map indices are stored in Eytzinger order, i.e. in
+/// the breadth first order of a complete binary search tree, such
+/// that the first levels of every search share the same few cache
+/// lines, and the lookup then only probes a handful of neighbouring
+/// map indices of the set.
+///
+/// The positions recorded in the index drift as bit vectors are
+/// inserted or removed. Lookups gallop from these positions, hence
+/// remain correct, and the index is rebuilt once the number of
+/// changes outgrows the number of samples.
+///
+/// Search indices are ignored when comparing or hashing sets.
+#[derive(Default, Clone)]
+struct SearchIndex(Option<Box<SearchLayout>>);
+
+/// Layout of an enabled [`SearchIndex`].
+#[derive(Default, Clone)]
+struct SearchLayout {
+    /// The sampled map indices, in Eytzinger order, starting
+    /// from the second slot, such that the children of the
+    /// slot `k` are at `2 * k` and `2 * k + 1`.
+    keys: Vec<usize>,
+    /// The positions of the sampled map indices in the
+    /// set, at the last rebuild, matching `keys`.
+    positions: Vec<usize>,
+    /// Number of bit vectors inserted or removed since the last rebuild.
+    stale: usize,
+}
+
+impl SearchIndex {
+    /// Create a new, disabled [`SearchIndex`].
+    #[inline]
+    const fn new() -> Self {
+        Self(None)
+    }
+
+    /// Build an index of the map indices in `keys`.
+    fn rebuild(&mut self, keys: &[usize]) {
+        let layout = self.0.get_or_insert_with(Default::default);
+        let len = keys.len().div_ceil(SEARCH_STRIDE) + 1;
+        layout.keys.clear();
+        layout.keys.resize(len, 0);
+        layout.positions.clear();
+        layout.positions.resize(len, 0);
+        layout.stale = 0;
+
+        // NB: visiting the slots in order yields the
+        // sampled map indices in ascending order
+        let mut sample = 0;
+        let mut slot = 1;
+        let mut stack = Vec::new();
+        while slot < len || !stack.is_empty() {
+            if slot < len {
+                stack.push(slot);
+                slot *= 2;
+                continue;
+            }
+            slot = stack.pop().unwrap();
+            layout.keys[slot] = keys[sample * SEARCH_STRIDE];
+            layout.positions[slot] = sample * SEARCH_STRIDE;
+            sample += 1;
+            slot = 2 * slot + 1;
+        }
+    }
+
+    /// Return the position to start looking up `map_index`
+    /// from, which may be past the end of the set.
+    ///
+    /// Always returns [`None`] if the index is disabled.
+    #[inline]
+    fn start(&self, map_index: usize) -> Option<usize> {
+        let layout = self.0.as_deref()?;
+        let mut slot = 1;
+        while slot < layout.keys.len() {
+            slot = 2 * slot + usize::from(layout.keys[slot] < map_index);
+        }
+
+        // NB: undo the trailing right turns, and the left turn
+        // before them, which leads to the first sample not lower
+        // than `map_index`, or to the slot zero if there is none
+        slot >>= (!slot).trailing_zeros() + 1;
+        Some(match slot {
+            0 => usize::MAX,
+            slot => layout.positions[slot],
+        })
+    }
+
+    /// Record `count` insertions or removals of bit vectors,
+    /// rebuilding the index from `keys` once it has gone stale.
+    #[inline]
+    fn note_changes(&mut self, count: usize, keys: &[usize]) {
+        let Some(layout) = &mut self.0 else {
+            return;
+        };
+        layout.stale += count;
+        if layout.stale > layout.keys.len().max(SEARCH_STRIDE) {
+            self.rebuild(keys);
+        }
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl zeroize::Zeroize for SearchIndex {
+    fn zeroize(&mut self) {
+        if let Some(layout) = &mut self.0 {
+            layout.keys.zeroize();
+            layout.positions.zeroize();
+            layout.stale = 0;
+        }
+    }
+}
+
+impl PartialEq for SearchIndex {
+    #[inline]
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SearchIndex {}
+
+impl Hash for SearchIndex {
+    #[inline]
+    fn hash<H: Hasher>(&self, _: &mut H) {}
+}
+
+/// Pairs of indices to bit vectors of a [`VecIndexSet`],
+/// in the order they are rendered and serialized.
+struct BitSets<'a, S>(&'a VecIndexSet<S>);
+
+impl<S: fmt::Debug> fmt::Debug for BitSets<'_, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.0.keys.iter().zip(&self.0.words))
+            .finish()
+    }
+}
+
+#[cfg(feature = "serialize-serde")]
+impl<S: Serialize> Serialize for BitSets<'_, S> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        serializer.collect_seq(self.0.keys.iter().zip(&self.0.words))
+    }
 }
 
 impl<S> VecIndexSet<S> {
     /// Create a new [`VecIndexSet`].
     pub const fn new() -> Self {
         Self {
-            bit_sets: Vec::new(),
+            keys: Vec::new(),
+            words: Vec::new(),
+            hint: BlockHint::new(),
+            summary: BlockSummary::new(),
+            search: SearchIndex::new(),
         }
     }
 
@@ -107,115 +506,1850 @@ impl<S> VecIndexSet<S> {
     #[inline]
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            bit_sets: Vec::with_capacity(capacity),
+            keys: Vec::with_capacity(capacity),
+            words: Vec::with_capacity(capacity),
+            hint: BlockHint::new(),
+            summary: BlockSummary::new(),
+            search: SearchIndex::new(),
         }
     }
-}
 
-impl<S: storage::Storage> VecIndexSet<S> {
-    /// Lookup the bit set at `map_index`, or initialize it
-    /// with zero, if it doesn't exist.
-    #[inline]
-    fn lookup_or_zero(&mut self, map_index: usize) -> &mut S {
-        let pair_index = self.lookup_or_initialize_pair(map_index);
-        let (_, set) = &mut self.bit_sets[pair_index];
-        set
+    /// Create a new [`VecIndexSet`] with the given capacity,
+    /// returning an error if the allocation fails.
+    pub fn try_with_capacity(capacity: usize) -> Result<Self, TryReserveError> {
+        let mut set = Self::new();
+        set.keys.try_reserve_exact(capacity)?;
+        set.words.try_reserve_exact(capacity)?;
+        Ok(set)
     }
 
-    /// Lookup the vec index of the bit set at `map_index`, or initialize it
-    /// with zero, if it doesn't exist, returning the initialized index.
+    /// Return the number of bit sets this [`VecIndexSet`]
+    /// can hold without reallocating.
     #[inline]
-    fn lookup_or_initialize_pair(&mut self, map_index: usize) -> usize {
-        self.lookup_pair(map_index)
-            .unwrap_or_else(|insert_at_index| {
-                self.bit_sets.insert(insert_at_index, (map_index, S::ZERO));
-                insert_at_index
-            })
+    pub fn capacity(&self) -> usize {
+        self.keys.capacity().min(self.words.capacity())
     }
 
-    /// Lookup the vec index of the bit set at `map_index`.
-    #[inline]
-    fn lookup_pair(&self, map_index: usize) -> Result<usize, usize> {
-        self.bit_sets.binary_search_by_key(&map_index, |&(i, _)| i)
+    /// Attempt to reserve space for the specified number of additional
+    /// bit sets, returning an error if the allocation fails.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.keys.try_reserve(additional)?;
+        self.words.try_reserve(additional)
+    }
+
+    /// Reserve space for exactly the specified number of additional
+    /// bit sets, without over-allocating as [`IndexSet::reserve`]
+    /// may do to amortize future insertions.
+    ///
+    /// Prefer this method when the final number of bit sets is
+    /// known, and memory usage matters more than reallocations.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.keys.reserve_exact(additional);
+        self.words.reserve_exact(additional);
+    }
+
+    /// Attempt to reserve space for exactly the specified number of
+    /// additional bit sets, returning an error if the allocation fails.
+    pub fn try_reserve_exact(&mut self, additional: usize) -> Result<(), TryReserveError> {
+        self.keys.try_reserve_exact(additional)?;
+        self.words.try_reserve_exact(additional)
     }
 }
 
-impl<S: storage::Storage> IndexSet for VecIndexSet<S> {
-    #[inline]
-    fn len(&self) -> usize {
-        self.bit_sets
-            .iter()
-            .map(|(_, set)| set.num_of_high_bits())
-            .sum::<usize>()
+impl<S: storage::Storage> VecIndexSet<S> {
+    /// Build a [`VecIndexSet`] from pairs of map indices and bit
+    /// sets, which must be sorted by map index, unique and non-empty.
+    ///
+    /// This is a checked alternative to implementing [`TryFrom`],
+    /// which conflicts with the [`From`] implementation for
+    /// iterators of indices.
+    pub fn try_from_blocks(blocks: Vec<(usize, S)>) -> Result<Self, FromBlocksError> {
+        super::check_invariants(blocks.iter().copied())?;
+        let (keys, words) = blocks.into_iter().unzip();
+        Ok(Self {
+            keys,
+            words,
+            ..Self::new()
+        })
     }
 
-    #[inline]
-    fn is_empty(&self) -> bool {
-        self.bit_sets.is_empty()
+    /// Add a new index to this [`VecIndexSet`], returning an
+    /// error if a new bit set can't be allocated.
+    ///
+    /// The set is left unchanged on error.
+    pub fn try_insert(&mut self, index: usize) -> Result<(), TryReserveError> {
+        let (map_index, _) = calculate_map_and_set_indices::<S>(index);
+        if self.seek_block(map_index).is_err() {
+            self.try_reserve(1)?;
+        }
+        self.insert(index);
+        Ok(())
     }
 
-    fn insert(&mut self, index: usize) {
-        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
-        let set = self.lookup_or_zero(map_index);
-        *set |= S::from_usize(1 << bit_set_index);
+    /// Merge another [`VecIndexSet`] into this one, returning an
+    /// error if the merged bit sets can't be allocated.
+    ///
+    /// The set is left unchanged on error.
+    pub fn try_union(&mut self, other: &Self) -> Result<(), TryReserveError> {
+        if other.is_empty() {
+            return Ok(());
+        }
+
+        let capacity = self.keys.len() + other.keys.len();
+        let mut keys = Vec::new();
+        let mut words = Vec::new();
+        keys.try_reserve_exact(capacity)?;
+        words.try_reserve_exact(capacity)?;
+
+        self.merge_blocks_into(other.blocks(), keys, words);
+        Ok(())
     }
 
-    fn remove(&mut self, index: usize) {
-        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
-        let maybe_remove_index = self.lookup_pair(map_index).ok().and_then(|pair_index| {
-            let (_, set) = &mut self.bit_sets[pair_index];
-            *set &= !S::from_usize(1 << bit_set_index);
-            if *set == S::ZERO {
-                Some(pair_index)
+    /// Replace the indices of this [`VecIndexSet`] with
+    /// the indices in either `a` or `b`.
+    ///
+    /// The allocation of this set is reused, such that nothing
+    /// is allocated if it has enough capacity for the result.
+    pub fn assign_union(&mut self, a: &Self, b: &Self) {
+        self.clear_blocks();
+
+        let mut blocks_a = a.blocks().peekable();
+        let mut blocks_b = b.blocks().peekable();
+        loop {
+            let block = match (blocks_a.peek(), blocks_b.peek()) {
+                (Some(&(i, mut set_a)), Some(&(j, set_b))) => match i.cmp(&j) {
+                    Ordering::Less => blocks_a.next().unwrap(),
+                    Ordering::Greater => blocks_b.next().unwrap(),
+                    Ordering::Equal => {
+                        blocks_a.next();
+                        blocks_b.next();
+                        set_a |= set_b;
+                        (i, set_a)
+                    }
+                },
+                (Some(_), None) => blocks_a.next().unwrap(),
+                (None, Some(_)) => blocks_b.next().unwrap(),
+                (None, None) => break,
+            };
+            self.push_block(block);
+        }
+
+        self.debug_assert_invariants();
+    }
+
+    /// Replace the indices of this [`VecIndexSet`] with
+    /// the indices in both `a` and `b`.
+    ///
+    /// The allocation of this set is reused, such that nothing
+    /// is allocated if it has enough capacity for the result.
+    pub fn assign_intersection(&mut self, a: &Self, b: &Self) {
+        self.clear_blocks();
+
+        let mut blocks_b = b.blocks().peekable();
+        for (map_index, set_a) in a.blocks() {
+            while blocks_b.next_if(|&(j, _)| j < map_index).is_some() {}
+            let Some((_, set_b)) = blocks_b.next_if(|&(j, _)| j == map_index) else {
+                continue;
+            };
+            let set = set_a & set_b;
+            if set != S::ZERO {
+                self.push_block((map_index, set));
+            }
+        }
+
+        self.debug_assert_invariants();
+    }
+
+    /// Remove all indices greater than or equal to `max`
+    /// from this [`VecIndexSet`].
+    pub fn truncate(&mut self, max: usize) {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(max);
+        let mut len = self.keys.partition_point(|&i| i < map_index);
+
+        if bit_set_index > 0 && self.keys.get(len) == Some(&map_index) {
+            let set = &mut self.words[len];
+            *set &= bit_range_mask(0, bit_set_index - 1);
+            if *set != S::ZERO {
+                len += 1;
+            }
+        }
+
+        let removed = self.keys.len() - len;
+        self.keys.truncate(len);
+        self.words.truncate(len);
+        self.note_removals(removed);
+        self.debug_assert_invariants();
+    }
+
+    /// Move all indices greater than or equal to `at` from
+    /// this [`VecIndexSet`] into a new set, which is returned.
+    pub fn split_off(&mut self, at: usize) -> Self {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(at);
+        let mut split = self.keys.partition_point(|&i| i < map_index);
+        let mut low = S::ZERO;
+
+        // NB: the bit set at `map_index` may hold indices
+        // on both sides of `at`
+        if bit_set_index > 0 && self.keys.get(split) == Some(&map_index) {
+            let mask: S = bit_range_mask(0, bit_set_index - 1);
+            let set = &mut self.words[split];
+            if *set & !mask == S::ZERO {
+                split += 1;
             } else {
-                None
+                low = *set & mask;
+                *set &= !mask;
             }
-        });
-        if let Some(pair_index) = maybe_remove_index {
-            self.bit_sets.remove(pair_index);
         }
+
+        let mut tail = Self::new();
+        tail.keys = self.keys.split_off(split);
+        tail.words = self.words.split_off(split);
+        self.note_removals(tail.keys.len());
+        if low != S::ZERO {
+            self.push_block((map_index, low));
+        }
+
+        self.debug_assert_invariants();
+        tail.debug_assert_invariants();
+        tail
     }
 
-    fn contains(&self, index: usize) -> bool {
-        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
-        self.lookup_pair(map_index)
-            .map(|pair_index| {
-                let &(_, set) = &self.bit_sets[pair_index];
-                set & S::from_usize(1 << bit_set_index) != S::ZERO
-            })
-            .unwrap_or(false)
+    /// Move all indices of `other` into this [`VecIndexSet`],
+    /// leaving `other` empty.
+    ///
+    /// The bit sets of `other` above those of this set are moved
+    /// as a whole, and this set takes over the allocation of
+    /// `other` if it is empty.
+    pub fn append(&mut self, other: &mut Self) {
+        let removed = other.keys.len();
+        let last = self.keys.last().copied();
+        let split = other.keys.partition_point(|&i| Some(i) <= last);
+        self.merge_blocks(
+            other.keys[..split]
+                .iter()
+                .copied()
+                .zip(other.words[..split].iter().copied()),
+        );
+
+        let old_len = self.keys.len();
+        if old_len == 0 {
+            core::mem::swap(&mut self.keys, &mut other.keys);
+            core::mem::swap(&mut self.words, &mut other.words);
+        } else {
+            self.keys.extend_from_slice(&other.keys[split..]);
+            self.words.extend_from_slice(&other.words[split..]);
+        }
+        for &map_index in &self.keys[old_len..] {
+            self.summary.mark(map_index);
+        }
+        self.search
+            .note_changes(self.keys.len() - old_len, &self.keys);
+
+        other.keys.clear();
+        other.words.clear();
+        other.note_removals(removed);
+        self.debug_assert_invariants();
     }
 
-    #[inline]
-    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
-        self.bit_sets.iter().flat_map(|&(map_index, set)| {
-            (0..S::WIDTH).filter_map(move |bit_set_index| {
-                let is_bit_set = (set & S::from_usize(1 << bit_set_index)) != S::ZERO;
-                if is_bit_set {
-                    Some(map_index * S::WIDTH + bit_set_index)
-                } else {
-                    None
+    /// Remove up to `n` of the lowest indices from this
+    /// [`VecIndexSet`], returning them in ascending order.
+    ///
+    /// Bit sets whose indices are all removed are dropped
+    /// at once, rather than one index at a time.
+    pub fn pop_smallest(&mut self, n: usize) -> Vec<usize> {
+        let mut popped = Vec::new();
+        let mut removed = 0;
+
+        for (&map_index, set) in self.keys.iter().zip(self.words.iter_mut()) {
+            let remaining = n - popped.len();
+            if remaining == 0 {
+                break;
+            }
+            if set.num_of_high_bits() <= remaining {
+                popped.extend(bit_set_indices(*set).map(|bit| map_index * S::WIDTH + bit));
+                removed += 1;
+            } else {
+                for _ in 0..remaining {
+                    popped.push(map_index * S::WIDTH + set.trailing_zeros());
+                    *set = set.without_lowest_bit();
                 }
-            })
-        })
+                break;
+            }
+        }
+
+        self.keys.drain(..removed);
+        self.words.drain(..removed);
+        self.note_removals(removed);
+        self.debug_assert_invariants();
+        popped
     }
 
-    #[inline]
-    fn union(&mut self, other: &VecIndexSet<S>) {
-        // naive implementation
-        for &(map_index, other_set) in other.bit_sets.iter() {
-            let set = self.lookup_or_zero(map_index);
-            *set |= other_set;
+    /// Remove up to `n` of the greatest indices from this
+    /// [`VecIndexSet`], returning them in descending order.
+    ///
+    /// Bit sets whose indices are all removed are dropped
+    /// at once, rather than one index at a time.
+    pub fn pop_largest(&mut self, n: usize) -> Vec<usize> {
+        let mut popped = Vec::new();
+        let mut len = self.keys.len();
+
+        for (&map_index, set) in self.keys.iter().zip(self.words.iter_mut()).rev() {
+            let remaining = n - popped.len();
+            if remaining == 0 {
+                break;
+            }
+            let indices = bit_set_indices(*set).rev();
+            if set.num_of_high_bits() <= remaining {
+                popped.extend(indices.map(|bit| map_index * S::WIDTH + bit));
+                len -= 1;
+            } else {
+                for bit in indices.take(remaining) {
+                    popped.push(map_index * S::WIDTH + bit);
+                    *set &= !S::from_usize(1 << bit);
+                }
+                break;
+            }
         }
+
+        let removed = self.keys.len() - len;
+        self.keys.truncate(len);
+        self.words.truncate(len);
+        self.note_removals(removed);
+        self.debug_assert_invariants();
+        popped
     }
 
-    #[inline]
-    fn reserve(&mut self, size: usize) {
-        self.bit_sets.reserve(size);
+    /// Add the indices of the bits set in `mask` to the bit
+    /// set at `map_index` of this [`VecIndexSet`].
+    ///
+    /// The bit set holds the indices from `map_index * W` up to
+    /// `map_index * W + W - 1`, where `W` is the width of the bit set.
+    /// Bits of `mask` at positions `W` and above are ignored.
+    pub fn insert_word(&mut self, map_index: usize, mask: S) {
+        let mask = mask & bit_range_mask(0, S::WIDTH - 1);
+        if mask != S::ZERO {
+            *self.lookup_or_zero(map_index) |= mask;
+        }
+        self.debug_assert_invariants();
     }
-}
 
-index_set_impl_from!(crate::vec::VecIndexSet);
-index_set_impl_from_iterator!(crate::vec::VecIndexSet);
-index_set_impl_extend!(crate::vec::VecIndexSet);
-index_set_tests!(crate::vec::VecIndexSet);
+    /// Remove the indices of the bits set in `mask` from the
+    /// bit set at `map_index` of this [`VecIndexSet`].
+    ///
+    /// See [`VecIndexSet::insert_word`] for the indices
+    /// held in each bit set.
+    pub fn remove_word(&mut self, map_index: usize, mask: S) {
+        let Ok(block_index) = self.seek_block(map_index) else {
+            return;
+        };
+        let set = &mut self.words[block_index];
+        *set &= !mask;
+        if *set == S::ZERO {
+            self.remove_block(block_index);
+        }
+        self.debug_assert_invariants();
+    }
+
+    /// Flip the membership of `index` in this [`VecIndexSet`],
+    /// returning whether it is now present.
+    pub fn toggle(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let mask = S::from_usize(1 << bit_set_index);
+        let block_index = self.lookup_or_initialize_block(map_index);
+        let set = &mut self.words[block_index];
+        *set = flip_bits(*set, mask);
+        let present = *set & mask != S::ZERO;
+        if *set == S::ZERO {
+            self.remove_block(block_index);
+        }
+        self.debug_assert_invariants();
+        present
+    }
+
+    /// Flip the membership of every index lower than `upto`
+    /// in this [`VecIndexSet`], leaving other indices as is.
+    ///
+    /// Every bit set up to `upto` is visited, including
+    /// those not currently stored in this set.
+    pub fn invert(&mut self, upto: usize) {
+        let Some(last) = upto.checked_sub(1) else {
+            return;
+        };
+        let (end_map_index, end_bit_set_index) = calculate_map_and_set_indices::<S>(last);
+
+        let tail = self.keys.len() - self.keys.partition_point(|&i| i <= end_map_index);
+        let capacity = end_map_index.saturating_add(1).saturating_add(tail);
+        let old_keys = core::mem::replace(&mut self.keys, Vec::with_capacity(capacity));
+        let old_words = core::mem::replace(&mut self.words, Vec::with_capacity(capacity));
+        let mut old_blocks = old_keys.into_iter().zip(old_words).peekable();
+        let mut removed = 0;
+
+        for map_index in 0..=end_map_index {
+            let last_bit = if map_index == end_map_index {
+                end_bit_set_index
+            } else {
+                S::WIDTH - 1
+            };
+            let mask = bit_range_mask(0, last_bit);
+            let set = match old_blocks.next_if(|&(i, _)| i == map_index) {
+                Some((_, set)) => flip_bits(set, mask),
+                None => mask,
+            };
+            if set == S::ZERO {
+                removed += 1;
+            } else {
+                self.push_block((map_index, set));
+            }
+        }
+        for block in old_blocks {
+            self.push_block(block);
+        }
+
+        self.note_removals(removed);
+        self.debug_assert_invariants();
+    }
+
+    /// Encode this [`VecIndexSet`] into its binary representation.
+    ///
+    /// The encoding consists of the number of bit sets, as a little
+    /// endian [`u32`], followed by each map index, as a little endian
+    /// [`u64`], and its bit set, in little endian order. This matches
+    /// the borsh encoding of this set, and can be read back without
+    /// copying with [`VecIndexSetRef`](crate::borrowed::VecIndexSetRef).
+    ///
+    /// Returns [`Error::LimitExceeded`] if this set holds
+    /// more than [`u32::MAX`] bit sets.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let num_of_blocks = u32::try_from(self.keys.len()).map_err(|_| Error::LimitExceeded)?;
+        let mut bytes = Vec::with_capacity(4 + self.keys.len() * (8 + S::WIDTH));
+
+        bytes.extend_from_slice(&num_of_blocks.to_le_bytes());
+        for (map_index, set) in self.blocks() {
+            bytes.extend_from_slice(&(map_index as u64).to_le_bytes());
+            set.extend_le_bytes(&mut bytes);
+        }
+
+        Ok(bytes)
+    }
+
+    /// Decode a [`VecIndexSet`] from the binary representation of
+    /// [`VecIndexSet::to_bytes`], repairing non-canonical bit sets
+    /// as with [`VecIndexSet::from_blocks_lenient`].
+    ///
+    /// Only input whose length does not match its number of
+    /// bit sets, or which holds indices past [`usize::MAX`],
+    /// is rejected.
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<Self, Error> {
+        let (header, encoded_blocks) = bytes
+            .split_first_chunk::<4>()
+            .ok_or(Error::InvalidEncoding)?;
+        let num_of_blocks = u32::from_le_bytes(*header) as usize;
+        if num_of_blocks.checked_mul(8 + S::WIDTH) != Some(encoded_blocks.len()) {
+            return Err(Error::InvalidEncoding);
+        }
+
+        let mut blocks = Vec::new();
+        blocks.try_reserve_exact(num_of_blocks)?;
+        for pair in encoded_blocks.chunks_exact(8 + S::WIDTH) {
+            let (key, word) = pair.split_at(8);
+            let map_index = usize::try_from(u64::from_le_bytes(key.try_into().unwrap()))
+                .ok()
+                .filter(|&map_index| map_index <= max_map_index::<S>())
+                .ok_or(Error::IndexOutOfBounds)?;
+            blocks.push((map_index, S::from_le_slice(word)));
+        }
+
+        Ok(Self::from_blocks_lenient(blocks))
+    }
+
+    /// Stream the binary representation of this [`VecIndexSet`],
+    /// as produced by [`VecIndexSet::to_bytes`], to `writer`.
+    ///
+    /// Bit sets are encoded and written in small chunks, rather
+    /// than buffering the whole encoding in memory.
+    #[cfg(feature = "async-io")]
+    pub async fn write_to<W>(&self, mut writer: W) -> std::io::Result<()>
+    where
+        W: futures_io::AsyncWrite + Unpin,
+    {
+        use crate::async_io::{flush, write_all, CHUNK_SIZE};
+
+        let num_of_blocks = u32::try_from(self.keys.len()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, Error::LimitExceeded)
+        })?;
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE + 8 + S::WIDTH);
+
+        chunk.extend_from_slice(&num_of_blocks.to_le_bytes());
+        for (map_index, set) in self.blocks() {
+            chunk.extend_from_slice(&(map_index as u64).to_le_bytes());
+            set.extend_le_bytes(&mut chunk);
+            if chunk.len() >= CHUNK_SIZE {
+                write_all(&mut writer, &chunk).await?;
+                chunk.clear();
+            }
+        }
+        write_all(&mut writer, &chunk).await?;
+
+        flush(&mut writer).await
+    }
+
+    /// Read a [`VecIndexSet`] streamed from `reader`, in the
+    /// binary representation of [`VecIndexSet::to_bytes`].
+    ///
+    /// Bit sets are validated as they are read, such that
+    /// malformed input is rejected before it is fully received.
+    #[cfg(feature = "async-io")]
+    pub async fn read_from<R>(mut reader: R) -> std::io::Result<Self>
+    where
+        R: futures_io::AsyncRead + Unpin,
+    {
+        use crate::async_io::{invalid_data, read_exact};
+
+        let mut header = [0; 4];
+        read_exact(&mut reader, &mut header).await?;
+        let num_of_blocks = u32::from_le_bytes(header) as usize;
+
+        let mut set = Self::with_capacity(num_of_blocks.min(MAX_PREALLOCATED_BLOCKS));
+        let mut pair = alloc::vec![0; 8 + S::WIDTH];
+        let mut last_map_index = None;
+        for _ in 0..num_of_blocks {
+            read_exact(&mut reader, &mut pair).await?;
+            set.push_encoded_block(&pair, &mut last_map_index)
+                .map_err(invalid_data)?;
+        }
+
+        set.debug_assert_invariants();
+        Ok(set)
+    }
+
+    /// Write the binary representation of this [`VecIndexSet`], as
+    /// produced by [`VecIndexSet::to_bytes`], to an [`embedded_io`]
+    /// `writer`, one bit set at a time.
+    ///
+    /// Nothing is written if this set holds more than [`u32::MAX`]
+    /// bit sets, which is reported as [`Error::LimitExceeded`].
+    #[cfg(feature = "embedded-io")]
+    pub fn write_to_embedded<W>(
+        &self,
+        mut writer: W,
+    ) -> Result<(), crate::error::WriteError<W::Error>>
+    where
+        W: embedded_io::Write,
+    {
+        use crate::error::WriteError;
+
+        let num_of_blocks =
+            u32::try_from(self.keys.len()).map_err(|_| WriteError::Encode(Error::LimitExceeded))?;
+        let mut pair = Vec::with_capacity(8 + S::WIDTH);
+
+        writer
+            .write_all(&num_of_blocks.to_le_bytes())
+            .map_err(WriteError::Write)?;
+        for (map_index, set) in self.blocks() {
+            pair.clear();
+            pair.extend_from_slice(&(map_index as u64).to_le_bytes());
+            set.extend_le_bytes(&mut pair);
+            writer.write_all(&pair).map_err(WriteError::Write)?;
+        }
+
+        writer.flush().map_err(WriteError::Write)
+    }
+
+    /// Read a [`VecIndexSet`] from an [`embedded_io`] `reader`, in the
+    /// binary representation of [`VecIndexSet::to_bytes`].
+    ///
+    /// Bit sets are validated as they are read, such that
+    /// malformed input is rejected before it is fully read.
+    #[cfg(feature = "embedded-io")]
+    pub fn read_from_embedded<R>(mut reader: R) -> Result<Self, crate::error::ReadError<R::Error>>
+    where
+        R: embedded_io::Read,
+    {
+        use crate::error::ReadError;
+
+        let mut header = [0; 4];
+        reader.read_exact(&mut header).map_err(ReadError::Read)?;
+        let num_of_blocks = u32::from_le_bytes(header) as usize;
+
+        let mut set = Self::with_capacity(num_of_blocks.min(MAX_PREALLOCATED_BLOCKS));
+        let mut pair = alloc::vec![0; 8 + S::WIDTH];
+        let mut last_map_index = None;
+        for _ in 0..num_of_blocks {
+            reader.read_exact(&mut pair).map_err(ReadError::Read)?;
+            set.push_encoded_block(&pair, &mut last_map_index)
+                .map_err(ReadError::Decode)?;
+        }
+
+        set.debug_assert_invariants();
+        Ok(set)
+    }
+
+    /// Decode a pair of map index and bit set, in the binary
+    /// representation of [`VecIndexSet::to_bytes`], and append
+    /// it to this [`VecIndexSet`] unless its bit set is empty.
+    ///
+    /// The map index must be greater than `last_map_index`,
+    /// which is updated to the decoded map index.
+    #[cfg(any(feature = "async-io", feature = "embedded-io"))]
+    fn push_encoded_block(
+        &mut self,
+        pair: &[u8],
+        last_map_index: &mut Option<usize>,
+    ) -> Result<(), Error> {
+        let (key, word) = pair.split_at(8);
+        let map_index = usize::try_from(u64::from_le_bytes(key.try_into().unwrap()))
+            .ok()
+            .filter(|&map_index| map_index <= crate::max_map_index::<S>())
+            .ok_or(Error::IndexOutOfBounds)?;
+        if last_map_index.is_some_and(|last| last >= map_index) {
+            return Err(Error::UnsortedBlocks);
+        }
+        *last_map_index = Some(map_index);
+
+        let word = S::from_le_slice(word);
+        if word != S::ZERO {
+            self.push_block((map_index, word));
+        }
+        Ok(())
+    }
+
+    /// Maintain a coarse summary of the ranges of indices occupied
+    /// in this [`VecIndexSet`].
+    ///
+    /// Lookups of indices in ranges that the summary marks as empty
+    /// are answered without searching the set, at the expense of
+    /// some extra work on insertions and removals. This pays off
+    /// when most lookups are for absent indices.
+    pub fn enable_summary(&mut self) {
+        self.summary.rebuild(&self.keys);
+    }
+
+    /// Stop maintaining the summary enabled with
+    /// [`VecIndexSet::enable_summary`].
+    #[inline]
+    pub fn disable_summary(&mut self) {
+        self.summary = BlockSummary::new();
+    }
+
+    /// Maintain a cache friendly index of a sample of the bit
+    /// vectors of this [`VecIndexSet`], to speed up lookups.
+    ///
+    /// Rather than binary searching all bit vectors, whose probes
+    /// are scattered across memory, lookups walk a compact tree of
+    /// sampled bit vectors, then only probe a few neighbouring ones.
+    /// This pays off for read heavy sets of millions of bit vectors,
+    /// at the expense of some extra memory and work on insertions
+    /// and removals.
+    pub fn enable_search_index(&mut self) {
+        self.search.rebuild(&self.keys);
+    }
+
+    /// Stop maintaining the search index enabled with
+    /// [`VecIndexSet::enable_search_index`].
+    #[inline]
+    pub fn disable_search_index(&mut self) {
+        self.search = SearchIndex::new();
+    }
+
+    /// Begin a batch of insertions into this [`VecIndexSet`].
+    ///
+    /// Indices inserted through the returned [`InsertBatch`] are
+    /// staged, and merged into this set in a single pass once the
+    /// batch is committed or dropped. This turns `k` random order
+    /// insertions into `O(n + k log k)` work, rather than `O(n * k)`.
+    #[inline]
+    pub fn begin_batch(&mut self) -> InsertBatch<'_, S> {
+        InsertBatch {
+            set: self,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Return a handle to the bit set holding `index`, allowing
+    /// several operations on its bits with a single lookup.
+    ///
+    /// Bits are addressed by their position in the bit set, which holds
+    /// the indices from [`BlockHandle::start_index`] up to that index
+    /// plus the width of the bit set, minus one. Changes are written
+    /// back to this set when the handle is dropped.
+    #[inline]
+    pub fn block_mut(&mut self, index: usize) -> BlockHandle<'_, S> {
+        let (map_index, _) = calculate_map_and_set_indices::<S>(index);
+        let position = self.seek_block(map_index);
+        let set = match position {
+            Ok(block_index) => self.words[block_index],
+            Err(_) => S::ZERO,
+        };
+        BlockHandle {
+            set_ref: self,
+            map_index,
+            position,
+            set,
+        }
+    }
+
+    /// Merge sorted, non-empty `blocks` into this [`VecIndexSet`],
+    /// in a single pass.
+    pub(crate) fn merge_blocks<I>(&mut self, blocks: I)
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        let mut blocks = blocks.into_iter().peekable();
+        if blocks.peek().is_none() {
+            return;
+        }
+
+        let capacity = self.keys.len() + blocks.size_hint().0;
+        self.merge_blocks_into(
+            blocks,
+            Vec::with_capacity(capacity),
+            Vec::with_capacity(capacity),
+        );
+    }
+
+    /// Merge sorted, non-empty `blocks` into this [`VecIndexSet`],
+    /// as with [`Self::merge_blocks`].
+    ///
+    /// This never fails, since the memory used by this set only
+    /// grows with the number of bit sets merged, but matches the
+    /// fallible merge of [`DenseIndexSet`](crate::dense::DenseIndexSet).
+    #[inline]
+    pub(crate) fn try_merge_blocks<I>(&mut self, blocks: I) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        self.merge_blocks(blocks);
+        Ok(())
+    }
+
+    /// Update each bit set of this [`VecIndexSet`] with `f`, in
+    /// ascending order of map indices, removing those left empty.
+    pub(crate) fn retain_blocks<F>(&mut self, mut f: F)
+    where
+        F: FnMut(usize, &mut S),
+    {
+        let mut len = 0;
+        for i in 0..self.keys.len() {
+            let map_index = self.keys[i];
+            let mut set = self.words[i];
+            f(map_index, &mut set);
+            if set != S::ZERO {
+                self.keys[len] = map_index;
+                self.words[len] = set;
+                len += 1;
+            }
+        }
+
+        let removed = self.keys.len() - len;
+        self.keys.truncate(len);
+        self.words.truncate(len);
+        self.note_removals(removed);
+        self.debug_assert_invariants();
+    }
+
+    /// Merge sorted, non-empty `blocks` into this [`VecIndexSet`],
+    /// in a single pass, replacing its bit sets with the empty
+    /// `keys` and `words`, which the merged bit sets are written to.
+    ///
+    /// Nothing is allocated if `keys` and `words` have enough
+    /// capacity for all the merged bit sets.
+    fn merge_blocks_into<I>(&mut self, blocks: I, keys: Vec<usize>, words: Vec<S>)
+    where
+        I: IntoIterator<Item = (usize, S)>,
+    {
+        debug_assert!(keys.is_empty() && words.is_empty());
+
+        let mut blocks = blocks.into_iter().peekable();
+        let old_len = self.keys.len();
+        let old_keys = core::mem::replace(&mut self.keys, keys);
+        let old_words = core::mem::replace(&mut self.words, words);
+        let mut old_blocks = old_keys.into_iter().zip(old_words).peekable();
+
+        loop {
+            let block = match (old_blocks.peek(), blocks.peek()) {
+                (Some(&(a, mut set_a)), Some(&(b, set_b))) => match a.cmp(&b) {
+                    Ordering::Less => old_blocks.next().unwrap(),
+                    Ordering::Greater => blocks.next().unwrap(),
+                    Ordering::Equal => {
+                        old_blocks.next();
+                        blocks.next();
+                        set_a |= set_b;
+                        (a, set_a)
+                    }
+                },
+                (Some(_), None) => old_blocks.next().unwrap(),
+                (None, Some(_)) => blocks.next().unwrap(),
+                (None, None) => break,
+            };
+            self.summary.mark(block.0);
+            self.keys.push(block.0);
+            self.words.push(block.1);
+        }
+
+        self.search
+            .note_changes(self.keys.len() - old_len, &self.keys);
+        self.debug_assert_invariants();
+    }
+
+    /// Remove all indices from this [`VecIndexSet`], returning
+    /// them in ascending order.
+    ///
+    /// The set is left empty, even if the returned iterator is
+    /// not fully consumed. Its bit vectors are moved into the
+    /// iterator, and freed once it is dropped.
+    pub fn drain(&mut self) -> impl FusedIterator<Item = usize> + '_ {
+        let keys = core::mem::take(&mut self.keys);
+        let words = core::mem::take(&mut self.words);
+        self.note_removals(keys.len());
+        Drain::new(keys.into_iter().zip(words))
+    }
+
+    /// Remove all bit sets from this [`VecIndexSet`],
+    /// keeping its allocation.
+    #[inline]
+    pub(crate) fn clear_blocks(&mut self) {
+        let removed = self.keys.len();
+        self.keys.clear();
+        self.words.clear();
+        self.note_removals(removed);
+    }
+
+    /// Record the removal of `count` bit sets in the
+    /// summary and search index of this [`VecIndexSet`].
+    #[inline]
+    fn note_removals(&mut self, count: usize) {
+        self.summary.note_removals(count, &self.keys);
+        self.search.note_changes(count, &self.keys);
+    }
+
+    /// Assert the invariants of this [`VecIndexSet`], with the
+    /// `strict-invariants` feature.
+    ///
+    /// This is a no-op in release builds.
+    #[inline]
+    #[track_caller]
+    fn debug_assert_invariants(&self) {
+        #[cfg(feature = "strict-invariants")]
+        debug_assert_eq!(
+            self.keys.len(),
+            self.words.len(),
+            "index set invariant violated: mismatched map indices and bit sets"
+        );
+        super::debug_assert_invariants(self.blocks());
+    }
+
+    /// Lookup the bit set at `map_index`, or initialize it
+    /// with zero, if it doesn't exist.
+    #[inline]
+    fn lookup_or_zero(&mut self, map_index: usize) -> &mut S {
+        let block_index = self.lookup_or_initialize_block(map_index);
+        &mut self.words[block_index]
+    }
+
+    /// Lookup the vec index of the bit set at `map_index`, or initialize it
+    /// with zero, if it doesn't exist, returning the initialized index.
+    #[inline]
+    fn lookup_or_initialize_block(&mut self, map_index: usize) -> usize {
+        self.seek_block(map_index)
+            .unwrap_or_else(|insert_at_index| {
+                self.insert_block(insert_at_index, (map_index, S::ZERO));
+                insert_at_index
+            })
+    }
+
+    /// Insert the bit set at `map_index` at vec index `block_index`.
+    #[inline]
+    fn insert_block(&mut self, block_index: usize, (map_index, set): (usize, S)) {
+        self.summary.mark(map_index);
+        self.keys.insert(block_index, map_index);
+        self.words.insert(block_index, set);
+        self.search.note_changes(1, &self.keys);
+    }
+
+    /// Lookup the vec index of the bit set at `map_index`.
+    ///
+    /// The search gallops outwards from the position given by the
+    /// search index, if enabled, or else from the position of the
+    /// last bit set looked up through [`Self::seek_block`], such
+    /// that clustered and nearly sorted updates only probe a few
+    /// nearby bit sets.
+    #[inline]
+    fn lookup_block(&self, map_index: usize) -> Result<usize, usize> {
+        let start = self
+            .search
+            .start(map_index)
+            .unwrap_or_else(|| self.hint.get());
+        gallop_search(&self.keys, start, map_index)
+    }
+
+    /// Lookup the vec index of the bit set at `map_index`, as with
+    /// [`Self::lookup_block`], and start the next lookup from there.
+    #[inline]
+    fn seek_block(&mut self, map_index: usize) -> Result<usize, usize> {
+        let result = self.lookup_block(map_index);
+        let (Ok(block_index) | Err(block_index)) = result;
+        self.hint.set(block_index);
+        result
+    }
+
+    /// Remove the bit set at vec index `block_index`.
+    #[inline]
+    fn remove_block(&mut self, block_index: usize) {
+        self.keys.remove(block_index);
+        self.words.remove(block_index);
+        self.note_removals(1);
+    }
+
+    /// Return the bit set at `map_index`, if any.
+    #[inline]
+    pub(crate) fn get_block(&self, map_index: usize) -> Option<S> {
+        if !self.summary.may_contain(map_index) {
+            return None;
+        }
+        self.lookup_block(map_index)
+            .ok()
+            .map(|block_index| self.words[block_index])
+    }
+
+    /// Iterate over the map indices and bit sets of
+    /// this [`VecIndexSet`], in ascending order.
+    #[inline]
+    pub(crate) fn blocks(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + ExactSizeIterator + FusedIterator + Clone + '_
+    {
+        self.keys.iter().copied().zip(self.words.iter().copied())
+    }
+
+    /// Iterate over the map indices and bit sets of this [`VecIndexSet`]
+    /// whose map indices lie within `map_range`, in ascending order.
+    #[inline]
+    fn blocks_in(
+        &self,
+        map_range: RangeInclusive<usize>,
+    ) -> impl DoubleEndedIterator<Item = (usize, S)> + '_ {
+        let start = self.keys.partition_point(|&i| i < *map_range.start());
+        let end = self.keys.partition_point(|&i| i <= *map_range.end());
+        let range = start..end.max(start);
+        self.keys[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.words[range].iter().copied())
+    }
+
+    /// Append a non-empty bit set at `map_index`, which must be
+    /// greater than the map indices of all other bit sets.
+    #[inline]
+    pub(crate) fn push_block(&mut self, (map_index, set): (usize, S)) {
+        debug_assert!(self.keys.last().is_none_or(|&i| i < map_index));
+        debug_assert!(set != S::ZERO);
+        self.summary.mark(map_index);
+        self.keys.push(map_index);
+        self.words.push(set);
+        self.search.note_changes(1, &self.keys);
+    }
+}
+
+/// Return the position of the first of the sorted `keys` not lower
+/// than `target`, or the length of `keys` if there is none.
+///
+/// Unlike [`slice::binary_search`], the number of iterations only
+/// depends on the length of `keys`, and the loop body compiles down
+/// to conditional moves.
+#[inline]
+fn lower_bound(keys: &[usize], target: usize) -> usize {
+    if keys.is_empty() {
+        return 0;
+    }
+
+    let mut base = 0;
+    let mut size = keys.len();
+    while size > 1 {
+        let half = size / 2;
+        let mid = base + half;
+        base = if keys[mid] < target { mid } else { base };
+        size -= half;
+    }
+
+    base + usize::from(keys[base] < target)
+}
+
+/// Search the sorted `keys` for `target`, galloping outwards from
+/// position `start`, with the same results as a binary search.
+///
+/// This takes `O(log d)` steps, where `d` is the distance between
+/// `start` and the position of `target`.
+fn gallop_search(keys: &[usize], start: usize, target: usize) -> Result<usize, usize> {
+    let Some(last) = keys.len().checked_sub(1) else {
+        return Err(0);
+    };
+    let start = start.min(last);
+
+    let (low, high) = match keys[start].cmp(&target) {
+        Ordering::Equal => return Ok(start),
+        Ordering::Less => {
+            let mut bound = 1;
+            while start + bound < keys.len() && keys[start + bound] < target {
+                bound *= 2;
+            }
+            (start + bound / 2 + 1, (start + bound + 1).min(keys.len()))
+        }
+        Ordering::Greater => {
+            let mut bound = 1;
+            while bound <= start && keys[start - bound] > target {
+                bound *= 2;
+            }
+            (start.saturating_sub(bound), start - bound / 2)
+        }
+    };
+
+    keys[low..high]
+        .binary_search(&target)
+        .map(|i| low + i)
+        .map_err(|i| low + i)
+}
+
+/// Handle to a bit set of a [`VecIndexSet`], created
+/// with [`VecIndexSet::block_mut`].
+///
+/// Bits are addressed by their position in the bit set, rather than
+/// by the index they represent in the set.
+pub struct BlockHandle<'a, S: storage::Storage> {
+    /// The set holding the bit set, written back on drop.
+    set_ref: &'a mut VecIndexSet<S>,
+    /// The map index of the bit set.
+    map_index: usize,
+    /// The vec index of the bit set, or the vec index
+    /// it should be inserted at, if it doesn't exist.
+    position: Result<usize, usize>,
+    /// The current value of the bit set.
+    set: S,
+}
+
+impl<S: storage::Storage> BlockHandle<'_, S> {
+    /// Return the map index of this bit set.
+    #[inline]
+    pub fn map_index(&self) -> usize {
+        self.map_index
+    }
+
+    /// Return the index represented by the first bit of this bit set.
+    #[inline]
+    pub fn start_index(&self) -> usize {
+        self.map_index * S::WIDTH
+    }
+
+    /// Return the current value of this bit set.
+    #[inline]
+    pub fn bits(&self) -> S {
+        self.set
+    }
+
+    /// Set the bit at position `bit` of this bit set.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `bit` is not lower than the width of the bit set.
+    #[inline]
+    pub fn insert_local(&mut self, bit: usize) {
+        self.set |= Self::mask(bit);
+    }
+
+    /// Clear the bit at position `bit` of this bit set.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `bit` is not lower than the width of the bit set.
+    #[inline]
+    pub fn remove_local(&mut self, bit: usize) {
+        self.set &= !Self::mask(bit);
+    }
+
+    /// Check if the bit at position `bit` of this bit set is set.
+    ///
+    /// ## Panics
+    ///
+    /// Panics if `bit` is not lower than the width of the bit set.
+    #[inline]
+    pub fn contains_local(&self, bit: usize) -> bool {
+        self.set & Self::mask(bit) != S::ZERO
+    }
+
+    /// Return a bit set with only the bit at position `bit` set.
+    #[inline]
+    fn mask(bit: usize) -> S {
+        assert!(bit < S::WIDTH, "bit position out of bounds");
+        S::from_usize(1 << bit)
+    }
+}
+
+impl<S: storage::Storage> Drop for BlockHandle<'_, S> {
+    fn drop(&mut self) {
+        match self.position {
+            Ok(block_index) if self.set == S::ZERO => self.set_ref.remove_block(block_index),
+            Ok(block_index) => self.set_ref.words[block_index] = self.set,
+            Err(block_index) if self.set != S::ZERO => {
+                self.set_ref
+                    .insert_block(block_index, (self.map_index, self.set));
+            }
+            Err(_) => {}
+        }
+        self.set_ref.debug_assert_invariants();
+    }
+}
+
+/// Batch of insertions into a [`VecIndexSet`], created
+/// with [`VecIndexSet::begin_batch`].
+///
+/// The staged indices are merged into the set when the batch
+/// is committed, or dropped.
+#[must_use = "dropping the batch immediately commits it"]
+pub struct InsertBatch<'a, S: storage::Storage> {
+    /// The set being inserted into.
+    set: &'a mut VecIndexSet<S>,
+    /// Indices staged for insertion.
+    pending: Vec<usize>,
+}
+
+impl<S: storage::Storage> InsertBatch<'_, S> {
+    /// Stage an index for insertion.
+    #[inline]
+    pub fn insert(&mut self, index: usize) {
+        self.pending.push(index);
+    }
+
+    /// Merge the staged indices into the set.
+    #[inline]
+    pub fn commit(self) {}
+}
+
+impl<S: storage::Storage> Extend<usize> for InsertBatch<'_, S> {
+    #[inline]
+    fn extend<T>(&mut self, iter: T)
+    where
+        T: IntoIterator<Item = usize>,
+    {
+        self.pending.extend(iter);
+    }
+}
+
+impl<S: storage::Storage> Drop for InsertBatch<'_, S> {
+    fn drop(&mut self) {
+        self.pending.sort_unstable();
+        self.set
+            .merge_blocks(super::pack_sorted(self.pending.drain(..)));
+    }
+}
+
+/// Wipe the map indices and bit vectors of this [`VecIndexSet`],
+/// along with its summary and search index, leaving it empty.
+///
+/// Memory released as the set grows or shrinks is not wiped.
+/// Reserve enough capacity up front, such that the bit vectors
+/// are never moved to a new allocation.
+///
+/// Wrap the set in [`Zeroizing`] to wipe it when it is dropped.
+///
+/// [`Zeroizing`]: zeroize::Zeroizing
+#[cfg(feature = "zeroize")]
+impl<S> zeroize::Zeroize for VecIndexSet<S> {
+    fn zeroize(&mut self) {
+        self.keys.zeroize();
+        self.words.clear();
+        self.words.spare_capacity_mut().zeroize();
+        self.hint.set(0);
+        self.summary.zeroize();
+        self.search.zeroize();
+    }
+}
+
+impl<S: PartialOrd> PartialOrd for VecIndexSet<S> {
+    /// Compare two [`VecIndexSet`] instances, lexicographically
+    /// by their pairs of indices and bit vectors.
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.keys
+            .iter()
+            .zip(&self.words)
+            .partial_cmp(other.keys.iter().zip(&other.words))
+    }
+}
+
+impl<S: Ord> Ord for VecIndexSet<S> {
+    /// Compare two [`VecIndexSet`] instances, lexicographically
+    /// by their pairs of indices and bit vectors.
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.keys
+            .iter()
+            .zip(&self.words)
+            .cmp(other.keys.iter().zip(&other.words))
+    }
+}
+
+#[cfg(feature = "serialize-serde")]
+impl<S: Serialize> Serialize for VecIndexSet<S> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("VecIndexSet", 1)?;
+        state.serialize_field("bit_sets", &BitSets(self))?;
+        state.end()
+    }
+}
+
+/// Human readable formats also accept a plain array of indices,
+/// besides the serialized representation of a [`VecIndexSet`].
+#[cfg(feature = "serialize-serde")]
+impl<'de, S: Deserialize<'de> + storage::Storage> Deserialize<'de> for VecIndexSet<S> {
+    #[inline]
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match super::serde_compat::deserialize::<_, repr::VecIndexSet<S>>(deserializer)? {
+            Shape::Repr(repr) => Ok(Self::from(repr)),
+            Shape::Indices(indices) => Ok(indices.into_iter().collect()),
+        }
+    }
+}
+
+#[cfg(feature = "serialize-borsh")]
+impl<S: BorshSerialize> BorshSerialize for VecIndexSet<S> {
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        let len = u32::try_from(self.keys.len()).map_err(|_| Error::LimitExceeded)?;
+        BorshSerialize::serialize(&len, writer)?;
+        for (map_index, set) in self.keys.iter().zip(&self.words) {
+            BorshSerialize::serialize(map_index, writer)?;
+            BorshSerialize::serialize(set, writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serialize-borsh")]
+impl<S: BorshDeserialize> BorshDeserialize for VecIndexSet<S> {
+    #[inline]
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        <repr::VecIndexSet<S> as BorshDeserialize>::deserialize_reader(reader).map(Self::from)
+    }
+}
+
+#[cfg(feature = "serialize-borsh")]
+impl<S: BorshSchema> BorshSchema for VecIndexSet<S> {
+    #[inline]
+    fn add_definitions_recursively(
+        definitions: &mut alloc::collections::BTreeMap<
+            borsh::schema::Declaration,
+            borsh::schema::Definition,
+        >,
+    ) {
+        repr::VecIndexSet::<S>::add_definitions_recursively(definitions)
+    }
+
+    #[inline]
+    fn declaration() -> borsh::schema::Declaration {
+        repr::VecIndexSet::<S>::declaration()
+    }
+}
+
+impl<S: storage::Storage> fmt::Debug for VecIndexSet<S> {
+    /// Format this [`VecIndexSet`].
+    ///
+    /// The alternate form (`{:#?}`) renders the indices stored
+    /// in the set, eliding them past a fixed cap, whereas the
+    /// default form renders the raw bit sets.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            debug_indices(self, f)
+        } else {
+            f.debug_struct("VecIndexSet")
+                .field("bit_sets", &BitSets(self))
+                .finish()
+        }
+    }
+}
+
+impl<S: storage::Storage> IndexSet for VecIndexSet<S> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|set| set.num_of_high_bits())
+            .sum::<usize>()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    fn insert(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let mask = S::from_usize(1 << bit_set_index);
+        let set = self.lookup_or_zero(map_index);
+        let added = *set & mask == S::ZERO;
+        *set |= mask;
+        self.debug_assert_invariants();
+        added
+    }
+
+    fn remove(&mut self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        let Ok(block_index) = self.seek_block(map_index) else {
+            return false;
+        };
+        let mask = S::from_usize(1 << bit_set_index);
+        let set = &mut self.words[block_index];
+        let removed = *set & mask != S::ZERO;
+        *set &= !mask;
+        if *set == S::ZERO {
+            self.remove_block(block_index);
+        }
+        self.debug_assert_invariants();
+        removed
+    }
+
+    #[inline]
+    fn clear(&mut self) {
+        self.clear_blocks();
+        self.debug_assert_invariants();
+    }
+
+    #[inline]
+    fn contains(&self, index: usize) -> bool {
+        let (map_index, bit_set_index) = calculate_map_and_set_indices::<S>(index);
+        if self.keys.is_empty() || !self.summary.may_contain(map_index) {
+            return false;
+        }
+
+        // NB: the slot is always in bounds, and only matches
+        // if it holds the bit set at `map_index`
+        let slot = match self.search.start(map_index) {
+            Some(start) => {
+                let (Ok(slot) | Err(slot)) = gallop_search(&self.keys, start, map_index);
+                slot
+            }
+            None => lower_bound(&self.keys, map_index),
+        }
+        .min(self.keys.len() - 1);
+        let is_block_found = self.keys[slot] == map_index;
+        let is_bit_set = self.words[slot] & S::from_usize(1 << bit_set_index) != S::ZERO;
+
+        is_block_found & is_bit_set
+    }
+
+    #[inline]
+    fn iter(&self) -> impl FusedIterator<Item = usize> + '_ {
+        Indices::new(self.blocks())
+    }
+
+    #[inline]
+    fn union(&mut self, other: &VecIndexSet<S>) {
+        self.merge_blocks(other.blocks());
+    }
+
+    #[inline]
+    fn reserve(&mut self, size: usize) {
+        self.keys.reserve(size);
+        self.words.reserve(size);
+    }
+
+    #[inline]
+    fn successor(&self, index: usize) -> Option<usize> {
+        self.successor_in_blocks(index)
+    }
+
+    #[inline]
+    fn predecessor(&self, index: usize) -> Option<usize> {
+        self.predecessor_in_blocks(index)
+    }
+}
+
+index_set_impl_adaptive!(crate::vec::VecIndexSet);
+index_set_impl_bit_set!(crate::vec::VecIndexSet);
+index_set_impl_bitmap!(crate::vec::VecIndexSet);
+index_set_impl_bools!(crate::vec::VecIndexSet);
+index_set_impl_borsh_compat!(crate::vec::VecIndexSet);
+index_set_impl_cbor!(crate::vec::VecIndexSet);
+index_set_impl_compact!(crate::vec::VecIndexSet);
+index_set_impl_contains_many!(crate::vec::VecIndexSet);
+index_set_impl_cross_width!(crate::vec::VecIndexSet);
+index_set_impl_hamming_distance!(crate::vec::VecIndexSet);
+index_set_impl_iter_u64_blocks!(crate::vec::VecIndexSet);
+index_set_impl_layout!(crate::vec::VecIndexSet);
+index_set_impl_lenient!(crate::vec::VecIndexSet);
+index_set_impl_map_monotonic!(crate::vec::VecIndexSet);
+index_set_impl_merkle!(crate::vec::VecIndexSet);
+index_set_impl_neighbors!(crate::vec::VecIndexSet);
+index_set_impl_range_serialize!(crate::vec::VecIndexSet);
+index_set_impl_reserve!(crate::vec::VecIndexSet);
+index_set_impl_roaring!(crate::vec::VecIndexSet);
+index_set_impl_scale!(crate::vec::VecIndexSet);
+index_set_impl_select!(crate::vec::VecIndexSet);
+index_set_impl_slice!(crate::vec::VecIndexSet);
+index_set_impl_stats!(Vec, crate::vec::VecIndexSet);
+index_set_impl_sync!(crate::vec::VecIndexSet);
+index_set_impl_partition!(crate::vec::VecIndexSet);
+index_set_impl_fixedbitset!(crate::vec::VecIndexSet);
+index_set_impl_from!(crate::vec::VecIndexSet);
+index_set_impl_from_iterator!(crate::vec::VecIndexSet);
+index_set_impl_from_str!(crate::vec::VecIndexSet);
+index_set_impl_hibitset!(crate::vec::VecIndexSet);
+index_set_impl_inclusion!(crate::vec::VecIndexSet);
+index_set_impl_index!(crate::vec::VecIndexSet);
+index_set_impl_partial_eq!(crate::vec::VecIndexSet);
+index_set_impl_prost!(crate::vec::VecIndexSet);
+index_set_impl_quickcheck!(crate::vec::VecIndexSet);
+index_set_impl_rayon!(crate::vec::VecIndexSet);
+index_set_impl_extend!(crate::vec::VecIndexSet);
+index_set_tests!(crate::vec::VecIndexSet);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test combining sets into a reused set, without allocating.
+    #[test]
+    fn test_vec_index_set_assign_ops() {
+        let a: VecIndexSet = [1, 4, 6, 31, 32, 100, 1000].into_iter().collect();
+        let b: VecIndexSet = [1, 5, 6, 32, 33, 200, 1000].into_iter().collect();
+        let mut out = VecIndexSet::with_capacity(16);
+        let keys = out.keys.as_ptr();
+        let words = out.words.as_ptr();
+
+        out.assign_union(&a, &b);
+        let mut expected = a.clone();
+        expected.union(&b);
+        assert_eq!(out, expected);
+
+        out.assign_intersection(&a, &b);
+        let expected: VecIndexSet = a.iter().filter(|&i| b.contains(i)).collect();
+        assert_eq!(out, expected);
+
+        out.assign_intersection(&a, &VecIndexSet::new());
+        assert!(out.is_empty());
+
+        assert_eq!(out.keys.as_ptr(), keys);
+        assert_eq!(out.words.as_ptr(), words);
+    }
+
+    /// Test batched insertions against one at a time insertions.
+    #[test]
+    fn test_vec_index_set_insert_batch() {
+        let indices = [900, 3, 64, 65, 7, 3, 1 << 20, 2, 511];
+        let mut set: VecIndexSet = [1, 64, 600, 1 << 21].into_iter().collect();
+        let mut expected = set.clone();
+
+        let mut batch = set.begin_batch();
+        batch.extend(indices[..4].iter().copied());
+        for &index in &indices[4..] {
+            batch.insert(index);
+        }
+        batch.commit();
+        expected.extend(indices);
+
+        assert_eq!(set, expected);
+        set.begin_batch().commit();
+        assert_eq!(set, expected);
+    }
+
+    /// Test galloping searches against binary searches.
+    #[test]
+    fn test_vec_index_set_gallop_search() {
+        let keys = [1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144];
+
+        for len in 0..=keys.len() {
+            let keys = &keys[..len];
+            for start in 0..=len + 1 {
+                for target in 0..150 {
+                    assert_eq!(
+                        gallop_search(keys, start, target),
+                        keys.binary_search(&target),
+                        "keys={keys:?} start={start} target={target}"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Test branchless lower bounds against partition points.
+    #[test]
+    fn test_vec_index_set_lower_bound() {
+        let keys = [1, 2, 3, 5, 8, 13, 21, 34, 55, 89, 144];
+
+        for len in 0..=keys.len() {
+            let keys = &keys[..len];
+            for target in 0..150 {
+                assert_eq!(
+                    lower_bound(keys, target),
+                    keys.partition_point(|&key| key < target),
+                    "keys={keys:?} target={target}"
+                );
+            }
+        }
+    }
+
+    /// Test lookups after the cached block position goes stale.
+    #[test]
+    fn test_vec_index_set_stale_hint() {
+        let mut set: VecIndexSet<u8> = [1, 9, 17, 25].into_iter().collect();
+
+        assert!(set.contains(17));
+        set.remove(9);
+        assert!(set.contains(17) && set.contains(25) && !set.contains(9));
+        set.insert(0);
+        set.insert(10);
+        assert_eq!(set, [0, 1, 10, 17, 25]);
+    }
+
+    /// Test lookups with a summary of the occupied ranges.
+    #[test]
+    fn test_vec_index_set_summary() {
+        let mut set: VecIndexSet<u8> = (0..100).map(|i| i * 37).collect();
+        let mut expected = set.clone();
+        set.enable_summary();
+
+        for i in 0..2000 {
+            set.remove(i * 17);
+            expected.remove(i * 17);
+        }
+        set.insert(1 << 40);
+        expected.insert(1 << 40);
+        set.begin_batch().extend([5, 50_000]);
+        expected.extend([5, 50_000]);
+        set.truncate(3000);
+        expected.truncate(3000);
+
+        assert_eq!(set, expected);
+        for index in 0..4000 {
+            assert_eq!(set.contains(index), expected.contains(index), "{index}");
+        }
+        assert!(set.contains_batch(&[5, 6]) == [true, false]);
+
+        set.disable_summary();
+        assert!(set.contains(5) && !set.contains(6));
+    }
+
+    /// Test lookups with a search index, as it goes stale.
+    #[test]
+    fn test_vec_index_set_search_index() {
+        let mut set: VecIndexSet<u8> = (0..500).map(|i| i * 37).collect();
+        let mut expected = set.clone();
+        set.enable_search_index();
+
+        for (position, &map_index) in set.keys.iter().enumerate() {
+            let start = position.next_multiple_of(SEARCH_STRIDE);
+            let start = if start < set.keys.len() {
+                start
+            } else {
+                usize::MAX
+            };
+            assert_eq!(set.search.start(map_index), Some(start));
+        }
+        for index in 0..20_000 {
+            assert_eq!(set.contains(index), expected.contains(index), "{index}");
+        }
+        for i in 0..300 {
+            set.remove(i * 53);
+            expected.remove(i * 53);
+            set.insert(i * 61 + 5);
+            expected.insert(i * 61 + 5);
+            assert_eq!(set.successor(i * 59), expected.successor(i * 59));
+        }
+        set.insert(1 << 40);
+        expected.insert(1 << 40);
+        set.truncate(15_000);
+        expected.truncate(15_000);
+
+        assert_eq!(set, expected);
+        for index in 0..20_000 {
+            assert_eq!(set.contains(index), expected.contains(index), "{index}");
+        }
+
+        set.disable_search_index();
+        assert_eq!(set, expected);
+    }
+
+    /// Test that sets are serialized as pairs of indices
+    /// and bit vectors, despite being stored apart.
+    #[test]
+    #[cfg(all(feature = "serialize-borsh", feature = "serialize-serde"))]
+    fn test_vec_index_set_serialized_as_pairs() {
+        let set: VecIndexSet<u8> = [1, 4, 6, 100].into_iter().collect();
+        let pairs: Vec<(usize, u8)> = set.blocks().collect();
+
+        assert_eq!(borsh::to_vec(&set).unwrap(), borsh::to_vec(&pairs).unwrap());
+        assert_eq!(
+            serde_json::to_string(&set).unwrap(),
+            format!(
+                "{{\"bit_sets\":{}}}",
+                serde_json::to_string(&pairs).unwrap()
+            )
+        );
+        assert_eq!(
+            <VecIndexSet<u8> as BorshSchema>::declaration(),
+            "VecIndexSet<u8>"
+        );
+    }
+
+    /// Test building sets from validated bit sets.
+    #[test]
+    fn test_vec_index_set_try_from_blocks() {
+        use crate::error::FromBlocksErrorKind;
+
+        let kind = |blocks| {
+            VecIndexSet::<u16>::try_from_blocks(blocks)
+                .unwrap_err()
+                .kind()
+        };
+
+        let set = VecIndexSet::<u16>::try_from_blocks(vec![(0, 0b10), (50, 0b01)]).unwrap();
+        assert_eq!(set, [1, 100]);
+        assert_eq!(kind(vec![(50, 1), (0, 1)]), FromBlocksErrorKind::Unsorted);
+        assert_eq!(kind(vec![(0, 1), (0, 2)]), FromBlocksErrorKind::Duplicate);
+        assert_eq!(kind(vec![(0, 1), (3, 0)]), FromBlocksErrorKind::EmptyBlock);
+        assert_eq!(kind(vec![(0, 1 << 2)]), FromBlocksErrorKind::InvalidBits);
+        assert_eq!(
+            kind(vec![(usize::MAX, 1)]),
+            FromBlocksErrorKind::IndexOverflow
+        );
+
+        let error = VecIndexSet::<u64>::try_from_blocks(vec![(usize::MAX / 8, 1 << 63)]);
+        assert_eq!(error.unwrap_err().kind(), FromBlocksErrorKind::InvalidBits);
+        assert_eq!(
+            VecIndexSet::<u64>::from_blocks_lenient([(0, 1 << 63 | 1)]),
+            [0]
+        );
+    }
+
+    /// Test the fallible variants of allocating operations.
+    #[test]
+    fn test_vec_index_set_try_alloc() {
+        let mut set = VecIndexSet::<u64>::try_with_capacity(4).unwrap();
+        set.try_insert(1).unwrap();
+        set.try_insert(100).unwrap();
+        set.try_union(&[4, 200].into_iter().collect()).unwrap();
+        assert_eq!(set, [1, 4, 100, 200]);
+
+        assert!(set.try_reserve(usize::MAX).is_err());
+        assert!(VecIndexSet::<u64>::try_with_capacity(usize::MAX).is_err());
+        assert_eq!(set, [1, 4, 100, 200]);
+    }
+
+    /// Test persisting sets through embedded-io readers and writers.
+    #[test]
+    #[cfg(feature = "embedded-io")]
+    fn test_vec_index_set_embedded_io() {
+        use crate::error::{ReadError, WriteError};
+
+        let set: VecIndexSet<u16> = [1, 4, 6, 100, 1 << 40].into_iter().collect();
+        let mut flash = [0u8; 64];
+        let mut writer = &mut flash[..];
+        set.write_to_embedded(&mut writer).unwrap();
+        let len = 64 - writer.len();
+        assert_eq!(&flash[..len], set.to_bytes().unwrap().as_slice());
+        assert!(matches!(
+            set.write_to_embedded(&mut [0u8; 8][..]),
+            Err(WriteError::Write(_))
+        ));
+
+        let read = VecIndexSet::<u16>::read_from_embedded(&flash[..len]).unwrap();
+        assert_eq!(read, set);
+
+        assert!(matches!(
+            VecIndexSet::<u16>::read_from_embedded(&flash[..len - 1]),
+            Err(ReadError::Read(embedded_io::ReadExactError::UnexpectedEof))
+        ));
+        let mut unsorted = flash[..len].to_vec();
+        unsorted[4..].rotate_left(10);
+        assert!(matches!(
+            VecIndexSet::<u16>::read_from_embedded(unsorted.as_slice()),
+            Err(ReadError::Decode(Error::UnsortedBlocks))
+        ));
+    }
+
+    /// Test decoding non-canonical binary representations leniently.
+    #[test]
+    fn test_vec_index_set_from_bytes_lenient() {
+        let mut bytes = 3u32.to_le_bytes().to_vec();
+        for (map_index, set) in [(4u64, 0b01u16), (1, 0b10), (4, 0b10)] {
+            bytes.extend_from_slice(&map_index.to_le_bytes());
+            bytes.extend_from_slice(&set.to_le_bytes());
+        }
+        assert!(crate::borrowed::VecIndexSetRef::<u16>::from_bytes(&bytes).is_err());
+
+        let set = VecIndexSet::<u16>::from_bytes_lenient(&bytes).unwrap();
+        assert_eq!(set, [3, 8, 9]);
+        assert_eq!(
+            VecIndexSet::<u16>::from_bytes_lenient(&bytes[..bytes.len() - 1]).unwrap_err(),
+            Error::InvalidEncoding
+        );
+
+        let mut past_max = 1u32.to_le_bytes().to_vec();
+        past_max.extend_from_slice(&u64::MAX.to_le_bytes());
+        past_max.extend_from_slice(&1u16.to_le_bytes());
+        assert_eq!(
+            VecIndexSet::<u16>::from_bytes_lenient(&past_max).unwrap_err(),
+            Error::IndexOutOfBounds
+        );
+    }
+
+    /// Test reserving exact capacities.
+    #[test]
+    fn test_vec_index_set_reserve_exact() {
+        let mut set: VecIndexSet = [1, 100].into_iter().collect();
+        set.reserve_exact(10);
+        assert_eq!(set.capacity(), 12);
+
+        set.try_reserve_exact(20).unwrap();
+        assert_eq!(set.capacity(), 22);
+        assert!(set.try_reserve_exact(usize::MAX).is_err());
+        assert_eq!(set, [1, 100]);
+    }
+
+    /// Test converting the errors of fallible operations
+    /// into their structured causes.
+    #[test]
+    fn test_vec_index_set_structured_errors() {
+        let error = VecIndexSet::<u64>::try_from_blocks(vec![(4, 1), (1, 1)]).unwrap_err();
+        assert_eq!(Error::from(error), Error::UnsortedBlocks);
+        let error = VecIndexSet::<u64>::try_from_blocks(vec![(4, 0)]).unwrap_err();
+        assert_eq!(Error::from(error), Error::ZeroBlock);
+
+        let error = crate::borrowed::VecIndexSetRef::<u64>::from_bytes(&[0; 3]).unwrap_err();
+        assert_eq!(error, Error::InvalidEncoding);
+
+        let error = VecIndexSet::<u64>::new()
+            .try_reserve(usize::MAX)
+            .unwrap_err();
+        assert_eq!(Error::from(error), Error::AllocError);
+
+        let set: VecIndexSet = [1, 100].into_iter().collect();
+        assert_eq!(set.to_bytes().unwrap().len(), 4 + 2 * 16);
+
+        #[cfg(feature = "serialize-borsh")]
+        {
+            let bytes = borsh::to_vec(&vec![(4usize, 1u64), (1, 1)]).unwrap();
+            let error = borsh::from_slice::<VecIndexSet>(&bytes).unwrap_err();
+            assert_eq!(error.to_string(), Error::UnsortedBlocks.to_string());
+        }
+    }
+
+    /// Test that clearing a set keeps its allocation.
+    #[test]
+    fn test_vec_index_set_clear_keeps_capacity() {
+        let mut set: VecIndexSet = (0..1_000).step_by(100).collect();
+        set.enable_search_index();
+        let capacity = set.capacity();
+        set.clear();
+        assert!(set.is_empty());
+        assert_eq!(set.capacity(), capacity);
+
+        set.insert(500);
+        assert!(set.contains(500));
+        assert!(!set.contains(100));
+    }
+
+    /// Test appending sets with their lookup accelerators enabled.
+    #[test]
+    fn test_vec_index_set_append_with_search_index() {
+        let mut set: VecIndexSet = (0..10_000).step_by(97).collect();
+        let mut other: VecIndexSet = (5_000..50_000).step_by(89).collect();
+        set.enable_search_index();
+        other.enable_search_index();
+        let mut expected: Vec<_> = set.iter().chain(other.iter()).collect();
+        expected.sort_unstable();
+        expected.dedup();
+
+        set.append(&mut other);
+        assert!(set.iter().eq(expected.iter().copied()));
+        assert!(expected.iter().all(|&i| set.contains(i)));
+        assert!(!set.contains(1));
+        assert!(other.is_empty() && !other.contains(5_000));
+    }
+
+    /// Test several bit operations through a block handle.
+    #[test]
+    fn test_vec_index_set_block_mut() {
+        let mut set: VecIndexSet<u64> = [1, 9, 100].into_iter().collect();
+
+        let mut handle = set.block_mut(12);
+        assert_eq!((handle.map_index(), handle.start_index()), (1, 8));
+        assert!(handle.contains_local(1) && !handle.contains_local(0));
+        handle.insert_local(0);
+        handle.insert_local(7);
+        handle.remove_local(1);
+        drop(handle);
+
+        let mut handle = set.block_mut(320);
+        handle.insert_local(2);
+        drop(handle);
+
+        let mut handle = set.block_mut(100);
+        handle.remove_local(4);
+        assert_eq!(handle.bits(), 0);
+        drop(handle);
+
+        set.block_mut(1).remove_local(1);
+        set.block_mut(16);
+
+        assert_eq!(set, [8, 15, 322]);
+        assert_eq!(set.blocks().len(), 2);
+    }
+
+    /// Test wiping a set, keeping its allocation.
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_vec_index_set_zeroize() {
+        use zeroize::Zeroize;
+
+        let mut set: VecIndexSet<u16> = (0..1000).step_by(3).collect();
+        set.enable_summary();
+        set.enable_search_index();
+        let capacity = set.capacity();
+
+        set.zeroize();
+        assert!(set.is_empty() && set.keys.is_empty() && set.words.is_empty());
+        assert_eq!(set.capacity(), capacity);
+        assert!(!set.contains(3));
+
+        set.extend([7, 900]);
+        assert_eq!(set, [7, 900]);
+        assert!(set.contains(900) && !set.contains(3));
+    }
+
+    /// Test that draining a set releases its storage.
+    #[test]
+    fn test_vec_index_set_drain_frees_storage() {
+        let mut set: VecIndexSet<u64> = (0..10_000).step_by(7).collect();
+        assert!(set.capacity() > 0);
+
+        assert_eq!(set.drain().count(), 10_000usize.div_ceil(7));
+        assert_eq!(set.capacity(), 0);
+
+        set.extend([3, 500]);
+        let mut drain = set.drain();
+        assert_eq!(drain.next(), Some(3));
+        drop(drain);
+        assert!(set.is_empty());
+        assert_eq!(set.capacity(), 0);
+    }
+
+    /// Test wiping a set on drop, by wrapping it in [`Zeroizing`].
+    ///
+    /// [`Zeroizing`]: zeroize::Zeroizing
+    #[test]
+    #[cfg(feature = "zeroize")]
+    fn test_vec_index_set_zeroizing() {
+        use zeroize::Zeroize;
+
+        let mut set = zeroize::Zeroizing::new(VecIndexSet::<u16>::with_capacity(4));
+        set.extend([3, 70]);
+        assert_eq!(*set, [3, 70]);
+
+        set.zeroize();
+        assert!(set.is_empty());
+
+        // without the wrapper, sets can still be taken apart
+        let VecIndexSet { keys, .. } = [5].into_iter().collect::<VecIndexSet<u16>>();
+        assert_eq!(keys.len(), 1);
+    }
+}
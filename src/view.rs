@@ -0,0 +1,92 @@
+//! Borrowed views over index sets.
+
+use super::IndexSet;
+
+/// Read-only view over an [`IndexSet`], presenting its indices
+/// shifted down by a constant offset.
+///
+/// The view only exposes the indices of the underlying set
+/// that are greater than or equal to the offset, such that
+/// a sub-window of a global index space can be passed around
+/// with local numbering, without copying the set.
+#[derive(Debug)]
+pub struct OffsetView<'a, T> {
+    /// The underlying set.
+    set: &'a T,
+    /// The index of the underlying set presented as index zero.
+    offset: usize,
+}
+
+impl<T> Clone for OffsetView<'_, T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for OffsetView<'_, T> {}
+
+impl<'a, T> OffsetView<'a, T> {
+    /// Create a new [`OffsetView`] over `set`, such that the index
+    /// `offset` of `set` is presented as index zero.
+    #[inline]
+    pub const fn new(set: &'a T, offset: usize) -> Self {
+        Self { set, offset }
+    }
+
+    /// Return the offset of this [`OffsetView`].
+    #[inline]
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Return the set underlying this [`OffsetView`].
+    #[inline]
+    pub const fn get_ref(&self) -> &'a T {
+        self.set
+    }
+}
+
+impl<'a, T: IndexSet> OffsetView<'a, T> {
+    /// Check the presence of an index in this [`OffsetView`].
+    #[inline]
+    pub fn contains(&self, index: usize) -> bool {
+        index
+            .checked_add(self.offset)
+            .is_some_and(|index| self.set.contains(index))
+    }
+
+    /// Return an iterator over the indices in
+    /// this [`OffsetView`], in ascending order.
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = usize> + 'a {
+        let offset = self.offset;
+        self.set
+            .iter()
+            .skip_while(move |&index| index < offset)
+            .map(move |index| index - offset)
+    }
+}
+
+#[cfg(all(test, feature = "vec"))]
+mod tests {
+    use super::*;
+    use crate::vec::VecIndexSet;
+
+    /// Test querying sets through an offset view.
+    #[test]
+    fn test_offset_view() {
+        let set: VecIndexSet = [1, 4, 100, 104, 250].into_iter().collect();
+        let view = OffsetView::new(&set, 100);
+
+        assert!(view.contains(0));
+        assert!(view.contains(4));
+        assert!(view.contains(150));
+        assert!(!view.contains(1));
+        assert!(!view.contains(usize::MAX));
+        assert_eq!(view.iter().collect::<Vec<_>>(), [0, 4, 150]);
+
+        let view = OffsetView::new(&set, 0);
+        assert!(view.iter().eq(set.iter()));
+    }
+}
@@ -0,0 +1,117 @@
+//! JavaScript bindings of index sets, with `wasm-bindgen`.
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use super::typed::VecIndexSet64;
+
+/// JavaScript class wrapping a [`VecIndexSet64`].
+///
+/// Indices are [`u64`] values, exchanged as `BigInt` values, since
+/// [`usize`] values are only 32 bits wide on `wasm32`. Sets are
+/// exchanged with Rust in the binary representation of
+/// [`VecIndexSet::to_bytes`](crate::vec::VecIndexSet::to_bytes),
+/// such that both sides work on the exact same bit sets.
+#[wasm_bindgen(js_name = IndexSet)]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct WasmIndexSet {
+    /// The wrapped index set.
+    inner: VecIndexSet64,
+}
+
+#[wasm_bindgen(js_class = IndexSet)]
+impl WasmIndexSet {
+    /// Create a new, empty [`WasmIndexSet`].
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decode a [`WasmIndexSet`] from the binary representation
+    /// of [`VecIndexSet64::to_bytes`].
+    #[wasm_bindgen(js_name = fromBytes)]
+    pub fn from_bytes(bytes: &[u8]) -> Result<WasmIndexSet, JsError> {
+        VecIndexSet64::from_bytes(bytes)
+            .map(|inner| Self { inner })
+            .map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    /// Encode this [`WasmIndexSet`] into the binary
+    /// representation of [`VecIndexSet64::to_bytes`].
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Result<Vec<u8>, JsError> {
+        self.inner
+            .to_bytes()
+            .map_err(|error| JsError::new(&error.to_string()))
+    }
+
+    /// Return the number of indices in this [`WasmIndexSet`].
+    #[wasm_bindgen(getter)]
+    pub fn size(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Add a new index to this [`WasmIndexSet`].
+    pub fn insert(&mut self, index: u64) {
+        self.inner.insert(index);
+    }
+
+    /// Remove an index from this [`WasmIndexSet`].
+    pub fn remove(&mut self, index: u64) {
+        self.inner.remove(index);
+    }
+
+    /// Check the presence of an index in this [`WasmIndexSet`].
+    pub fn contains(&self, index: u64) -> bool {
+        self.inner.contains(index)
+    }
+
+    /// Return the indices in this [`WasmIndexSet`],
+    /// in ascending order.
+    #[wasm_bindgen(js_name = toArray)]
+    pub fn to_array(&self) -> Vec<u64> {
+        self.inner.iter().collect()
+    }
+}
+
+impl From<VecIndexSet64> for WasmIndexSet {
+    #[inline]
+    fn from(inner: VecIndexSet64) -> Self {
+        Self { inner }
+    }
+}
+
+impl From<WasmIndexSet> for VecIndexSet64 {
+    #[inline]
+    fn from(set: WasmIndexSet) -> Self {
+        set.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test manipulating sets through their JavaScript bindings.
+    #[test]
+    fn test_wasm_index_set() {
+        let mut set = WasmIndexSet::new();
+        for index in [1, 4, 100, 1 << 20, 1 << 40] {
+            set.insert(index);
+        }
+        set.remove(4);
+
+        assert!(set.contains(100) && !set.contains(4));
+        assert!(set.contains(1 << 40) && !set.contains(1 << 41));
+        assert_eq!(set.size(), 4);
+        assert_eq!(set.to_array(), [1, 100, 1 << 20, 1 << 40]);
+
+        let bytes = set.to_bytes().unwrap();
+        let expected: VecIndexSet64 = [1, 100, 1 << 20, 1 << 40].into_iter().collect();
+        assert_eq!(bytes, expected.to_bytes().unwrap());
+        assert_eq!(WasmIndexSet::from_bytes(&bytes).unwrap(), set);
+        assert_eq!(VecIndexSet64::from(set), expected);
+    }
+}
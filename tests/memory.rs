@@ -0,0 +1,72 @@
+//! Measure the heap memory held by index sets.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use index_set::btree::BTreeIndexSet;
+use index_set::IndexSet;
+
+/// Allocator which keeps track of the number of bytes in use.
+struct Counting;
+
+/// The number of bytes currently allocated.
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// Return the number of bytes allocated by `build`
+/// for the value it returns, which is then dropped.
+fn heap_size<T>(build: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATED.load(Ordering::Relaxed);
+    let value = build();
+    let size = ALLOCATED.load(Ordering::Relaxed) - before;
+    drop(value);
+    size
+}
+
+/// Test that a [`BTreeIndexSet`] built in ascending order of indices
+/// takes at least 30% less memory than a [`BTreeMap`] of its bit sets.
+#[test]
+fn test_btree_index_set_memory() {
+    const NUM_OF_BLOCKS: usize = 10_000;
+
+    let set_size = heap_size(|| {
+        let mut set = BTreeIndexSet::<u64>::new();
+        for map_index in 0..NUM_OF_BLOCKS {
+            set.insert(map_index * 64);
+        }
+        set
+    });
+    let map_size = heap_size(|| {
+        let mut map = BTreeMap::<usize, u64>::new();
+        for map_index in 0..NUM_OF_BLOCKS {
+            map.insert(map_index, 1);
+        }
+        map
+    });
+
+    assert!(
+        set_size * 10 <= map_size * 7,
+        "{set_size} bytes for the set against {map_size} bytes for the map"
+    );
+}